@@ -0,0 +1,664 @@
+use super::args::{Cli, Commands};
+use super::handlers;
+use super::output;
+use super::summary::{self, StepCounts, format_delta, used_bytes};
+use super::{OutputFormat, RetryConfig};
+use crate::error::ErrorKind;
+use crate::utils::{ci, env, env_registry, exec, lock, os, progress};
+use anyhow::{Context, Result};
+use clap::{ArgAction, CommandFactory, Parser};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::task::JoinSet;
+
+/// Loads the environment earlier picolayer runs wrote to /etc/profile.d before a hook runs
+pub(super) const HOOK_PRELUDE: &str =
+    "for f in /etc/profile.d/picolayer-*.sh; do if [ -r \"$f\" ]; then . \"$f\"; fi; done; ";
+
+/// A manifest of installer steps, each a single-key map of subcommand to options:
+///
+/// ```yaml
+/// steps:
+///   - apt-get:
+///       packages: [curl, git]
+///   - gh-release:
+///       owner: cli
+///       repo: cli
+///       binary: gh
+///       post: gh --version
+/// ```
+#[derive(Debug, Deserialize)]
+pub(super) struct Manifest {
+    pub(super) steps: Vec<BTreeMap<String, serde_yaml::Value>>,
+}
+
+impl Manifest {
+    pub(super) fn read(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {}", path))?;
+        let manifest: Manifest = serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse manifest {}", path))?;
+        anyhow::ensure!(!manifest.steps.is_empty(), "Manifest {} has no steps", path);
+        Ok(manifest)
+    }
+
+    /// Where the lockfile for this manifest lives
+    pub(super) fn lock_path(path: &str) -> PathBuf {
+        Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(lock::LOCKFILE_NAME)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum StepStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+struct StepResult {
+    step: usize,
+    command: String,
+    status: StepStatus,
+    duration_ms: u128,
+    /// Bytes fetched over the network
+    downloaded_bytes: u64,
+    /// Change in used bytes on the root filesystem, with --report-size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_delta: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+pub struct ApplyConfig<'a> {
+    /// Path to the manifest
+    pub manifest: &'a str,
+    /// Refuse artifacts that differ from the lockfile instead of updating it
+    pub frozen: bool,
+    /// Measure how much each step grows the root filesystem
+    pub report_size: bool,
+    /// Maximum number of steps running at once
+    pub jobs: usize,
+    /// Print the environment the steps contributed as Dockerfile `ENV` lines
+    pub print_env: bool,
+}
+
+/// A manifest step ready to run
+struct Step {
+    name: String,
+    cli: Cli,
+    /// Step numbers that must succeed before this one starts
+    after: Vec<usize>,
+    hooks: Hooks,
+}
+
+/// Shell commands a step runs before and after its installer, for glue such as creating
+/// users or writing config files
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Hooks {
+    pub(super) pre: Vec<String>,
+    pub(super) post: Vec<String>,
+}
+
+impl Hooks {
+    /// Remove the `pre` and `post` options, each one command or a list of them
+    pub(super) fn take(options: &mut serde_yaml::Mapping) -> Result<Self> {
+        let mut take = |key: &str| match options.remove(key) {
+            Some(value) => {
+                scalar_values(&value).with_context(|| format!("Invalid value for '{}'", key))
+            }
+            None => Ok(Vec::new()),
+        };
+        Ok(Hooks {
+            pre: take("pre")?,
+            post: take("post")?,
+        })
+    }
+}
+
+pub async fn run(
+    config: &ApplyConfig<'_>,
+    retry_config: &RetryConfig,
+    output: OutputFormat,
+) -> Result<()> {
+    let manifest_path = config.manifest;
+    anyhow::ensure!(config.jobs > 0, "--jobs must be at least 1");
+    let manifest = Manifest::read(manifest_path)?;
+
+    // Parse every step up front so a typo in a late step fails before anything is installed
+    let mut steps = manifest
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            parse_step(i + 1, step)
+                .with_context(|| format!("Invalid step {} in {}", i + 1, manifest_path))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (i, step) in steps.iter().enumerate() {
+        super::policy::check(&step.cli.command)
+            .with_context(|| format!("Step {} in {}", i + 1, manifest_path))?;
+    }
+    add_barriers(&mut steps);
+
+    let lock_path = Manifest::lock_path(manifest_path);
+    let frozen_lock = if config.frozen {
+        Some(
+            lock::Lockfile::read(&lock_path)
+                .context("--frozen requires an existing lockfile next to the manifest")?,
+        )
+    } else {
+        None
+    };
+    lock::begin(frozen_lock);
+
+    let mut results: Vec<Option<StepResult>> = steps.iter().map(|_| None).collect();
+    let mut pending: Vec<Option<Step>> = steps.into_iter().map(Some).collect();
+    let mut busy = HashSet::new();
+    let mut running = JoinSet::new();
+    let mut failure = None;
+    loop {
+        // After a failure, running steps finish but no new ones start
+        while failure.is_none()
+            && running.len() < config.jobs
+            && let Some(i) = next_ready(&pending, &results, &busy)
+        {
+            let step = pending[i].take().context("Step started twice")?;
+            if let Some(group) = lock_group(&step.name) {
+                busy.insert(group.to_string());
+            }
+            info!("Step {}: {}", i + 1, step.name);
+            let retry_config = retry_config.clone();
+            let report_size = config.report_size;
+            // Collapsible CI groups cannot interleave, so steps running side by side are
+            // told apart by their line prefixes alone
+            let group = config.jobs == 1;
+            let handle = Handle::current();
+            running.spawn_blocking(move || {
+                run_step(
+                    i + 1,
+                    step,
+                    &retry_config,
+                    output,
+                    report_size,
+                    group,
+                    &handle,
+                )
+            });
+        }
+
+        let Some(joined) = running.join_next().await else {
+            break;
+        };
+        let (result, error) = joined.context("Manifest step panicked")?;
+        if let Some(group) = lock_group(&result.command) {
+            busy.remove(group);
+        }
+        if let Some(e) = error
+            && failure.is_none()
+        {
+            failure = Some(e.context(format!("Step {} ({}) failed", result.step, result.command)));
+        }
+        let index = result.step - 1;
+        results[index] = Some(result);
+    }
+
+    let results: Vec<StepResult> = results
+        .into_iter()
+        .zip(pending)
+        .enumerate()
+        .map(|(i, (result, step))| {
+            result.unwrap_or_else(|| StepResult {
+                step: i + 1,
+                command: step.map(|s| s.name).unwrap_or_default(),
+                status: StepStatus::Skipped,
+                duration_ms: 0,
+                downloaded_bytes: 0,
+                size_delta: None,
+                details: None,
+                error: None,
+                error_code: None,
+            })
+        })
+        .collect();
+
+    print_summary(&results, output)?;
+    let lockfile = lock::finish();
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    if config.print_env && output == OutputFormat::Text {
+        print!("{}", env_registry::snapshot().dockerfile());
+    }
+
+    let lockfile = lockfile?;
+    if output == OutputFormat::Json {
+        output::set_artifacts(lockfile.artifacts.clone());
+    }
+    if config.frozen {
+        info!("All resolved artifacts match {}", lock_path.display());
+    } else {
+        lockfile.write(&lock_path)?;
+        info!(
+            "Wrote {} ({} artifacts)",
+            lock_path.display(),
+            lockfile.artifacts.len()
+        );
+    }
+    Ok(())
+}
+
+/// Run a step on a blocking thread, since installers shell out and wait on the result
+fn run_step(
+    number: usize,
+    step: Step,
+    retry_config: &RetryConfig,
+    output: OutputFormat,
+    report_size: bool,
+    group: bool,
+    handle: &Handle,
+) -> (StepResult, Option<anyhow::Error>) {
+    let _group = group.then(|| ci::group(&format!("Step {}: {}", number, step.name)));
+    let used_before = report_size.then(used_bytes).flatten();
+    let started = Instant::now();
+    let (result, details, downloaded_bytes) =
+        match run_hooks(number, &step.name, "pre", &step.hooks.pre) {
+            Err(e) => (Err(e), None, 0),
+            Ok(()) => {
+                let ((result, details), downloaded_bytes) =
+                    handle.block_on(progress::measure(lock::in_step(
+                        number,
+                        &step.name,
+                        output::capture_details(handlers::handle_command(
+                            step.cli.command,
+                            retry_config,
+                            output,
+                        )),
+                    )));
+                let result =
+                    result.and_then(|()| run_hooks(number, &step.name, "post", &step.hooks.post));
+                (result, details, downloaded_bytes)
+            }
+        };
+    let duration_ms = started.elapsed().as_millis();
+    let size_delta = used_before
+        .zip(used_bytes())
+        .map(|(before, after)| after as i64 - before as i64);
+
+    let (status, error) = match result {
+        Ok(()) => (StepStatus::Ok, None),
+        Err(e) => (StepStatus::Failed, Some(e)),
+    };
+    let result = StepResult {
+        step: number,
+        command: step.name,
+        status,
+        duration_ms,
+        downloaded_bytes,
+        size_delta,
+        details,
+        error: error.as_ref().map(|e| format!("{:#}", e)),
+        error_code: error.as_ref().map(|e| ErrorKind::of(e).code()),
+    };
+    (result, error)
+}
+
+/// Run hooks one after another with `sh -e`, so a failing line fails the step. They see
+/// the environment earlier steps contributed, whether from this run or an earlier one.
+fn run_hooks(number: usize, command: &str, stage: &str, hooks: &[String]) -> Result<()> {
+    for hook in hooks {
+        info!("Step {} {} hook: {}", number, stage, hook);
+        let description = format!("step {} {} hook", number, stage);
+        let script = format!("{}{}", HOOK_PRELUDE, hook);
+        let mut cmd = Command::new("sh");
+        cmd.args(["-ec", &script]);
+        env_registry::snapshot().apply_to(&mut cmd);
+        exec::Exec::new(&mut cmd, &description)
+            .streamed()
+            .prefixed(format!("{} {}", number, command))
+            .run()?;
+    }
+    Ok(())
+}
+
+/// Make steps that change the whole system wait for every earlier step, and every
+/// later step wait for them
+fn add_barriers(steps: &mut [Step]) {
+    let mut barrier = None;
+    for (i, step) in steps.iter_mut().enumerate() {
+        if matches!(step.name.as_str(), "clean" | "uninstall" | "upgrade") {
+            step.after = (1..=i).collect();
+            barrier = Some(i + 1);
+        } else if let Some(barrier) = barrier {
+            step.after.push(barrier);
+        }
+    }
+}
+
+/// Package manager database a step writes to; steps sharing one never run concurrently
+fn lock_group(command: &str) -> Option<&str> {
+    match command {
+        // Devcontainer features usually install their dependencies with apt
+        "apt-get" | "apt" | "aptitude" | "devcontainer-feature" => Some("dpkg"),
+        // Downloads and runtimes unpack into directories of their own
+        "gh-release" | "runtime" | "jdk" | "venv" => None,
+        tool => Some(tool),
+    }
+}
+
+/// The first unstarted step whose dependencies succeeded and whose package manager is free
+fn next_ready(
+    pending: &[Option<Step>],
+    results: &[Option<StepResult>],
+    busy: &HashSet<String>,
+) -> Option<usize> {
+    pending.iter().enumerate().find_map(|(i, step)| {
+        let step = step.as_ref()?;
+        let ready = step.after.iter().all(|&dep| {
+            results[dep - 1]
+                .as_ref()
+                .is_some_and(|r| r.status == StepStatus::Ok)
+        }) && lock_group(&step.name).is_none_or(|group| !busy.contains(group));
+        ready.then_some(i)
+    })
+}
+
+fn print_summary(results: &[StepResult], output: OutputFormat) -> Result<()> {
+    let mut counts = StepCounts::default();
+    for result in results {
+        match result.status {
+            StepStatus::Ok => counts.ok += 1,
+            StepStatus::Failed => counts.failed += 1,
+            StepStatus::Skipped => counts.skipped += 1,
+        }
+    }
+    summary::record_steps(counts);
+
+    if output == OutputFormat::Json {
+        return output::set_details(&results);
+    }
+    for result in results {
+        let mut timing = format!("{:.1}s", result.duration_ms as f64 / 1000.0);
+        if result.downloaded_bytes > 0 {
+            timing.push_str(&format!(
+                ", {} downloaded",
+                os::format_bytes(result.downloaded_bytes)
+            ));
+        }
+        let mut line = format!(
+            "Step {} {}: {:?} ({})",
+            result.step, result.command, result.status, timing
+        );
+        if let Some(delta) = result.size_delta {
+            line.push_str(&format!(" {}", format_delta(delta)));
+        }
+        match result.status {
+            StepStatus::Failed => error!("{}", line),
+            _ => info!("{}", line),
+        }
+    }
+    let deltas: Vec<i64> = results.iter().filter_map(|r| r.size_delta).collect();
+    if !deltas.is_empty() {
+        info!("Layer size change: {}", format_delta(deltas.iter().sum()));
+    }
+    Ok(())
+}
+
+/// The command and hooks of every step in a manifest, to show what applying it would do
+pub(super) fn step_commands(manifest_path: &str) -> Result<Vec<(Commands, Hooks)>> {
+    let manifest = Manifest::read(manifest_path)?;
+    manifest
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            parse_step(i + 1, step)
+                .map(|step| (step.cli.command, step.hooks))
+                .with_context(|| format!("Invalid step {} in {}", i + 1, manifest_path))
+        })
+        .collect()
+}
+
+/// Turn a `{subcommand: {option: value}}` step into a parsed CLI invocation. The
+/// `after` option lists earlier step numbers that must succeed first, and `pre` and
+/// `post` list shell hooks.
+fn parse_step(number: usize, step: &BTreeMap<String, serde_yaml::Value>) -> Result<Step> {
+    anyhow::ensure!(
+        step.len() == 1,
+        "Each step must have exactly one command, found {}",
+        step.len()
+    );
+    let (name, options) = step.iter().next().context("Empty step")?;
+    anyhow::ensure!(name != "apply", "Manifests cannot apply other manifests");
+
+    let mut options = options.clone();
+    let after = match options.as_mapping_mut().and_then(|o| o.remove("after")) {
+        Some(value) => scalar_values(&value)?
+            .iter()
+            .map(|dep| {
+                dep.parse()
+                    .ok()
+                    .filter(|dep| (1..number).contains(dep))
+                    .with_context(|| format!("'after' must name earlier steps, found {}", dep))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let hooks = match options.as_mapping_mut() {
+        Some(options) => Hooks::take(options)?,
+        None => Hooks::default(),
+    };
+
+    let args = step_args(name, &options)?
+        .iter()
+        .map(|arg| env::expand(arg))
+        .collect::<Result<Vec<_>>>()?;
+    let cli = Cli::try_parse_from(std::iter::once("picolayer".to_string()).chain(args))
+        .map_err(|e| anyhow::anyhow!("{}", e.render().to_string().trim()))?;
+    Ok(Step {
+        name: name.clone(),
+        cli,
+        after,
+        hooks,
+    })
+}
+
+/// Build the command line for a step, using the subcommand's clap definition to
+/// decide whether each option is positional, a switch, or a repeatable flag
+pub(super) fn step_args(name: &str, options: &serde_yaml::Value) -> Result<Vec<String>> {
+    let mut command = Cli::command();
+    command.build();
+    let subcommand = command
+        .find_subcommand(name)
+        .with_context(|| format!("Unknown command '{}'", name))?;
+
+    let options = match options {
+        serde_yaml::Value::Null => serde_yaml::Mapping::new(),
+        serde_yaml::Value::Mapping(options) => options.clone(),
+        _ => anyhow::bail!("Options for '{}' must be a mapping", name),
+    };
+
+    let mut args = vec![name.to_string()];
+    let mut positionals = Vec::new();
+    for (key, value) in &options {
+        let key = key.as_str().context("Option names must be strings")?;
+        let id = key.replace('-', "_");
+        let arg = subcommand
+            .get_arguments()
+            // Global flags come from the outer invocation, not from individual steps
+            .filter(|a| !a.is_global_set())
+            .find(|a| a.get_id().as_str() == id || a.get_long() == Some(key))
+            .with_context(|| format!("Unknown option '{}' for {}", key, name))?;
+        let values =
+            scalar_values(value).with_context(|| format!("Invalid value for option '{}'", key))?;
+
+        if arg.is_positional() {
+            let values = match arg.get_action() {
+                ArgAction::Append => values,
+                _ => vec![values.join(",")],
+            };
+            positionals.push((arg.get_index().unwrap_or_default(), values));
+            continue;
+        }
+
+        let long = arg
+            .get_long()
+            .with_context(|| format!("Option '{}' cannot be set from a manifest", key))?;
+        match arg.get_action() {
+            ArgAction::SetTrue => {
+                let enabled = value
+                    .as_bool()
+                    .with_context(|| format!("Option '{}' must be true or false", key))?;
+                if enabled {
+                    args.push(format!("--{}", long));
+                }
+            }
+            ArgAction::Append => {
+                args.extend(values.iter().map(|v| format!("--{}={}", long, v)));
+            }
+            _ => args.push(format!("--{}={}", long, values.join(","))),
+        }
+    }
+
+    positionals.sort_by_key(|(index, _)| *index);
+    if !positionals.is_empty() {
+        args.push("--".to_string());
+        args.extend(positionals.into_iter().flat_map(|(_, values)| values));
+    }
+    Ok(args)
+}
+
+/// Flatten a scalar or a list of scalars into strings
+fn scalar_values(value: &serde_yaml::Value) -> Result<Vec<String>> {
+    let scalar = |v: &serde_yaml::Value| match v {
+        serde_yaml::Value::String(s) => Ok(s.clone()),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        _ => anyhow::bail!("expected a string, number, boolean, or list of them"),
+    };
+    match value {
+        serde_yaml::Value::Sequence(items) => items.iter().map(scalar).collect(),
+        v => Ok(vec![scalar(v)?]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(yaml: &str) -> BTreeMap<String, serde_yaml::Value> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn step_args_maps_options_onto_cli_flags() {
+        let options: serde_yaml::Value =
+            serde_yaml::from_str("packages: [curl, git]\narch: [arm64, i386]\nkeep-cache: true")
+                .unwrap();
+        assert_eq!(
+            step_args("apt-get", &options).unwrap(),
+            [
+                "apt-get",
+                "--arch=arm64",
+                "--arch=i386",
+                "--keep-cache",
+                "--",
+                "curl,git"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_step_builds_cli_command() {
+        let parsed = parse_step(1, &step("npm:\n  packages: [typescript, eslint]")).unwrap();
+        assert_eq!(parsed.name, "npm");
+        assert!(parsed.after.is_empty());
+    }
+
+    #[test]
+    fn parse_step_reads_dependencies() {
+        let parsed = parse_step(3, &step("npm:\n  packages: typescript\n  after: [1, 2]")).unwrap();
+        assert_eq!(parsed.after, [1, 2]);
+        assert!(parse_step(2, &step("npm:\n  packages: typescript\n  after: 2")).is_err());
+    }
+
+    #[test]
+    fn parse_step_takes_hooks_out_of_the_options() {
+        let parsed = parse_step(
+            1,
+            &step("npm:\n  packages: typescript\n  pre: useradd -m dev\n  post: [tsc --version, true]"),
+        )
+        .unwrap();
+        assert_eq!(parsed.hooks.pre, ["useradd -m dev"]);
+        assert_eq!(parsed.hooks.post, ["tsc --version", "true"]);
+        assert!(parse_step(1, &step("npm:\n  packages: a\n  pre: {user: dev}")).is_err());
+    }
+
+    #[test]
+    fn hooks_see_the_environment_and_fail_the_step() {
+        assert!(run_hooks(1, "apt-get", "pre", &["test -n \"$PATH\"".to_string()]).is_ok());
+        assert!(
+            run_hooks(
+                1,
+                "apt-get",
+                "post",
+                &["false\necho unreachable".to_string()]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn steps_sharing_a_package_manager_wait_for_each_other() {
+        let steps: Vec<Step> = ["apt-get:\n  packages: curl", "apt:\n  packages: git"]
+            .iter()
+            .enumerate()
+            .map(|(i, yaml)| parse_step(i + 1, &step(yaml)).unwrap())
+            .collect();
+        let pending: Vec<Option<Step>> = steps.into_iter().map(Some).collect();
+        let results = [None, None];
+
+        assert_eq!(next_ready(&pending, &results, &HashSet::new()), Some(0));
+        let busy = HashSet::from(["dpkg".to_string()]);
+        assert_eq!(next_ready(&pending, &results, &busy), None);
+        assert_eq!(lock_group("gh-release"), None);
+    }
+
+    #[test]
+    fn barriers_order_surrounding_steps() {
+        let mut steps: Vec<Step> = [
+            "npm:\n  packages: a",
+            "pipx:\n  packages: b",
+            "clean:",
+            "gem:\n  packages: c",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, yaml)| parse_step(i + 1, &step(yaml)).unwrap())
+        .collect();
+        add_barriers(&mut steps);
+        assert!(steps[1].after.is_empty());
+        assert_eq!(steps[2].after, [1, 2]);
+        assert_eq!(steps[3].after, [3]);
+    }
+
+    #[test]
+    fn parse_step_rejects_unknown_commands_and_options() {
+        assert!(parse_step(1, &step("does-not-exist: {}")).is_err());
+        assert!(parse_step(1, &step("npm:\n  packages: cowsay\n  bogus: 1")).is_err());
+        assert!(parse_step(1, &step("apply:\n  manifest: other.yaml")).is_err());
+    }
+}
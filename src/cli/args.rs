@@ -1,24 +1,174 @@
+use crate::utils::policy::{ChecksumAlgorithm, Verification};
 use clap::{Parser, Subcommand};
 use log::warn;
+#[cfg(feature = "devcontainer")]
 use std::collections::HashMap;
+use std::time::Duration;
+
+/// HTTP statuses retried by default: timeouts, rate limits, and transient server errors
+pub const DEFAULT_RETRY_STATUSES: [u16; 6] = [408, 429, 500, 502, 503, 504];
 
 #[derive(Clone)]
 pub struct RetryConfig {
     pub max_retries: u32,
     pub initial_delay_ms: u64,
     pub backoff_multiplier: f64,
+    /// Fraction of each delay randomly added or removed, so parallel builds spread out
+    pub jitter: f64,
+    /// Stop retrying once this much time has passed since the first attempt
+    pub max_elapsed_ms: Option<u64>,
+    /// HTTP statuses worth retrying; any other status fails immediately
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_delay_ms: 1000,
+            backoff_multiplier: 2.0,
+            jitter: 0.1,
+            max_elapsed_ms: None,
+            retry_statuses: DEFAULT_RETRY_STATUSES.to_vec(),
+        }
+    }
 }
 
 impl RetryConfig {
+    /// Retry flags, falling back to the config file and then the defaults
     pub fn from_cli(cli: &Cli) -> Self {
+        let defaults = Self::default();
+        let settings = &crate::utils::settings::get().retry;
         Self {
-            max_retries: cli.max_retries,
-            initial_delay_ms: cli.retry_delay_ms,
-            backoff_multiplier: cli.retry_backoff_multiplier,
+            max_retries: cli
+                .max_retries
+                .or(settings.max_retries)
+                .unwrap_or(defaults.max_retries),
+            initial_delay_ms: cli
+                .retry_delay_ms
+                .or(settings.delay_ms)
+                .unwrap_or(defaults.initial_delay_ms),
+            backoff_multiplier: cli
+                .retry_backoff_multiplier
+                .or(settings.backoff_multiplier)
+                .unwrap_or(defaults.backoff_multiplier),
+            jitter: cli
+                .retry_jitter
+                .or(settings.jitter)
+                .unwrap_or(defaults.jitter)
+                .clamp(0.0, 1.0),
+            max_elapsed_ms: cli.retry_max_elapsed_ms.or(settings.max_elapsed_ms),
+            retry_statuses: cli
+                .retry_statuses
+                .as_deref()
+                .map(parse_status_list)
+                .or_else(|| settings.statuses.clone())
+                .unwrap_or(defaults.retry_statuses),
+        }
+    }
+}
+
+/// Format for command results printed to stdout
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Tool used to install global Node.js packages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NodePackageManager {
+    #[default]
+    Npm,
+    Pnpm,
+    Yarn,
+    Bun,
+}
+
+impl NodePackageManager {
+    pub fn program(self) -> &'static str {
+        match self {
+            NodePackageManager::Npm => "npm",
+            NodePackageManager::Pnpm => "pnpm",
+            NodePackageManager::Yarn => "yarn",
+            NodePackageManager::Bun => "bun",
         }
     }
 }
 
+/// Leftovers removed by the `clean` command
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CleanTarget {
+    /// apt package lists under /var/lib/apt/lists
+    AptLists,
+    /// Downloaded apt and apk packages
+    PackageCache,
+    /// pip caches under ~/.cache/pip
+    Pip,
+    /// npm and yarn caches
+    Npm,
+    /// pkgx caches under ~/.cache/pkgx
+    Pkgx,
+    /// Contents of /tmp and /var/tmp
+    Temp,
+    /// Documentation under /usr/share (doc, man, info); only with --aggressive
+    Docs,
+    /// apt and dpkg logs under /var/log; only with --aggressive
+    Logs,
+}
+
+/// Output format for `picolayer generate`
+#[derive(Subcommand)]
+pub enum GenerateTarget {
+    /// Print a Dockerfile RUN instruction that applies a manifest with BuildKit cache mounts
+    Dockerfile {
+        /// Path to the manifest (e.g. picolayer.yaml)
+        manifest: String,
+    },
+}
+
+/// Actions of `picolayer cache`
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Remove cached downloads, OCI layers, and pkgx data from the cache directory
+    #[command(group = clap::ArgGroup::new("limit").required(true).multiple(true))]
+    Prune {
+        /// Remove the oldest entries until the cache fits in this size (e.g. 2G, 500M)
+        #[arg(long, value_name = "SIZE", value_parser = byte_size, group = "limit")]
+        max_size: Option<u64>,
+
+        /// Remove entries not modified for this long (e.g. 30d, 12h)
+        #[arg(long, value_name = "AGE", value_parser = age, group = "limit")]
+        older_than: Option<Duration>,
+
+        /// Remove everything in the cache
+        #[arg(long, group = "limit")]
+        all: bool,
+
+        /// Show what would be removed and how much space it takes, without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Runtime installed by the `runtime` command from its upstream release layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RuntimePreset {
+    Deno,
+    Bun,
+    Node,
+    Zig,
+}
+
+/// JDK distribution installed by the `jdk` command
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum JdkDistribution {
+    #[default]
+    Temurin,
+}
+
 #[derive(Parser)]
 #[command(name = "picolayer")]
 #[command(about = "Ensures minimal container layers")]
@@ -27,29 +177,131 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
 
-    /// Maximum number of retry attempts for downloads (default: 0, no retries)
-    #[arg(long, global = true, default_value = "0")]
-    pub max_retries: u32,
+    /// Maximum number of retry attempts for downloads and package mirror operations (default: 0, no retries)
+    #[arg(long, global = true)]
+    pub max_retries: Option<u32>,
 
     /// Initial delay in milliseconds for retry backoff (default: 1000)
-    #[arg(long, global = true, default_value = "1000")]
-    pub retry_delay_ms: u64,
+    #[arg(long, global = true)]
+    pub retry_delay_ms: Option<u64>,
 
     /// Multiplier for exponential backoff (default: 2.0)
-    #[arg(long, global = true, default_value = "2.0")]
-    pub retry_backoff_multiplier: f64,
+    #[arg(long, global = true)]
+    pub retry_backoff_multiplier: Option<f64>,
+
+    /// Fraction of each retry delay to randomize, from 0 to 1 (default: 0.1)
+    #[arg(long, global = true)]
+    pub retry_jitter: Option<f64>,
+
+    /// Stop retrying once this many milliseconds have passed since the first attempt
+    #[arg(long, global = true)]
+    pub retry_max_elapsed_ms: Option<u64>,
+
+    /// Comma-separated HTTP statuses to retry (default: 408,429,500,502,503,504)
+    #[arg(long, global = true, value_name = "STATUSES", value_parser = status_list)]
+    pub retry_statuses: Option<String>,
 
     /// Increase logging verbosity (-v for info, -vv for debug, -vvv for trace)
     #[arg(short = 'v', long = "verbose", global = true, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Output format for command results (json prints one structured result object on stdout)
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
     /// Suppress all output except errors
     #[arg(short = 'q', long = "quiet", global = true, conflicts_with = "verbose")]
     pub quiet: bool,
+
+    /// Fail after this many seconds, stopping every process picolayer started
+    #[arg(long, global = true, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Never use the network; install only from local caches and pre-seeded artifacts
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Skip installs whose packages, binaries, or features are already present at a satisfying version
+    #[arg(long, global = true)]
+    pub skip_if_present: bool,
+
+    /// Never escalate privileges: install binaries to ~/.local/bin, use user prefixes for npm
+    /// and pipx, and skip system package managers
+    #[arg(long, global = true)]
+    pub user_mode: bool,
+
+    /// Never escalate privileges with sudo, doas or su; steps that need root fail unless
+    /// picolayer already runs as root
+    #[arg(long, global = true)]
+    pub no_sudo: bool,
+
+    /// Refuse installs of downloaded artifacts verified less strongly than this, on top of
+    /// the [policy] section of the config file
+    #[arg(long, global = true, value_enum, value_name = "LEVEL")]
+    pub require_verification: Option<Verification>,
+
+    /// Refuse to verify downloads with MD5 or SHA-1 checksums, on top of the [policy]
+    /// section of the config file
+    #[arg(long, global = true)]
+    pub reject_weak_checksums: bool,
+
+    /// Show what the command will install, download, and run, and ask before doing it
+    #[arg(long, global = true)]
+    pub confirm: bool,
+
+    /// Proceed without asking, even with --confirm
+    #[arg(short = 'y', long, global = true)]
+    pub yes: bool,
+
+    /// Proxy for downloads (default: HTTPS_PROXY, HTTP_PROXY and NO_PROXY)
+    #[arg(long, global = true, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// PEM bundle of extra CA certificates to trust for downloads (default: PICOLAYER_CA_BUNDLE)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub ca_bundle: Option<String>,
+
+    /// Append a JSON line for every downloaded artifact (URL, version, sha256, size, verification)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub audit_log: Option<String>,
+
+    /// Write a JSON summary of the run (duration, steps, bytes downloaded, cache hits, size change)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub summary_file: Option<String>,
+
+    /// Directory for downloads and extraction, when /tmp is small (default: PICOLAYER_TMP, TMPDIR)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub tmp_dir: Option<String>,
+
+    /// Downloads to run at once, across parallel manifest steps too (default: PICOLAYER_MAX_DOWNLOADS, 4)
+    #[arg(long, global = true, value_name = "N")]
+    pub max_downloads: Option<usize>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Run the installer steps listed in a YAML manifest, in order
+    Apply {
+        /// Path to the manifest (e.g. picolayer.yaml)
+        manifest: String,
+
+        /// Fail if any download resolves differently from picolayer.lock instead of updating it
+        #[arg(long, default_value = "false")]
+        frozen: bool,
+
+        /// Report how much each step grew the root filesystem
+        #[arg(long, default_value = "false")]
+        report_size: bool,
+
+        /// Maximum number of steps to run at once; steps sharing a package manager still run one at a time
+        #[arg(long, default_value = "1")]
+        jobs: usize,
+
+        /// Print the environment the steps set up (PATH entries, JAVA_HOME) as Dockerfile ENV lines
+        #[arg(long, default_value = "false")]
+        print_env: bool,
+    },
+
     /// Install packages using apt-get
     #[command(name = "apt-get")]
     AptGet {
@@ -58,6 +310,12 @@ pub enum Commands {
 
         #[command(flatten)]
         ppa_args: PpaArgs,
+
+        #[command(flatten)]
+        apt_args: AptArgs,
+
+        #[command(flatten)]
+        pm_args: PackageManagerArgs,
     },
 
     /// Install packages using apt
@@ -67,30 +325,273 @@ pub enum Commands {
 
         #[command(flatten)]
         ppa_args: PpaArgs,
+
+        #[command(flatten)]
+        apt_args: AptArgs,
+
+        #[command(flatten)]
+        pm_args: PackageManagerArgs,
     },
 
     /// Install packages using aptitude
     Aptitude {
         /// Comma-separated list of packages to install
         packages: String,
+
+        #[command(flatten)]
+        apt_args: AptArgs,
+
+        #[command(flatten)]
+        pm_args: PackageManagerArgs,
     },
 
     /// Install packages using apk
     Apk {
-        /// Comma-separated list of packages to install
+        /// Comma-separated list of packages to install (use package@tag for pinned repositories)
         packages: String,
+
+        /// Additional repository URL, or @tag=URL to pin packages as package@tag; can be repeated
+        #[arg(long)]
+        repository: Vec<String>,
+
+        #[command(flatten)]
+        pm_args: PackageManagerArgs,
     },
 
     /// Install packages using Homebrew
     Brew {
         /// Comma-separated list of packages to install
         packages: String,
+
+        #[command(flatten)]
+        pm_args: PackageManagerArgs,
+    },
+
+    /// Upgrade installed system packages using the detected package manager
+    Upgrade {
+        /// Allow dependency changes (apt-get dist-upgrade, apk upgrade --available)
+        #[arg(long, default_value = "false")]
+        dist_upgrade: bool,
+
+        #[command(flatten)]
+        apt_args: AptArgs,
+
+        #[command(flatten)]
+        pm_args: PackageManagerArgs,
     },
 
     /// Install npm packages
     Npm {
-        /// Comma-separated list of packages to install
+        /// Comma-separated list of packages to install, optionally with a version or range (e.g. typescript@^5)
+        packages: String,
+
+        /// Package manager used for the global install (installed with npm if missing)
+        #[arg(long, value_enum, default_value_t = NodePackageManager::Npm)]
+        manager: NodePackageManager,
+
+        /// Registry URL to install from (defaults to the registry configured in .npmrc)
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Auth token for the registry (written to a temporary .npmrc, not passed on the command line)
+        #[arg(long)]
+        auth_token: Option<String>,
+
+        /// Install into a dedicated prefix (e.g. /opt/npm-tools) and add <prefix>/bin to PATH via /etc/profile.d/picolayer-env.sh
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Node.js version to install from nodejs.org instead of the distro package (e.g. 20, 22.11.0, lts)
+        #[arg(long)]
+        node_version: Option<String>,
+    },
+
+    /// Install snaps (requires a running snapd, so not inside containers)
+    Snap {
+        /// Comma-separated list of snaps to install
+        packages: String,
+
+        /// Channel to install from (e.g. latest/stable, 3.x/edge)
+        #[arg(long)]
+        channel: Option<String>,
+
+        /// Install with classic confinement
+        #[arg(long, default_value = "false")]
+        classic: bool,
+    },
+
+    /// Install Flatpak applications
+    Flatpak {
+        /// Comma-separated list of application IDs to install (e.g. org.gimp.GIMP)
+        packages: String,
+
+        /// Name of the remote to install from
+        #[arg(long, default_value = crate::installers::flatpak::FLATHUB_REMOTE)]
+        remote: String,
+
+        /// URL of the .flatpakrepo file for the remote
+        #[arg(long, default_value = crate::installers::flatpak::FLATHUB_URL)]
+        remote_url: String,
+
+        /// Install for the current user instead of system-wide
+        #[arg(long, default_value = "false")]
+        user: bool,
+    },
+
+    /// Install packages into the nix profile (installs a static nix if needed)
+    Nix {
+        /// Comma-separated list of packages (bare names resolve against --flake)
+        packages: String,
+
+        /// Flake that bare package names are resolved against
+        #[arg(long, default_value = "nixpkgs")]
+        flake: String,
+    },
+
+    /// Install Rust CLIs with cargo-binstall (bootstrapped if needed) or cargo install
+    Cargo {
+        /// Comma-separated list of crates to install, optionally with a version (e.g. ripgrep@14.1.0)
+        packages: String,
+
+        /// Directory to install binaries (default: PICOLAYER_INSTALL_DIR, install_dir from the config file, or /usr/local/bin)
+        #[arg(long)]
+        install_dir: Option<String>,
+
+        /// Always build from source with cargo install
+        #[arg(long, default_value = "false")]
+        no_binstall: bool,
+
+        /// Add the install directory to PATH for login shells via /etc/profile.d (the shell rc in --user-mode)
+        #[arg(long, default_value = "false")]
+        add_to_path: bool,
+    },
+
+    /// Install Go binaries with go install (provisions a temporary Go toolchain if needed)
+    #[command(name = "go-install")]
+    GoInstall {
+        /// Comma-separated list of module paths with versions (e.g. golang.org/x/tools/gopls@latest)
+        packages: String,
+
+        /// Directory to install binaries (default: PICOLAYER_INSTALL_DIR, install_dir from the config file, or /usr/local/bin)
+        #[arg(long)]
+        install_dir: Option<String>,
+
+        /// Go version to provision when go is not installed
+        #[arg(long, default_value = "latest")]
+        go_version: String,
+
+        /// Add the install directory to PATH for login shells via /etc/profile.d (the shell rc in --user-mode)
+        #[arg(long, default_value = "false")]
+        add_to_path: bool,
+    },
+
+    /// Install Ruby gems globally (installs Ruby if needed)
+    Gem {
+        /// Comma-separated list of gems to install, optionally with a version (e.g. fpm:1.15.1)
+        packages: String,
+
+        /// Generate rdoc/ri documentation (skipped by default)
+        #[arg(long, default_value = "false")]
+        with_docs: bool,
+    },
+
+    /// Install helm plugins from their GitHub release archives
+    #[command(name = "helm-plugin")]
+    HelmPlugin {
+        /// Comma-separated list of plugins as owner/repo, optionally with a version (e.g. databus23/helm-diff@v3.9.11)
+        plugins: String,
+
+        /// Plugin directory (defaults to `helm env HELM_PLUGINS`)
+        #[arg(long)]
+        plugins_dir: Option<String>,
+    },
+
+    /// Install Lua rocks with luarocks (installs luarocks if needed)
+    Luarocks {
+        /// Comma-separated list of rocks to install, optionally with a version (e.g. luacheck@1.2.0-1)
+        packages: String,
+
+        /// Lua version to install rocks for (Neovim uses LuaJIT, which is 5.1)
+        #[arg(long, default_value = "5.1")]
+        lua_version: String,
+    },
+
+    /// Install .NET tools with dotnet tool install
+    #[command(name = "dotnet-tool")]
+    DotnetTool {
+        /// Comma-separated list of tool package IDs, optionally with a version (e.g. dotnet-ef@8.0.0)
+        packages: String,
+
+        /// Directory the tool shims are installed to
+        #[arg(long, default_value = "/usr/local/bin")]
+        tool_path: String,
+    },
+
+    /// Install PHP CLIs with composer global require (bootstraps composer if needed)
+    Composer {
+        /// Comma-separated list of packages, optionally with a constraint (e.g. phpstan/phpstan:^1.10)
         packages: String,
+
+        /// Directory for globally required packages (COMPOSER_HOME)
+        #[arg(long, default_value = "/usr/local/share/composer")]
+        composer_home: String,
+
+        /// Directory package executables are linked into
+        #[arg(long, default_value = "/usr/local/bin")]
+        bin_dir: String,
+    },
+
+    /// Install a pinned runtime (deno, bun, node, zig) from its official releases
+    Runtime {
+        /// Runtime to install
+        #[arg(value_enum)]
+        runtime: RuntimePreset,
+
+        /// Version to install (e.g. 2.1.4, 20 for the newest 20.x, lts for node, latest)
+        #[arg(long, default_value = "latest")]
+        version: String,
+
+        /// Directory to install or link executables into (default: PICOLAYER_INSTALL_DIR, install_dir from the config file, or /usr/local/bin)
+        #[arg(long)]
+        install_dir: Option<String>,
+
+        /// Add the install directory to PATH for login shells via /etc/profile.d (the shell rc in --user-mode)
+        #[arg(long, default_value = "false")]
+        add_to_path: bool,
+    },
+
+    /// Install a JDK from Adoptium and set JAVA_HOME via /etc/profile.d
+    Jdk {
+        /// Feature release to install (e.g. 17, 21)
+        #[arg(long, default_value_t = 21)]
+        version: u32,
+
+        /// JDK distribution
+        #[arg(long, value_enum, default_value_t = JdkDistribution::Temurin)]
+        dist: JdkDistribution,
+
+        /// Directory the JDK is extracted into (becomes JAVA_HOME)
+        #[arg(long, default_value = "/opt/java")]
+        install_dir: String,
+    },
+
+    /// Create a Python virtualenv and install pinned requirements into it
+    Venv {
+        /// Directory to create the virtualenv in
+        #[arg(long)]
+        path: String,
+
+        /// requirements.txt to install into the virtualenv
+        #[arg(long)]
+        requirements: Option<String>,
+
+        /// Python version (e.g. 3.12) or interpreter (e.g. /usr/bin/python3)
+        #[arg(long)]
+        python: Option<String>,
+
+        /// Require every requirement to be pinned with --hash
+        #[arg(long, default_value = "false")]
+        require_hashes: bool,
     },
 
     /// Install Python packages using pipx
@@ -98,7 +599,19 @@ pub enum Commands {
         /// Python version to use (e.g., python3.9, python3.10)
         #[arg(long)]
         python: Option<String>,
-        /// Comma-separated list of packages to install
+        /// Extra arguments passed to pip (e.g. "--index-url https://example.com/simple")
+        #[arg(long, allow_hyphen_values = true)]
+        pip_args: Option<String>,
+
+        /// Install source for a single package: git URL (git+https://...), archive URL, or local path
+        #[arg(long)]
+        spec: Option<String>,
+
+        /// Directory app executables are linked into (added to PATH via /etc/profile.d if needed)
+        #[arg(long)]
+        bin_dir: Option<String>,
+
+        /// Comma-separated list of packages to install: requirements (e.g. black[d]==24.1.0), git URLs, or local paths
         packages: String,
     },
 
@@ -139,6 +652,14 @@ pub enum Commands {
         /// Registry bearer token for authentication
         #[arg(long)]
         registry_token: Option<String>,
+        /// Start the install script with only PATH, HOME, locale, and terminal variables from
+        /// picolayer's environment, plus any --env-pass names
+        #[arg(long)]
+        env_clean: bool,
+
+        /// Variable to keep with --env-clean, e.g. HTTPS_PROXY (repeatable)
+        #[arg(long, value_name = "VAR", requires = "env_clean")]
+        env_pass: Vec<String>,
     },
 
     /// Install binary from GitHub release
@@ -160,14 +681,32 @@ pub enum Commands {
         #[arg(long, default_value = "latest")]
         version: String,
 
-        /// Directory to install binaries
-        #[arg(long, default_value = "/usr/local/bin")]
-        install_dir: String,
+        /// Only consider tags starting with this prefix, e.g. `cli-v` in a monorepo that also
+        /// tags `agent-v2.0.0`; it is added to a --version that lacks it
+        #[arg(long, value_name = "PREFIX", value_parser = non_empty_string)]
+        tag_prefix: Option<String>,
+
+        /// Directory to install binaries (default: PICOLAYER_INSTALL_DIR, install_dir from the config file, or /usr/local/bin)
+        #[arg(long)]
+        install_dir: Option<String>,
 
         /// Regex pattern for asset filtering
         #[arg(long)]
         filter: Option<String>,
 
+        /// Add the install directory to PATH for login shells via /etc/profile.d (the shell rc in --user-mode)
+        #[arg(long, default_value = "false")]
+        add_to_path: bool,
+
+        /// Owner of the installed binaries as user:group, user, or :group; numeric ids are
+        /// used as they are, e.g. for a rootfs whose users this host does not have
+        #[arg(long, value_name = "USER:GROUP", value_parser = non_empty_string)]
+        chown: Option<String>,
+
+        /// Permissions of the installed binaries in octal (default: 755)
+        #[arg(long, value_name = "MODE", value_parser = file_mode)]
+        mode: Option<u32>,
+
         /// Verify checksums using checksum files
         #[arg(long, default_value = "false", conflicts_with = "checksum_text")]
         verify_checksum: bool,
@@ -176,6 +715,10 @@ pub enum Commands {
         #[arg(long, conflicts_with = "verify_checksum")]
         checksum_text: Option<String>,
 
+        /// Checksum files to prefer with --verify-checksum when a release publishes several
+        #[arg(long, value_enum, default_value_t = ChecksumAlgorithm::Sha256)]
+        checksum_algo: ChecksumAlgorithm,
+
         /// GPG public key for signature verification (can be a URL, file path, or key content)
         #[arg(long)]
         gpg_key: Option<String>,
@@ -183,6 +726,20 @@ pub enum Commands {
         /// Include prerelease versions
         #[arg(long, default_value = "false")]
         include_prerelease: bool,
+
+        /// Install from the source tarball of a tag (--version, or the newest version tag),
+        /// for repositories that tag versions without publishing releases
+        #[arg(long, conflicts_with_all = ["verify_checksum", "gpg_key", "filter", "include_prerelease"])]
+        from_tag: bool,
+
+        /// Poll until the release and the asset to install are published, for pipelines that
+        /// build images right after cutting a release
+        #[arg(long, conflicts_with = "from_tag")]
+        wait_for_release: bool,
+
+        /// How long --wait-for-release polls before giving up (e.g. 15m, 1h)
+        #[arg(long, value_name = "DURATION", value_parser = age, default_value = "15m")]
+        wait_timeout: Duration,
     },
 
     /// Run a command using pkgx
@@ -206,7 +763,84 @@ pub enum Commands {
         /// Environment variables (key=value pairs)
         #[arg(long)]
         env: Vec<String>,
+
+        /// Sync the pantry cached under the cache dir again once it is this old (e.g. 12h, 7d)
+        #[arg(long, value_name = "AGE", value_parser = age, default_value = "1d")]
+        pantry_max_age: Duration,
+
+        /// Also write the tool's stdout to this file, for scripts that parse it
+        #[arg(long, value_name = "FILE")]
+        capture_output: Option<String>,
+        /// Start the tool with only PATH, HOME, locale, and terminal variables from
+        /// picolayer's environment, plus any --env-pass names
+        #[arg(long)]
+        env_clean: bool,
+
+        /// Variable to keep with --env-clean, e.g. HTTPS_PROXY (repeatable)
+        #[arg(long, value_name = "VAR", requires = "env_clean")]
+        env_pass: Vec<String>,
+    },
+
+    /// Remove caches, package lists, and temp files to minimize the layer
+    Clean {
+        /// Also remove documentation and package manager logs
+        #[arg(long)]
+        aggressive: bool,
+
+        /// Leave a target untouched (repeatable)
+        #[arg(long, value_enum)]
+        skip: Vec<CleanTarget>,
+
+        /// Show what would be removed and how much space it takes, without removing it
+        #[arg(long)]
+        dry_run: bool,
     },
+
+    /// Manage picolayer's artifact cache (default: PICOLAYER_CACHE_DIR, or cache_dir from the config file)
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+
+    /// Check the environment for common causes of install failures
+    Doctor,
+
+    /// List what picolayer has installed, from its state database
+    List,
+
+    /// Check installed files and versions against the state database and a lockfile
+    Verify {
+        /// Lockfile to compare against (default: picolayer.lock in the current directory, if any)
+        lockfile: Option<String>,
+    },
+
+    /// Print the exit status picolayer uses for each class of failure
+    #[command(name = "exit-codes")]
+    ExitCodes,
+
+    /// Render a manifest in another build format
+    Generate {
+        #[command(subcommand)]
+        target: GenerateTarget,
+    },
+
+    /// Remove something picolayer installed, using the removal logic for how it was installed
+    Uninstall {
+        /// Names as shown by `picolayer list` (comma-separated)
+        names: String,
+    },
+
+    /// Replace this picolayer binary with a checksum-verified release
+    #[command(name = "self-update")]
+    SelfUpdate {
+        /// Release tag to install (e.g. "v0.6.0", "latest")
+        #[arg(long, default_value = "latest")]
+        version: String,
+    },
+
+    /// Any other command runs the `picolayer-<command>` plugin found on PATH
+    #[command(external_subcommand)]
+    Plugin(Vec<String>),
 }
 
 /// Common PPA arguments for apt-based installers
@@ -221,6 +855,38 @@ pub struct PpaArgs {
     pub force_ppas_on_non_ubuntu: bool,
 }
 
+/// Common arguments for system package manager installers
+#[derive(clap::Args)]
+pub struct PackageManagerArgs {
+    /// Preview what would be installed without changing the system
+    #[arg(long, default_value = "false")]
+    pub dry_run: bool,
+
+    /// HTTP(S) proxy for reaching package mirrors (e.g. http://proxy:3128)
+    #[arg(long)]
+    pub pkg_proxy: Option<String>,
+
+    /// Keep the package cache (e.g. for BuildKit cache mounts) instead of cleaning it
+    #[arg(long, default_value = "false")]
+    pub keep_cache: bool,
+}
+
+/// Common arguments for apt-based installers
+#[derive(clap::Args)]
+pub struct AptArgs {
+    /// Seconds to wait for the dpkg lock when another apt/dpkg process holds it
+    #[arg(long, default_value = "300")]
+    pub lock_timeout: u64,
+
+    /// Foreign architecture to enable and install packages for (e.g. i386); can be repeated
+    #[arg(long = "arch")]
+    pub architectures: Vec<String>,
+
+    /// apt pin as PACKAGE:PIN:PRIORITY (e.g. 'nginx:release a=bookworm-backports:900'); can be repeated
+    #[arg(long = "pin")]
+    pub pins: Vec<String>,
+}
+
 fn non_empty_string(s: &str) -> Result<String, String> {
     let trimmed = s.trim();
     if trimmed.is_empty() {
@@ -230,6 +896,78 @@ fn non_empty_string(s: &str) -> Result<String, String> {
     }
 }
 
+fn status_list(s: &str) -> Result<String, String> {
+    match s
+        .split(',')
+        .map(str::trim)
+        .find(|status| !matches!(status.parse::<u16>(), Ok(100..=599)))
+    {
+        Some(status) => Err(format!("'{}' is not an HTTP status", status)),
+        None => Ok(s.to_string()),
+    }
+}
+
+/// Octal permission bits, e.g. `755` or `0750`
+fn file_mode(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s.trim(), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("'{}' is not an octal file mode such as 755", s)),
+    }
+}
+
+/// A size in bytes, with an optional K, M, G, or T suffix (powers of 1024), e.g. `2G`
+fn byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = &s[digits.len()..];
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("'{}' is not a size like 500M or 2G", s)),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("'{}' is not a size like 500M or 2G", s))
+}
+
+/// An age with an s, m, h, d, or w suffix, e.g. `30d`
+fn age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let invalid = || format!("'{}' is not an age like 12h or 30d", s);
+    let Some((last, _)) = s.char_indices().last() else {
+        return Err(invalid());
+    };
+    let (digits, unit) = s.split_at(last);
+    let secs: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(secs))
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Parse a comma-separated list of HTTP statuses, e.g. `429,503`
+pub fn parse_status_list(input: &str) -> Vec<u16> {
+    input
+        .split(',')
+        .filter_map(|status| status.trim().parse().ok())
+        .collect()
+}
+
 /// Parse comma-separated string into a vector of trimmed strings
 pub fn normalize_package_list(input: &str) -> Vec<String> {
     let result: Vec<String> = input
@@ -243,7 +981,50 @@ pub fn normalize_package_list(input: &str) -> Vec<String> {
     result
 }
 
+/// Split a comma-separated list of Python requirements, keeping commas that belong
+/// to extras (`black[d,jupyter]`) or version ranges (`ruff>=0.4,<0.5`) intact
+pub fn normalize_requirement_list(input: &str) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+
+    let push = |segment: &str, result: &mut Vec<String>| {
+        let segment = segment.trim();
+        if segment.is_empty() {
+            return;
+        }
+        match result.last_mut() {
+            Some(last) if segment.starts_with(['<', '>', '=', '!', '~']) => {
+                last.push(',');
+                last.push_str(segment);
+            }
+            _ => result.push(segment.to_string()),
+        }
+    };
+
+    for c in input.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                push(&current, &mut result);
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    push(&current, &mut result);
+
+    if result.is_empty() {
+        warn!("Package list is empty after normalization: '{}'", input);
+    }
+    result
+}
+
 /// Parse key=value pairs into a HashMap
+#[cfg(feature = "devcontainer")]
 pub fn parse_key_value_pairs(pairs: &[String]) -> Option<HashMap<String, String>> {
     if pairs.is_empty() {
         return None;
@@ -265,6 +1046,39 @@ pub fn parse_key_value_pairs(pairs: &[String]) -> Option<HashMap<String, String>
 mod tests {
     use super::*;
 
+    #[test]
+    fn status_list_rejects_non_statuses() {
+        assert!(status_list("429, 503").is_ok());
+        assert!(status_list("429,abc").is_err());
+        assert!(status_list("99").is_err());
+        assert_eq!(parse_status_list("429, 503"), [429, 503]);
+    }
+
+    #[test]
+    fn file_mode_is_octal() {
+        assert_eq!(file_mode("755"), Ok(0o755));
+        assert_eq!(file_mode("0750"), Ok(0o750));
+        assert!(file_mode("789").is_err());
+        assert!(file_mode("17777").is_err());
+    }
+
+    #[test]
+    fn byte_size_accepts_binary_suffixes() {
+        assert_eq!(byte_size("2G"), Ok(2 << 30));
+        assert_eq!(byte_size("500MiB"), Ok(500 << 20));
+        assert_eq!(byte_size("1024"), Ok(1024));
+        assert!(byte_size("2X").is_err());
+        assert!(byte_size("G").is_err());
+    }
+
+    #[test]
+    fn age_accepts_unit_suffixes() {
+        assert_eq!(age("30d"), Ok(Duration::from_secs(30 * 24 * 60 * 60)));
+        assert_eq!(age("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert!(age("30").is_err());
+        assert!(age("d").is_err());
+    }
+
     #[test]
     fn normalize_package_list_basic() {
         let result = normalize_package_list("foo,bar,baz");
@@ -296,6 +1110,16 @@ mod tests {
     }
 
     #[test]
+    fn normalize_requirement_list_keeps_extras_and_ranges() {
+        let result = normalize_requirement_list("black[d,jupyter]==24.1.0, ruff>=0.4,<0.5,httpie");
+        assert_eq!(
+            result,
+            vec!["black[d,jupyter]==24.1.0", "ruff>=0.4,<0.5", "httpie"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devcontainer")]
     fn parse_key_value_pairs_basic() {
         let pairs = vec!["key1=val1".to_string(), "key2=val2".to_string()];
         let result = parse_key_value_pairs(&pairs).unwrap();
@@ -304,12 +1128,14 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "devcontainer")]
     fn parse_key_value_pairs_empty_input() {
         let result = parse_key_value_pairs(&[]);
         assert!(result.is_none());
     }
 
     #[test]
+    #[cfg(feature = "devcontainer")]
     fn parse_key_value_pairs_malformed_entries_dropped() {
         let pairs = vec!["key1=val1".to_string(), "not-a-pair".to_string()];
         let result = parse_key_value_pairs(&pairs).unwrap();
@@ -318,6 +1144,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "devcontainer")]
     fn parse_key_value_pairs_all_malformed() {
         let pairs = vec!["no-equals".to_string(), "another-one".to_string()];
         let result = parse_key_value_pairs(&pairs);
@@ -325,6 +1152,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "devcontainer")]
     fn parse_key_value_pairs_value_with_equals() {
         // Values containing '=' should keep everything after the first '='
         let pairs = vec!["key=val=ue".to_string()];
@@ -0,0 +1,382 @@
+use super::args::{
+    CacheCommand, Commands, RuntimePreset, normalize_package_list, normalize_requirement_list,
+};
+use crate::utils::settings;
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Write};
+
+/// Show what `command` will do and ask before doing it
+pub fn confirm(command: &Commands) -> Result<()> {
+    let plan = plan(command)?;
+    if plan.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("picolayer will:");
+    for line in &plan {
+        eprintln!("  - {}", line);
+    }
+    anyhow::ensure!(
+        std::io::stdin().is_terminal(),
+        "--confirm needs a terminal to prompt on; pass --yes to proceed without prompting"
+    );
+    eprint!("Proceed? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read the answer")?;
+    anyhow::ensure!(
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        "Cancelled, nothing was changed"
+    );
+    Ok(())
+}
+
+/// One line per change `command` makes. Commands that change nothing, such as
+/// `list` or a `--dry-run`, have an empty plan and are not prompted for.
+fn plan(command: &Commands) -> Result<Vec<String>> {
+    let list = |packages: &str| normalize_package_list(packages).join(", ");
+    let plan = match command {
+        Commands::Apply { manifest, .. } => super::apply::step_commands(manifest)?
+            .iter()
+            .enumerate()
+            .map(|(i, (step, hooks))| {
+                let pre = hooks.pre.iter().map(|hook| format!("run `{}`", hook));
+                let post = hooks.post.iter().map(|hook| format!("run `{}`", hook));
+                Ok(pre
+                    .chain(plan(step)?)
+                    .chain(post)
+                    .map(|line| format!("step {}: {}", i + 1, line))
+                    .collect::<Vec<_>>())
+            })
+            .collect::<Result<Vec<_>>>()?
+            .concat(),
+
+        Commands::AptGet {
+            packages,
+            ppa_args,
+            pm_args,
+            ..
+        }
+        | Commands::Apt {
+            packages,
+            ppa_args,
+            pm_args,
+            ..
+        } if !pm_args.dry_run => {
+            let tool = match command {
+                Commands::AptGet { .. } => "apt-get",
+                _ => "apt",
+            };
+            let mut plan = Vec::new();
+            if let Some(ppas) = &ppa_args.ppas {
+                plan.push(format!("add PPAs {}", list(ppas)));
+            }
+            plan.push(format!("install {} with {}", list(packages), tool));
+            plan
+        }
+        Commands::Aptitude {
+            packages, pm_args, ..
+        } if !pm_args.dry_run => vec![format!("install {} with aptitude", list(packages))],
+        Commands::Apk {
+            packages,
+            repository,
+            pm_args,
+        } if !pm_args.dry_run => {
+            let mut plan: Vec<String> = repository
+                .iter()
+                .map(|repository| format!("add apk repository {}", repository))
+                .collect();
+            plan.push(format!("install {} with apk", list(packages)));
+            plan
+        }
+        Commands::Brew { packages, pm_args } if !pm_args.dry_run => {
+            vec![format!("install {} with brew", list(packages))]
+        }
+        Commands::Upgrade {
+            dist_upgrade,
+            pm_args,
+            ..
+        } if !pm_args.dry_run => vec![if *dist_upgrade {
+            "upgrade every system package, allowing dependency changes".to_string()
+        } else {
+            "upgrade every system package".to_string()
+        }],
+        Commands::AptGet { .. }
+        | Commands::Apt { .. }
+        | Commands::Aptitude { .. }
+        | Commands::Apk { .. }
+        | Commands::Brew { .. }
+        | Commands::Upgrade { .. } => Vec::new(),
+
+        Commands::Npm {
+            packages,
+            manager,
+            registry,
+            prefix,
+            node_version,
+            ..
+        } => {
+            let mut plan = Vec::new();
+            if let Some(version) = node_version {
+                plan.push(format!(
+                    "install Node.js {} from https://nodejs.org/dist/",
+                    version
+                ));
+            }
+            let manager = format!("{:?}", manager).to_lowercase();
+            let mut line = format!("install {} globally with {}", list(packages), manager);
+            if let Some(registry) = registry {
+                line.push_str(&format!(" from {}", registry));
+            }
+            if let Some(prefix) = prefix {
+                line.push_str(&format!(" into {}", prefix));
+            }
+            plan.push(line);
+            plan
+        }
+        Commands::Snap {
+            packages, channel, ..
+        } => vec![match channel {
+            Some(channel) => format!("install snaps {} from {}", list(packages), channel),
+            None => format!("install snaps {}", list(packages)),
+        }],
+        Commands::Flatpak {
+            packages,
+            remote,
+            remote_url,
+            ..
+        } => vec![format!(
+            "install {} from flatpak remote {} ({})",
+            list(packages),
+            remote,
+            remote_url
+        )],
+        Commands::Nix { packages, flake } => vec![
+            "install a static nix from https://hydra.nixos.org if nix is missing".to_string(),
+            format!(
+                "install {} from {} into the nix profile",
+                list(packages),
+                flake
+            ),
+        ],
+        Commands::Cargo {
+            packages,
+            install_dir,
+            no_binstall,
+            ..
+        } => vec![format!(
+            "{} {} into {}",
+            if *no_binstall {
+                "build and install"
+            } else {
+                "install"
+            },
+            list(packages),
+            settings::install_dir(install_dir.clone())
+        )],
+        Commands::GoInstall {
+            packages,
+            install_dir,
+            ..
+        } => vec![format!(
+            "go install {} into {}",
+            list(packages),
+            settings::install_dir(install_dir.clone())
+        )],
+        Commands::Gem { packages, .. } => vec![format!("install gems {}", list(packages))],
+        Commands::HelmPlugin { plugins, .. } => vec![format!(
+            "install helm plugins {} from their GitHub releases",
+            list(plugins)
+        )],
+        Commands::Luarocks {
+            packages,
+            lua_version,
+        } => vec![format!(
+            "install rocks {} for Lua {}",
+            list(packages),
+            lua_version
+        )],
+        Commands::DotnetTool {
+            packages,
+            tool_path,
+        } => vec![format!(
+            "install .NET tools {} into {}",
+            list(packages),
+            tool_path
+        )],
+        Commands::Composer {
+            packages, bin_dir, ..
+        } => vec![
+            "install composer from https://getcomposer.org/installer if it is missing".to_string(),
+            format!(
+                "composer global require {}, linking executables into {}",
+                list(packages),
+                bin_dir
+            ),
+        ],
+        Commands::Runtime {
+            runtime,
+            version,
+            install_dir,
+            ..
+        } => {
+            let source = match runtime {
+                RuntimePreset::Deno => "https://github.com/denoland/deno/releases",
+                RuntimePreset::Bun => "https://github.com/oven-sh/bun/releases",
+                RuntimePreset::Node => "https://nodejs.org/dist/",
+                RuntimePreset::Zig => "https://ziglang.org/download/",
+            };
+            vec![format!(
+                "install {:?} {} from {} into {}",
+                runtime,
+                version,
+                source,
+                settings::install_dir(install_dir.clone())
+            )]
+        }
+        Commands::Jdk {
+            version,
+            dist,
+            install_dir,
+        } => vec![format!(
+            "install {:?} JDK {} from https://api.adoptium.net into {}",
+            dist, version, install_dir
+        )],
+        Commands::Venv {
+            path, requirements, ..
+        } => vec![match requirements {
+            Some(requirements) => format!(
+                "create a virtualenv in {} and install {} into it",
+                path, requirements
+            ),
+            None => format!("create a virtualenv in {}", path),
+        }],
+        Commands::Pipx { packages, spec, .. } => vec![match spec {
+            Some(spec) => format!("install {} with pipx from {}", packages, spec),
+            None => format!(
+                "install {} with pipx",
+                normalize_requirement_list(packages).join(", ")
+            ),
+        }],
+        Commands::DevcontainerFeature {
+            feature,
+            option,
+            script,
+            ..
+        } => {
+            let mut line = format!("pull {} and run its {} as root", feature, script);
+            if !option.is_empty() {
+                line.push_str(&format!(" with {}", option.join(" ")));
+            }
+            vec![line]
+        }
+        Commands::GhRelease {
+            owner,
+            repo,
+            binary,
+            version,
+            install_dir,
+            from_tag,
+            ..
+        } => vec![format!(
+            "install {} ({}) from https://github.com/{}/{}/{} into {}",
+            binary.as_deref().unwrap_or(repo),
+            version,
+            owner,
+            repo,
+            if *from_tag { "tags" } else { "releases" },
+            settings::install_dir(install_dir.clone())
+        )],
+        Commands::Pkgx {
+            tool,
+            version,
+            args,
+            working_dir,
+            ..
+        } => vec![format!(
+            "run {}@{} {} in {}, fetching it with pkgx",
+            tool,
+            version,
+            args.join(" "),
+            working_dir
+        )],
+        Commands::Clean {
+            aggressive,
+            dry_run: false,
+            ..
+        } => vec![if *aggressive {
+            "remove caches, package lists, temporary files, and documentation".to_string()
+        } else {
+            "remove caches, package lists, and temporary files".to_string()
+        }],
+        Commands::Cache {
+            action: CacheCommand::Prune { dry_run: false, .. },
+        } => vec![format!(
+            "remove cached artifacts from {}",
+            settings::cache_dir().display()
+        )],
+        Commands::Uninstall { names } => vec![format!("uninstall {}", list(names))],
+        Commands::SelfUpdate { version, .. } => vec![format!(
+            "replace this picolayer with release {} from https://github.com/skevetter/picolayer/releases",
+            version
+        )],
+        Commands::Plugin(args) => vec![format!(
+            "run the picolayer-{} plugin",
+            args.first().map(String::as_str).unwrap_or_default()
+        )],
+        Commands::Clean { .. }
+        | Commands::Cache { .. }
+        | Commands::Doctor
+        | Commands::List
+        | Commands::Verify { .. }
+        | Commands::ExitCodes
+        | Commands::Generate { .. } => Vec::new(),
+    };
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn plan_for(args: &[&str]) -> Vec<String> {
+        let cli =
+            crate::cli::Cli::parse_from(std::iter::once("picolayer").chain(args.iter().copied()));
+        plan(&cli.command).unwrap()
+    }
+
+    #[test]
+    fn plan_names_packages_and_sources() {
+        assert_eq!(
+            plan_for(&[
+                "gh-release",
+                "--owner",
+                "cli",
+                "--repo",
+                "cli",
+                "--binary",
+                "gh",
+                "--install-dir",
+                "/opt/bin"
+            ]),
+            ["install gh (latest) from https://github.com/cli/cli/releases into /opt/bin"]
+        );
+        assert_eq!(
+            plan_for(&["apt-get", "curl,git", "--ppas", "ppa:git-core/ppa"]),
+            [
+                "add PPAs ppa:git-core/ppa",
+                "install curl, git with apt-get"
+            ]
+        );
+    }
+
+    #[test]
+    fn read_only_commands_have_no_plan() {
+        assert!(plan_for(&["list"]).is_empty());
+        assert!(plan_for(&["apt-get", "curl", "--dry-run"]).is_empty());
+        assert!(plan_for(&["clean", "--dry-run"]).is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+use super::OutputFormat;
+use super::output;
+use crate::error::EXIT_CODES;
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ExitCode {
+    code: i32,
+    meaning: &'static str,
+}
+
+pub fn run(output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Json {
+        let codes: Vec<ExitCode> = EXIT_CODES
+            .into_iter()
+            .map(|(code, meaning)| ExitCode { code, meaning })
+            .collect();
+        return output::set_details(&codes);
+    }
+
+    for (code, meaning) in EXIT_CODES {
+        println!("{:>3}  {}", code, meaning);
+    }
+    Ok(())
+}
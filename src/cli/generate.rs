@@ -0,0 +1,299 @@
+use super::OutputFormat;
+use super::apply::{HOOK_PRELUDE, Hooks, Manifest, step_args};
+use super::output;
+use crate::utils::lock::Lockfile;
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+
+/// Install script used to add a pinned picolayer to the image
+const INSTALL_SCRIPT_URL: &str =
+    "https://raw.githubusercontent.com/skevetter/picolayer/main/install.sh";
+
+/// A BuildKit cache mount and the `clean` target that must leave it alone
+struct CacheMount {
+    target: &'static str,
+    locked: bool,
+    clean_target: &'static str,
+}
+
+const APT_CACHES: &[CacheMount] = &[
+    CacheMount {
+        target: "/var/cache/apt",
+        locked: true,
+        clean_target: "package-cache",
+    },
+    CacheMount {
+        target: "/var/lib/apt/lists",
+        locked: true,
+        clean_target: "apt-lists",
+    },
+];
+const APK_CACHES: &[CacheMount] = &[CacheMount {
+    target: "/var/cache/apk",
+    locked: true,
+    clean_target: "package-cache",
+}];
+const NPM_CACHES: &[CacheMount] = &[CacheMount {
+    target: "/root/.npm",
+    locked: false,
+    clean_target: "npm",
+}];
+const PIP_CACHES: &[CacheMount] = &[CacheMount {
+    target: "/root/.cache/pip",
+    locked: false,
+    clean_target: "pip",
+}];
+
+/// Cache mounts that speed up a command across builds
+fn cache_mounts(command: &str) -> &'static [CacheMount] {
+    match command {
+        "apt-get" | "apt" | "aptitude" | "devcontainer-feature" => APT_CACHES,
+        "apk" => APK_CACHES,
+        "npm" => NPM_CACHES,
+        "pipx" | "venv" => PIP_CACHES,
+        _ => &[],
+    }
+}
+
+/// Print a Dockerfile fragment that runs a manifest in a single cached RUN instruction
+pub fn dockerfile(manifest_path: &str, output: OutputFormat) -> Result<()> {
+    let manifest = Manifest::read(manifest_path)?;
+    let lock_path = Manifest::lock_path(manifest_path);
+    let lockfile = if lock_path.exists() {
+        Some(Lockfile::read(&lock_path)?)
+    } else {
+        None
+    };
+    let fragment = render(manifest_path, &manifest, lockfile.as_ref())?;
+
+    if output == OutputFormat::Json {
+        return output::set_details(&serde_json::json!({ "dockerfile": fragment }));
+    }
+    print!("{}", fragment);
+    Ok(())
+}
+
+fn render(manifest_path: &str, manifest: &Manifest, lockfile: Option<&Lockfile>) -> Result<String> {
+    let mut mounts: Vec<&CacheMount> = Vec::new();
+    let mut commands = Vec::new();
+    // A single clean runs last, after every install, whatever the manifest order
+    let mut clean = serde_yaml::Mapping::new();
+
+    for (i, step) in manifest.steps.iter().enumerate() {
+        let number = i + 1;
+        let (name, options) = step
+            .iter()
+            .next()
+            .with_context(|| format!("Step {} in {} is empty", number, manifest_path))?;
+        let mut options = match options {
+            serde_yaml::Value::Null => serde_yaml::Mapping::new(),
+            serde_yaml::Value::Mapping(options) => options.clone(),
+            _ => anyhow::bail!("Options for '{}' must be a mapping", name),
+        };
+        // Steps run one after another in a RUN instruction
+        options.remove("after");
+        let hooks = Hooks::take(&mut options)
+            .with_context(|| format!("Invalid step {} in {}", number, manifest_path))?;
+        let pre = hooks
+            .pre
+            .iter()
+            .map(|hook| hook_invocation(number, hook))
+            .collect::<Result<Vec<_>>>()?;
+        let post = hooks
+            .post
+            .iter()
+            .map(|hook| hook_invocation(number, hook))
+            .collect::<Result<Vec<_>>>()?;
+
+        if name == "clean" {
+            clean.extend(options);
+            continue;
+        }
+        if name == "gh-release"
+            && let Some(tag) = locked_version(lockfile, number)
+        {
+            options.insert("version".into(), tag.into());
+        }
+        for mount in cache_mounts(name) {
+            if !mounts.iter().any(|m| m.target == mount.target) {
+                mounts.push(mount);
+            }
+        }
+        if matches!(name.as_str(), "apt-get" | "apt" | "aptitude" | "apk") {
+            options.insert("keep-cache".into(), true.into());
+        }
+
+        let args = step_args(name, &serde_yaml::Value::Mapping(options))
+            .with_context(|| format!("Invalid step {} in {}", number, manifest_path))?;
+        commands.extend(pre);
+        commands.push(invocation(&args));
+        commands.extend(post);
+    }
+
+    // Cleaning a cache mount would throw away the cache it exists to keep
+    let mut skip: BTreeSet<String> = match clean.remove("skip") {
+        Some(serde_yaml::Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(serde_yaml::Value::String(item)) => BTreeSet::from([item]),
+        _ => BTreeSet::new(),
+    };
+    skip.extend(mounts.iter().map(|m| m.clean_target.to_string()));
+    if !skip.is_empty() {
+        clean.insert(
+            "skip".into(),
+            serde_yaml::Value::Sequence(skip.into_iter().map(Into::into).collect()),
+        );
+    }
+    commands.push(invocation(&step_args(
+        "clean",
+        &serde_yaml::Value::Mapping(clean),
+    )?));
+
+    let mut fragment = format!(
+        "# Generated by picolayer {} from {}; regenerate after editing the manifest\n",
+        env!("CARGO_PKG_VERSION"),
+        manifest_path
+    );
+    fragment.push_str("# Requires BuildKit, and curl and bash in the base image\n");
+    fragment.push_str("RUN");
+    for mount in &mounts {
+        let sharing = if mount.locked { ",sharing=locked" } else { "" };
+        fragment.push_str(&format!(
+            " --mount=type=cache,target={}{} \\\n   ",
+            mount.target, sharing
+        ));
+    }
+    fragment.push_str(" set -eux; \\\n");
+    fragment.push_str(&format!(
+        "    curl -fsSL {} | PICOLAYER_VERSION=v{} bash; \\\n",
+        INSTALL_SCRIPT_URL,
+        env!("CARGO_PKG_VERSION")
+    ));
+    let last = commands.len() - 1;
+    for (i, command) in commands.iter().enumerate() {
+        let separator = if i == last { "\n" } else { "; \\\n" };
+        fragment.push_str(&format!("    {}{}", command, separator));
+    }
+    Ok(fragment)
+}
+
+/// The release tag the lockfile resolved for a gh-release step
+fn locked_version(lockfile: Option<&Lockfile>, step: usize) -> Option<&str> {
+    lockfile?
+        .artifacts
+        .iter()
+        .find(|a| a.step == step && a.command == "gh-release")
+        .map(|a| a.version.as_str())
+}
+
+/// A hook as its own `sh -ec`, so a hook's `set +e` or `exit` stays within it. RUN lines
+/// are joined with `\`, which a hook spanning lines would break.
+fn hook_invocation(step: usize, hook: &str) -> Result<String> {
+    anyhow::ensure!(
+        !hook.contains('\n'),
+        "Step {} has a hook spanning several lines, which cannot be written into a RUN instruction",
+        step
+    );
+    Ok(format!(
+        "sh -ec {}",
+        shell_quote(&format!("{}{}", HOOK_PRELUDE, hook))
+    ))
+}
+
+fn invocation(args: &[String]) -> String {
+    std::iter::once("picolayer".to_string())
+        .chain(args.iter().map(|arg| shell_quote(arg)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote an argument for /bin/sh unless it only has characters the shell leaves alone
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::lock::LockedArtifact;
+
+    #[test]
+    fn shell_quote_only_quotes_when_needed() {
+        assert_eq!(shell_quote("--packages=curl,git"), "--packages=curl,git");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn render_pins_versions_and_cleans_last() {
+        let manifest: Manifest = serde_yaml::from_str(
+            "steps:
+  - clean:
+      aggressive: true
+  - apt-get:
+      packages: [curl, git]
+  - gh-release:
+      owner: cli
+      repo: cli
+      binary: gh
+      after: 1
+",
+        )
+        .unwrap();
+        let lockfile = Lockfile {
+            version: 1,
+            artifacts: vec![LockedArtifact {
+                step: 3,
+                command: "gh-release".to_string(),
+                name: "cli/cli".to_string(),
+                version: "v2.60.0".to_string(),
+                url: String::new(),
+                sha256: String::new(),
+            }],
+        };
+
+        let fragment = render("picolayer.yaml", &manifest, Some(&lockfile)).unwrap();
+        assert!(fragment.contains("--mount=type=cache,target=/var/lib/apt/lists,sharing=locked"));
+        assert!(fragment.contains("picolayer apt-get --keep-cache -- curl,git; \\\n"));
+        assert!(fragment.contains("--version=v2.60.0"));
+        assert!(
+            fragment
+                .ends_with("picolayer clean --aggressive --skip=apt-lists --skip=package-cache\n")
+        );
+    }
+
+    #[test]
+    fn render_runs_hooks_around_their_step() {
+        let manifest: Manifest = serde_yaml::from_str(
+            "steps:
+  - npm:
+      packages: [typescript]
+      pre: useradd -m dev
+      post: tsc --version
+",
+        )
+        .unwrap();
+        let fragment = render("picolayer.yaml", &manifest, None).unwrap();
+        let pre = fragment.find("useradd -m dev'").unwrap();
+        let install = fragment.find("picolayer npm").unwrap();
+        let post = fragment.find("tsc --version'").unwrap();
+        assert!(pre < install && install < post);
+        assert!(fragment.contains("sh -ec 'for f in /etc/profile.d/picolayer-*.sh;"));
+
+        let multiline: Manifest =
+            serde_yaml::from_str("steps:\n  - npm:\n      packages: a\n      pre: \"a\\nb\"\n")
+                .unwrap();
+        assert!(render("picolayer.yaml", &multiline, None).is_err());
+    }
+}
@@ -1,12 +1,66 @@
-use super::RetryConfig;
-use super::args::{Commands, normalize_package_list, parse_key_value_pairs};
+#[cfg(feature = "devcontainer")]
+use super::args::parse_key_value_pairs;
+use super::args::{
+    CacheCommand, Commands, GenerateTarget, normalize_package_list, normalize_requirement_list,
+};
+use super::{OutputFormat, RetryConfig};
 use crate::installers;
 use crate::utils;
 use anyhow::Result;
+use log::{info, warn};
+use std::time::Duration;
+
+pub async fn handle_command(
+    command: Commands,
+    retry_config: &RetryConfig,
+    output: OutputFormat,
+) -> Result<()> {
+    if super::presence::is_enabled()
+        && let Some(reason) = super::presence::satisfied(&command)
+    {
+        info!("Skipping install: {}", reason);
+        return super::output::set_details(&serde_json::json!({ "skipped": reason }));
+    }
+    if utils::user_mode::is_enabled()
+        && let Some(manager) = system_package_manager(&command)
+    {
+        let reason = format!(
+            "{} installs system-wide, which --user-mode does not do",
+            manager
+        );
+        warn!("Skipping install: {}", reason);
+        return super::output::set_details(&serde_json::json!({ "skipped": reason }));
+    }
+    super::policy::check(&command)?;
 
-pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Result<()> {
     match command {
-        Commands::AptGet { packages, ppa_args } => {
+        Commands::Apply {
+            manifest,
+            frozen,
+            report_size,
+            jobs,
+            print_env,
+        } => {
+            super::apply::run(
+                &super::apply::ApplyConfig {
+                    manifest: &manifest,
+                    frozen,
+                    report_size,
+                    jobs,
+                    print_env,
+                },
+                retry_config,
+                output,
+            )
+            .await
+        }
+
+        Commands::AptGet {
+            packages,
+            ppa_args,
+            apt_args,
+            pm_args,
+        } => {
             anyhow::ensure!(
                 utils::os::is_debian_like(),
                 "apt-get command is only supported on Debian/Ubuntu systems. Use 'apk' on Alpine Linux."
@@ -19,11 +73,31 @@ pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Re
                     packages: &pkg_list,
                     ppas: ppa_list.as_deref(),
                     force_ppas_on_non_ubuntu: ppa_args.force_ppas_on_non_ubuntu,
+                    lock_timeout: Duration::from_secs(apt_args.lock_timeout),
+                    architectures: &apt_args.architectures,
+                    pins: &apt_args.pins,
+                    retry_config: retry_config.clone(),
+                    dry_run: pm_args.dry_run,
+                    report: output == OutputFormat::Json,
+                    proxy: pm_args.pkg_proxy.as_deref(),
+                    keep_cache: pm_args.keep_cache,
+                    ..Default::default()
                 },
             )
+            .and_then(print_report)
+            .inspect(|_| {
+                if !pm_args.dry_run {
+                    utils::state::record_packages("apt-get", &pkg_list)
+                }
+            })
         }
 
-        Commands::Apt { packages, ppa_args } => {
+        Commands::Apt {
+            packages,
+            ppa_args,
+            apt_args,
+            pm_args,
+        } => {
             anyhow::ensure!(
                 utils::os::is_debian_like(),
                 "apt command is only supported on Debian/Ubuntu systems. Use 'apk' on Alpine Linux."
@@ -36,47 +110,357 @@ pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Re
                     packages: &pkg_list,
                     ppas: ppa_list.as_deref(),
                     force_ppas_on_non_ubuntu: ppa_args.force_ppas_on_non_ubuntu,
+                    lock_timeout: Duration::from_secs(apt_args.lock_timeout),
+                    architectures: &apt_args.architectures,
+                    pins: &apt_args.pins,
+                    retry_config: retry_config.clone(),
+                    dry_run: pm_args.dry_run,
+                    report: output == OutputFormat::Json,
+                    proxy: pm_args.pkg_proxy.as_deref(),
+                    keep_cache: pm_args.keep_cache,
+                    ..Default::default()
                 },
             )
+            .and_then(print_report)
+            .inspect(|_| {
+                if !pm_args.dry_run {
+                    utils::state::record_packages("apt", &pkg_list)
+                }
+            })
         }
 
-        Commands::Aptitude { packages } => {
+        Commands::Aptitude {
+            packages,
+            apt_args,
+            pm_args,
+        } => {
             anyhow::ensure!(
                 utils::os::is_debian_like(),
                 "aptitude command is only supported on Debian/Ubuntu systems. Use 'apk' on Alpine Linux."
             );
             let pkg_list = normalize_package_list(&packages);
-            installers::package_manager::install_aptitude(&pkg_list)
+            installers::package_manager::install_aptitude(
+                &installers::package_manager::PackageManagerConfig {
+                    packages: &pkg_list,
+                    lock_timeout: Duration::from_secs(apt_args.lock_timeout),
+                    architectures: &apt_args.architectures,
+                    pins: &apt_args.pins,
+                    retry_config: retry_config.clone(),
+                    dry_run: pm_args.dry_run,
+                    report: output == OutputFormat::Json,
+                    proxy: pm_args.pkg_proxy.as_deref(),
+                    keep_cache: pm_args.keep_cache,
+                    ..Default::default()
+                },
+            )
+            .and_then(print_report)
+            .inspect(|_| {
+                if !pm_args.dry_run {
+                    utils::state::record_packages("aptitude", &pkg_list)
+                }
+            })
         }
 
-        Commands::Apk { packages } => {
+        Commands::Apk {
+            packages,
+            repository,
+            pm_args,
+        } => {
             anyhow::ensure!(
                 utils::os::is_alpine(),
                 "apk command is only supported on Alpine Linux. Use 'apt-get' on Debian/Ubuntu systems."
             );
             let pkg_list = normalize_package_list(&packages);
-            installers::package_manager::install_apk(&pkg_list)
+            installers::package_manager::install_apk(
+                &installers::package_manager::PackageManagerConfig {
+                    packages: &pkg_list,
+                    retry_config: retry_config.clone(),
+                    dry_run: pm_args.dry_run,
+                    report: output == OutputFormat::Json,
+                    proxy: pm_args.pkg_proxy.as_deref(),
+                    keep_cache: pm_args.keep_cache,
+                    repositories: &repository,
+                    ..Default::default()
+                },
+            )
+            .and_then(print_report)
+            .inspect(|_| {
+                if !pm_args.dry_run {
+                    utils::state::record_packages("apk", &pkg_list)
+                }
+            })
         }
 
-        Commands::Brew { packages } => {
+        Commands::Brew { packages, pm_args } => {
             anyhow::ensure!(
                 utils::os::is_macos(),
                 "brew command is only supported on macOS. Use 'apt-get' on Debian/Ubuntu or 'apk' on Alpine Linux."
             );
             let pkg_list = normalize_package_list(&packages);
-            installers::package_manager::install_brew(&pkg_list)
+            installers::package_manager::install_brew(
+                &installers::package_manager::PackageManagerConfig {
+                    packages: &pkg_list,
+                    retry_config: retry_config.clone(),
+                    dry_run: pm_args.dry_run,
+                    report: output == OutputFormat::Json,
+                    proxy: pm_args.pkg_proxy.as_deref(),
+                    keep_cache: pm_args.keep_cache,
+                    ..Default::default()
+                },
+            )
+            .and_then(print_report)
+            .inspect(|_| {
+                if !pm_args.dry_run {
+                    utils::state::record_packages("brew", &pkg_list)
+                }
+            })
+        }
+
+        Commands::Upgrade {
+            dist_upgrade,
+            apt_args,
+            pm_args,
+        } => {
+            let config = installers::package_manager::PackageManagerConfig {
+                lock_timeout: Duration::from_secs(apt_args.lock_timeout),
+                pins: &apt_args.pins,
+                retry_config: retry_config.clone(),
+                dry_run: pm_args.dry_run,
+                report: output == OutputFormat::Json,
+                proxy: pm_args.pkg_proxy.as_deref(),
+                keep_cache: pm_args.keep_cache,
+                ..Default::default()
+            };
+
+            if utils::os::is_debian_like() {
+                installers::package_manager::upgrade_apt(&config, dist_upgrade)
+            } else if utils::os::is_alpine() {
+                installers::package_manager::upgrade_apk(&config, dist_upgrade)
+            } else if utils::os::is_macos() {
+                installers::package_manager::upgrade_brew(&config)
+            } else {
+                anyhow::bail!(
+                    "upgrade command is only supported on Debian/Ubuntu, Alpine Linux, and macOS."
+                )
+            }
+            .and_then(print_report)
+        }
+
+        Commands::Npm {
+            packages,
+            manager,
+            registry,
+            auth_token,
+            prefix,
+            node_version,
+        } => {
+            let pkg_list = normalize_package_list(&packages);
+            installers::npm::install(
+                &installers::npm::NpmConfig {
+                    packages: &pkg_list,
+                    manager,
+                    registry: registry.as_deref(),
+                    auth_token: auth_token.as_deref(),
+                    prefix: prefix.as_deref(),
+                    node_version: node_version.as_deref(),
+                },
+                retry_config,
+            )
+            .await
+            .inspect(|_| utils::state::record_packages("npm", &pkg_list))
+        }
+
+        Commands::Snap {
+            packages,
+            channel,
+            classic,
+        } => {
+            let pkg_list = normalize_package_list(&packages);
+            installers::snap::install(&pkg_list, channel.as_deref(), classic)
+                .inspect(|_| utils::state::record_packages("snap", &pkg_list))
+        }
+
+        Commands::Flatpak {
+            packages,
+            remote,
+            remote_url,
+            user,
+        } => {
+            let pkg_list = normalize_package_list(&packages);
+            installers::flatpak::install(&pkg_list, &remote, &remote_url, user)
+                .inspect(|_| utils::state::record_packages("flatpak", &pkg_list))
+        }
+
+        Commands::Nix { packages, flake } => {
+            let pkg_list = normalize_package_list(&packages);
+            installers::nix::install(&pkg_list, &flake, retry_config)
+                .await
+                .inspect(|_| utils::state::record_packages("nix", &pkg_list))
+        }
+
+        Commands::Cargo {
+            packages,
+            install_dir,
+            no_binstall,
+            add_to_path,
+        } => {
+            let install_dir = utils::settings::install_dir(install_dir);
+            let pkg_list = normalize_package_list(&packages);
+            installers::cargo::install(
+                &installers::cargo::CargoConfig {
+                    packages: &pkg_list,
+                    install_dir: &install_dir,
+                    no_binstall,
+                },
+                retry_config,
+            )
+            .await
+            .inspect(|_| utils::state::record_packages("cargo", &pkg_list))?;
+            add_install_dir_to_path(add_to_path, &install_dir)
+        }
+
+        Commands::GoInstall {
+            packages,
+            install_dir,
+            go_version,
+            add_to_path,
+        } => {
+            let install_dir = utils::settings::install_dir(install_dir);
+            let pkg_list = normalize_package_list(&packages);
+            installers::go::install(&installers::go::GoInstallConfig {
+                packages: &pkg_list,
+                install_dir: &install_dir,
+                go_version: &go_version,
+            })
+            .await
+            .inspect(|_| utils::state::record_packages("go-install", &pkg_list))?;
+            add_install_dir_to_path(add_to_path, &install_dir)
+        }
+
+        Commands::Gem {
+            packages,
+            with_docs,
+        } => {
+            let pkg_list = normalize_package_list(&packages);
+            installers::gem::install(&pkg_list, with_docs)
+                .inspect(|_| utils::state::record_packages("gem", &pkg_list))
+        }
+
+        #[cfg(feature = "gh-release")]
+        Commands::HelmPlugin {
+            plugins,
+            plugins_dir,
+        } => {
+            let plugin_list = normalize_package_list(&plugins);
+            installers::helm_plugin::install(
+                &installers::helm_plugin::HelmPluginConfig {
+                    plugins: &plugin_list,
+                    plugins_dir: plugins_dir.as_deref(),
+                },
+                retry_config,
+            )
+            .await
+        }
+
+        Commands::Luarocks {
+            packages,
+            lua_version,
+        } => {
+            let pkg_list = normalize_package_list(&packages);
+            installers::luarocks::install(&installers::luarocks::LuarocksConfig {
+                packages: &pkg_list,
+                lua_version: &lua_version,
+            })
+            .inspect(|_| utils::state::record_packages("luarocks", &pkg_list))
         }
 
-        Commands::Npm { packages } => {
+        Commands::DotnetTool {
+            packages,
+            tool_path,
+        } => {
             let pkg_list = normalize_package_list(&packages);
-            installers::npm::install(&pkg_list)
+            installers::dotnet_tool::install(&pkg_list, &tool_path)
+                .inspect(|_| utils::state::record_packages("dotnet-tool", &pkg_list))
         }
 
-        Commands::Pipx { packages, python } => {
+        Commands::Composer {
+            packages,
+            composer_home,
+            bin_dir,
+        } => {
             let pkg_list = normalize_package_list(&packages);
-            installers::pipx::install(&pkg_list, python.as_deref())
+            installers::composer::install(&installers::composer::ComposerConfig {
+                packages: &pkg_list,
+                composer_home: &composer_home,
+                bin_dir: &bin_dir,
+            })
+            .await
+            .inspect(|_| utils::state::record_packages("composer", &pkg_list))
         }
 
+        Commands::Runtime {
+            runtime,
+            version,
+            install_dir,
+            add_to_path,
+        } => {
+            let install_dir = utils::settings::install_dir(install_dir);
+            installers::runtime::install(
+                &installers::runtime::RuntimeConfig {
+                    runtime,
+                    version: &version,
+                    install_dir: &install_dir,
+                },
+                retry_config,
+            )
+            .await?;
+            add_install_dir_to_path(add_to_path, &install_dir)
+        }
+
+        Commands::Jdk {
+            version,
+            dist,
+            install_dir,
+        } => {
+            installers::jdk::install(&installers::jdk::JdkConfig {
+                version,
+                distribution: dist,
+                install_dir: &install_dir,
+            })
+            .await
+        }
+
+        Commands::Venv {
+            path,
+            requirements,
+            python,
+            require_hashes,
+        } => installers::venv::install(&installers::venv::VenvConfig {
+            path: &path,
+            requirements: requirements.as_deref(),
+            python: python.as_deref(),
+            require_hashes,
+        }),
+
+        Commands::Pipx {
+            packages,
+            python,
+            pip_args,
+            spec,
+            bin_dir,
+        } => {
+            let pkg_list = normalize_requirement_list(&packages);
+            installers::pipx::install(&installers::pipx::PipxConfig {
+                packages: &pkg_list,
+                python: python.as_deref(),
+                pip_args: pip_args.as_deref(),
+                spec: spec.as_deref(),
+                bin_dir: bin_dir.as_deref(),
+            })
+            .inspect(|_| utils::state::record_packages("pipx", &pkg_list))
+        }
+
+        #[cfg(feature = "devcontainer")]
         Commands::DevcontainerFeature {
             feature,
             option,
@@ -87,6 +471,8 @@ pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Re
             registry_username,
             registry_password,
             registry_token,
+            env_clean,
+            env_pass,
         } => {
             anyhow::ensure!(
                 utils::os::is_linux(),
@@ -105,28 +491,49 @@ pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Re
                 registry_username: registry_username.as_deref(),
                 registry_password: registry_password.as_deref(),
                 registry_token: registry_token.as_deref(),
+                env_filter: utils::env::EnvFilter {
+                    clean: env_clean,
+                    pass: &env_pass,
+                },
             };
 
-            installers::devcontainer_feature::install_async(&config, retry_config).await
+            installers::devcontainer_feature::install_async(&config, retry_config)
+                .await
+                .inspect(|_| {
+                    utils::state::record([utils::state::Installed::new(
+                        "devcontainer-feature",
+                        &feature,
+                    )])
+                })
         }
 
+        #[cfg(feature = "gh-release")]
         Commands::GhRelease {
             owner,
             repo,
             binary,
             version,
+            tag_prefix,
             install_dir,
             filter,
+            add_to_path,
+            chown,
+            mode,
             verify_checksum,
             checksum_text,
+            checksum_algo,
             gpg_key,
             include_prerelease,
+            from_tag,
+            wait_for_release,
+            wait_timeout,
         } => {
             anyhow::ensure!(
                 utils::os::is_debian_like(),
                 "gh-release command is only supported on Debian/Ubuntu systems."
             );
             let binary_list = normalize_package_list(&binary.unwrap_or_else(|| repo.clone()));
+            let install_dir = utils::settings::install_dir(install_dir);
 
             installers::gh_release::install(
                 &installers::gh_release::GhReleaseConfig {
@@ -134,23 +541,35 @@ pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Re
                     repo: &repo,
                     binary_names: &binary_list,
                     version: &version,
+                    tag_prefix: tag_prefix.as_deref(),
                     install_dir: &install_dir,
                     filter: filter.as_deref(),
+                    chown: chown.as_deref(),
+                    mode,
                     verify_checksum,
                     checksum_text: checksum_text.as_deref(),
+                    checksum_algorithm: checksum_algo,
                     gpg_key: gpg_key.as_deref(),
                     include_prerelease,
+                    from_tag,
+                    wait_for_release: wait_for_release.then_some(wait_timeout),
                 },
                 retry_config,
             )
-            .await
+            .await?;
+            add_install_dir_to_path(add_to_path, &install_dir)
         }
+        #[cfg(feature = "pkgx")]
         Commands::Pkgx {
             tool,
             version,
             args,
             working_dir,
             env,
+            pantry_max_age,
+            capture_output,
+            env_clean,
+            env_pass,
         } => {
             let config = installers::pkgx::PkgxConfig {
                 tool: &tool,
@@ -158,8 +577,142 @@ pub async fn handle_command(command: Commands, retry_config: &RetryConfig) -> Re
                 args,
                 working_dir: &working_dir,
                 env_vars: env,
+                pantry_max_age,
+                capture_output: capture_output.as_deref(),
+                env_filter: utils::env::EnvFilter {
+                    clean: env_clean,
+                    pass: &env_pass,
+                },
             };
             installers::pkgx::execute(&config).await
         }
+        Commands::Clean {
+            aggressive,
+            skip,
+            dry_run,
+        } => installers::clean::clean(&installers::clean::CleanConfig {
+            aggressive,
+            skip: &skip,
+            dry_run,
+        })
+        .and_then(|report| print_clean(&report, dry_run, output)),
+        Commands::Cache {
+            action:
+                CacheCommand::Prune {
+                    max_size,
+                    older_than,
+                    all,
+                    dry_run,
+                },
+        } => installers::cache::prune(&installers::cache::PruneConfig {
+            max_size,
+            older_than,
+            all,
+            dry_run,
+        })
+        .and_then(|result| print_prune(&result, dry_run, output)),
+        Commands::Doctor => super::doctor::run(output).await,
+        Commands::List => super::list::run(output),
+        Commands::Verify { lockfile } => super::verify::run(lockfile.as_deref(), output),
+        Commands::ExitCodes => super::exit_codes::run(output),
+        Commands::Generate {
+            target: GenerateTarget::Dockerfile { manifest },
+        } => super::generate::dockerfile(&manifest, output),
+        Commands::Uninstall { names } => {
+            installers::uninstall::uninstall(&normalize_package_list(&names))
+        }
+        #[cfg(feature = "gh-release")]
+        Commands::SelfUpdate { version } => {
+            installers::self_update::update(
+                &installers::self_update::SelfUpdateConfig { version: &version },
+                retry_config,
+            )
+            .await
+        }
+        #[cfg(not(feature = "gh-release"))]
+        Commands::GhRelease { .. } | Commands::HelmPlugin { .. } | Commands::SelfUpdate { .. } => {
+            Err(installers::not_built("gh-release"))
+        }
+        #[cfg(not(feature = "pkgx"))]
+        Commands::Pkgx { .. } => Err(installers::not_built("pkgx")),
+        #[cfg(not(feature = "devcontainer"))]
+        Commands::DevcontainerFeature { .. } => Err(installers::not_built("devcontainer")),
+        Commands::Plugin(args) => super::plugin::run(&args, retry_config, output),
+    }
+}
+
+/// Put an installer's target directory on PATH when it was asked to with --add-to-path
+fn add_install_dir_to_path(add_to_path: bool, install_dir: &str) -> Result<()> {
+    if add_to_path {
+        utils::env_registry::add_path(std::path::Path::new(install_dir));
+    }
+    Ok(())
+}
+
+/// Print a package manager install report as JSON when one was collected
+fn print_report(report: Option<installers::package_manager::InstallReport>) -> Result<()> {
+    match report {
+        Some(report) => super::output::set_details(&report),
+        None => Ok(()),
+    }
+}
+
+/// Attach what `clean` reclaimed to the JSON output, or print a `--dry-run` preview
+fn print_clean(
+    report: &installers::clean::CleanReport,
+    dry_run: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    if output == OutputFormat::Json {
+        return super::output::set_details(report);
+    }
+    if dry_run {
+        // Printed like package manager simulations so the preview shows without -v
+        for result in &report.targets {
+            println!(
+                "Would remove {} paths ({} bytes) from {:?}",
+                result.paths, result.bytes, result.target
+            );
+        }
+        println!("{} bytes would be reclaimed", report.bytes);
+    }
+    Ok(())
+}
+
+/// Attach what `cache prune` removed to the JSON output, or print a `--dry-run` preview
+fn print_prune(
+    result: &installers::cache::PruneResult,
+    dry_run: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    if output == OutputFormat::Json {
+        return super::output::set_details(result);
+    }
+    if dry_run {
+        // Printed like `clean --dry-run` so the preview shows without -v
+        for path in &result.paths {
+            println!("Would remove {}", path.display());
+        }
+        println!(
+            "{} bytes would be reclaimed from {}",
+            result.bytes,
+            result.cache_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// The system package manager a command installs with, if any
+fn system_package_manager(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::AptGet { .. } => Some("apt-get"),
+        Commands::Apt { .. } => Some("apt"),
+        Commands::Aptitude { .. } => Some("aptitude"),
+        Commands::Apk { .. } => Some("apk"),
+        Commands::Upgrade { .. } => Some("upgrade"),
+        Commands::Snap { .. } => Some("snap"),
+        Commands::Flatpak { user: false, .. } => Some("flatpak (without --user)"),
+        Commands::DevcontainerFeature { .. } => Some("devcontainer-feature"),
+        _ => None,
     }
 }
@@ -0,0 +1,37 @@
+use super::OutputFormat;
+use super::output;
+use crate::utils::state::State;
+use anyhow::Result;
+
+pub fn run(output: OutputFormat) -> Result<()> {
+    let state = State::load()?;
+    if output == OutputFormat::Json {
+        return output::set_details(&state.installed);
+    }
+
+    if state.installed.is_empty() {
+        println!(
+            "Nothing installed by picolayer ({})",
+            State::path().display()
+        );
+        return Ok(());
+    }
+
+    let width = state
+        .installed
+        .iter()
+        .map(|i| i.kind.len())
+        .max()
+        .unwrap_or_default();
+    for installed in &state.installed {
+        let mut line = format!("{:<width$}  {}", installed.kind, installed.name);
+        if let Some(version) = &installed.version {
+            line.push_str(&format!(" {}", version));
+        }
+        for file in &installed.files {
+            line.push_str(&format!("\n{:<width$}    {}", "", file.path));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
@@ -1,11 +1,140 @@
+mod apply;
 mod args;
+mod confirm;
+mod doctor;
+mod exit_codes;
+mod generate;
 mod handlers;
+mod list;
+mod output;
+mod plugin;
+mod policy;
+mod presence;
+mod summary;
+mod verify;
 
+use crate::utils::{self, lock};
 use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub use args::{Cli, RetryConfig};
+pub use args::{
+    CleanTarget, Cli, JdkDistribution, NodePackageManager, OutputFormat, RetryConfig, RuntimePreset,
+};
+pub(crate) use output::set_details;
 
 pub async fn run(cli: Cli) -> Result<()> {
+    utils::settings::init(utils::settings::Settings::load()?);
+    let settings = utils::settings::get();
     let retry_config = args::RetryConfig::from_cli(&cli);
-    handlers::handle_command(cli.command, &retry_config).await
+    if cli.offline {
+        utils::offline::enable();
+    }
+    if cli.skip_if_present {
+        presence::enable();
+    }
+    if cli.user_mode {
+        utils::user_mode::enable();
+    }
+    if cli.no_sudo {
+        utils::sudo::disable();
+    }
+    utils::policy::configure(cli.require_verification, cli.reject_weak_checksums);
+    utils::tmp::configure(cli.tmp_dir.clone());
+    utils::scheduler::configure(cli.max_downloads);
+    if cli.output == OutputFormat::Json {
+        utils::exec::reserve_stdout();
+    }
+    if let Some(path) = &cli.audit_log {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        utils::audit::enable(Path::new(path), &output::action_name(&args))?;
+    }
+    utils::http::configure(utils::http::HttpConfig {
+        proxy: cli.proxy.clone().or_else(|| settings.proxy.clone()),
+        ca_bundle: cli
+            .ca_bundle
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| settings.ca_bundle.clone()),
+    });
+    if cli.confirm && !cli.yes {
+        confirm::confirm(&cli.command)?;
+    }
+    let run = summary::Run::start(&cli.command);
+    let summary_file = cli.summary_file.as_ref().map(PathBuf::from);
+    if let Some(secs) = cli.timeout {
+        let output = cli.output;
+        let summary_file = summary_file.clone();
+        utils::timeout::start(Duration::from_secs(secs), move || {
+            let error = anyhow::anyhow!("Timed out after {}s", secs);
+            let summary = run.finish(false);
+            write_summary(summary_file.as_deref(), &summary);
+            if output == OutputFormat::Json {
+                let _ = output::print_result(&Err(error), summary, Vec::new());
+            } else {
+                eprintln!("Error: {}", error);
+            }
+        });
+    }
+    if cli.output == OutputFormat::Text {
+        let read_only = summary::is_read_only(&cli.command);
+        let result = handlers::handle_command(cli.command, &retry_config, cli.output)
+            .await
+            .and_then(|()| utils::env_registry::write_profile());
+        if result.is_ok() {
+            add_user_bin_dir_to_path();
+        }
+        let summary = run.finish(result.is_ok());
+        if !read_only && log::max_level() >= log::LevelFilter::Warn {
+            eprintln!("{}", summary.line());
+        }
+        write_summary(summary_file.as_deref(), &summary);
+        return result;
+    }
+
+    // apply keeps its own lock session, which it reports as the result's artifacts
+    let record = !matches!(cli.command, args::Commands::Apply { .. });
+    if record {
+        lock::begin(None);
+    }
+    let result = handlers::handle_command(cli.command, &retry_config, cli.output)
+        .await
+        .and_then(|()| utils::env_registry::write_profile());
+    if result.is_ok() {
+        add_user_bin_dir_to_path();
+    }
+    let artifacts = if record {
+        lock::finish().map(|l| l.artifacts).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let summary = run.finish(result.is_ok());
+    write_summary(summary_file.as_deref(), &summary);
+    output::print_result(&result, summary, artifacts)?;
+    result
+}
+
+/// Write the run summary for --summary-file; failing to write it does not fail the run
+fn write_summary(path: Option<&Path>, summary: &summary::RunSummary) {
+    if let Some(path) = path
+        && let Err(e) = summary.write(path)
+    {
+        log::warn!("{:#}", e);
+    }
+}
+
+/// In user mode, put ~/.local/bin on PATH in the shell rc once something was installed there
+fn add_user_bin_dir_to_path() {
+    if !utils::user_mode::is_enabled() {
+        return;
+    }
+    let Ok(bin_dir) = utils::user_mode::bin_dir() else {
+        return;
+    };
+    if !bin_dir.is_dir() || utils::profile::is_on_path(&bin_dir) {
+        return;
+    }
+    if let Err(e) = utils::user_mode::add_to_path(&bin_dir) {
+        log::warn!("Failed to add {} to PATH: {:#}", bin_dir.display(), e);
+    }
 }
@@ -0,0 +1,157 @@
+use crate::error::{self, ErrorKind};
+use crate::utils::lock::LockedArtifact;
+use anyhow::Result;
+use clap::CommandFactory;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Mutex;
+
+use super::args::Cli;
+use super::summary::RunSummary;
+
+/// Structured result printed on stdout for `--output json`
+#[derive(Debug, Serialize)]
+pub struct CommandResult {
+    /// Action, status, duration, and download and size figures
+    #[serde(flatten)]
+    pub summary: RunSummary,
+    pub inputs: Vec<String>,
+    /// Downloaded artifacts with their resolved versions and digests
+    pub artifacts: Vec<LockedArtifact>,
+    /// Command-specific result, such as a package manager install report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Stable code for the kind of failure, such as `checksum-mismatch`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+    /// What to try next, when the failure suggests something specific
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+#[derive(Default)]
+struct Collected {
+    details: Option<serde_json::Value>,
+    artifacts: Vec<LockedArtifact>,
+}
+
+static COLLECTED: Mutex<Option<Collected>> = Mutex::new(None);
+
+tokio::task_local! {
+    /// Details set by a manifest step, kept apart from steps running concurrently
+    static STEP_DETAILS: RefCell<Option<serde_json::Value>>;
+}
+
+/// Attach a command-specific result to the JSON output
+pub fn set_details<T: Serialize>(details: &T) -> Result<()> {
+    let mut value = Some(serde_json::to_value(details)?);
+    let _ = STEP_DETAILS.try_with(|step| *step.borrow_mut() = value.take());
+    if let Some(value) = value {
+        COLLECTED.lock().unwrap().get_or_insert_default().details = Some(value);
+    }
+    Ok(())
+}
+
+/// Run a manifest step, returning the details it set instead of attaching them to the output
+pub async fn capture_details<F: Future>(future: F) -> (F::Output, Option<serde_json::Value>) {
+    STEP_DETAILS
+        .scope(RefCell::new(None), async {
+            let output = future.await;
+            (output, STEP_DETAILS.with(|step| step.take()))
+        })
+        .await
+}
+
+/// Attach downloaded artifacts recorded outside of the top-level lock session
+pub fn set_artifacts(artifacts: Vec<LockedArtifact>) {
+    COLLECTED.lock().unwrap().get_or_insert_default().artifacts = artifacts;
+}
+
+pub fn print_result(
+    result: &Result<()>,
+    summary: RunSummary,
+    mut artifacts: Vec<LockedArtifact>,
+) -> Result<()> {
+    let collected = COLLECTED.lock().unwrap().take().unwrap_or_default();
+    artifacts.extend(collected.artifacts);
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = CommandResult {
+        summary,
+        inputs: redact_inputs(&args),
+        artifacts,
+        details: collected.details,
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        error_code: result.as_ref().err().map(|e| ErrorKind::of(e).code()),
+        suggestion: result.as_ref().err().and_then(error::suggestion),
+    };
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+/// The subcommand named on the command line
+pub(super) fn action_name(args: &[String]) -> String {
+    let command = Cli::command();
+    args.iter()
+        .find(|arg| command.find_subcommand(arg.as_str()).is_some())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Hide the values of secret-bearing flags such as `--auth-token`
+fn redact_inputs(args: &[String]) -> Vec<String> {
+    let is_secret = |flag: &str| {
+        flag.starts_with("--") && (flag.contains("token") || flag.contains("password"))
+    };
+
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut redact_next = false;
+    for arg in args {
+        if redact_next {
+            redacted.push("***".to_string());
+            redact_next = false;
+        } else if let Some((flag, _)) = arg.split_once('=')
+            && is_secret(flag)
+        {
+            redacted.push(format!("{}=***", flag));
+        } else {
+            redact_next = is_secret(arg);
+            redacted.push(arg.clone());
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn action_name_skips_global_flags() {
+        assert_eq!(
+            action_name(&args(&["--output", "json", "apt-get", "curl"])),
+            "apt-get"
+        );
+    }
+
+    #[test]
+    fn redact_inputs_hides_tokens() {
+        assert_eq!(
+            redact_inputs(&args(&[
+                "npm",
+                "--auth-token",
+                "s3cret",
+                "--registry-token=abc",
+                "x"
+            ])),
+            args(&["npm", "--auth-token", "***", "--registry-token=***", "x"])
+        );
+    }
+}
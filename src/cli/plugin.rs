@@ -0,0 +1,79 @@
+use super::output;
+use super::{OutputFormat, RetryConfig};
+use crate::utils;
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+
+/// Executables named `picolayer-<name>` on PATH provide the `<name>` subcommand
+const PLUGIN_PREFIX: &str = "picolayer-";
+
+/// Run an external installer plugin for a subcommand picolayer does not know.
+///
+/// Global options are passed to the plugin through `PICOLAYER_*` environment variables.
+/// With `--output json` the plugin's stdout becomes the result's details.
+pub fn run(args: &[String], retry_config: &RetryConfig, output: OutputFormat) -> Result<()> {
+    let (name, args) = args.split_first().context("Missing command")?;
+    let executable = format!("{}{}", PLUGIN_PREFIX, name);
+    let path = which::which(&executable).with_context(|| {
+        format!(
+            "Unknown command '{}' (no {} plugin found on PATH)",
+            name, executable
+        )
+    })?;
+
+    let mut cmd = Command::new(&path);
+    cmd.args(args)
+        .env("PICOLAYER_VERSION", env!("CARGO_PKG_VERSION"))
+        .env("PICOLAYER_OUTPUT", output_name(output))
+        .env(
+            "PICOLAYER_MAX_RETRIES",
+            retry_config.max_retries.to_string(),
+        )
+        .env(
+            "PICOLAYER_RETRY_DELAY_MS",
+            retry_config.initial_delay_ms.to_string(),
+        )
+        .env(
+            "PICOLAYER_RETRY_BACKOFF_MULTIPLIER",
+            retry_config.backoff_multiplier.to_string(),
+        )
+        .env(
+            "PICOLAYER_OFFLINE",
+            if utils::offline::is_enabled() {
+                "1"
+            } else {
+                "0"
+            },
+        )
+        .env(
+            "PICOLAYER_USER_MODE",
+            if utils::user_mode::is_enabled() {
+                "1"
+            } else {
+                "0"
+            },
+        );
+    let description = format!("Plugin {}", path.display());
+
+    if output == OutputFormat::Text {
+        return utils::exec::run_streaming(&mut cmd, &description);
+    }
+
+    // Keep stdout for the single JSON document; the plugin's logs still reach stderr
+    let result = utils::exec::run_command(cmd.stderr(Stdio::inherit()), &description)?;
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(());
+    }
+    match serde_json::from_str::<serde_json::Value>(&stdout) {
+        Ok(details) => output::set_details(&details),
+        Err(_) => output::set_details(&stdout.trim()),
+    }
+}
+
+fn output_name(output: OutputFormat) -> &'static str {
+    match output {
+        OutputFormat::Text => "text",
+        OutputFormat::Json => "json",
+    }
+}
@@ -0,0 +1,89 @@
+use super::args::{Commands, RuntimePreset};
+#[cfg(feature = "gh-release")]
+use crate::installers;
+use crate::utils::policy::{self, Source, Verification};
+use anyhow::Result;
+
+/// Check `command` against the verification policy before it downloads anything.
+///
+/// Only artifacts picolayer downloads itself are covered; package managers verify
+/// their packages with their own signing.
+pub fn check(command: &Commands) -> Result<()> {
+    match command {
+        #[cfg(feature = "gh-release")]
+        Commands::GhRelease {
+            owner,
+            repo,
+            verify_checksum,
+            checksum_text,
+            gpg_key,
+            ..
+        } => {
+            let provided = installers::gh_release::verification(
+                *verify_checksum,
+                checksum_text.as_deref(),
+                gpg_key.as_deref(),
+            );
+            enforce("gh-release", owner, repo, provided)
+        }
+        Commands::Runtime { runtime, .. } => {
+            let (owner, repo, provided) = match runtime {
+                RuntimePreset::Deno => ("denoland", "deno", Verification::None),
+                RuntimePreset::Bun => ("oven-sh", "bun", Verification::None),
+                RuntimePreset::Node => ("nodejs", "node", Verification::Checksum),
+                RuntimePreset::Zig => ("ziglang", "zig", Verification::Checksum),
+            };
+            enforce("runtime", owner, repo, provided)
+        }
+        Commands::Jdk { .. } => enforce("jdk", "adoptium", "temurin", Verification::Checksum),
+        Commands::Composer { .. } => {
+            enforce("composer", "composer", "composer", Verification::Checksum)
+        }
+        // Registries address every layer by digest
+        Commands::DevcontainerFeature { feature, .. } => {
+            let (owner, repo) = feature_owner_repo(feature);
+            enforce("devcontainer-feature", owner, repo, Verification::Checksum)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn enforce(command: &str, owner: &str, repo: &str, provided: Verification) -> Result<()> {
+    policy::enforce(
+        &Source {
+            command,
+            owner,
+            repo,
+        },
+        provided,
+    )
+}
+
+/// The namespace and name of a feature reference, without registry or tag:
+/// `ghcr.io/devcontainers/features/node:1` is `devcontainers` and `features/node`
+fn feature_owner_repo(feature: &str) -> (&str, &str) {
+    let path = feature.split_once('/').map_or(feature, |(_, path)| path);
+    let path = path.split_once('@').map_or(path, |(path, _)| path);
+    let path = match path.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => path,
+    };
+    path.split_once('/').unwrap_or((path, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_owner_repo_drops_registry_and_tag() {
+        assert_eq!(
+            feature_owner_repo("ghcr.io/devcontainers/features/node:1"),
+            ("devcontainers", "features/node")
+        );
+        assert_eq!(
+            feature_owner_repo("registry.example.com:5000/team/tool@sha256:abc"),
+            ("team", "tool")
+        );
+    }
+}
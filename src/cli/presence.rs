@@ -0,0 +1,253 @@
+use super::args::{Commands, RuntimePreset, normalize_package_list, normalize_requirement_list};
+use crate::utils::state::State;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Skip installs whose results are already present
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Why `command` has nothing to do, if everything it would install is already present.
+///
+/// Package manager installs are checked against the package database, runtimes
+/// against the version their binary reports, and everything else against the
+/// installation state picolayer recorded. Commands that cannot be checked always run.
+pub fn satisfied(command: &Commands) -> Option<String> {
+    let state = State::load().ok()?;
+    let all_recorded = |kind: &str, names: &[String]| {
+        (!names.is_empty() && names.iter().all(|name| recorded(&state, kind, name, None)))
+            .then(|| format!("{} already installed", names.join(", ")))
+    };
+
+    match command {
+        Commands::AptGet { packages, .. }
+        | Commands::Apt { packages, .. }
+        | Commands::Aptitude { packages, .. } => all_packages(packages, "dpkg", dpkg_installed),
+        Commands::Apk { packages, .. } => all_packages(packages, "apk", apk_installed),
+        Commands::Brew { packages, .. } => all_packages(packages, "brew", brew_installed),
+        Commands::Npm { packages, .. } => all_recorded("npm", &normalize_package_list(packages)),
+        Commands::Snap { packages, .. } => all_recorded("snap", &normalize_package_list(packages)),
+        Commands::Flatpak { packages, .. } => {
+            all_recorded("flatpak", &normalize_package_list(packages))
+        }
+        Commands::Nix { packages, .. } => all_recorded("nix", &normalize_package_list(packages)),
+        Commands::Cargo { packages, .. } => {
+            all_recorded("cargo", &normalize_package_list(packages))
+        }
+        Commands::GoInstall { packages, .. } => {
+            all_recorded("go-install", &normalize_package_list(packages))
+        }
+        Commands::Gem { packages, .. } => all_recorded("gem", &normalize_package_list(packages)),
+        Commands::Luarocks { packages, .. } => {
+            all_recorded("luarocks", &normalize_package_list(packages))
+        }
+        Commands::DotnetTool { packages, .. } => {
+            all_recorded("dotnet-tool", &normalize_package_list(packages))
+        }
+        Commands::Composer { packages, .. } => {
+            all_recorded("composer", &normalize_package_list(packages))
+        }
+        Commands::Pipx { packages, .. } => {
+            all_recorded("pipx", &normalize_requirement_list(packages))
+        }
+        Commands::DevcontainerFeature { feature, .. } => {
+            all_recorded("devcontainer-feature", std::slice::from_ref(feature))
+        }
+        Commands::HelmPlugin { plugins, .. } => {
+            let plugins = normalize_package_list(plugins);
+            plugins
+                .iter()
+                .all(|plugin| {
+                    let (name, version) = plugin
+                        .split_once('@')
+                        .unwrap_or((plugin.as_str(), "latest"));
+                    recorded(&state, "helm-plugin", name, Some(version))
+                })
+                .then(|| format!("{} already installed", plugins.join(", ")))
+        }
+        Commands::GhRelease {
+            owner,
+            repo,
+            version,
+            ..
+        } => {
+            let name = format!("{}/{}", owner, repo);
+            recorded(&state, "gh-release", &name, Some(version.as_str()))
+                .then(|| format!("{} {} already installed", name, version))
+        }
+        Commands::Jdk { version, .. } => {
+            let name = format!("temurin-{}", version);
+            recorded(&state, "jdk", &name, None).then(|| format!("{} already installed", name))
+        }
+        Commands::Runtime {
+            runtime,
+            version,
+            install_dir,
+            ..
+        } => runtime_satisfied(*runtime, version, install_dir.as_deref()),
+        _ => None,
+    }
+}
+
+/// Whether the state records `name` installed by `kind`, at a version satisfying
+/// `version` if given, with every recorded file still in place
+fn recorded(state: &State, kind: &str, name: &str, version: Option<&str>) -> bool {
+    state.installed.iter().any(|installed| {
+        installed.kind == kind
+            && installed.name == name
+            && version.is_none_or(|requested| {
+                installed
+                    .version
+                    .as_deref()
+                    .is_some_and(|v| version_satisfies(v, requested))
+            })
+            && installed
+                .files
+                .iter()
+                .all(|file| Path::new(&file.path).symlink_metadata().is_ok())
+    })
+}
+
+/// Whether an installed version meets a requested one: `latest` and `lts` accept any
+/// version, and `20` accepts `v20.11.1`
+fn version_satisfies(installed: &str, requested: &str) -> bool {
+    let installed = installed.trim_start_matches('v');
+    let requested = requested.trim_start_matches('v');
+    matches!(requested, "" | "latest" | "lts" | "stable")
+        || installed == requested
+        || installed.starts_with(&format!("{}.", requested))
+}
+
+fn all_packages(packages: &str, manager: &str, installed: fn(&str) -> bool) -> Option<String> {
+    let packages = normalize_package_list(packages);
+    (!packages.is_empty() && packages.iter().all(|p| installed(p))).then(|| {
+        format!(
+            "{} already installed according to {}",
+            packages.join(", "),
+            manager
+        )
+    })
+}
+
+/// `curl`, `curl=8.5.0-2` or `curl/bookworm-backports` is installed, at the pinned version if any
+pub(super) fn dpkg_installed(spec: &str) -> bool {
+    let (name, pin) = match spec.split_once('=') {
+        Some((name, pin)) => (name, Some(pin)),
+        None => (spec, None),
+    };
+    let name = name.split('/').next().unwrap_or(name);
+    let Ok(output) = Command::new("dpkg-query")
+        .args(["-W", "-f=${Status} ${Version}", name])
+        .output()
+    else {
+        return false;
+    };
+    let status = String::from_utf8_lossy(&output.stdout);
+    output.status.success()
+        && status
+            .strip_prefix("install ok installed ")
+            .is_some_and(|version| pin.is_none_or(|pin| version.trim() == pin))
+}
+
+/// Version-constrained apk specs are never considered present, since `apk info` cannot check them
+pub(super) fn apk_installed(spec: &str) -> bool {
+    let name = spec.split('@').next().unwrap_or(spec);
+    !name.contains(['=', '<', '>', '~'])
+        && Command::new("apk")
+            .args(["info", "-e", name])
+            .output()
+            .is_ok_and(|output| output.status.success())
+}
+
+pub(super) fn brew_installed(name: &str) -> bool {
+    Command::new("brew")
+        .args(["list", "--versions", name])
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+/// The runtime's binary, in the install dir or on PATH, reports a satisfying version
+fn runtime_satisfied(
+    runtime: RuntimePreset,
+    version: &str,
+    install_dir: Option<&str>,
+) -> Option<String> {
+    let (binary, args): (&str, &[&str]) = match runtime {
+        RuntimePreset::Deno => ("deno", &["--version"]),
+        RuntimePreset::Bun => ("bun", &["--version"]),
+        RuntimePreset::Node => ("node", &["--version"]),
+        RuntimePreset::Zig => ("zig", &["version"]),
+    };
+    let install_dir = crate::utils::settings::install_dir(install_dir.map(str::to_string));
+    let path = Some(Path::new(&install_dir).join(binary))
+        .filter(|path| path.is_file())
+        .or_else(|| which::which(binary).ok())?;
+
+    let output = Command::new(&path).args(args).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let installed = first_version(&stdout)?;
+    (output.status.success() && version_satisfies(installed, version))
+        .then(|| format!("{} {} already installed", binary, installed))
+}
+
+/// The first word that looks like a version, e.g. `2.1.4` in `deno 2.1.4 (stable, ...)`
+fn first_version(text: &str) -> Option<&str> {
+    text.split_whitespace().find(|word| {
+        word.trim_start_matches('v')
+            .starts_with(|c: char| c.is_ascii_digit())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::state::Installed;
+
+    #[test]
+    fn version_satisfies_prefixes_and_channels() {
+        assert!(version_satisfies("v20.11.1", "20"));
+        assert!(version_satisfies("v2.1.4", "2.1.4"));
+        assert!(version_satisfies("0.13.0", "latest"));
+        assert!(!version_satisfies("v20.11.1", "2"));
+        assert!(!version_satisfies("v1.2.0", "1.2.1"));
+    }
+
+    #[test]
+    fn first_version_skips_program_names() {
+        assert_eq!(
+            first_version("deno 2.1.4 (stable, release, x86_64)"),
+            Some("2.1.4")
+        );
+        assert_eq!(first_version("v22.11.0\n"), Some("v22.11.0"));
+        assert_eq!(first_version("unknown"), None);
+    }
+
+    #[test]
+    fn recorded_requires_files_and_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("gh");
+        std::fs::write(&binary, b"").unwrap();
+
+        let mut state = State::default();
+        state.insert(
+            Installed::new("gh-release", "cli/cli")
+                .version("v2.60.0")
+                .files([&binary]),
+        );
+        assert!(recorded(&state, "gh-release", "cli/cli", Some("latest")));
+        assert!(recorded(&state, "gh-release", "cli/cli", Some("v2.60.0")));
+        assert!(!recorded(&state, "gh-release", "cli/cli", Some("v2.61.0")));
+        assert!(!recorded(&state, "npm", "cli/cli", None));
+
+        std::fs::remove_file(&binary).unwrap();
+        assert!(!recorded(&state, "gh-release", "cli/cli", Some("latest")));
+    }
+}
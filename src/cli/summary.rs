@@ -0,0 +1,179 @@
+use super::args::Commands;
+use crate::utils::{os, progress};
+use anyhow::{Context, Result};
+use log::warn;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// How the steps of a manifest ended
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct StepCounts {
+    pub ok: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+static STEPS: Mutex<Option<StepCounts>> = Mutex::new(None);
+
+/// Report the outcome of `apply`'s steps in the run summary
+pub fn record_steps(counts: StepCounts) {
+    *STEPS.lock().unwrap() = Some(counts);
+}
+
+/// What a run did, printed when it ends and written as JSON by `--summary-file`
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub action: String,
+    pub status: &'static str,
+    pub duration_ms: u128,
+    /// Manifest steps by outcome, for `apply`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps: Option<StepCounts>,
+    /// Bytes fetched over the network
+    pub downloaded_bytes: u64,
+    /// Artifacts served from picolayer's caches instead of the network
+    pub cache_hits: u64,
+    /// Change in used bytes on the root filesystem; approximate, since other
+    /// processes can write to it too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_delta: Option<i64>,
+}
+
+/// Measurements taken when a run starts
+#[derive(Debug, Clone, Copy)]
+pub struct Run {
+    started: Instant,
+    used_before: Option<u64>,
+}
+
+impl Run {
+    /// Commands that only report are not measured for size
+    pub fn start(command: &Commands) -> Self {
+        Self {
+            started: Instant::now(),
+            used_before: (!is_read_only(command)).then(used_bytes).flatten(),
+        }
+    }
+
+    pub fn finish(&self, succeeded: bool) -> RunSummary {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        RunSummary {
+            action: super::output::action_name(&args),
+            status: if succeeded { "ok" } else { "failed" },
+            duration_ms: self.started.elapsed().as_millis(),
+            steps: STEPS.lock().unwrap().take(),
+            downloaded_bytes: progress::downloaded(),
+            cache_hits: progress::cache_hits(),
+            size_delta: self
+                .used_before
+                .zip(used_bytes())
+                .map(|(before, after)| after as i64 - before as i64),
+        }
+    }
+}
+
+impl RunSummary {
+    /// One line such as `apply: ok in 41.2s, 3 steps ok, 96.1 MiB downloaded, +212.4 MiB`
+    pub fn line(&self) -> String {
+        let mut line = format!(
+            "{}: {} in {:.1}s",
+            self.action,
+            self.status,
+            self.duration_ms as f64 / 1000.0
+        );
+        if let Some(steps) = self.steps {
+            line.push_str(&format!(", {} steps ok", steps.ok));
+            if steps.failed > 0 {
+                line.push_str(&format!(", {} failed", steps.failed));
+            }
+            if steps.skipped > 0 {
+                line.push_str(&format!(", {} skipped", steps.skipped));
+            }
+        }
+        if self.downloaded_bytes > 0 {
+            line.push_str(&format!(
+                ", {} downloaded",
+                os::format_bytes(self.downloaded_bytes)
+            ));
+        }
+        if self.cache_hits > 0 {
+            line.push_str(&format!(", {} cache hits", self.cache_hits));
+        }
+        if let Some(delta) = self.size_delta {
+            line.push_str(&format!(", {}", format_delta(delta)));
+        }
+        line
+    }
+
+    /// Write the summary as JSON to `path`
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Commands that only report on the system, which get no summary line
+pub fn is_read_only(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Doctor
+            | Commands::List
+            | Commands::Verify { .. }
+            | Commands::ExitCodes
+            | Commands::Generate { .. }
+    )
+}
+
+/// Bytes used on the root filesystem, which other processes can also change, so
+/// deltas are approximate
+pub fn used_bytes() -> Option<u64> {
+    match os::filesystem_usage(Path::new("/")) {
+        Ok(usage) => Some(usage.used),
+        Err(e) => {
+            warn!("Failed to measure filesystem usage: {}", e);
+            None
+        }
+    }
+}
+
+/// A signed size such as `+12.3 MiB` or `-4 KiB`
+pub fn format_delta(delta: i64) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{}{}", sign, os::format_bytes(delta.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_delta_is_signed() {
+        assert_eq!(format_delta(3 * 1024 * 1024 / 2), "+1.5 MiB");
+        assert_eq!(format_delta(-8192), "-8 KiB");
+        assert_eq!(format_delta(0), "+0 KiB");
+    }
+
+    #[test]
+    fn line_lists_only_what_happened() {
+        let summary = RunSummary {
+            action: "apply".to_string(),
+            status: "ok",
+            duration_ms: 41_200,
+            steps: Some(StepCounts {
+                ok: 3,
+                failed: 0,
+                skipped: 1,
+            }),
+            downloaded_bytes: 0,
+            cache_hits: 2,
+            size_delta: Some(2048),
+        };
+        assert_eq!(
+            summary.line(),
+            "apply: ok in 41.2s, 3 steps ok, 1 skipped, 2 cache hits, +2 KiB"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use super::OutputFormat;
+use super::output;
+use super::presence;
+use crate::error::PicolayerError;
+use crate::utils::lock::{self, LockedArtifact, Lockfile};
+use crate::utils::state::{Installed, State};
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Drift,
+}
+
+#[derive(Debug, Serialize)]
+struct Finding {
+    kind: String,
+    name: String,
+    status: Status,
+    message: String,
+}
+
+impl Finding {
+    fn new(kind: &str, name: &str, status: Status, message: String) -> Self {
+        Self {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            status,
+            message,
+        }
+    }
+}
+
+/// Compare what is installed with the state database and, if there is one, the lockfile
+pub fn run(lockfile: Option<&str>, output: OutputFormat) -> Result<()> {
+    let state = State::load()?;
+    let lockfile = match lockfile {
+        Some(path) => Some(Lockfile::read(Path::new(path))?),
+        None if Path::new(lock::LOCKFILE_NAME).exists() => {
+            Some(Lockfile::read(Path::new(lock::LOCKFILE_NAME))?)
+        }
+        None => None,
+    };
+
+    let mut findings: Vec<Finding> = state.installed.iter().map(check_installed).collect();
+    if let Some(lockfile) = &lockfile {
+        findings.extend(
+            lockfile
+                .artifacts
+                .iter()
+                .map(|artifact| check_locked(&state, artifact)),
+        );
+    }
+
+    if output == OutputFormat::Json {
+        output::set_details(&findings)?;
+    } else if findings.is_empty() {
+        println!(
+            "Nothing installed by picolayer ({})",
+            State::path().display()
+        );
+    } else {
+        for finding in &findings {
+            let label = match finding.status {
+                Status::Ok => "ok",
+                Status::Drift => "DRIFT",
+            };
+            println!(
+                "[{:>5}] {} {}: {}",
+                label, finding.kind, finding.name, finding.message
+            );
+        }
+    }
+
+    let drifted = findings
+        .iter()
+        .filter(|f| f.status == Status::Drift)
+        .count();
+    if drifted > 0 {
+        return Err(PicolayerError::Drift(drifted).into());
+    }
+    Ok(())
+}
+
+/// Recorded files still exist with their recorded digests, and packages are still installed
+fn check_installed(installed: &Installed) -> Finding {
+    let drift =
+        |message: String| Finding::new(&installed.kind, &installed.name, Status::Drift, message);
+
+    for file in &installed.files {
+        let path = Path::new(&file.path);
+        if path.symlink_metadata().is_err() {
+            return drift(format!("{} is missing", file.path));
+        }
+        if let Some(expected) = &file.sha256 {
+            let actual = std::fs::read(path)
+                .map(|data| hex::encode(Sha256::digest(&data)))
+                .unwrap_or_default();
+            if !actual.eq_ignore_ascii_case(expected) {
+                return drift(format!("{} has been modified", file.path));
+            }
+        }
+    }
+
+    let package_installed = match installed.kind.as_str() {
+        "apt-get" | "apt" | "aptitude" => Some(presence::dpkg_installed(&installed.name)),
+        "apk" if !installed.name.contains(['=', '<', '>', '~']) => {
+            Some(presence::apk_installed(&installed.name))
+        }
+        "brew" => Some(presence::brew_installed(&installed.name)),
+        _ => None,
+    };
+    if package_installed == Some(false) {
+        return drift(format!(
+            "{} is no longer installed at the recorded version",
+            installed.name
+        ));
+    }
+
+    let message = match (&installed.version, installed.files.len()) {
+        (Some(version), 0) => format!("{} installed", version),
+        (Some(version), files) => format!("{}, {} file(s) unchanged", version, files),
+        (None, 0) => "installed".to_string(),
+        (None, files) => format!("{} file(s) unchanged", files),
+    };
+    Finding::new(&installed.kind, &installed.name, Status::Ok, message)
+}
+
+/// The state records the version the lockfile pins for an artifact
+fn check_locked(state: &State, artifact: &LockedArtifact) -> Finding {
+    let name = state_name(artifact);
+    let versions: Vec<&str> = state
+        .installed
+        .iter()
+        .filter(|installed| installed.name == name)
+        .filter_map(|installed| installed.version.as_deref())
+        .collect();
+
+    let (status, message) = if versions.contains(&artifact.version.as_str()) {
+        (
+            Status::Ok,
+            format!("{} matches the lockfile", artifact.version),
+        )
+    } else if versions.is_empty() {
+        (
+            Status::Drift,
+            format!("locked at {} but not installed", artifact.version),
+        )
+    } else {
+        (
+            Status::Drift,
+            format!(
+                "locked at {} but {} is installed",
+                artifact.version,
+                versions.join(", ")
+            ),
+        )
+    };
+    Finding::new(&artifact.command, &artifact.name, status, message)
+}
+
+/// The name an artifact's install is recorded under in the state database
+fn state_name(artifact: &LockedArtifact) -> &str {
+    match (artifact.command.as_str(), artifact.name.as_str()) {
+        ("runtime", "nodejs") => "node",
+        (_, name) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(command: &str, name: &str, version: &str) -> LockedArtifact {
+        LockedArtifact {
+            step: 1,
+            command: command.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            url: String::new(),
+            sha256: String::new(),
+        }
+    }
+
+    #[test]
+    fn check_installed_detects_modified_and_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("tool");
+        std::fs::write(&binary, b"v1").unwrap();
+        let installed = Installed::new("gh-release", "owner/tool")
+            .version("v1.0.0")
+            .files([&binary]);
+        assert_eq!(check_installed(&installed).status, Status::Ok);
+
+        std::fs::write(&binary, b"tampered").unwrap();
+        assert_eq!(check_installed(&installed).status, Status::Drift);
+
+        std::fs::remove_file(&binary).unwrap();
+        assert!(check_installed(&installed).message.contains("missing"));
+    }
+
+    #[test]
+    fn check_locked_compares_versions() {
+        let mut state = State::default();
+        state.insert(Installed::new("runtime", "node").version("v22.11.0"));
+
+        let locked = artifact("runtime", "nodejs", "v22.11.0");
+        assert_eq!(check_locked(&state, &locked).status, Status::Ok);
+
+        let finding = check_locked(&state, &artifact("runtime", "nodejs", "v22.12.0"));
+        assert_eq!(finding.status, Status::Drift);
+        assert!(finding.message.contains("v22.11.0 is installed"));
+
+        let finding = check_locked(&state, &artifact("gh-release", "cli/cli", "v2.60.0"));
+        assert!(finding.message.contains("not installed"));
+    }
+}
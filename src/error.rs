@@ -1,93 +1,441 @@
+use crate::utils::timeout::EXIT_TIMED_OUT;
 use std::fmt;
 
+/// Exit status for failures without a more specific code
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status clap uses for invalid arguments
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NETWORK: i32 = 10;
+pub const EXIT_NOT_FOUND: i32 = 11;
+pub const EXIT_VERIFICATION: i32 = 12;
+pub const EXIT_PERMISSION: i32 = 13;
+pub const EXIT_NO_MATCHING_ASSETS: i32 = 14;
+pub const EXIT_FEATURE_DOWNLOAD: i32 = 15;
+pub const EXIT_DISK_SPACE: i32 = 16;
+
+/// Every exit status picolayer uses and what it means, for `picolayer exit-codes`
+pub const EXIT_CODES: [(i32, &str); 11] = [
+    (0, "success"),
+    (EXIT_FAILURE, "failure without a more specific code"),
+    (EXIT_USAGE, "invalid command-line arguments"),
+    (EXIT_NETWORK, "network connection failed"),
+    (EXIT_NOT_FOUND, "repository not found or not accessible"),
+    (
+        EXIT_VERIFICATION,
+        "checksum or signature verification failed",
+    ),
+    (EXIT_PERMISSION, "permission denied"),
+    (EXIT_NO_MATCHING_ASSETS, "no matching release assets"),
+    (
+        EXIT_FEATURE_DOWNLOAD,
+        "devcontainer feature download failed",
+    ),
+    (EXIT_DISK_SPACE, "insufficient disk space"),
+    (EXIT_TIMED_OUT, "--timeout expired"),
+];
+
+/// Whether another attempt at a failed operation could succeed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Network hiccups and the like, worth retrying
+    Transient,
+    /// Missing assets, checksum mismatches, invalid references: retrying cannot help
+    Permanent,
+    /// An HTTP status; the retry config decides which statuses are worth retrying
+    Status(u16),
+}
+
+/// Errors that know whether they are worth retrying
+pub trait Classify {
+    /// None when this error alone does not tell
+    fn class(&self) -> Option<ErrorClass>;
+}
+
+/// A failure that another attempt cannot fix, returned at once by `retry_async`
 #[derive(Debug)]
+pub struct Permanent(String);
+
+impl Permanent {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl fmt::Display for Permanent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Permanent {}
+
+impl Classify for Permanent {
+    fn class(&self) -> Option<ErrorClass> {
+        Some(ErrorClass::Permanent)
+    }
+}
+
+impl Classify for std::io::Error {
+    fn class(&self) -> Option<ErrorClass> {
+        use std::io::ErrorKind;
+        Some(match self.kind() {
+            ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::InvalidInput => {
+                ErrorClass::Permanent
+            }
+            _ => ErrorClass::Transient,
+        })
+    }
+}
+
+impl Classify for reqwest::Error {
+    fn class(&self) -> Option<ErrorClass> {
+        Some(match self.status() {
+            Some(status) => ErrorClass::Status(status.as_u16()),
+            None if self.is_builder() || self.is_decode() || self.is_redirect() => {
+                ErrorClass::Permanent
+            }
+            None => ErrorClass::Transient,
+        })
+    }
+}
+
+#[cfg(feature = "gh-release")]
+impl Classify for octocrab::Error {
+    fn class(&self) -> Option<ErrorClass> {
+        match self {
+            octocrab::Error::GitHub { source, .. } => {
+                Some(ErrorClass::Status(source.status_code.as_u16()))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "devcontainer")]
+impl Classify for oci_client::errors::OciDistributionError {
+    fn class(&self) -> Option<ErrorClass> {
+        use oci_client::errors::OciDistributionError;
+        match self {
+            OciDistributionError::ImageManifestNotFoundError(_)
+            | OciDistributionError::AuthenticationFailure(_)
+            | OciDistributionError::UnauthorizedError { .. } => Some(ErrorClass::Permanent),
+            OciDistributionError::ServerError { code, .. } => Some(ErrorClass::Status(*code)),
+            _ => None,
+        }
+    }
+}
+
+fn class_of<E: Classify + std::error::Error + 'static>(
+    cause: &(dyn std::error::Error + 'static),
+) -> Option<ErrorClass> {
+    cause.downcast_ref::<E>().and_then(Classify::class)
+}
+
+/// The first classification found along the chain of causes of `error`
+pub fn classify(error: &anyhow::Error) -> Option<ErrorClass> {
+    error.chain().find_map(|cause| {
+        let class = class_of::<Permanent>(cause)
+            .or_else(|| class_of::<PicolayerError>(cause))
+            .or_else(|| class_of::<crate::utils::http::StatusError>(cause))
+            .or_else(|| class_of::<reqwest::Error>(cause));
+        #[cfg(feature = "gh-release")]
+        let class = class.or_else(|| class_of::<octocrab::Error>(cause));
+        #[cfg(feature = "devcontainer")]
+        let class = class.or_else(|| class_of::<oci_client::errors::OciDistributionError>(cause));
+        class.or_else(|| class_of::<std::io::Error>(cause))
+    })
+}
+
+/// A failure picolayer can name. Installers return these inside `anyhow::Error`, so
+/// the context added on the way up is kept, and the top level finds them with a
+/// downcast instead of by matching on messages.
+#[derive(Debug, thiserror::Error)]
 pub enum PicolayerError {
+    #[error("Repository {0} not found or not accessible")]
+    RepositoryNotFound(String),
+    #[error("Failed to pull devcontainer feature {0}")]
+    FeatureDownloadFailed(String),
+    #[error("{message}")]
+    AssetNotFound {
+        message: String,
+        /// What to try instead, such as the assets the release does have
+        suggestion: Option<String>,
+    },
+    #[error("No pkgx package provides {tool}")]
+    ToolNotFound {
+        tool: String,
+        /// Pantry entries with similar names
+        similar: Vec<String>,
+    },
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("Signature verification failed: {0}")]
+    SignatureInvalid(String),
+    #[error("Installation verification failed: {0} item(s) drifted")]
+    Drift(usize),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    InsufficientDiskSpace(String),
+    #[error("{script} failed with exit code: {code:?}")]
+    ScriptFailed { script: String, code: Option<i32> },
+}
+
+impl PicolayerError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PicolayerError::RepositoryNotFound(_) => ErrorKind::RepositoryNotFound,
+            PicolayerError::FeatureDownloadFailed(_) => ErrorKind::FeatureDownloadFailed,
+            PicolayerError::AssetNotFound { .. } => ErrorKind::AssetNotFound,
+            PicolayerError::ToolNotFound { .. } => ErrorKind::ToolNotFound,
+            PicolayerError::ChecksumMismatch { .. } => ErrorKind::ChecksumMismatch,
+            PicolayerError::SignatureInvalid(_) => ErrorKind::SignatureInvalid,
+            PicolayerError::Drift(_) => ErrorKind::Drift,
+            PicolayerError::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            PicolayerError::InsufficientDiskSpace(_) => ErrorKind::InsufficientDiskSpace,
+            PicolayerError::ScriptFailed { .. } => ErrorKind::ScriptFailed,
+        }
+    }
+
+    /// A next step specific to this failure, shown instead of the kind's generic hint
+    pub fn suggestion(&self) -> Option<String> {
+        match self {
+            PicolayerError::AssetNotFound { suggestion, .. } => suggestion.clone(),
+            PicolayerError::ToolNotFound { similar, .. } if !similar.is_empty() => {
+                Some(format!("Did you mean {}?", similar.join(", ")))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A specific next step for `error`: the first one a [`PicolayerError`] along its chain
+/// offers, or else a GitHub token for requests GitHub refused
+pub fn suggestion(error: &anyhow::Error) -> Option<String> {
+    if let Some(suggestion) = error
+        .downcast_ref::<PicolayerError>()
+        .and_then(PicolayerError::suggestion)
+    {
+        return Some(suggestion);
+    }
+    error.chain().find_map(|cause| {
+        if let Some(error) = cause.downcast_ref::<PicolayerError>() {
+            error.suggestion()
+        } else if github_refused(cause) {
+            Some(if crate::utils::settings::github_token().is_some() {
+                "GitHub refused the request; check that GITHUB_TOKEN is valid and can read the repository".to_string()
+            } else {
+                "GitHub limits unauthenticated requests; set GITHUB_TOKEN, or github_token in the config file".to_string()
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// A 403 or 429 from the GitHub API or a GitHub download, which rate limiting causes
+fn github_refused(cause: &(dyn std::error::Error + 'static)) -> bool {
+    let refused = |status: u16| status == 403 || status == 429;
+    if let Some(error) = cause.downcast_ref::<crate::utils::http::StatusError>() {
+        return refused(error.status.as_u16())
+            && (error.url.contains("github.com") || error.url.contains("githubusercontent.com"));
+    }
+    #[cfg(feature = "gh-release")]
+    let api_status = match cause.downcast_ref::<octocrab::Error>() {
+        Some(octocrab::Error::GitHub { source, .. }) => Some(source.status_code.as_u16()),
+        _ => None,
+    };
+    #[cfg(not(feature = "gh-release"))]
+    let api_status = None;
+    api_status.is_some_and(refused)
+}
+
+/// Up to `limit` of `candidates` that look most like `target`, best first
+pub fn closest<'a>(
+    target: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    limit: usize,
+) -> Vec<&'a str> {
+    let target = target.to_lowercase();
+    let mut scored: Vec<(f64, &str)> = candidates
+        .into_iter()
+        .map(|candidate| {
+            (
+                strsim::jaro_winkler(&target, &candidate.to_lowercase()),
+                candidate,
+            )
+        })
+        .filter(|(score, _)| *score >= 0.8)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+impl Classify for PicolayerError {
+    fn class(&self) -> Option<ErrorClass> {
+        match self {
+            // A failed command may be a flaky mirror; whoever retries it decides
+            PicolayerError::ScriptFailed { .. } => None,
+            _ => Some(ErrorClass::Permanent),
+        }
+    }
+}
+
+/// The classes of failure picolayer reports, each with an exit status and a stable code
+/// that scripts can match on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
     RepositoryNotFound,
-    ContainerFeatureDownloadFailed,
-    NoMatchingAssets,
+    FeatureDownloadFailed,
+    AssetNotFound,
+    ToolNotFound,
+    ChecksumMismatch,
+    SignatureInvalid,
+    Drift,
     PermissionDenied,
     InsufficientDiskSpace,
-    NetworkConnectionFailed,
-    CatchAll(anyhow::Error),
+    Network,
+    ScriptFailed,
+    Other,
 }
 
-impl fmt::Display for PicolayerError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 12] = [
+        ErrorKind::RepositoryNotFound,
+        ErrorKind::FeatureDownloadFailed,
+        ErrorKind::AssetNotFound,
+        ErrorKind::ToolNotFound,
+        ErrorKind::ChecksumMismatch,
+        ErrorKind::SignatureInvalid,
+        ErrorKind::Drift,
+        ErrorKind::PermissionDenied,
+        ErrorKind::InsufficientDiskSpace,
+        ErrorKind::Network,
+        ErrorKind::ScriptFailed,
+        ErrorKind::Other,
+    ];
+
+    /// The first failure picolayer can name along the chain of `error`: a
+    /// [`PicolayerError`], or else an I/O or HTTP error that says what went wrong
+    pub fn of(error: &anyhow::Error) -> Self {
+        // downcast_ref also finds errors attached as context
+        if let Some(error) = error.downcast_ref::<PicolayerError>() {
+            return error.kind();
+        }
+        error
+            .chain()
+            .find_map(|cause| {
+                if let Some(error) = cause.downcast_ref::<PicolayerError>() {
+                    Some(error.kind())
+                } else if let Some(error) = cause.downcast_ref::<std::io::Error>() {
+                    use std::io::ErrorKind as Io;
+                    match error.kind() {
+                        Io::PermissionDenied => Some(ErrorKind::PermissionDenied),
+                        Io::StorageFull => Some(ErrorKind::InsufficientDiskSpace),
+                        Io::ConnectionRefused
+                        | Io::ConnectionReset
+                        | Io::ConnectionAborted
+                        | Io::HostUnreachable
+                        | Io::NetworkUnreachable
+                        | Io::TimedOut => Some(ErrorKind::Network),
+                        _ => None,
+                    }
+                } else if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+                    (error.is_connect() || error.is_timeout()).then_some(ErrorKind::Network)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(ErrorKind::Other)
+    }
+
+    /// Stable identifier printed with the error and in JSON output
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::RepositoryNotFound => "repository-not-found",
+            ErrorKind::FeatureDownloadFailed => "feature-download-failed",
+            ErrorKind::AssetNotFound => "asset-not-found",
+            ErrorKind::ToolNotFound => "tool-not-found",
+            ErrorKind::ChecksumMismatch => "checksum-mismatch",
+            ErrorKind::SignatureInvalid => "signature-invalid",
+            ErrorKind::Drift => "drift",
+            ErrorKind::PermissionDenied => "permission-denied",
+            ErrorKind::InsufficientDiskSpace => "disk-full",
+            ErrorKind::Network => "network",
+            ErrorKind::ScriptFailed => "script-failed",
+            ErrorKind::Other => "error",
+        }
+    }
+
+    /// Process exit status for this failure class
+    pub fn exit_code(self) -> i32 {
         match self {
-            PicolayerError::RepositoryNotFound => {
-                writeln!(f, "Error: Repository not found or not accessible")?;
-                write!(
-                    f,
-                    "Check the owner/repo names and ensure the repository exists"
-                )
+            ErrorKind::RepositoryNotFound => EXIT_NOT_FOUND,
+            ErrorKind::FeatureDownloadFailed => EXIT_FEATURE_DOWNLOAD,
+            ErrorKind::AssetNotFound => EXIT_NO_MATCHING_ASSETS,
+            ErrorKind::ChecksumMismatch | ErrorKind::SignatureInvalid | ErrorKind::Drift => {
+                EXIT_VERIFICATION
             }
-            PicolayerError::ContainerFeatureDownloadFailed => {
-                writeln!(f, "Error: Failed to download container feature")?;
-                write!(f, "Check the feature reference and your network connection")
+            ErrorKind::PermissionDenied => EXIT_PERMISSION,
+            ErrorKind::InsufficientDiskSpace => EXIT_DISK_SPACE,
+            ErrorKind::Network => EXIT_NETWORK,
+            ErrorKind::ToolNotFound | ErrorKind::ScriptFailed | ErrorKind::Other => EXIT_FAILURE,
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            ErrorKind::RepositoryNotFound => {
+                "Check the owner/repo names and ensure the repository exists"
             }
-            PicolayerError::NoMatchingAssets => {
-                writeln!(f, "Error: No matching release assets found")?;
-                write!(f, "Check your filter criteria or try a different version")
+            ErrorKind::FeatureDownloadFailed => {
+                "Check the feature reference and your network connection"
             }
-            PicolayerError::PermissionDenied => {
-                writeln!(f, "Error: Permission denied")?;
-                write!(
-                    f,
-                    "Check file permissions or run with appropriate privileges"
-                )
+            ErrorKind::AssetNotFound => "Check your filter criteria or try a different version",
+            ErrorKind::ToolNotFound => "Check the tool name against the pkgx pantry",
+            ErrorKind::ChecksumMismatch | ErrorKind::SignatureInvalid => {
+                "The download does not match its published checksum or signature; check the pinned values"
             }
-            PicolayerError::InsufficientDiskSpace => {
-                writeln!(f, "Error: Insufficient disk space")?;
-                write!(f, "Free up disk space and try again")
+            ErrorKind::Drift => {
+                "Reinstall the drifted items, or update the lockfile if the change was intended"
             }
-            PicolayerError::NetworkConnectionFailed => {
-                writeln!(f, "Error: Network connection failed")?;
-                write!(f, "Check your internet connection and try again")
-            }
-            PicolayerError::CatchAll(e) => {
-                writeln!(f, "Error: {}", e)?;
-                if std::env::var("RUST_BACKTRACE").is_ok()
-                    || std::env::var("PICOLAYER_DEBUG").is_ok()
-                {
-                    writeln!(f, "\nTechnical details:")?;
-                    write!(f, "{:?}", e)
-                } else {
-                    write!(f, "\nFor technical details, set PICOLAYER_DEBUG=1")
-                }
+            ErrorKind::PermissionDenied => {
+                "Check file permissions or run with appropriate privileges"
             }
+            ErrorKind::InsufficientDiskSpace => "Free up disk space and try again",
+            ErrorKind::Network => "Check your internet connection and try again",
+            ErrorKind::ScriptFailed => "The command's output above says why it failed",
+            ErrorKind::Other => "For technical details, set PICOLAYER_DEBUG=1",
         }
     }
 }
 
-impl From<anyhow::Error> for PicolayerError {
-    fn from(error: anyhow::Error) -> Self {
-        let full_error = format!("{:?}", error);
-
-        if (error.to_string().contains("GitHub") || full_error.contains("GitHub"))
-            && (full_error.contains("Not Found") || full_error.contains("not found"))
-        {
-            PicolayerError::RepositoryNotFound
-        } else if full_error.contains("Failed to pull OCI image")
-            || full_error.contains("Not authorized")
-        {
-            PicolayerError::ContainerFeatureDownloadFailed
-        } else if full_error.contains("No matching")
-            || full_error.contains("filter")
-            || full_error.contains("No suitable asset found")
-        {
-            PicolayerError::NoMatchingAssets
-        } else if full_error.contains("Permission denied") || full_error.contains("Access denied") {
-            PicolayerError::PermissionDenied
-        } else if full_error.contains("No space left") {
-            PicolayerError::InsufficientDiskSpace
-        } else if full_error.contains("Network")
-            || full_error.contains("connection")
-            || full_error.contains("timeout")
-        {
-            PicolayerError::NetworkConnectionFailed
-        } else {
-            PicolayerError::CatchAll(error)
+/// How a failed run is shown on stderr: the error with its context, its code, and what
+/// to do about it
+pub struct Report<'a>(pub &'a anyhow::Error);
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = ErrorKind::of(self.0);
+        match kind {
+            ErrorKind::Other => writeln!(f, "Error: {:#}", self.0)?,
+            _ => writeln!(f, "Error [{}]: {:#}", kind.code(), self.0)?,
+        }
+        let debug = kind == ErrorKind::Other
+            && (std::env::var("RUST_BACKTRACE").is_ok()
+                || std::env::var("PICOLAYER_DEBUG").is_ok());
+        match suggestion(self.0) {
+            Some(suggestion) if debug => writeln!(f, "{}", suggestion)?,
+            Some(suggestion) => return write!(f, "{}", suggestion),
+            None if !debug => return write!(f, "{}", kind.hint()),
+            None => {}
         }
+        writeln!(f, "\nTechnical details:")?;
+        write!(f, "{:?}", self.0)
     }
 }
 
@@ -96,75 +444,115 @@ mod tests {
     use super::*;
 
     #[test]
-    fn from_anyhow_classifies_github_not_found() {
-        let err = anyhow::anyhow!("GitHub API returned Not Found");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(picolayer_err, PicolayerError::RepositoryNotFound));
+    fn classify_finds_the_first_known_cause() {
+        let err = anyhow::Error::from(Permanent::new("No suitable asset found for this platform"))
+            .context("Installing from release v1.0.0");
+        assert_eq!(classify(&err), Some(ErrorClass::Permanent));
+
+        let err = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+            .context("Failed to download");
+        assert_eq!(classify(&err), Some(ErrorClass::Transient));
+
+        assert_eq!(classify(&anyhow::anyhow!("something else")), None);
     }
 
     #[test]
-    fn from_anyhow_classifies_oci_pull_failure() {
-        let err = anyhow::anyhow!("Failed to pull OCI image");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(
-            picolayer_err,
-            PicolayerError::ContainerFeatureDownloadFailed
-        ));
+    fn kind_of_finds_typed_errors_under_context() {
+        let err = anyhow::Error::from(PicolayerError::ChecksumMismatch {
+            expected: "abc".to_string(),
+            actual: "def".to_string(),
+        })
+        .context("Verifying gh_2.0.0_linux_amd64.tar.gz");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::ChecksumMismatch);
+        assert_eq!(
+            format!("{:#}", err),
+            "Verifying gh_2.0.0_linux_amd64.tar.gz: Checksum mismatch: expected abc, got def"
+        );
     }
 
     #[test]
-    fn from_anyhow_classifies_no_matching_assets() {
-        let err = anyhow::anyhow!("No matching release found");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(picolayer_err, PicolayerError::NoMatchingAssets));
+    fn kind_of_finds_typed_errors_attached_as_context() {
+        let err = anyhow::anyhow!("manifest unknown")
+            .context(PicolayerError::FeatureDownloadFailed(
+                "ghcr.io/a/b:1".to_string(),
+            ))
+            .context("Installing feature");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::FeatureDownloadFailed);
     }
 
     #[test]
-    fn from_anyhow_classifies_permission_denied() {
-        let err = anyhow::anyhow!("Permission denied writing to /usr/local/bin");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(picolayer_err, PicolayerError::PermissionDenied));
+    fn kind_of_reads_io_errors_but_not_messages() {
+        let err = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            .context("Failed to write /usr/local/bin/gh");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::PermissionDenied);
+
+        let err = anyhow::anyhow!("Checksum mismatch in a message");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Other);
     }
 
     #[test]
-    fn from_anyhow_classifies_disk_space() {
-        let err = anyhow::anyhow!("No space left on device");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(
-            picolayer_err,
-            PicolayerError::InsufficientDiskSpace
-        ));
+    fn codes_are_distinct_and_exit_codes_documented() {
+        let codes: std::collections::BTreeSet<&str> =
+            ErrorKind::ALL.iter().map(|kind| kind.code()).collect();
+        assert_eq!(codes.len(), ErrorKind::ALL.len());
+        let documented =
+            |kind: &ErrorKind| EXIT_CODES.iter().any(|(code, _)| *code == kind.exit_code());
+        assert!(ErrorKind::ALL.iter().all(documented));
     }
 
     #[test]
-    fn from_anyhow_classifies_network_error() {
-        let err = anyhow::anyhow!("Network connection refused");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(
-            picolayer_err,
-            PicolayerError::NetworkConnectionFailed
-        ));
+    fn report_shows_code_context_and_hint() {
+        let err = anyhow::Error::from(PicolayerError::RepositoryNotFound("cli/nope".to_string()))
+            .context("Installing from GitHub");
+        let report = Report(&err).to_string();
+        assert!(report.starts_with("Error [repository-not-found]: Installing from GitHub: "));
+        assert!(report.contains("Check the owner/repo names"));
     }
 
     #[test]
-    fn from_anyhow_falls_through_to_catch_all() {
-        let err = anyhow::anyhow!("Some completely unknown error");
-        let picolayer_err: PicolayerError = err.into();
-        assert!(matches!(picolayer_err, PicolayerError::CatchAll(_)));
+    fn suggestions_come_from_typed_errors_and_github_refusals() {
+        let err = anyhow::Error::from(PicolayerError::AssetNotFound {
+            message: "No asset matching filter pattern linux".to_string(),
+            suggestion: Some("Available assets: tool-darwin.tar.gz".to_string()),
+        })
+        .context("Installing owner/tool");
+        assert_eq!(
+            suggestion(&err).as_deref(),
+            Some("Available assets: tool-darwin.tar.gz")
+        );
+        assert!(
+            Report(&err)
+                .to_string()
+                .ends_with("Available assets: tool-darwin.tar.gz")
+        );
+
+        let err = anyhow::Error::from(crate::utils::http::StatusError {
+            url: "https://api.github.com/repos/cli/cli/releases".to_string(),
+            status: reqwest::StatusCode::FORBIDDEN,
+        });
+        assert!(suggestion(&err).unwrap().contains("GITHUB_TOKEN"));
+
+        let err = anyhow::Error::from(crate::utils::http::StatusError {
+            url: "https://example.com/tool.tar.gz".to_string(),
+            status: reqwest::StatusCode::FORBIDDEN,
+        });
+        assert_eq!(suggestion(&err), None);
     }
 
     #[test]
-    fn display_includes_helpful_message() {
-        let err = PicolayerError::RepositoryNotFound;
-        let display = format!("{}", err);
-        assert!(display.contains("Repository not found"));
-        assert!(display.contains("Check the owner/repo names"));
+    fn closest_ranks_similar_names_first() {
+        let names = ["ripgrep", "rg", "node", "go"];
+        assert_eq!(closest("ripgrpe", names, 2), ["ripgrep"]);
+        assert!(closest("zzzzzz", names, 3).is_empty());
     }
 
     #[test]
-    fn display_catch_all_includes_error_message() {
-        let err = PicolayerError::CatchAll(anyhow::anyhow!("test error message"));
-        let display = format!("{}", err);
-        assert!(display.contains("test error message"));
+    fn report_for_other_errors_has_no_code() {
+        let err = anyhow::anyhow!("test error message");
+        assert!(
+            Report(&err)
+                .to_string()
+                .starts_with("Error: test error message")
+        );
     }
 }
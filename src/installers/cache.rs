@@ -0,0 +1,236 @@
+use super::clean::{disk_usage, remove_paths};
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub struct PruneConfig {
+    /// Remove the oldest entries until the cache is at most this many bytes
+    pub max_size: Option<u64>,
+    /// Remove entries not modified for this long
+    pub older_than: Option<Duration>,
+    /// Remove everything
+    pub all: bool,
+    /// Report what would be removed without removing it
+    pub dry_run: bool,
+}
+
+/// What pruning removed, or would remove with --dry-run
+#[derive(Debug, Serialize)]
+pub struct PruneResult {
+    pub cache_dir: PathBuf,
+    /// The entries removed, or that would be
+    pub paths: Vec<PathBuf>,
+    pub removed: usize,
+    pub bytes: u64,
+    pub remaining_bytes: u64,
+}
+
+/// One unit of the cache, removed as a whole: a downloaded file, an OCI layer, or a
+/// pkgx package directory
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    bytes: u64,
+    /// When anything inside was last written
+    modified: SystemTime,
+}
+
+pub fn prune(config: &PruneConfig) -> Result<PruneResult> {
+    let cache_dir = utils::settings::cache_dir();
+    let mut entries = entries(&cache_dir);
+    let total: u64 = entries.iter().map(|e| e.bytes).sum();
+    debug!(
+        "{}: {} entries, {} bytes",
+        cache_dir.display(),
+        entries.len(),
+        total
+    );
+
+    let doomed = select(&mut entries, config, SystemTime::now());
+    let bytes: u64 = doomed.iter().map(|e| e.bytes).sum();
+    let paths: Vec<PathBuf> = doomed.into_iter().map(|e| e.path).collect();
+
+    if config.dry_run {
+        debug!("Dry run: not removing {} entries", paths.len());
+    } else if !paths.is_empty() {
+        remove_paths(&paths).with_context(|| format!("Failed to prune {}", cache_dir.display()))?;
+        info!(
+            "Pruned {} cache entries ({}) from {}",
+            paths.len(),
+            utils::os::format_bytes(bytes),
+            cache_dir.display()
+        );
+    } else {
+        info!("Nothing to prune in {}", cache_dir.display());
+    }
+
+    Ok(PruneResult {
+        cache_dir,
+        removed: paths.len(),
+        paths,
+        bytes,
+        remaining_bytes: total - bytes,
+    })
+}
+
+/// The entries to remove: everything with `all`, else those older than `older_than`, then
+/// the oldest of the rest until they fit in `max_size`
+fn select(entries: &mut Vec<Entry>, config: &PruneConfig, now: SystemTime) -> Vec<Entry> {
+    if config.all {
+        return std::mem::take(entries);
+    }
+
+    // Oldest first, so both limits remove the least recently written entries
+    entries.sort_by_key(|e| e.modified);
+    let expired = match config.older_than {
+        Some(age) => entries
+            .iter()
+            .take_while(|e| now.duration_since(e.modified).unwrap_or_default() > age)
+            .count(),
+        None => 0,
+    };
+    let mut doomed: Vec<Entry> = entries.drain(..expired).collect();
+
+    if let Some(max_size) = config.max_size {
+        let mut remaining: u64 = entries.iter().map(|e| e.bytes).sum();
+        let over = entries
+            .iter()
+            .take_while(|e| {
+                let over = remaining > max_size;
+                remaining -= e.bytes;
+                over
+            })
+            .count();
+        doomed.extend(entries.drain(..over));
+    }
+    doomed
+}
+
+/// Children of each top-level cache directory, and files directly in the cache
+fn entries(cache_dir: &Path) -> Vec<Entry> {
+    let Ok(read_dir) = std::fs::read_dir(cache_dir) else {
+        return Vec::new();
+    };
+    read_dir
+        .filter_map(|entry| entry.ok())
+        .flat_map(|entry| {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => std::fs::read_dir(&path)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|child| child.ok())
+                    .map(|child| child.path())
+                    .collect(),
+                _ => vec![path],
+            }
+        })
+        .map(|path| Entry {
+            bytes: disk_usage(&path),
+            modified: last_modified(&path),
+            path,
+        })
+        .collect()
+}
+
+/// The latest modification time of `path` or anything under it
+fn last_modified(path: &Path) -> SystemTime {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn entry(name: &str, bytes: u64, age_days: u64, now: SystemTime) -> Entry {
+        Entry {
+            path: PathBuf::from(name),
+            bytes,
+            modified: now - DAY * age_days as u32,
+        }
+    }
+
+    fn config() -> PruneConfig {
+        PruneConfig {
+            max_size: None,
+            older_than: None,
+            all: false,
+            dry_run: false,
+        }
+    }
+
+    fn names(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn select_removes_expired_then_oldest_over_the_limit() {
+        let now = SystemTime::now();
+        let mut entries = vec![
+            entry("fresh", 100, 1, now),
+            entry("stale", 100, 40, now),
+            entry("week", 300, 7, now),
+            entry("month", 100, 20, now),
+        ];
+        let doomed = select(
+            &mut entries,
+            &PruneConfig {
+                older_than: Some(DAY * 30),
+                // Once stale expires, the other three take 500 bytes; dropping the oldest
+                // of them, month, brings that down to exactly the limit
+                max_size: Some(400),
+                ..config()
+            },
+            now,
+        );
+        assert_eq!(names(&doomed), ["stale", "month"]);
+        assert_eq!(names(&entries), ["week", "fresh"]);
+    }
+
+    #[test]
+    fn select_all_takes_everything() {
+        let now = SystemTime::now();
+        let mut entries = vec![entry("a", 1, 0, now), entry("b", 1, 0, now)];
+        let doomed = select(
+            &mut entries,
+            &PruneConfig {
+                all: true,
+                ..config()
+            },
+            now,
+        );
+        assert_eq!(doomed.len(), 2);
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn entries_are_the_children_of_cache_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("downloads")).unwrap();
+        std::fs::create_dir_all(dir.path().join("pkgx/nodejs.org/v20.0.0/bin")).unwrap();
+        std::fs::write(dir.path().join("downloads/asset.tar.gz"), [0u8; 64]).unwrap();
+        std::fs::write(
+            dir.path().join("pkgx/nodejs.org/v20.0.0/bin/node"),
+            [0u8; 32],
+        )
+        .unwrap();
+
+        let mut entries = entries(dir.path());
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].path.ends_with("downloads/asset.tar.gz"));
+        assert_eq!(entries[0].bytes, 64);
+        assert!(entries[1].path.ends_with("pkgx/nodejs.org"));
+        assert_eq!(entries[1].bytes, 32);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::cli::RetryConfig;
+#[cfg(feature = "gh-release")]
+use crate::installers::gh_release;
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub struct CargoConfig<'a> {
+    pub packages: &'a [String],
+    pub install_dir: &'a str,
+    /// Build from source with `cargo install` instead of fetching prebuilt binaries
+    pub no_binstall: bool,
+}
+
+pub async fn install(config: &CargoConfig<'_>, retry_config: &RetryConfig) -> Result<()> {
+    utils::offline::ensure_online("cargo")?;
+    // Point CARGO_HOME at a temporary directory so registry caches and build
+    // artifacts never end up in the image layer
+    let temp_dir = utils::tmp::tempdir()?;
+    let cargo_available = which::which("cargo").is_ok();
+
+    let binaries = if config.no_binstall {
+        anyhow::ensure!(cargo_available, "--no-binstall requires cargo in PATH");
+        install_with_cargo(config, temp_dir.path())?
+    } else {
+        match ensure_binstall(temp_dir.path(), retry_config).await {
+            Ok(binstall) => {
+                install_with_binstall(&binstall, config, temp_dir.path(), cargo_available)?
+            }
+            Err(e) if cargo_available => {
+                warn!(
+                    "cargo-binstall is unavailable ({}), falling back to cargo install",
+                    e
+                );
+                install_with_cargo(config, temp_dir.path())?
+            }
+            Err(e) => {
+                return Err(
+                    e.context("Failed to bootstrap cargo-binstall and cargo is not installed")
+                );
+            }
+        }
+    };
+
+    utils::verify::verify_executables(&binaries, Some(Path::new(config.install_dir)))
+}
+
+/// Use cargo-binstall from PATH, or download a prebuilt one into `temp_dir`
+async fn ensure_binstall(temp_dir: &Path, retry_config: &RetryConfig) -> Result<PathBuf> {
+    if let Ok(path) = which::which("cargo-binstall") {
+        debug!("cargo-binstall is already available at {}", path.display());
+        return Ok(path);
+    }
+
+    download_binstall(temp_dir, retry_config).await
+}
+
+#[cfg(feature = "gh-release")]
+async fn download_binstall(temp_dir: &Path, retry_config: &RetryConfig) -> Result<PathBuf> {
+    info!("cargo-binstall not found, downloading a prebuilt release");
+    let bin_dir = temp_dir.join("bin");
+    std::fs::create_dir_all(&bin_dir).context("Failed to create temporary bin directory")?;
+    let bin_dir_str = bin_dir
+        .to_str()
+        .context("Failed to convert temporary bin directory path to string")?;
+
+    let filter = binstall_asset_filter(std::env::consts::ARCH, std::env::consts::OS)?;
+    gh_release::install(
+        &gh_release::GhReleaseConfig {
+            owner: "cargo-bins",
+            repo: "cargo-binstall",
+            binary_names: &["cargo-binstall".to_string()],
+            version: "latest",
+            tag_prefix: None,
+            install_dir: bin_dir_str,
+            filter: Some(&filter),
+            chown: None,
+            mode: None,
+            verify_checksum: false,
+            checksum_text: None,
+            checksum_algorithm: Default::default(),
+            gpg_key: None,
+            include_prerelease: false,
+            from_tag: false,
+            wait_for_release: None,
+        },
+        retry_config,
+    )
+    .await?;
+
+    Ok(bin_dir.join("cargo-binstall"))
+}
+
+/// Without release downloads, only a cargo-binstall already in PATH can be used
+#[cfg(not(feature = "gh-release"))]
+async fn download_binstall(_temp_dir: &Path, _retry_config: &RetryConfig) -> Result<PathBuf> {
+    Err(super::not_built("gh-release"))
+}
+
+/// Regex selecting the cargo-binstall release asset for this platform
+#[cfg_attr(not(feature = "gh-release"), allow(dead_code))]
+fn binstall_asset_filter(arch: &str, os: &str) -> Result<String> {
+    anyhow::ensure!(
+        matches!(arch, "x86_64" | "aarch64"),
+        "No prebuilt cargo-binstall for architecture {}",
+        arch
+    );
+    // Only the Linux builds ship as tarballs that the gh-release extractor understands
+    anyhow::ensure!(
+        os == "linux",
+        "No prebuilt cargo-binstall tarball for {}",
+        os
+    );
+    Ok(format!(
+        r"^cargo-binstall-{}-unknown-linux-musl\.tgz$",
+        arch
+    ))
+}
+
+/// Install with cargo-binstall, returning the names of the installed binaries
+fn install_with_binstall(
+    binstall: &Path,
+    config: &CargoConfig,
+    cargo_home: &Path,
+    cargo_available: bool,
+) -> Result<Vec<String>> {
+    debug!(
+        "Installing crates with cargo-binstall: {:?}",
+        config.packages
+    );
+
+    let mut cmd = Command::new(binstall);
+    cmd.env("CARGO_HOME", cargo_home);
+    cmd.args(["--no-confirm", "--install-path", config.install_dir]);
+    if !cargo_available {
+        // Without cargo, building from source is impossible, so fail fast instead
+        cmd.args(["--disable-strategies", "compile"]);
+    }
+    cmd.args(config.packages);
+    utils::exec::run_command(&mut cmd, "Install crates with cargo-binstall")?;
+
+    info!("Successfully installed crates: {:?}", config.packages);
+
+    // cargo-binstall records what it installed in CARGO_HOME, like cargo install does
+    let binaries = std::fs::read_to_string(cargo_home.join(".crates2.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .map(|manifest| tracked_binaries(&manifest))
+        .unwrap_or_default();
+    if binaries.is_empty() {
+        warn!("cargo-binstall did not record installed binaries, skipping verification");
+    }
+    Ok(binaries)
+}
+
+/// Binary names listed in a cargo `.crates2.json` install manifest
+fn tracked_binaries(manifest: &serde_json::Value) -> Vec<String> {
+    manifest["installs"]
+        .as_object()
+        .into_iter()
+        .flat_map(|installs| installs.values())
+        .filter_map(|install| install["bins"].as_array())
+        .flatten()
+        .filter_map(|bin| bin.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Build with cargo install, returning the names of the installed binaries
+fn install_with_cargo(config: &CargoConfig, cargo_home: &Path) -> Result<Vec<String>> {
+    debug!(
+        "Installing crates with cargo install: {:?}",
+        config.packages
+    );
+
+    let root = cargo_home.join("root");
+    let mut cmd = Command::new("cargo");
+    cmd.env("CARGO_HOME", cargo_home);
+    cmd.env("CARGO_TARGET_DIR", cargo_home.join("target"));
+    cmd.args(["install", "--locked", "--root"]);
+    cmd.arg(&root);
+    cmd.args(config.packages);
+    utils::exec::run_command(&mut cmd, "Install crates with cargo")?;
+
+    let install_dir = Path::new(config.install_dir);
+    utils::sudo::create_dir_all(install_dir)?;
+    let mut binaries = Vec::new();
+    for entry in
+        std::fs::read_dir(root.join("bin")).context("cargo install produced no binaries")?
+    {
+        let entry = entry?;
+        let dest = install_dir.join(entry.file_name());
+        utils::sudo::install_executable(&entry.path(), &dest)
+            .with_context(|| format!("Failed to install {}", dest.display()))?;
+        info!("Installed {}", dest.display());
+        binaries.push(entry.file_name().to_string_lossy().to_string());
+    }
+
+    info!("Successfully installed crates: {:?}", config.packages);
+    Ok(binaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binstall_asset_filter_matches_release_names() {
+        let filter = binstall_asset_filter("x86_64", "linux").unwrap();
+        let regex = regex::Regex::new(&filter).unwrap();
+        assert!(regex.is_match("cargo-binstall-x86_64-unknown-linux-musl.tgz"));
+        assert!(!regex.is_match("cargo-binstall-x86_64-unknown-linux-musl.tgz.sig"));
+        assert!(!regex.is_match("cargo-binstall-x86_64-unknown-linux-gnu.tgz"));
+
+        assert!(binstall_asset_filter("aarch64", "linux").is_ok());
+        assert!(binstall_asset_filter("aarch64", "macos").is_err());
+        assert!(binstall_asset_filter("riscv64", "linux").is_err());
+    }
+
+    #[test]
+    fn tracked_binaries_reads_crates2_manifest() {
+        let manifest = serde_json::json!({
+            "installs": {
+                "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+                    "bins": ["rg"]
+                },
+                "fd-find 10.2.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+                    "bins": ["fd"]
+                }
+            }
+        });
+        let mut binaries = tracked_binaries(&manifest);
+        binaries.sort();
+        assert_eq!(binaries, ["fd", "rg"]);
+        assert!(tracked_binaries(&serde_json::json!({})).is_empty());
+    }
+}
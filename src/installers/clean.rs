@@ -0,0 +1,253 @@
+use crate::cli::CleanTarget;
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+pub struct CleanConfig<'a> {
+    /// Also remove documentation and package manager logs
+    pub aggressive: bool,
+    /// Targets left untouched
+    pub skip: &'a [CleanTarget],
+    /// Report what would be removed without removing it
+    pub dry_run: bool,
+}
+
+/// Space reclaimed from one target
+#[derive(Debug, Serialize)]
+pub struct CleanResult {
+    pub target: CleanTarget,
+    pub paths: usize,
+    pub bytes: u64,
+}
+
+/// Space reclaimed by a clean, or reclaimable with --dry-run
+#[derive(Debug, Serialize)]
+pub struct CleanReport {
+    pub targets: Vec<CleanResult>,
+    pub bytes: u64,
+}
+
+/// A directory whose contents are removed. Entries named in `keep` survive, and with
+/// `extension` set only regular files with that extension are removed.
+#[derive(Clone)]
+struct Location {
+    dir: PathBuf,
+    keep: &'static [&'static str],
+    extension: Option<&'static str>,
+}
+
+impl Location {
+    fn contents(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            keep: &[],
+            extension: None,
+        }
+    }
+
+    fn keeping(dir: impl Into<PathBuf>, keep: &'static [&'static str]) -> Self {
+        Self {
+            keep,
+            ..Self::contents(dir)
+        }
+    }
+
+    fn files_with_extension(dir: impl Into<PathBuf>, extension: &'static str) -> Self {
+        Self {
+            extension: Some(extension),
+            ..Self::contents(dir)
+        }
+    }
+}
+
+pub fn clean(config: &CleanConfig) -> Result<CleanReport> {
+    let homes = home_dirs();
+    let mut results = Vec::new();
+
+    for target in targets(config) {
+        let paths: Vec<PathBuf> = locations(target, &homes).iter().flat_map(entries).collect();
+        let bytes = paths.iter().map(|p| disk_usage(p)).sum();
+        debug!("{:?}: {} paths, {} bytes", target, paths.len(), bytes);
+
+        if !config.dry_run && !paths.is_empty() {
+            remove_paths(&paths).with_context(|| format!("Failed to clean {:?}", target))?;
+        }
+        results.push(CleanResult {
+            target,
+            paths: paths.len(),
+            bytes,
+        });
+    }
+
+    let bytes: u64 = results.iter().map(|r| r.bytes).sum();
+    if !config.dry_run {
+        info!("Reclaimed {} bytes", bytes);
+    }
+    Ok(CleanReport {
+        targets: results,
+        bytes,
+    })
+}
+
+/// The default policy, plus documentation and logs when aggressive, minus skipped targets
+fn targets(config: &CleanConfig) -> Vec<CleanTarget> {
+    use CleanTarget::*;
+
+    let mut targets = vec![AptLists, PackageCache, Pip, Npm, Pkgx, Temp];
+    if config.aggressive {
+        targets.extend([Docs, Logs]);
+    }
+    targets.retain(|t| !config.skip.contains(t));
+    targets
+}
+
+fn locations(target: CleanTarget, homes: &BTreeSet<PathBuf>) -> Vec<Location> {
+    let in_homes = |relative: &str| {
+        homes
+            .iter()
+            .map(|home| Location::contents(home.join(relative)))
+            .collect::<Vec<_>>()
+    };
+
+    match target {
+        CleanTarget::AptLists => vec![Location::keeping(
+            "/var/lib/apt/lists",
+            &["lock", "partial"],
+        )],
+        CleanTarget::PackageCache => vec![
+            Location::keeping("/var/cache/apt", &["archives"]),
+            Location::keeping("/var/cache/apt/archives", &["lock", "partial"]),
+            Location::contents("/var/cache/apk"),
+        ],
+        CleanTarget::Pip => in_homes(".cache/pip"),
+        CleanTarget::Npm => [in_homes(".npm/_cacache"), in_homes(".cache/yarn")].concat(),
+        CleanTarget::Pkgx => in_homes(".cache/pkgx"),
+        CleanTarget::Temp => vec![Location::contents("/tmp"), Location::contents("/var/tmp")],
+        CleanTarget::Docs => ["doc", "man", "info", "groff", "lintian", "linda"]
+            .iter()
+            .map(|dir| Location::contents(Path::new("/usr/share").join(dir)))
+            .collect(),
+        // Loose logs such as dpkg.log; service log directories are left in place
+        CleanTarget::Logs => vec![
+            Location::contents("/var/log/apt"),
+            Location::files_with_extension("/var/log", "log"),
+        ],
+    }
+}
+
+/// Paths inside a location that should be removed
+fn entries(location: &Location) -> Vec<PathBuf> {
+    let Ok(read_dir) = std::fs::read_dir(&location.dir) else {
+        return Vec::new();
+    };
+
+    read_dir
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            !location.keep.iter().any(|keep| name == *keep)
+        })
+        .filter(|entry| match location.extension {
+            Some(extension) => {
+                entry.file_type().is_ok_and(|t| t.is_file())
+                    && entry.path().extension().is_some_and(|ext| ext == extension)
+            }
+            None => true,
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// HOME and root's home, where per-user caches live
+fn home_dirs() -> BTreeSet<PathBuf> {
+    let mut homes = BTreeSet::from([PathBuf::from("/root")]);
+    if let Some(home) = std::env::var_os("HOME").filter(|h| !h.is_empty()) {
+        homes.insert(PathBuf::from(home));
+    }
+    homes
+}
+
+/// Apparent size of a file or directory tree, without following symlinks
+pub(super) fn disk_usage(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| !metadata.is_dir())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+pub(super) fn remove_paths(paths: &[PathBuf]) -> Result<()> {
+    let mut denied = Vec::new();
+    for path in paths {
+        let removed = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+            Ok(_) => std::fs::remove_file(path),
+            Err(_) => continue,
+        };
+        if removed.is_err() {
+            denied.push(path);
+        }
+    }
+
+    // System directories are root-owned; remove what is left in one privileged call
+    if !denied.is_empty() {
+        utils::sudo::ensure_privileges("Cleaning system directories")?;
+        utils::exec::run_command(
+            utils::sudo::command("rm").arg("-rf").args(&denied),
+            &format!("rm -rf ({} paths)", denied.len()),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_follow_policy() {
+        let config = CleanConfig {
+            aggressive: false,
+            skip: &[CleanTarget::Temp],
+            dry_run: true,
+        };
+        let selected = targets(&config);
+        assert!(selected.contains(&CleanTarget::AptLists));
+        assert!(!selected.contains(&CleanTarget::Temp));
+        assert!(!selected.contains(&CleanTarget::Docs));
+
+        let aggressive = CleanConfig {
+            aggressive: true,
+            ..config
+        };
+        assert!(targets(&aggressive).contains(&CleanTarget::Docs));
+    }
+
+    #[test]
+    fn entries_respect_keep_list() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["lock", "partial", "deb.debian.org_dists_bookworm_InRelease"] {
+            std::fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        let entries = entries(&Location::keeping(dir.path(), &["lock", "partial"]));
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].ends_with("deb.debian.org_dists_bookworm_InRelease"));
+    }
+
+    #[test]
+    fn clean_removes_entries_and_reports_size() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("cache")).unwrap();
+        std::fs::write(dir.path().join("cache/blob"), [0u8; 128]).unwrap();
+
+        let paths = entries(&Location::contents(dir.path()));
+        assert_eq!(paths.iter().map(|p| disk_usage(p)).sum::<u64>(), 128);
+        remove_paths(&paths).unwrap();
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+}
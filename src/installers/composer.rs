@@ -0,0 +1,119 @@
+use crate::error::PicolayerError;
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use sha2::{Digest, Sha384};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const INSTALLER_URL: &str = "https://getcomposer.org/installer";
+const INSTALLER_SIGNATURE_URL: &str = "https://composer.github.io/installer.sig";
+
+pub struct ComposerConfig<'a> {
+    pub packages: &'a [String],
+    /// Where global packages are installed (COMPOSER_HOME)
+    pub composer_home: &'a str,
+    /// Where package executables are linked (COMPOSER_BIN_DIR)
+    pub bin_dir: &'a str,
+}
+
+pub async fn install(config: &ComposerConfig<'_>) -> Result<()> {
+    utils::offline::ensure_online("composer")?;
+    anyhow::ensure!(
+        which::which("php").is_ok(),
+        "php command not found in PATH. Install PHP before installing composer packages."
+    );
+
+    // The composer download cache and a bootstrapped composer.phar live in a
+    // temporary directory so neither ends up in the layer
+    let temp_dir = utils::tmp::tempdir()?;
+    let composer = match which::which("composer") {
+        Ok(path) => {
+            debug!("composer is already available at {}", path.display());
+            path
+        }
+        Err(_) => bootstrap_composer(temp_dir.path()).await?,
+    };
+
+    debug!("Installing composer packages: {:?}", config.packages);
+    let mut cmd = Command::new(&composer);
+    cmd.env("COMPOSER_HOME", config.composer_home);
+    cmd.env("COMPOSER_BIN_DIR", config.bin_dir);
+    cmd.env("COMPOSER_CACHE_DIR", temp_dir.path().join("cache"));
+    if utils::sudo::is_root() {
+        cmd.env("COMPOSER_ALLOW_SUPERUSER", "1");
+    }
+    cmd.args([
+        "global",
+        "require",
+        "--no-interaction",
+        "--no-progress",
+        "--optimize-autoloader",
+    ]);
+    cmd.args(config.packages);
+    utils::exec::run_command(&mut cmd, "Install composer packages")?;
+
+    info!(
+        "Successfully installed composer packages to {}: {:?}",
+        config.bin_dir, config.packages
+    );
+    Ok(())
+}
+
+/// Download the composer installer, verify it against the published SHA-384, and run it
+async fn bootstrap_composer(temp_dir: &Path) -> Result<PathBuf> {
+    info!("composer not found, bootstrapping it with the official installer");
+
+    let (installer, signature) = tokio::try_join!(
+        utils::download::fetch(INSTALLER_URL),
+        utils::download::fetch(INSTALLER_SIGNATURE_URL)
+    )?;
+    let signature =
+        String::from_utf8(signature).context("Composer installer signature is not valid UTF-8")?;
+    let digests = utils::download::Digests::of(&installer);
+    if let Err(e) = verify_installer(&installer, &signature) {
+        utils::audit::verification_failed(INSTALLER_URL, &digests, "sha384");
+        return Err(e);
+    }
+    utils::audit::verified(INSTALLER_URL, "sha384");
+    utils::audit::fetched("composer-setup", "latest", INSTALLER_URL, &digests);
+
+    let installer_path = temp_dir.join("composer-setup.php");
+    std::fs::write(&installer_path, &installer).context("Failed to write composer installer")?;
+
+    let mut cmd = Command::new("php");
+    cmd.arg(&installer_path);
+    cmd.arg(format!("--install-dir={}", temp_dir.display()));
+    cmd.args(["--filename=composer", "--quiet"]);
+    utils::exec::run_command(&mut cmd, "Run composer installer")?;
+
+    Ok(temp_dir.join("composer"))
+}
+
+fn verify_installer(installer: &[u8], signature: &str) -> Result<()> {
+    let mut hasher = Sha384::new();
+    hasher.update(installer);
+    let actual = hex::encode(hasher.finalize());
+    let expected = signature.trim().to_lowercase();
+
+    if actual != expected {
+        return Err(
+            anyhow::Error::from(PicolayerError::ChecksumMismatch { expected, actual })
+                .context("Composer installer signature mismatch"),
+        );
+    }
+    debug!("Composer installer signature verified");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_installer_checks_sha384() {
+        let signature = "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b";
+        assert!(verify_installer(b"", &format!("{}\n", signature)).is_ok());
+        assert!(verify_installer(b"tampered", signature).is_err());
+    }
+}
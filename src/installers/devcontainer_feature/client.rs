@@ -1,11 +1,17 @@
 use anyhow::{Context, Result};
 use log::info;
-use oci_client::{Client, Reference, client::ClientConfig};
-use std::io::Cursor;
+use oci_client::{
+    Client, Reference,
+    client::{Certificate, CertificateEncoding, ClientConfig},
+    secrets::RegistryAuth,
+};
+use std::io::{BufReader, Read};
 use std::path::Path;
 
 use crate::cli::RetryConfig;
-use crate::utils::retry::retry_async;
+use crate::error::{Permanent, PicolayerError};
+use crate::utils::download::DownloadedFile;
+use crate::utils::{self, retry::retry_async};
 
 /// Download and extract OCI layer
 pub(super) async fn download_and_extract_layer(
@@ -18,12 +24,24 @@ pub(super) async fn download_and_extract_layer(
 ) -> Result<()> {
     let reference: Reference = feature_ref
         .parse()
-        .with_context(|| format!("Invalid OCI reference: {}", feature_ref))?;
+        .map_err(|e| Permanent::new(format!("Invalid OCI reference: {}: {}", feature_ref, e)))?;
 
     info!("Parsed OCI reference: {}", reference);
 
+    let http = utils::http::config();
+    let extra_root_certificates = match http.ca_bundle_pem()? {
+        Some(data) => vec![Certificate {
+            encoding: CertificateEncoding::Pem,
+            data,
+        }],
+        None => Vec::new(),
+    };
     let config = ClientConfig {
         accept_invalid_certificates: false,
+        extra_root_certificates,
+        https_proxy: http.proxy.clone(),
+        http_proxy: http.proxy.clone(),
+        user_agent: utils::http::USER_AGENT,
         ..Default::default()
     };
 
@@ -31,6 +49,19 @@ pub(super) async fn download_and_extract_layer(
 
     info!("Pulling OCI image: {}", reference);
 
+    // Flags win; otherwise use the credentials configured for this registry
+    let configured = (token.is_none() && username.is_none() && password.is_none())
+        .then(|| utils::settings::registry_credentials(reference.registry()))
+        .flatten();
+    let (token, username, password) = match configured {
+        Some(credentials) => (
+            credentials.token.as_deref(),
+            credentials.username.as_deref(),
+            credentials.password.as_deref(),
+        ),
+        None => (token, username, password),
+    };
+
     let auth = match (token, username, password) {
         (Some(token), _, _) => {
             info!("Using bearer token authentication for registry");
@@ -54,35 +85,40 @@ pub(super) async fn download_and_extract_layer(
         "application/vnd.docker.image.rootfs.diff.tar.gzip",
     ];
 
-    let image_data = retry_async(retry_config, "OCI image pull", || async {
-        client
-            .pull(&reference, &auth, accepted_media_types.clone())
+    let spinner = utils::progress::spinner(&format!("Pulling {}", reference));
+    let layer = retry_async(retry_config, "OCI image pull", || async {
+        pull_layer(&client, &reference, &auth, &accepted_media_types)
             .await
-            .with_context(|| format!("Failed to pull OCI image: {}", reference))
+            .with_context(|| PicolayerError::FeatureDownloadFailed(reference.to_string()))
     })
-    .await?;
-
-    let layer = image_data
-        .layers
-        .first()
-        .ok_or_else(|| anyhow::anyhow!("Feature OCI image has no layers"))?;
+    .await;
+    spinner.finish_and_clear();
+    let layer = layer?;
+    utils::progress::record_download(layer.digests.size);
+    utils::audit::fetched(
+        feature_ref,
+        reference.tag().unwrap_or("latest"),
+        &reference.whole(),
+        &layer.digests,
+    );
 
-    let is_gzipped = layer.data.len() >= 2 && layer.data[0] == 0x1f && layer.data[1] == 0x8b;
+    let mut magic = Vec::with_capacity(2);
+    layer.open()?.take(2).read_to_end(&mut magic)?;
+    let is_gzipped = magic == [0x1f, 0x8b];
     info!(
         "Extracting layer with {} bytes (gzipped: {})",
-        layer.data.len(),
-        is_gzipped
+        layer.digests.size, is_gzipped
     );
 
+    let reader = BufReader::new(layer.open()?);
     if is_gzipped {
-        let decoder = flate2::read::GzDecoder::new(&layer.data[..]);
+        let decoder = flate2::read::GzDecoder::new(reader);
         let mut archive = tar::Archive::new(decoder);
         archive
             .unpack(output_dir)
             .context("Failed to extract gzipped layer archive")?;
     } else {
-        let cursor = Cursor::new(&layer.data);
-        let mut archive = tar::Archive::new(cursor);
+        let mut archive = tar::Archive::new(reader);
         archive
             .unpack(output_dir)
             .context("Failed to extract plain tar layer archive")?;
@@ -90,3 +126,24 @@ pub(super) async fn download_and_extract_layer(
 
     Ok(())
 }
+
+/// Stream the image's first layer to a temporary file rather than into memory
+async fn pull_layer(
+    client: &Client,
+    reference: &Reference,
+    auth: &RegistryAuth,
+    accepted_media_types: &[&str],
+) -> Result<DownloadedFile> {
+    let _slot = utils::scheduler::slot().await;
+    let (manifest, _) = client.pull_image_manifest(reference, auth).await?;
+    let layer = manifest
+        .layers
+        .first()
+        .context("Feature OCI image has no layers")?;
+    anyhow::ensure!(
+        accepted_media_types.contains(&layer.media_type.as_str()),
+        "Feature layer has unsupported media type {}",
+        layer.media_type
+    );
+    DownloadedFile::save(async |writer| Ok(client.pull_blob(reference, layer, writer).await?)).await
+}
@@ -42,6 +42,12 @@ impl Feature {
                         _ => String::new(),
                     };
                     resolved.insert(name.clone(), default_str);
+                } else if option.option_type == "boolean"
+                    && let Some(value) = resolved.get_mut(name)
+                    && (value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false"))
+                {
+                    // Scripts test booleans with `[ "$OPTION" = "true" ]`
+                    *value = value.to_ascii_lowercase();
                 }
             }
         }
@@ -49,3 +55,52 @@ impl Feature {
         resolved
     }
 }
+
+/// The environment variable a feature's install script reads an option from, per the
+/// spec: characters other than letters, digits and `_` become `_`, leading digits and
+/// underscores collapse into one `_`, and the result is uppercased
+pub(super) fn option_env_name(id: &str) -> String {
+    let sanitized: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let rest = sanitized.trim_start_matches(|c: char| c.is_ascii_digit() || c == '_');
+    let name = if rest.len() < sanitized.len() {
+        format!("_{}", rest)
+    } else {
+        sanitized
+    };
+    name.to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn option_env_names_follow_the_spec() {
+        assert_eq!(option_env_name("version"), "VERSION");
+        assert_eq!(option_env_name("installZsh"), "INSTALLZSH");
+        assert_eq!(option_env_name("node.version"), "NODE_VERSION");
+        assert_eq!(option_env_name("enable-tls"), "ENABLE_TLS");
+        assert_eq!(option_env_name("3rd_party"), "_RD_PARTY");
+        assert_eq!(option_env_name("__private"), "_PRIVATE");
+    }
+
+    #[test]
+    fn boolean_options_are_lowercased() {
+        let feature: Feature = serde_json::from_value(serde_json::json!({
+            "id": "tool",
+            "options": {
+                "install": { "type": "boolean", "default": false },
+                "flavor": { "type": "string", "default": "Full" }
+            }
+        }))
+        .unwrap();
+
+        let resolved =
+            feature.resolve_options(Some(HashMap::from([("install".into(), "True".into())])));
+        assert_eq!(resolved["install"], "true");
+        assert_eq!(resolved["flavor"], "Full");
+    }
+}
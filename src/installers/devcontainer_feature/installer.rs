@@ -5,8 +5,9 @@ use std::fs;
 use std::path::Path;
 use std::process::Command;
 
-use super::feature::Feature;
+use super::feature::{Feature, option_env_name};
 use super::{DevcontainerFeatureConfig, client};
+use crate::utils::env::EnvFilter;
 
 const ORDERED_BASE_USERS: &[&str] = &["vscode", "node", "codespace"];
 
@@ -16,7 +17,7 @@ pub(super) async fn install_async(
 ) -> Result<()> {
     info!("Installing devcontainer feature: {}", config.feature_ref);
 
-    let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
+    let temp_dir = crate::utils::tmp::tempdir()?;
 
     info!("Downloading and extracting feature");
     client::download_and_extract_layer(
@@ -51,12 +52,19 @@ pub(super) async fn install_async(
     env_vars.insert("_REMOTE_USER_HOME".to_string(), remote_user_home.clone());
 
     for (key, value) in resolved_options {
-        env_vars.insert(key.to_uppercase(), value);
+        env_vars.insert(option_env_name(&key), value);
     }
 
-    execute_install_script(temp_dir.path(), &env_vars, config.script_name)?;
+    let _group = crate::utils::ci::group(&format!("Feature {}", feature.id));
+    execute_install_script(
+        &feature,
+        temp_dir.path(),
+        &env_vars,
+        config.script_name,
+        &config.env_filter,
+    )?;
     set_container_env(&feature)?;
-    execute_entrypoint(&feature)?;
+    execute_entrypoint(&feature, &config.env_filter)?;
 
     info!("Devcontainer feature installation completed successfully");
     Ok(())
@@ -131,9 +139,11 @@ fn resolve_remote_user(remote_user: Option<&str>) -> Result<(String, String)> {
 }
 
 fn execute_install_script(
+    feature: &Feature,
     feature_dir: &Path,
     env_vars: &HashMap<String, String>,
     script_name: &str,
+    env_filter: &EnvFilter,
 ) -> Result<()> {
     let install_script = feature_dir.join(script_name);
     if !install_script.exists() {
@@ -157,23 +167,17 @@ fn execute_install_script(
         env_vars.len()
     );
 
-    let output = Command::new("bash")
-        .args(["-i", "+H", "-x", &format!("./{}", script_name)])
+    // Each line is labelled with the feature, so features installed side by side by
+    // `apply` stay readable
+    let mut cmd = Command::new("bash");
+    env_filter.apply_to(&mut cmd);
+    cmd.args(["-i", "+H", "-x", &format!("./{}", script_name)])
         .current_dir(feature_dir)
-        .envs(env_vars)
-        .output()
-        .context("Failed to execute install script")?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        warn!("Script output:\n{}", stdout);
-        warn!("Script errors:\n{}", stderr);
-        anyhow::bail!(
-            "Feature installation script failed with exit code: {:?}",
-            output.status.code()
-        );
-    }
+        .envs(env_vars);
+    crate::utils::exec::Exec::new(&mut cmd, "Feature installation script")
+        .streamed()
+        .prefixed(feature.id.as_str())
+        .run()?;
 
     info!("Feature installation script completed successfully");
     Ok(())
@@ -216,14 +220,13 @@ fn set_container_env(feature: &Feature) -> Result<()> {
 /// which is downloaded from a container registry. The devcontainer spec explicitly
 /// defines entrypoints as shell commands, so shell execution here is intentional.
 /// Security relies on the caller verifying the feature source (registry + signature).
-fn execute_entrypoint(feature: &Feature) -> Result<()> {
+fn execute_entrypoint(feature: &Feature, env_filter: &EnvFilter) -> Result<()> {
     if let Some(entrypoint) = &feature.entrypoint {
         info!("Executing feature entrypoint: {}", entrypoint);
-        let output = Command::new("sh")
-            .arg("-c")
-            .arg(entrypoint)
-            .output()
-            .context("Failed to execute entrypoint")?;
+        let mut cmd = Command::new("sh");
+        env_filter.apply_to(&mut cmd);
+        cmd.arg("-c").arg(entrypoint);
+        let output = crate::utils::exec::Exec::new(&mut cmd, "Feature entrypoint").output()?;
 
         if !output.status.success() {
             warn!(
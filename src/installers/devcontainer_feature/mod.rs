@@ -2,6 +2,7 @@ mod client;
 mod feature;
 mod installer;
 
+use crate::utils;
 use anyhow::Result;
 use std::collections::HashMap;
 
@@ -15,6 +16,8 @@ pub struct DevcontainerFeatureConfig<'a> {
     pub registry_username: Option<&'a str>,
     pub registry_password: Option<&'a str>,
     pub registry_token: Option<&'a str>,
+    /// Which of picolayer's environment variables the install script and entrypoint inherit
+    pub env_filter: utils::env::EnvFilter<'a>,
 }
 
 /// Install a devcontainer feature from an OCI reference (async)
@@ -22,5 +25,6 @@ pub async fn install_async(
     config: &DevcontainerFeatureConfig<'_>,
     retry_config: &crate::cli::RetryConfig,
 ) -> Result<()> {
+    utils::offline::ensure_online("devcontainer-feature")?;
     installer::install_async(config, retry_config).await
 }
@@ -0,0 +1,65 @@
+use crate::utils;
+use anyhow::Result;
+use log::{debug, info};
+use std::process::Command;
+
+/// Keep the dotnet CLI quiet and out of telemetry during image builds
+const DOTNET_ENV: &[(&str, &str)] = &[
+    ("DOTNET_CLI_TELEMETRY_OPTOUT", "1"),
+    ("DOTNET_NOLOGO", "1"),
+    ("DOTNET_SKIP_FIRST_TIME_EXPERIENCE", "1"),
+];
+
+pub fn install(packages: &[String], tool_path: &str) -> Result<()> {
+    utils::offline::ensure_online("dotnet-tool")?;
+    anyhow::ensure!(
+        which::which("dotnet").is_ok(),
+        "dotnet command not found in PATH. Install the .NET SDK first."
+    );
+
+    // Restore packages into a temporary NuGet cache so it does not end up in the layer
+    let nuget_cache = utils::tmp::tempdir()?;
+
+    for package in packages {
+        let (name, version) = split_version(package);
+        debug!("Installing dotnet tool {} ({:?})", name, version);
+
+        let mut cmd = Command::new("dotnet");
+        cmd.envs(DOTNET_ENV.iter().copied());
+        cmd.env("NUGET_PACKAGES", nuget_cache.path());
+        cmd.args(["tool", "install", "--tool-path", tool_path, name]);
+        if let Some(version) = version {
+            cmd.args(["--version", version]);
+        }
+        utils::exec::run_command(&mut cmd, &format!("Install dotnet tool {}", name))?;
+    }
+
+    info!(
+        "Successfully installed dotnet tools to {}: {:?}",
+        tool_path, packages
+    );
+    Ok(())
+}
+
+/// Split `dotnet-ef@8.0.0` into the package ID and its version
+fn split_version(package: &str) -> (&str, Option<&str>) {
+    match package.split_once('@') {
+        Some((name, version)) if !version.is_empty() => (name, Some(version)),
+        _ => (package.trim_end_matches('@'), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_version_extracts_pinned_version() {
+        assert_eq!(
+            split_version("dotnet-ef@8.0.0"),
+            ("dotnet-ef", Some("8.0.0"))
+        );
+        assert_eq!(split_version("csharpier"), ("csharpier", None));
+        assert_eq!(split_version("csharpier@"), ("csharpier", None));
+    }
+}
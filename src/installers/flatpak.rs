@@ -0,0 +1,97 @@
+use crate::installers::package_manager;
+use crate::utils;
+use anyhow::Result;
+use log::{debug, info};
+use std::process::Command;
+
+pub const FLATHUB_REMOTE: &str = "flathub";
+pub const FLATHUB_URL: &str = "https://dl.flathub.org/repo/flathub.flatpakrepo";
+
+pub fn install(packages: &[String], remote: &str, remote_url: &str, user: bool) -> Result<()> {
+    utils::offline::ensure_online("flatpak")?;
+    if !user {
+        utils::sudo::ensure_privileges("System-wide flatpak installation (use --user instead)")?;
+    }
+    ensure_flatpak_available()?;
+    add_remote(remote, remote_url, user)?;
+    install_packages(packages, remote, user)?;
+    Ok(())
+}
+
+fn ensure_flatpak_available() -> Result<()> {
+    if which::which("flatpak").is_ok() {
+        debug!("flatpak is already available");
+        return Ok(());
+    }
+
+    info!("flatpak not found, installing it");
+    if utils::os::is_debian_like() {
+        let mut cmd = package_manager::apt_command("apt-get");
+        cmd.args(["update"]);
+        utils::exec::run_command(&mut cmd, "Update package lists")?;
+
+        let mut cmd = package_manager::apt_install_command("apt-get");
+        cmd.arg("flatpak");
+        utils::exec::run_command(&mut cmd, "Install flatpak")?;
+    } else if utils::os::is_alpine() {
+        let mut cmd = utils::sudo::command("apk");
+        cmd.args(["add", "flatpak"]);
+        utils::exec::run_command(&mut cmd, "Install flatpak")?;
+    } else {
+        anyhow::bail!("Unsupported OS for automatic flatpak installation")
+    }
+    Ok(())
+}
+
+fn add_remote(remote: &str, remote_url: &str, user: bool) -> Result<()> {
+    info!("Adding flatpak remote {} ({})", remote, remote_url);
+    let mut cmd = flatpak_command(user);
+    cmd.args(["remote-add", "--if-not-exists"]);
+    cmd.arg(scope_flag(user));
+    cmd.args([remote, remote_url]);
+    utils::exec::run_command(&mut cmd, "Add flatpak remote")?;
+    Ok(())
+}
+
+fn install_packages(packages: &[String], remote: &str, user: bool) -> Result<()> {
+    debug!("Installing flatpak applications: {:?}", packages);
+
+    let mut cmd = flatpak_command(user);
+    cmd.args(["install", "-y", "--noninteractive"]);
+    cmd.arg(scope_flag(user));
+    cmd.arg(remote);
+    cmd.args(packages);
+    utils::exec::run_command(&mut cmd, "Install flatpak applications")?;
+
+    info!(
+        "Successfully installed flatpak applications: {:?}",
+        packages
+    );
+    Ok(())
+}
+
+/// System-wide installs need root; per-user installs must run as the invoking user
+fn flatpak_command(user: bool) -> Command {
+    if user {
+        Command::new("flatpak")
+    } else {
+        utils::sudo::command("flatpak")
+    }
+}
+
+fn scope_flag(user: bool) -> &'static str {
+    if user { "--user" } else { "--system" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_installs_do_not_use_sudo() {
+        let cmd = flatpak_command(true);
+        assert_eq!(cmd.get_program(), "flatpak");
+        assert_eq!(scope_flag(true), "--user");
+        assert_eq!(scope_flag(false), "--system");
+    }
+}
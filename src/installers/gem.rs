@@ -0,0 +1,172 @@
+use crate::installers::package_manager;
+use crate::utils;
+use anyhow::Result;
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// Seconds to wait for the dpkg lock while bootstrapping Ruby, matching the apt-get default
+const RUBY_LOCK_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub fn install(packages: &[String], with_docs: bool) -> Result<()> {
+    ensure_gem_available()?;
+    install_packages(packages, with_docs)?;
+    cleanup()?;
+    verify_installed(packages)?;
+    Ok(())
+}
+
+fn ensure_gem_available() -> Result<()> {
+    if which::which("gem").is_ok() {
+        debug!("gem is already available");
+        return Ok(());
+    }
+
+    utils::offline::ensure_online("Installing Ruby")?;
+    utils::sudo::ensure_privileges("Installing Ruby")?;
+    info!("gem not found, installing Ruby");
+    install_ruby()?;
+    Ok(())
+}
+
+fn install_ruby() -> Result<()> {
+    let packages = ["ruby".to_string()];
+    let config = package_manager::PackageManagerConfig {
+        packages: &packages,
+        lock_timeout: RUBY_LOCK_TIMEOUT,
+        ..Default::default()
+    };
+    if utils::os::is_debian_like() {
+        debug!("Installing Ruby on Debian-like system");
+        package_manager::install_apt_get(&config)?;
+    } else if utils::os::is_alpine() {
+        debug!("Installing Ruby on Alpine Linux");
+        package_manager::install_apk(&config)?;
+    } else {
+        anyhow::bail!("Unsupported OS for automatic Ruby installation")
+    }
+    Ok(())
+}
+
+fn install_packages(packages: &[String], with_docs: bool) -> Result<()> {
+    debug!("Installing gems: {:?}", packages);
+
+    let mut cmd = utils::sudo::command("gem");
+    cmd.args(install_args(
+        packages,
+        with_docs,
+        utils::offline::is_enabled(),
+    ));
+    utils::exec::run_command(&mut cmd, "Install gems")?;
+
+    info!("Successfully installed gems: {:?}", packages);
+    Ok(())
+}
+
+/// Build the `gem install` arguments for the requested gems
+fn install_args(packages: &[String], with_docs: bool, offline: bool) -> Vec<String> {
+    let mut args = vec!["install".to_string()];
+    if !with_docs {
+        args.push("--no-document".to_string());
+    }
+    if offline {
+        // Only .gem files in the working directory or the gem cache
+        args.push("--local".to_string());
+    }
+    args.extend(packages.iter().cloned());
+    args
+}
+
+/// Gem name of a `NAME[:VERSION]` spec, as understood by `gem specification`
+fn gem_name(spec: &str) -> &str {
+    spec.split(':').next().unwrap_or(spec)
+}
+
+/// Run `--version` on the executables each installed gem declares
+fn verify_installed(packages: &[String]) -> Result<()> {
+    let mut executables = Vec::new();
+    for package in packages {
+        let name = gem_name(package);
+        let mut cmd = Command::new("gem");
+        cmd.args(["specification", name, "executables"]);
+        let output =
+            utils::exec::run_command(&mut cmd, &format!("Read gem specification for {}", name))?;
+        executables.extend(parse_executables(&String::from_utf8_lossy(&output.stdout)));
+    }
+    utils::verify::verify_executables(&executables, None)
+}
+
+/// Parse the YAML list printed by `gem specification NAME executables`
+fn parse_executables(yaml: &str) -> Vec<String> {
+    yaml.lines()
+        .filter_map(|line| line.trim().strip_prefix("- "))
+        .map(|name| name.trim().trim_matches(['"', '\'']).to_string())
+        .collect()
+}
+
+/// Remove the downloaded .gem archives, which are only needed to reinstall
+fn cleanup() -> Result<()> {
+    let output = Command::new("gem").args(["env", "gemdir"]).output()?;
+    let gemdir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || gemdir.is_empty() {
+        debug!("Could not determine gem directory, skipping cache cleanup");
+        return Ok(());
+    }
+
+    let cache = Path::new(&gemdir).join("cache");
+    if !cache.is_dir() {
+        return Ok(());
+    }
+
+    info!("Cleaning gem cache: {}", cache.display());
+    for entry in std::fs::read_dir(&cache)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "gem") {
+            utils::sudo::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_executables_reads_yaml_list() {
+        assert_eq!(
+            parse_executables("---\n- rake\n- 'rdoc'\n"),
+            ["rake", "rdoc"]
+        );
+        assert!(parse_executables("--- []\n").is_empty());
+    }
+
+    #[test]
+    fn install_args_skip_docs_by_default() {
+        let packages = ["rake".to_string(), "bundler:2.5.0".to_string()];
+        assert_eq!(
+            install_args(&packages, false, false),
+            ["install", "--no-document", "rake", "bundler:2.5.0"]
+        );
+        assert_eq!(
+            install_args(&packages, true, false),
+            ["install", "rake", "bundler:2.5.0"]
+        );
+    }
+
+    #[test]
+    fn install_args_use_local_gems_offline() {
+        let packages = ["rake".to_string()];
+        assert_eq!(
+            install_args(&packages, false, true),
+            ["install", "--no-document", "--local", "rake"]
+        );
+    }
+
+    #[test]
+    fn gem_name_drops_the_version() {
+        assert_eq!(gem_name("bundler:2.5.0"), "bundler");
+        assert_eq!(gem_name("rake"), "rake");
+    }
+}
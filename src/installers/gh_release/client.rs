@@ -1,70 +1,296 @@
 use anyhow::Result;
-use log::info;
+use log::{info, warn};
+use octocrab::Octocrab;
 use octocrab::models::repos::Release;
 
 use crate::cli::RetryConfig;
-use crate::utils::retry::retry_async;
+use crate::error::PicolayerError;
+use crate::utils::{retry::retry_async, settings};
 
 pub(super) async fn fetch_release(
     owner: &str,
     repo: &str,
     version: &str,
+    tag_prefix: Option<&str>,
     include_prerelease: bool,
     retry_config: &RetryConfig,
 ) -> Result<Release> {
-    let octocrab = if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-        octocrab::Octocrab::builder()
-            .personal_token(token)
-            .build()?
-    } else {
-        (*octocrab::instance()).clone()
-    };
+    fetch_following_moves(owner, repo, version, tag_prefix, include_prerelease, retry_config)
+        .await
+        .map_err(|e| {
+            if !is_not_found(&e) {
+                e
+            } else if version == "latest" {
+                e.context(PicolayerError::RepositoryNotFound(format!(
+                    "{}/{}",
+                    owner, repo
+                )))
+            } else {
+                e.context(PicolayerError::AssetNotFound {
+                    message: format!("No release {} in {}/{}", version, owner, repo),
+                    suggestion: Some(format!(
+                        "Check the tag at https://github.com/{}/{}/releases, or omit --version for the latest release",
+                        owner, repo
+                    )),
+                })
+            }
+        })
+}
 
-    if version == "latest" {
-        if include_prerelease {
-            retry_async(
+/// The tag to install from a repository that tags versions without publishing releases:
+/// `version` itself, or for `latest` the newest version-like tag starting with `tag_prefix`
+pub(super) async fn resolve_tag(
+    owner: &str,
+    repo: &str,
+    version: &str,
+    tag_prefix: Option<&str>,
+    retry_config: &RetryConfig,
+) -> Result<String> {
+    if version != "latest" {
+        return Ok(prefixed_tag(version, tag_prefix));
+    }
+    let octocrab = client()?;
+    let tags = retry_async(retry_config, "GitHub API - fetch tags", || async {
+        Ok(octocrab
+            .repos(owner, repo)
+            .list_tags()
+            .per_page(100)
+            .send()
+            .await?)
+    })
+    .await
+    .map_err(|e| {
+        if is_not_found(&e) {
+            e.context(PicolayerError::RepositoryNotFound(format!(
+                "{}/{}",
+                owner, repo
+            )))
+        } else {
+            e
+        }
+    })?;
+
+    let names: Vec<&str> = tags
+        .items
+        .iter()
+        .map(|tag| tag.name.as_str())
+        .filter(|name| tag_prefix.is_none_or(|prefix| name.starts_with(prefix)))
+        .collect();
+    newest_tag(&names, tag_prefix.unwrap_or_default())
+        .or(names.first().copied())
+        .map(str::to_string)
+        .ok_or_else(|| {
+            PicolayerError::AssetNotFound {
+                message: format!("No tags in {}/{}", owner, repo),
+                suggestion: Some(
+                    "Drop --from-tag if the repository publishes releases".to_string(),
+                ),
+            }
+            .into()
+        })
+}
+
+/// The highest stable version among tags like `v1.2.3` or `1.2` once `prefix` is removed
+fn newest_tag<'a>(tags: &[&'a str], prefix: &str) -> Option<&'a str> {
+    tags.iter()
+        .filter_map(|tag| {
+            let parts: Vec<u64> = tag
+                .strip_prefix(prefix)?
+                .trim_start_matches('v')
+                .split('.')
+                .map(|p| p.parse().ok())
+                .collect::<Option<_>>()?;
+            Some((parts, *tag))
+        })
+        .max()
+        .map(|(_, tag)| tag)
+}
+
+/// Fetch the release, following a renamed or transferred repository to its new name
+async fn fetch_following_moves(
+    owner: &str,
+    repo: &str,
+    version: &str,
+    tag_prefix: Option<&str>,
+    include_prerelease: bool,
+    retry_config: &RetryConfig,
+) -> Result<Release> {
+    let octocrab = client()?;
+    let release = match fetch(
+        &octocrab,
+        owner,
+        repo,
+        version,
+        tag_prefix,
+        include_prerelease,
+        retry_config,
+    )
+    .await
+    {
+        Err(e) if is_moved(&e) => {
+            let Some((new_owner, new_repo)) = canonical_name(&octocrab, owner, repo).await else {
+                return Err(e);
+            };
+            fetch(
+                &octocrab,
+                &new_owner,
+                &new_repo,
+                version,
+                tag_prefix,
+                include_prerelease,
                 retry_config,
-                "GitHub API - fetch latest release",
-                || async { Ok(octocrab.repos(owner, repo).releases().get_latest().await?) },
             )
-            .await
-        } else {
-            let releases =
-                retry_async(retry_config, "GitHub API - fetch releases list", || async {
-                    Ok(octocrab.repos(owner, repo).releases().list().send().await?)
-                })
-                .await?;
-
-            let stable_release = releases
-                .items
-                .into_iter()
-                .find(|r| !r.prerelease)
-                .ok_or_else(|| anyhow::anyhow!("No stable releases found"))?;
-
-            info!(
-                "Skipping prereleases, using stable release: {}",
-                stable_release.tag_name
-            );
-            Ok(stable_release)
+            .await?
         }
+        fetched => fetched?,
+    };
+    if let Some((new_owner, new_repo)) = release_repo(&release)
+        && !(new_owner.eq_ignore_ascii_case(owner) && new_repo.eq_ignore_ascii_case(repo))
+    {
+        warn!(
+            "{}/{} has moved to {}/{}; update --owner and --repo to the new name",
+            owner, repo, new_owner, new_repo
+        );
+    }
+    Ok(release)
+}
+
+fn client() -> Result<Octocrab> {
+    Ok(if let Some(token) = settings::github_token() {
+        Octocrab::builder().personal_token(token).build()?
     } else {
-        retry_async(
+        (*octocrab::instance()).clone()
+    })
+}
+
+/// Whether GitHub answered with a redirect that was not followed
+fn is_moved(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<octocrab::Error>(),
+            Some(octocrab::Error::GitHub { source, .. }) if source.status_code.is_redirection()
+        )
+    })
+}
+
+/// The current owner and name of a repository that may have been renamed or transferred
+async fn canonical_name(octocrab: &Octocrab, owner: &str, repo: &str) -> Option<(String, String)> {
+    let repository = octocrab.repos(owner, repo).get().await.ok()?;
+    let new_owner = repository.owner?.login;
+    info!(
+        "{}/{} is now {}/{}",
+        owner, repo, new_owner, repository.name
+    );
+    Some((new_owner, repository.name))
+}
+
+/// The owner and repository a release belongs to, from its page on github.com
+fn release_repo(release: &Release) -> Option<(String, String)> {
+    let mut segments = release.html_url.path_segments()?;
+    Some((segments.next()?.to_string(), segments.next()?.to_string()))
+}
+
+/// Whether GitHub answered 404, which it also does for private repositories the
+/// token cannot see
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<octocrab::Error>(),
+            Some(octocrab::Error::GitHub { source, .. }) if source.status_code.as_u16() == 404
+        )
+    })
+}
+
+async fn fetch(
+    octocrab: &Octocrab,
+    owner: &str,
+    repo: &str,
+    version: &str,
+    tag_prefix: Option<&str>,
+    include_prerelease: bool,
+    retry_config: &RetryConfig,
+) -> Result<Release> {
+    if version != "latest" {
+        let tag = prefixed_tag(version, tag_prefix);
+        return retry_async(
             retry_config,
             "GitHub API - fetch release by tag",
             || async {
                 Ok(octocrab
                     .repos(owner, repo)
                     .releases()
-                    .get_by_tag(version)
+                    .get_by_tag(&tag)
                     .await?)
             },
         )
-        .await
+        .await;
+    }
+
+    if include_prerelease && tag_prefix.is_none() {
+        return retry_async(
+            retry_config,
+            "GitHub API - fetch latest release",
+            || async { Ok(octocrab.repos(owner, repo).releases().get_latest().await?) },
+        )
+        .await;
+    }
+
+    let releases = retry_async(retry_config, "GitHub API - fetch releases list", || async {
+        Ok(octocrab
+            .repos(owner, repo)
+            .releases()
+            .list()
+            .per_page(100)
+            .send()
+            .await?)
+    })
+    .await?;
+
+    let release = releases
+        .items
+        .into_iter()
+        .filter(|r| tag_prefix.is_none_or(|prefix| r.tag_name.starts_with(prefix)))
+        .find(|r| include_prerelease || !r.prerelease)
+        .ok_or_else(|| match tag_prefix {
+            Some(prefix) => PicolayerError::AssetNotFound {
+                message: format!("No releases tagged {}* in {}/{}", prefix, owner, repo),
+                suggestion: Some(format!(
+                    "Check --tag-prefix against the tags at https://github.com/{}/{}/releases",
+                    owner, repo
+                )),
+            },
+            None => PicolayerError::AssetNotFound {
+                message: "No stable releases found".into(),
+                suggestion: Some("Pass --include-prerelease to install a prerelease".into()),
+            },
+        })?;
+
+    match tag_prefix {
+        Some(prefix) => info!(
+            "Using the newest release tagged {}*: {}",
+            prefix, release.tag_name
+        ),
+        None => info!(
+            "Skipping prereleases, using stable release: {}",
+            release.tag_name
+        ),
+    }
+    Ok(release)
+}
+
+/// The tag for `version` in a repository whose tags start with `tag_prefix`, e.g.
+/// `cli-v1.2.3` for `1.2.3` with the prefix `cli-v`. A version that already names the
+/// whole tag is left as it is.
+fn prefixed_tag(version: &str, tag_prefix: Option<&str>) -> String {
+    match tag_prefix {
+        Some(prefix) if !version.starts_with(prefix) => format!("{}{}", prefix, version),
+        _ => version.to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{newest_tag, prefixed_tag, release_repo};
     use octocrab::models::repos::Release;
 
     fn create_mock_release(tag_name: &str, prerelease: bool) -> Release {
@@ -80,7 +306,7 @@ mod tests {
             "published_at": "2024-01-01T00:00:00Z",
             "assets": [],
             "upload_url": "https://example.com/upload",
-            "html_url": "https://example.com",
+            "html_url": format!("https://github.com/cli/cli/releases/tag/{}", tag_name),
             "assets_url": "https://example.com/assets",
             "tarball_url": "https://example.com/tarball",
             "zipball_url": "https://example.com/zipball",
@@ -96,6 +322,35 @@ mod tests {
         assert!(!release.prerelease);
     }
 
+    #[test]
+    fn test_release_repo_reads_the_release_page() {
+        let release = create_mock_release("v1.0.0", false);
+        assert_eq!(
+            release_repo(&release),
+            Some(("cli".to_string(), "cli".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_newest_tag_compares_versions_numerically() {
+        assert_eq!(
+            newest_tag(&["v1.9.0", "v1.10.0", "v2.0.0-rc1", "nightly"], ""),
+            Some("v1.10.0")
+        );
+        assert_eq!(newest_tag(&["nightly"], ""), None);
+        assert_eq!(
+            newest_tag(&["cli-v1.2.3", "cli-v1.10.0"], "cli-"),
+            Some("cli-v1.10.0")
+        );
+    }
+
+    #[test]
+    fn test_prefixed_tag_adds_the_component_prefix() {
+        assert_eq!(prefixed_tag("1.2.3", Some("cli-v")), "cli-v1.2.3");
+        assert_eq!(prefixed_tag("cli-v1.2.3", Some("cli-v")), "cli-v1.2.3");
+        assert_eq!(prefixed_tag("v1.2.3", None), "v1.2.3");
+    }
+
     #[test]
     fn test_mock_prerelease_creation() {
         let release = create_mock_release("v1.0.0-beta", true);
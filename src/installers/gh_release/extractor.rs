@@ -1,10 +1,10 @@
-use anyhow::Result;
-use futures_util::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
-use log::{LevelFilter, info, warn};
+use crate::utils;
+use crate::utils::download::{Digests, DownloadedFile};
+use anyhow::{Context, Result};
+use log::{info, warn};
 use octocrab::models::repos::Asset;
 use std::fs::{self, File};
-use std::io::{BufReader, Write};
+use std::io::{BufReader, Read};
 use std::path::{Component, Path};
 
 enum AssetExtractor {
@@ -13,32 +13,40 @@ enum AssetExtractor {
 }
 
 impl AssetExtractor {
-    async fn extract(
-        &self,
-        asset: &Asset,
-        binary_names: &[String],
-        bin_location: &str,
-    ) -> Result<()> {
+    fn extract(&self, download: &Path, binary_names: &[String], bin_location: &str) -> Result<()> {
         match self {
             AssetExtractor::Archive => {
-                info!("Downloading archive asset");
-                let archive_data = download_asset_data(asset).await?;
                 info!(
                     "Extracting binaries from archive: {}",
                     binary_names.join(", ")
                 );
-                extract_archive(&archive_data, binary_names, bin_location)
+                extract_archive(download, binary_names, bin_location)
             }
             AssetExtractor::RawBinary => {
-                info!("Downloading raw binary asset");
-                let binary_data = download_asset_data(asset).await?;
                 info!("Installing raw binary: {}", binary_names.join(", "));
-                extract_raw_binary(&binary_data, binary_names, bin_location)
+                extract_raw_binary(download, binary_names, bin_location)
             }
         }
     }
 }
 
+/// Identifies a release in the lockfile: `owner/repo` at a tag
+pub(super) struct LockedRelease<'a> {
+    pub name: &'a str,
+    pub tag: &'a str,
+}
+
+impl LockedRelease<'_> {
+    pub(super) fn record(&self, asset: &Asset, digests: &Digests) -> Result<()> {
+        utils::lock::record(
+            self.name,
+            self.tag,
+            asset.browser_download_url.as_str(),
+            digests,
+        )
+    }
+}
+
 fn create_extractor(asset: &Asset) -> AssetExtractor {
     if is_archive(&asset.name.to_lowercase()) {
         AssetExtractor::Archive
@@ -47,94 +55,81 @@ fn create_extractor(asset: &Asset) -> AssetExtractor {
     }
 }
 
-pub(super) async fn extract_and_install(
+/// Record the downloaded `asset` in the lockfile, then install its binaries
+pub(super) fn extract_and_install(
     asset: &Asset,
+    download: &DownloadedFile,
+    release: &LockedRelease<'_>,
     binary_names: &[String],
     bin_location: &str,
 ) -> Result<()> {
-    let extractor = create_extractor(asset);
-    extractor.extract(asset, binary_names, bin_location).await
+    release.record(asset, &download.digests)?;
+    create_extractor(asset).extract(download.path(), binary_names, bin_location)
 }
 
-const MAX_DOWNLOAD_SIZE: u64 = 500 * 1024 * 1024; // 500MB limit
+/// Record a tag's source tarball in the lockfile, then install the binaries it contains
+pub(super) fn extract_tag_archive(
+    download: &DownloadedFile,
+    release: &LockedRelease<'_>,
+    url: &str,
+    binary_names: &[String],
+    bin_location: &str,
+) -> Result<()> {
+    utils::lock::record(release.name, release.tag, url, &download.digests)?;
+    AssetExtractor::Archive.extract(download.path(), binary_names, bin_location)
+}
 
-async fn download_asset_data(asset: &Asset) -> Result<Vec<u8>> {
-    let response = reqwest::get(asset.browser_download_url.clone()).await?;
-    if !response.status().is_success() {
-        anyhow::bail!("Failed to download asset: {}", response.status());
-    }
+const MAX_DOWNLOAD_SIZE: u64 = 500 * 1024 * 1024; // 500MB limit
 
-    let content_length = response.content_length();
+/// Download `asset` to a temporary file, hashing it on the way for verification
+pub(super) async fn download_asset(asset: &Asset) -> Result<DownloadedFile> {
+    utils::download::fetch_to_file(asset.browser_download_url.as_str(), Some(MAX_DOWNLOAD_SIZE))
+        .await
+}
 
-    // Check content-length header before downloading the full body
-    if let Some(len) = content_length {
-        if len > MAX_DOWNLOAD_SIZE {
-            anyhow::bail!(
-                "Asset too large: {} bytes (max {} bytes)",
-                len,
-                MAX_DOWNLOAD_SIZE
-            );
-        }
-    }
+/// Download the source tarball GitHub generates for a tag to a temporary file
+pub(super) async fn download_tag_archive(url: &str) -> Result<DownloadedFile> {
+    utils::download::fetch_to_file(url, Some(MAX_DOWNLOAD_SIZE)).await
+}
 
-    let show_progress = log::max_level() >= LevelFilter::Info;
-    let pb = if show_progress {
-        if let Some(len) = content_length {
-            let bar = ProgressBar::new(len);
-            bar.set_style(
-                ProgressStyle::with_template(
-                    "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
-            );
-            bar
-        } else {
-            let bar = ProgressBar::new_spinner();
-            bar.set_style(
-                ProgressStyle::with_template("{spinner:.green} {bytes} ({bytes_per_sec})").unwrap(),
-            );
-            bar
-        }
-    } else {
-        ProgressBar::hidden()
+/// Fail before downloading `asset` when there is no room to unpack it into
+/// `bin_location` or, for archives, in the temp directory
+pub(super) fn ensure_space(asset: &Asset, bin_location: &str) -> Result<()> {
+    let Some(size) = u64::try_from(asset.size).ok().filter(|size| *size > 0) else {
+        return Ok(());
     };
-
-    let mut data = Vec::with_capacity(content_length.unwrap_or(0) as usize);
-    let mut stream = response.bytes_stream();
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        data.extend_from_slice(&chunk);
-        pb.inc(chunk.len() as u64);
-
-        if data.len() as u64 > MAX_DOWNLOAD_SIZE {
-            pb.finish_and_clear();
-            anyhow::bail!("Downloaded asset exceeds size limit");
-        }
+    let needed = size.saturating_mul(utils::preflight::EXTRACT_FACTOR);
+    utils::preflight::ensure_space(Path::new(bin_location), needed, &asset.name)?;
+    if let AssetExtractor::Archive = create_extractor(asset) {
+        utils::tmp::ensure_space(needed, &asset.name)?;
     }
-
-    pb.finish_and_clear();
-    Ok(data)
+    Ok(())
 }
 
-fn extract_archive(archive_data: &[u8], binary_names: &[String], bin_location: &str) -> Result<()> {
-    let temp_dir = tempfile::tempdir()?;
-
-    if is_tar_xz_archive(archive_data) {
-        extract_tar_xz(archive_data, binary_names, bin_location, &temp_dir)
-    } else if is_gzip_archive(archive_data) {
-        extract_tar_gz(archive_data, binary_names, bin_location, &temp_dir)
+fn extract_archive(archive: &Path, binary_names: &[String], bin_location: &str) -> Result<()> {
+    let size = fs::metadata(archive)
+        .with_context(|| format!("Failed to read {}", archive.display()))?
+        .len();
+    utils::tmp::ensure_space(
+        size * utils::preflight::EXTRACT_FACTOR,
+        "extracting the archive",
+    )?;
+    let temp_dir = utils::tmp::tempdir()?;
+
+    let mut magic = Vec::with_capacity(6);
+    File::open(archive)?.take(6).read_to_end(&mut magic)?;
+    if is_tar_xz_archive(&magic) {
+        extract_tar_xz(archive, binary_names, bin_location, &temp_dir)
+    } else if is_gzip_archive(&magic) {
+        extract_tar_gz(archive, binary_names, bin_location, &temp_dir)
+    } else if is_zip_archive(&magic) {
+        extract_zip(archive, binary_names, bin_location, &temp_dir)
     } else {
-        anyhow::bail!("Unsupported archive format. Supported formats: tar.gz, tgz, tar.xz")
+        anyhow::bail!("Unsupported archive format. Supported formats: tar.gz, tgz, tar.xz, zip")
     }
 }
 
-fn extract_raw_binary(
-    binary_data: &[u8],
-    binary_names: &[String],
-    bin_location: &str,
-) -> Result<()> {
+fn extract_raw_binary(binary: &Path, binary_names: &[String], bin_location: &str) -> Result<()> {
     fs::create_dir_all(bin_location)?;
 
     let binary_name = binary_names
@@ -142,7 +137,7 @@ fn extract_raw_binary(
         .ok_or_else(|| anyhow::anyhow!("No binary name specified for raw binary"))?;
 
     let dest_path = Path::new(bin_location).join(binary_name);
-    fs::write(&dest_path, binary_data)?;
+    fs::copy(binary, &dest_path)?;
 
     #[cfg(unix)]
     {
@@ -161,7 +156,10 @@ fn extract_raw_binary(
 }
 
 fn is_archive(filename: &str) -> bool {
-    filename.ends_with(".tar.gz") || filename.ends_with(".tgz") || filename.ends_with(".tar.xz")
+    filename.ends_with(".tar.gz")
+        || filename.ends_with(".tgz")
+        || filename.ends_with(".tar.xz")
+        || filename.ends_with(".zip")
 }
 
 fn is_tar_xz_archive(data: &[u8]) -> bool {
@@ -172,6 +170,10 @@ fn is_gzip_archive(data: &[u8]) -> bool {
     data.len() >= 2 && data[0] == 0x1f && data[1] == 0x8b
 }
 
+fn is_zip_archive(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
 /// Validates that a tar entry path is safe to extract into the given directory.
 /// Returns `true` only if the path contains no `..` components and, once joined
 /// with `extract_dir`, stays within `extract_dir`.
@@ -205,7 +207,7 @@ fn validate_tar_entry_path(entry_path: &Path, extract_dir: &Path) -> bool {
 }
 
 fn extract_tar_gz(
-    archive_data: &[u8],
+    archive_path: &Path,
     binary_names: &[String],
     bin_location: &str,
     temp_dir: &tempfile::TempDir,
@@ -213,11 +215,7 @@ fn extract_tar_gz(
     use flate2::read::GzDecoder;
     use tar::Archive;
 
-    let archive_path = temp_dir.path().join("download.tar.gz");
-    let mut file = File::create(&archive_path)?;
-    file.write_all(archive_data)?;
-
-    let file = File::open(&archive_path)?;
+    let file = File::open(archive_path)?;
     let reader = BufReader::new(file);
     let decoder = GzDecoder::new(reader);
     let mut archive = Archive::new(decoder);
@@ -267,7 +265,7 @@ fn extract_tar_gz(
 }
 
 fn extract_tar_xz(
-    archive_data: &[u8],
+    archive_path: &Path,
     binary_names: &[String],
     bin_location: &str,
     temp_dir: &tempfile::TempDir,
@@ -279,8 +277,7 @@ fn extract_tar_xz(
     fs::create_dir_all(&extract_dir)?;
     fs::create_dir_all(bin_location)?;
 
-    let cursor = std::io::Cursor::new(archive_data);
-    let xz_decoder = XzDecoder::new(cursor);
+    let xz_decoder = XzDecoder::new(BufReader::new(File::open(archive_path)?));
     let mut archive = Archive::new(xz_decoder);
 
     // Manually iterate entries instead of archive.unpack() to validate each path
@@ -320,6 +317,49 @@ fn extract_tar_xz(
     Ok(())
 }
 
+fn extract_zip(
+    archive_path: &Path,
+    binary_names: &[String],
+    bin_location: &str,
+    temp_dir: &tempfile::TempDir,
+) -> Result<()> {
+    let extract_dir = temp_dir.path().join("extracted");
+    fs::create_dir_all(&extract_dir)?;
+    fs::create_dir_all(bin_location)?;
+
+    let mut archive = zip::ZipArchive::new(BufReader::new(File::open(archive_path)?))?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        // enclosed_name rejects absolute paths and `..` components
+        let Some(path) = entry.enclosed_name() else {
+            warn!("Skipping zip entry with unsafe path: {}", entry.name());
+            continue;
+        };
+
+        if entry.is_symlink() {
+            warn!("Skipping symlink entry in zip archive: {}", path.display());
+            continue;
+        }
+
+        let dest = extract_dir.join(&path);
+        if entry.is_dir() {
+            fs::create_dir_all(&dest)?;
+        } else if entry.is_file() {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(&dest)?;
+            std::io::copy(&mut entry, &mut file)?;
+        }
+    }
+
+    find_and_install_binaries(&extract_dir, binary_names, bin_location)?;
+
+    Ok(())
+}
+
 fn find_and_install_binaries(
     extract_dir: &std::path::Path,
     binary_names: &[String],
@@ -429,11 +469,11 @@ mod tests {
         assert!(is_archive("tool.tar.gz"));
         assert!(is_archive("tool.tgz"));
         assert!(is_archive("tool.tar.xz"));
+        assert!(is_archive("tool.zip"));
     }
 
     #[test]
     fn is_archive_rejects_unsupported() {
-        assert!(!is_archive("tool.zip"));
         assert!(!is_archive("tool.tar.bz2"));
         assert!(!is_archive("tool.7z"));
         assert!(!is_archive("tool.exe"));
@@ -470,6 +510,41 @@ mod tests {
         assert!(!is_gzip_archive(&[])); // empty
     }
 
+    // ── zip archives ───────────────────────────────────────────────────
+
+    #[test]
+    fn is_zip_archive_checks_magic_bytes() {
+        assert!(is_zip_archive(b"PK\x03\x04rest"));
+        assert!(!is_zip_archive(b"PK"));
+        assert!(!is_zip_archive(&[0x1f, 0x8b]));
+    }
+
+    #[test]
+    fn extract_archive_installs_binary_from_zip() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file("deno", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"#!/bin/sh\necho deno\n").unwrap();
+        writer
+            .start_file("README.md", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"docs").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("deno.zip");
+        fs::write(&archive, data).unwrap();
+        let bin_dir = tmp.path().join("bin");
+        extract_archive(&archive, &["deno".to_string()], bin_dir.to_str().unwrap()).unwrap();
+
+        assert!(bin_dir.join("deno").is_file());
+        assert!(!bin_dir.join("README.md").exists());
+    }
+
     // ── extract_raw_binary ─────────────────────────────────────────────
 
     #[test]
@@ -479,8 +554,10 @@ mod tests {
 
         let data = b"#!/bin/sh\necho hello\n";
         let names = vec!["my-tool".to_string()];
+        let download = tmp.path().join("my-tool-linux-amd64");
+        fs::write(&download, data).unwrap();
 
-        extract_raw_binary(data, &names, bin_dir.to_str().unwrap())
+        extract_raw_binary(&download, &names, bin_dir.to_str().unwrap())
             .expect("extract_raw_binary failed");
 
         let dest = bin_dir.join("my-tool");
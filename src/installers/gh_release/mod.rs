@@ -3,53 +3,309 @@ mod extractor;
 mod selector;
 mod verifier;
 
+use crate::error::ErrorKind;
+use crate::utils;
+use crate::utils::policy::{ChecksumAlgorithm, Source, Verification};
 use anyhow::Result;
 use log::info;
+use octocrab::models::repos::Asset;
+use std::path::Path;
+use std::time::Duration;
+use utils::download::DownloadedFile;
 
 pub struct GhReleaseConfig<'a> {
     pub owner: &'a str,
     pub repo: &'a str,
     pub binary_names: &'a [String],
     pub version: &'a str,
+    /// Start of the tags of one component in a monorepo, e.g. `cli-v`
+    pub tag_prefix: Option<&'a str>,
     pub install_dir: &'a str,
     pub filter: Option<&'a str>,
+    /// Owner of the installed binaries, as for `--chown user:group`
+    pub chown: Option<&'a str>,
+    /// Permissions of the installed binaries, 0o755 when unset
+    pub mode: Option<u32>,
     pub verify_checksum: bool,
     pub checksum_text: Option<&'a str>,
+    /// Checksum files to prefer with `verify_checksum` when a release publishes several
+    pub checksum_algorithm: ChecksumAlgorithm,
     pub gpg_key: Option<&'a str>,
     pub include_prerelease: bool,
+    /// Install from the source tarball of a tag, for repositories without releases
+    pub from_tag: bool,
+    /// Poll this long for the release and its asset to be published before giving up
+    pub wait_for_release: Option<Duration>,
+}
+
+impl GhReleaseConfig<'_> {
+    fn verification(&self) -> Verification {
+        verification(self.verify_checksum, self.checksum_text, self.gpg_key)
+    }
+}
+
+/// How strongly an install with these flags verifies its asset. With a key, a release
+/// that turns out to have no signature is refused when the policy requires one.
+pub fn verification(
+    verify_checksum: bool,
+    checksum_text: Option<&str>,
+    gpg_key: Option<&str>,
+) -> Verification {
+    match (verify_checksum, checksum_text, gpg_key) {
+        (_, Some(_), _) => Verification::Checksum,
+        (true, None, Some(_)) => Verification::Gpg,
+        (true, None, None) => Verification::Checksum,
+        (false, None, _) => Verification::None,
+    }
+}
+
+/// Releases are matched by policy rules as `gh-release owner/repo`, whichever command fetches them
+fn release_source<'a>(owner: &'a str, repo: &'a str) -> Source<'a> {
+    Source {
+        command: "gh-release",
+        owner,
+        repo,
+    }
+}
+
+/// A release asset downloaded for an installer that unpacks it itself
+pub struct DownloadedAsset {
+    pub tag_name: String,
+    pub name: String,
+    /// The asset in a temporary file, deleted when this is dropped
+    pub file: DownloadedFile,
+}
+
+/// Download the release asset matching `filter`, or the best match for this platform,
+/// optionally verifying it against the release's checksum file or signature
+pub async fn download(
+    owner: &str,
+    repo: &str,
+    version: &str,
+    filter: Option<&str>,
+    verify_checksum: bool,
+    retry_config: &crate::cli::RetryConfig,
+) -> Result<DownloadedAsset> {
+    let source = release_source(owner, repo);
+    let provided = if verify_checksum {
+        Verification::Checksum
+    } else {
+        Verification::None
+    };
+    utils::policy::enforce(&source, provided)?;
+    info!("Fetching release information for {}/{}", owner, repo);
+    let release = client::fetch_release(owner, repo, version, None, false, retry_config).await?;
+
+    let selector = selector::create_selector(filter)?;
+    let asset = selector.select(&release.assets)?;
+    info!("Downloading {} from {}", asset.name, release.tag_name);
+    let file = download_verified(
+        &release.assets,
+        asset,
+        verify_checksum,
+        ChecksumAlgorithm::default(),
+        None,
+        false,
+        retry_config,
+    )
+    .await?;
+    let name = format!("{}/{}", owner, repo);
+    extractor::LockedRelease {
+        name: &name,
+        tag: &release.tag_name,
+    }
+    .record(asset, &file.digests)?;
+
+    Ok(DownloadedAsset {
+        tag_name: release.tag_name.clone(),
+        name: asset.name.clone(),
+        file,
+    })
+}
+
+/// Download `asset` and, when `verify` is set, the signature or checksum file the
+/// release publishes for it side by side, then verify one against the other
+async fn download_verified(
+    assets: &[Asset],
+    asset: &Asset,
+    verify: bool,
+    preferred: ChecksumAlgorithm,
+    gpg_key: Option<&str>,
+    require_signature: bool,
+    retry_config: &crate::cli::RetryConfig,
+) -> Result<DownloadedFile> {
+    let batch = utils::scheduler::Batch::new(retry_config);
+    let evidence = async {
+        if !verify {
+            return Ok(None);
+        }
+        verifier::fetch_evidence(&batch, assets, asset, preferred, gpg_key, require_signature)
+            .await
+            .map(Some)
+    };
+    let (download, evidence) = tokio::try_join!(
+        batch.fetch(&asset.name, || extractor::download_asset(asset)),
+        evidence
+    )?;
+    if let Some(evidence) = evidence {
+        verifier::verify_asset(asset, &download, evidence)?;
+    }
+    Ok(download)
 }
 
 pub async fn install(
     config: &GhReleaseConfig<'_>,
     retry_config: &crate::cli::RetryConfig,
 ) -> Result<()> {
+    utils::offline::ensure_online("gh-release")?;
+    let source = release_source(config.owner, config.repo);
+    utils::policy::enforce(&source, config.verification())?;
+    utils::preflight::ensure_writable(Path::new(config.install_dir))?;
+    let ownership = config
+        .chown
+        .map(utils::ownership::Ownership::parse)
+        .transpose()?;
+
+    let name = format!("{}/{}", config.owner, config.repo);
+    let tag = if config.from_tag {
+        install_tag(config, &name, retry_config).await?
+    } else {
+        install_release(config, &name, retry_config).await?
+    };
+
+    let files: Vec<_> = config
+        .binary_names
+        .iter()
+        .map(|binary| Path::new(config.install_dir).join(binary))
+        .filter(|path| path.is_file())
+        .collect();
+    if ownership.is_some() || config.mode.is_some() {
+        for file in &files {
+            utils::ownership::apply(file, ownership, config.mode)?;
+        }
+    }
+    utils::state::record([utils::state::Installed::new("gh-release", &name)
+        .version(&tag)
+        .files(files)]);
+
+    info!("Installation complete!");
+    Ok(())
+}
+
+/// Install the binaries from a release asset, returning the release's tag
+async fn install_release(
+    config: &GhReleaseConfig<'_>,
+    name: &str,
+    retry_config: &crate::cli::RetryConfig,
+) -> Result<String> {
     info!(
         "Fetching release information for {}/{}",
         config.owner, config.repo
     );
+    let selector = selector::create_selector(config.filter)?;
+    let fetch = || async {
+        let release = client::fetch_release(
+            config.owner,
+            config.repo,
+            config.version,
+            config.tag_prefix,
+            config.include_prerelease,
+            retry_config,
+        )
+        .await?;
+        // Assets are uploaded after the release is created, so wait for the one to install too
+        selector.select(&release.assets)?;
+        Ok::<_, anyhow::Error>(release)
+    };
+    let release = match config.wait_for_release {
+        Some(timeout) => {
+            let what = format!("a release of {}/{}", config.owner, config.repo);
+            utils::retry::poll_async(retry_config, &what, timeout, fetch, |e| {
+                ErrorKind::of(e) == ErrorKind::AssetNotFound
+            })
+            .await?
+        }
+        None => fetch().await?,
+    };
+    info!("Installing from release: {}", release.tag_name);
 
-    let release = client::fetch_release(
+    let asset = selector.select(&release.assets)?;
+    info!("Selected asset: {}", asset.name);
+    extractor::ensure_space(asset, config.install_dir)?;
+
+    // Downloaded once, hashed as it streams to disk, then verified and unpacked from there
+    let require_signature =
+        utils::policy::required(&release_source(config.owner, config.repo)) == Verification::Gpg;
+    let download = download_verified(
+        &release.assets,
+        asset,
+        config.verify_checksum && config.checksum_text.is_none(),
+        config.checksum_algorithm,
+        config.gpg_key,
+        require_signature,
+        retry_config,
+    )
+    .await?;
+    if let Some(checksum_text) = config.checksum_text {
+        verifier::verify_with_checksum_text(
+            &asset.name,
+            asset.browser_download_url.as_str(),
+            &download.digests,
+            checksum_text,
+        )?;
+    }
+
+    let locked = extractor::LockedRelease {
+        name,
+        tag: &release.tag_name,
+    };
+    extractor::extract_and_install(
+        asset,
+        &download,
+        &locked,
+        config.binary_names,
+        config.install_dir,
+    )?;
+    Ok(release.tag_name)
+}
+
+/// Install the binaries from the source tarball GitHub generates for a tag, for
+/// repositories that tag versions without publishing releases. Returns the tag.
+async fn install_tag(
+    config: &GhReleaseConfig<'_>,
+    name: &str,
+    retry_config: &crate::cli::RetryConfig,
+) -> Result<String> {
+    info!("Fetching tags for {}/{}", config.owner, config.repo);
+    let tag = client::resolve_tag(
         config.owner,
         config.repo,
         config.version,
-        config.include_prerelease,
+        config.tag_prefix,
         retry_config,
     )
     .await?;
-    info!("Installing from release: {}", release.tag_name);
-
-    let selector = selector::create_selector(config.filter)?;
-    let asset = selector.select(&release.assets)?;
-    info!("Selected asset: {}", asset.name);
+    info!("Installing from tag: {}", tag);
 
+    let url = format!(
+        "https://github.com/{}/{}/archive/refs/tags/{}.tar.gz",
+        config.owner, config.repo, tag
+    );
+    let archive = format!("{}-{}.tar.gz", config.repo, tag);
+    let download = utils::scheduler::Batch::new(retry_config)
+        .fetch(&archive, || extractor::download_tag_archive(&url))
+        .await?;
     if let Some(checksum_text) = config.checksum_text {
-        verifier::verify_with_checksum_text(asset, checksum_text).await?;
-    } else if config.verify_checksum {
-        verifier::verify_asset(&release.assets, asset, config.gpg_key).await?;
+        verifier::verify_with_checksum_text(&archive, &url, &download.digests, checksum_text)?;
     }
 
-    extractor::extract_and_install(asset, config.binary_names, config.install_dir).await?;
-
-    info!("Installation complete!");
-    Ok(())
+    let locked = extractor::LockedRelease { name, tag: &tag };
+    extractor::extract_tag_archive(
+        &download,
+        &locked,
+        &url,
+        config.binary_names,
+        config.install_dir,
+    )?;
+    Ok(tag)
 }
@@ -1,3 +1,4 @@
+use crate::error::PicolayerError;
 use anyhow::{Context, Result};
 use octocrab::models::repos::Asset;
 use regex::Regex;
@@ -22,7 +23,13 @@ impl AssetSelector for FilterSelector {
         assets
             .iter()
             .find(|a| self.regex.is_match(&a.name) && !is_signature_file(&a.name))
-            .context("No asset matching filter pattern")
+            .ok_or_else(|| {
+                PicolayerError::AssetNotFound {
+                    message: format!("No asset matching filter pattern {}", self.regex),
+                    suggestion: Some(suggest_assets(assets, Some(self.regex.as_str()))),
+                }
+                .into()
+            })
     }
 }
 
@@ -32,7 +39,13 @@ impl AssetSelector for PlatformSelector {
     fn select<'a>(&self, assets: &'a [Asset]) -> Result<&'a Asset> {
         select_by_platform(assets)
             .or_else(|| select_any_archive(assets))
-            .context("No suitable asset found for this platform")
+            .ok_or_else(|| {
+                PicolayerError::AssetNotFound {
+                    message: "No suitable asset found for this platform".into(),
+                    suggestion: Some(suggest_assets(assets, None)),
+                }
+                .into()
+            })
     }
 }
 
@@ -109,6 +122,48 @@ fn is_archive(filename: &str) -> bool {
         || filename.ends_with(".7z")
 }
 
+/// Most asset names to list in a suggestion
+const MAX_LISTED_ASSETS: usize = 20;
+
+/// Name the assets the release does have, and those closest to `pattern`, so the
+/// filter can be fixed
+fn suggest_assets(assets: &[Asset], pattern: Option<&str>) -> String {
+    let names: Vec<&str> = assets
+        .iter()
+        .map(|asset| asset.name.as_str())
+        .filter(|name| !is_signature_file(name))
+        .collect();
+    if names.is_empty() {
+        return "The release has no downloadable assets; try a different --version".to_string();
+    }
+
+    let mut listed = names
+        .iter()
+        .take(MAX_LISTED_ASSETS)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > MAX_LISTED_ASSETS {
+        listed.push_str(&format!(" and {} more", names.len() - MAX_LISTED_ASSETS));
+    }
+    let Some(pattern) = pattern else {
+        return format!("Pick one with --filter. Available assets: {}", listed);
+    };
+    // Compare the pattern's literal text, since a regex rarely resembles what it matches
+    let literal: String = pattern
+        .chars()
+        .filter(|c| !r"\^$.*+?()[]{}|".contains(*c))
+        .collect();
+    match crate::error::closest(&literal, names.iter().copied(), 3).as_slice() {
+        [] => format!("Available assets: {}", listed),
+        closest => format!(
+            "Closest to the filter: {}. Available assets: {}",
+            closest.join(", "),
+            listed
+        ),
+    }
+}
+
 fn is_signature_file(filename: &str) -> bool {
     let lower = filename.to_lowercase();
     lower.ends_with(".asc")
@@ -208,6 +263,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn filter_errors_suggest_the_closest_assets() {
+        let assets = vec![
+            mock_asset("tool-v1.0-linux-amd64.tar.gz"),
+            mock_asset("tool-v1.0-linux-amd64.tar.gz.sig"),
+            mock_asset("tool-v1.0-darwin-arm64.tar.gz"),
+        ];
+
+        let suggestion = suggest_assets(&assets, Some(r"tool-v1\.0-linux-x86_64\.tar\.gz"));
+        assert!(
+            suggestion.starts_with("Closest to the filter: tool-v1.0-linux-amd64.tar.gz"),
+            "{}",
+            suggestion
+        );
+        assert!(!suggestion.contains(".sig"));
+        assert_eq!(
+            suggest_assets(&assets[..1], None),
+            "Pick one with --filter. Available assets: tool-v1.0-linux-amd64.tar.gz"
+        );
+    }
+
     #[test]
     fn filter_selector_error_when_only_signature_files_match() {
         let assets = vec![
@@ -1,41 +1,180 @@
+use crate::error::{Permanent, PicolayerError};
+use crate::utils::audit;
+use crate::utils::download::{Digests, DownloadedFile};
+use crate::utils::policy::{self, ChecksumAlgorithm};
+use crate::utils::scheduler::Batch;
 use anyhow::{Context, Result};
-use log::info;
+use log::{info, warn};
 use octocrab::models::repos::Asset;
-use sha2::{Digest, Sha256, Sha512};
+use pgp::composed::SignedPublicKey;
 use std::collections::HashMap;
 
-pub(super) async fn verify_with_checksum_text(asset: &Asset, checksum_text: &str) -> Result<()> {
+/// What a release publishes to verify an asset against, fetched while the asset downloads
+pub(super) enum Evidence<'a> {
+    Signature {
+        signature: &'a Asset,
+        data: Vec<u8>,
+        key: SignedPublicKey,
+    },
+    ChecksumFile {
+        checksums: &'a Asset,
+        content: String,
+    },
+    /// A checksum file the release signs in place of each asset, e.g. `SHA256SUMS.sig`
+    SignedChecksumFile {
+        checksums: &'a Asset,
+        content: String,
+        signature: &'a Asset,
+        data: Vec<u8>,
+        key: SignedPublicKey,
+    },
+}
+
+/// Verify the download of `name` from `url` against a `--checksum-text` digest
+pub(super) fn verify_with_checksum_text(
+    name: &str,
+    url: &str,
+    digests: &Digests,
+    checksum_text: &str,
+) -> Result<()> {
     info!("Verifying asset with provided checksum text");
 
     let (algorithm, expected_hash) = parse_checksum_text(checksum_text)?;
-    let asset_data = download_asset_data(asset).await?;
-    let computed_hash = compute_hash(&asset_data, &algorithm)?;
+    policy::ensure_strong(&algorithm, "the provided checksum")?;
+    let computed_hash = digest_for(digests, &algorithm)?;
 
     if computed_hash.eq_ignore_ascii_case(&expected_hash) {
-        info!("Checksum verification passed");
+        info!("Checksum verification passed ({})", algorithm);
+        audit::verified_with(url, "checksum", &algorithm);
         Ok(())
     } else {
-        anyhow::bail!(
-            "Checksum verification failed!\nExpected: {}\nComputed: {}",
-            expected_hash,
-            computed_hash
-        );
+        audit::verification_failed(url, digests, "checksum");
+        Err(anyhow::Error::from(PicolayerError::ChecksumMismatch {
+            expected: expected_hash,
+            actual: computed_hash.to_string(),
+        })
+        .context(format!("Verifying {} against the provided checksum", name)))
     }
 }
 
-pub(super) async fn verify_asset(
-    assets: &[Asset],
+/// Fetch the signature, or else the checksum file and any signature over it, that the
+/// release publishes for `asset`
+pub(super) async fn fetch_evidence<'a>(
+    batch: &Batch<'_>,
+    assets: &'a [Asset],
     asset: &Asset,
+    preferred: ChecksumAlgorithm,
     gpg_key: Option<&str>,
-) -> Result<()> {
-    info!("Verifying asset");
+    require_signature: bool,
+) -> Result<Evidence<'a>> {
+    if let Some(signature) = find_signature_asset(assets, asset) {
+        let Some(key_content) = gpg_key else {
+            anyhow::bail!(
+                "Signature file found ({}) but no GPG key provided. \
+                 Use --gpg-key to provide a public key for verification.",
+                signature.name
+            );
+        };
+        let (data, key) = tokio::try_join!(
+            batch.fetch(&signature.name, || download_asset_data(signature)),
+            load_public_key(key_content)
+        )?;
+        return Ok(Evidence::Signature {
+            signature,
+            data,
+            key,
+        });
+    }
+
+    let checksums = find_checksum_asset(assets, asset, preferred);
+    let signed_checksums = checksums.as_ref().ok().and_then(|&checksums| {
+        find_signature_asset(assets, checksums).map(|signature| (checksums, signature))
+    });
+    if let (Some((checksums, signature)), Some(key_content)) = (signed_checksums, gpg_key) {
+        let (content, data, key) = tokio::try_join!(
+            fetch_checksum_file(batch, checksums),
+            batch.fetch(&signature.name, || download_asset_data(signature)),
+            load_public_key(key_content)
+        )?;
+        return Ok(Evidence::SignedChecksumFile {
+            checksums,
+            content,
+            signature,
+            data,
+            key,
+        });
+    }
+    if require_signature {
+        return Err(Permanent::new(format!(
+            "Policy requires GPG signature verification, but the release has no {}.asc or {}.sig \
+             and no signed checksum file",
+            asset.name, asset.name
+        ))
+        .into());
+    }
 
-    if let Some(sig_asset) = find_signature_asset(assets, asset) {
-        return verify_gpg_signature(asset, sig_asset, gpg_key).await;
+    let checksums = checksums?;
+    if let Some((_, signature)) = signed_checksums {
+        warn!(
+            "{} is signed ({}), but no GPG key was provided; verifying the checksum only. \
+             Use --gpg-key to verify the signature too.",
+            checksums.name, signature.name
+        );
     }
+    let content = fetch_checksum_file(batch, checksums).await?;
+    Ok(Evidence::ChecksumFile { checksums, content })
+}
 
-    let checksum_asset = find_checksum_asset(assets, asset)?;
-    verify_checksum_file(asset, checksum_asset).await
+async fn fetch_checksum_file(batch: &Batch<'_>, checksums: &Asset) -> Result<String> {
+    let url = checksums.browser_download_url.as_str();
+    let data = batch
+        .fetch(&checksums.name, || crate::utils::http_cache::fetch(url))
+        .await?;
+    String::from_utf8(data).with_context(|| format!("{} is not valid UTF-8", checksums.name))
+}
+
+/// Verify a downloaded `asset` against the evidence its release published
+pub(super) fn verify_asset(
+    asset: &Asset,
+    download: &DownloadedFile,
+    evidence: Evidence<'_>,
+) -> Result<()> {
+    info!("Verifying asset");
+    match evidence {
+        Evidence::Signature {
+            signature,
+            data,
+            key,
+        } => verify_gpg_signature(asset, download, signature, data, &key),
+        Evidence::ChecksumFile { checksums, content } => verify_checksum_file(
+            asset,
+            &download.digests,
+            checksums,
+            &content,
+            "checksum-file",
+        ),
+        Evidence::SignedChecksumFile {
+            checksums,
+            content,
+            signature,
+            data,
+            key,
+        } => {
+            info!(
+                "Verifying GPG signature of {} ({})",
+                checksums.name, signature.name
+            );
+            if let Err(e) = parse_signature(data)?.verify(&key, content.as_bytes()) {
+                let url = asset.browser_download_url.as_str();
+                audit::verification_failed(url, &download.digests, "gpg");
+                return Err(
+                    PicolayerError::SignatureInvalid(format!("{}: {}", checksums.name, e)).into(),
+                );
+            }
+            info!("GPG signature verification passed!");
+            verify_checksum_file(asset, &download.digests, checksums, &content, "gpg")
+        }
+    }
 }
 
 fn parse_checksum_text(checksum_text: &str) -> Result<(String, String)> {
@@ -48,9 +187,8 @@ fn parse_checksum_text(checksum_text: &str) -> Result<(String, String)> {
     let hash = parts[1].trim();
 
     // Validate algorithm and hash length
-    match algorithm.as_str() {
-        "sha256" if hash.len() == 64 => Ok((algorithm, hash.to_string())),
-        "sha512" if hash.len() == 128 => Ok((algorithm, hash.to_string())),
+    match hex_len(&algorithm) {
+        Some(len) if hash.len() == len => Ok((algorithm, hash.to_string())),
         _ => anyhow::bail!(
             "Unsupported algorithm '{}' or invalid hash length",
             algorithm
@@ -58,106 +196,182 @@ fn parse_checksum_text(checksum_text: &str) -> Result<(String, String)> {
     }
 }
 
-fn compute_hash(data: &[u8], algorithm: &str) -> Result<String> {
+/// Length of a hex digest for the algorithms [`Digests::get`] knows
+fn hex_len(algorithm: &str) -> Option<usize> {
     match algorithm {
-        "sha256" => {
-            let mut hasher = Sha256::new();
-            hasher.update(data);
-            Ok(hex::encode(hasher.finalize()))
-        }
-        "sha512" => {
-            let mut hasher = Sha512::new();
-            hasher.update(data);
-            Ok(hex::encode(hasher.finalize()))
-        }
-        _ => anyhow::bail!("Unsupported hash algorithm: {}", algorithm),
+        "sha1" => Some(40),
+        "sha256" | "blake3" => Some(64),
+        "sha512" | "blake2b" => Some(128),
+        _ => None,
     }
 }
 
+/// The digest computed for `algorithm` while the asset downloaded
+fn digest_for<'a>(digests: &'a Digests, algorithm: &str) -> Result<&'a str> {
+    digests
+        .get(algorithm)
+        .with_context(|| format!("Unsupported hash algorithm: {}", algorithm))
+}
+
 fn find_signature_asset<'a>(assets: &'a [Asset], asset: &Asset) -> Option<&'a Asset> {
     let exact_patterns = [format!("{}.asc", asset.name), format!("{}.sig", asset.name)];
     assets.iter().find(|a| exact_patterns.contains(&a.name))
 }
 
-fn find_checksum_asset<'a>(assets: &'a [Asset], asset: &Asset) -> Result<&'a Asset> {
-    let patterns = build_checksum_patterns(&asset.name);
+/// Extensions of checksum files covering a single asset, e.g. `kubectl.sha256`
+const PER_ASSET_EXTENSIONS: &[&str] = &[
+    ".sha256",
+    ".sha256sum",
+    ".sha512",
+    ".sha512sum",
+    ".sha1",
+    ".sha1sum",
+    ".b2",
+    ".b3",
+];
+
+/// Combined checksum files, in the order they are looked for
+const COMBINED_NAMES: &[&str] = &[
+    "SHA256SUMS",
+    "sha256sums.txt",
+    "checksums.txt",
+    "CHECKSUMS",
+    "checksums.sha256",
+    "SHA512SUMS",
+    "checksums.sha512",
+    "B3SUMS",
+    "b3sums.txt",
+    "B2SUMS",
+    "b2sums.txt",
+    "SHA1SUMS",
+    "sha1sums.txt",
+    "MD5SUMS",
+    "md5sums.txt",
+];
+
+/// Combined checksum files that releases prefix with the project and version, e.g.
+/// goreleaser's `tool_1.2.3_checksums.txt`
+const COMBINED_SUFFIXES: &[&str] = &[
+    "checksums.txt",
+    "checksums.sha256",
+    "sha256sums",
+    "sha256sums.txt",
+];
+
+fn find_checksum_asset<'a>(
+    assets: &'a [Asset],
+    asset: &Asset,
+    preferred: ChecksumAlgorithm,
+) -> Result<&'a Asset> {
+    let patterns =
+        build_checksum_patterns(&asset.name, preferred, policy::rejects_weak_checksums());
 
-    assets
+    patterns
         .iter()
-        .find(|a| patterns.iter().any(|p| a.name.eq_ignore_ascii_case(p)))
+        .find_map(|p| assets.iter().find(|a| a.name.eq_ignore_ascii_case(p)))
+        .or_else(|| assets.iter().find(|a| is_prefixed_checksum_file(&a.name)))
         .context("No checksum file found")
 }
 
-fn build_checksum_patterns(filename: &str) -> Vec<String> {
+fn is_prefixed_checksum_file(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    COMBINED_SUFFIXES.iter().any(|suffix| {
+        name.strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.ends_with(['_', '-', '.']))
+    })
+}
+
+/// The asset a per-asset checksum file covers: `kubectl.sha256` -> `kubectl`
+fn checksummed_file(checksum_file: &str) -> Option<&str> {
+    PER_ASSET_EXTENSIONS.iter().find_map(|ext| {
+        let split = checksum_file.len().checked_sub(ext.len())?;
+        (checksum_file.is_char_boundary(split) && checksum_file[split..].eq_ignore_ascii_case(ext))
+            .then(|| &checksum_file[..split])
+            .filter(|name| !name.is_empty())
+    })
+}
+
+/// Checksum file names that could cover `filename`, the `preferred` algorithm's first, then
+/// those that do not name one, then weak ones unless `reject_weak` leaves them out
+fn build_checksum_patterns(
+    filename: &str,
+    preferred: ChecksumAlgorithm,
+    reject_weak: bool,
+) -> Vec<String> {
     let mut patterns = Vec::new();
-    let variants = get_filename_variants(filename);
-
-    for variant in &variants {
-        patterns.extend([
-            format!("{}.sha256", variant),
-            format!("{}.sha256sum", variant),
-            format!("{}.sha512", variant),
-            format!("{}.sha512sum", variant),
-        ]);
-    }
-
-    // Common checksum file names (prioritized order)
-    patterns.extend([
-        "SHA256SUMS".to_string(),
-        "sha256sums.txt".to_string(),
-        "checksums.txt".to_string(),
-        "CHECKSUMS".to_string(),
-        "checksums.sha256".to_string(),
-        "SHA512SUMS".to_string(),
-        "checksums.sha512".to_string(),
-    ]);
+    for variant in &get_filename_variants(filename) {
+        patterns.extend(
+            PER_ASSET_EXTENSIONS
+                .iter()
+                .map(|ext| (*ext, format!("{}{}", variant, ext))),
+        );
+    }
+    patterns.extend(COMBINED_NAMES.iter().map(|name| (*name, name.to_string())));
+
+    patterns.retain(|(kind, _)| !(reject_weak && named_algorithm(kind).is_some_and(is_weak)));
+    patterns.sort_by_key(|(kind, _)| match named_algorithm(kind) {
+        Some(algorithm) if algorithm == preferred.name() => 0,
+        Some(algorithm) if is_weak(algorithm) => 2,
+        _ => 1,
+    });
+    patterns.into_iter().map(|(_, pattern)| pattern).collect()
+}
 
-    patterns
+/// The algorithm a checksum file name or extension like `SHA512SUMS` or `.sha1` names
+fn named_algorithm(kind: &str) -> Option<&'static str> {
+    let kind = kind.to_ascii_lowercase();
+    ["sha256", "sha512", "sha1", "md5"]
+        .into_iter()
+        .find(|algorithm| kind.contains(algorithm))
+}
+
+fn is_weak(algorithm: &str) -> bool {
+    policy::WEAK_ALGORITHMS.contains(&algorithm)
 }
 
-async fn verify_gpg_signature(
+fn verify_gpg_signature(
     asset: &Asset,
+    download: &DownloadedFile,
     signature_asset: &Asset,
-    gpg_key: Option<&str>,
+    sig_data: Vec<u8>,
+    public_key: &SignedPublicKey,
 ) -> Result<()> {
-    if let Some(key_content) = gpg_key {
-        info!("Verifying GPG signature");
-
-        let (asset_data, sig_data, public_key) = tokio::try_join!(
-            download_asset_data(asset),
-            download_asset_data(signature_asset),
-            load_public_key(key_content)
-        )?;
-
-        use pgp::composed::{Deserializable, DetachedSignature};
-        use std::io::Cursor;
+    info!("Verifying GPG signature ({})", signature_asset.name);
+    let asset_data = std::fs::read(download.path())
+        .with_context(|| format!("Failed to read {}", download.path().display()))?;
+    let signature = parse_signature(sig_data)?;
+
+    let url = asset.browser_download_url.as_str();
+    if let Err(e) = signature.verify(public_key, &asset_data[..]) {
+        audit::verification_failed(url, &download.digests, "gpg");
+        return Err(PicolayerError::SignatureInvalid(format!("{}: {}", asset.name, e)).into());
+    }
+    audit::verified(url, "gpg");
+    info!("GPG signature verification passed!");
+    Ok(())
+}
 
-        let signature = if sig_data.starts_with(b"-----BEGIN PGP SIGNATURE-----") {
-            let sig_str = String::from_utf8(sig_data)?;
-            let (sig, _) = DetachedSignature::from_string(&sig_str)?;
-            sig
-        } else {
-            DetachedSignature::from_bytes(Cursor::new(&sig_data[..]))?
-        };
+/// A detached signature, ASCII-armored (`.asc`) or binary (`.sig`)
+fn parse_signature(sig_data: Vec<u8>) -> Result<pgp::composed::DetachedSignature> {
+    use pgp::composed::{Deserializable, DetachedSignature};
+    use std::io::Cursor;
 
-        signature.verify(&public_key, &asset_data[..])?;
-        info!("GPG signature verification passed!");
-        Ok(())
+    if sig_data.starts_with(b"-----BEGIN PGP SIGNATURE-----") {
+        let sig_str = String::from_utf8(sig_data)?;
+        let (sig, _) = DetachedSignature::from_string(&sig_str)?;
+        Ok(sig)
     } else {
-        anyhow::bail!(
-            "Signature file found ({}) but no GPG key provided. \
-             Use --gpg-key to provide a public key for verification.",
-            signature_asset.name
-        );
+        Ok(DetachedSignature::from_bytes(Cursor::new(&sig_data[..]))?)
     }
 }
 
-async fn load_public_key(key_content: &str) -> Result<pgp::composed::SignedPublicKey> {
-    use pgp::composed::{Deserializable, SignedPublicKey};
+async fn load_public_key(key_content: &str) -> Result<SignedPublicKey> {
+    use pgp::composed::Deserializable;
 
     let key_data = if key_content.starts_with("https://") {
         info!("Downloading GPG public key from URL");
-        reqwest::get(key_content).await?.text().await?
+        let data = crate::utils::http_cache::fetch(key_content).await?;
+        String::from_utf8(data).context("GPG public key is not valid UTF-8")?
     } else if key_content.starts_with("http://") {
         anyhow::bail!("Refusing to download GPG key over insecure HTTP. Use HTTPS instead.");
     } else if std::path::Path::new(key_content).exists() {
@@ -170,40 +384,73 @@ async fn load_public_key(key_content: &str) -> Result<pgp::composed::SignedPubli
     Ok(public_key)
 }
 
-async fn verify_checksum_file(asset: &Asset, checksum_asset: &Asset) -> Result<()> {
+fn verify_checksum_file(
+    asset: &Asset,
+    digests: &Digests,
+    checksum_asset: &Asset,
+    checksum_content: &str,
+    method: &'static str,
+) -> Result<()> {
     info!("Verifying checksum from file: {}", checksum_asset.name);
 
-    let (asset_data, checksum_content) = tokio::try_join!(
-        download_asset_data(asset),
-        download_asset_text(checksum_asset)
-    )?;
-
-    let checksums = parse_checksum_file(&checksum_content)?;
+    let checksums = parse_checksum_file(checksum_content, &checksum_asset.name)?;
     let asset_variants = get_filename_variants(&asset.name);
 
     for variant in &asset_variants {
         if let Some((algorithm, expected_hash)) = checksums.get(variant) {
-            let computed_hash = compute_hash(&asset_data, algorithm)?;
+            policy::ensure_strong(
+                algorithm,
+                &format!("{} in {}", asset.name, checksum_asset.name),
+            )?;
+            let computed_hash = digest_for(digests, algorithm)?;
 
+            let url = asset.browser_download_url.as_str();
             if computed_hash.eq_ignore_ascii_case(expected_hash) {
-                info!("Checksum verification passed ({})", algorithm);
+                info!(
+                    "Checksum verification passed ({} from {})",
+                    algorithm, checksum_asset.name
+                );
+                audit::verified_with(url, method, algorithm);
                 return Ok(());
             } else {
-                anyhow::bail!(
-                    "Checksum verification failed!\nFile: {}\nAlgorithm: {}\nExpected: {}\nComputed: {}",
-                    variant,
-                    algorithm,
-                    expected_hash,
-                    computed_hash
-                );
+                audit::verification_failed(url, digests, method);
+                return Err(anyhow::Error::from(PicolayerError::ChecksumMismatch {
+                    expected: expected_hash.clone(),
+                    actual: computed_hash.to_string(),
+                })
+                .context(format!(
+                    "Verifying {} against {} ({})",
+                    variant, checksum_asset.name, algorithm
+                )));
             }
         }
     }
 
-    anyhow::bail!("No matching checksum found for asset: {}", asset.name)
+    Err(PicolayerError::AssetNotFound {
+        message: format!(
+            "No matching checksum found for asset: {} in {}",
+            asset.name, checksum_asset.name
+        ),
+        suggestion: Some(
+            match crate::error::closest(&asset.name, checksums.keys().map(String::as_str), 3)
+                .as_slice()
+            {
+                [] => "Pin the asset's digest with --checksum-text instead".to_string(),
+                closest => format!(
+                    "{} lists {}; pin the asset's digest with --checksum-text instead",
+                    checksum_asset.name,
+                    closest.join(", ")
+                ),
+            },
+        ),
+    }
+    .into())
 }
 
-fn parse_checksum_file(content: &str) -> Result<HashMap<String, (String, String)>> {
+fn parse_checksum_file(
+    content: &str,
+    checksum_file: &str,
+) -> Result<HashMap<String, (String, String)>> {
     let mut checksums = HashMap::new();
 
     for line in content.lines() {
@@ -212,9 +459,17 @@ fn parse_checksum_file(content: &str) -> Result<HashMap<String, (String, String)
             continue;
         }
 
-        if let Some((hash, filename)) = parse_checksum_line_format(line) {
-            let algorithm = detect_algorithm_from_hash(&hash);
-            checksums.insert(filename, (algorithm, hash));
+        if let Some((algorithm, hash, filename)) = parse_tagged_line(line) {
+            checksums.insert(base_name(&filename), (algorithm, hash));
+        } else if let Some((hash, filename)) = parse_checksum_line_format(line) {
+            let algorithm = detect_algorithm_from_hash(&hash, checksum_file);
+            checksums.insert(base_name(&filename), (algorithm, hash));
+        } else if is_hex(line)
+            && let Some(filename) = checksummed_file(checksum_file)
+        {
+            // Per-asset files for raw binaries often hold only the hash, e.g. kubectl.sha256
+            let algorithm = detect_algorithm_from_hash(line, checksum_file);
+            checksums.insert(filename.to_string(), (algorithm, line.to_string()));
         }
     }
 
@@ -225,6 +480,27 @@ fn parse_checksum_file(content: &str) -> Result<HashMap<String, (String, String)
     Ok(checksums)
 }
 
+/// The file name of a checksum entry, which may list a build path like `./dist/tool`
+fn base_name(filename: &str) -> String {
+    filename.rsplit('/').next().unwrap_or(filename).to_string()
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// A BSD-style line naming its algorithm, as written by `sha256sum --tag` or `b2sum --tag`:
+/// `SHA256 (file) = hash`
+fn parse_tagged_line(line: &str) -> Option<(String, String, String)> {
+    let (tag, rest) = line.split_once(" (")?;
+    let (filename, hash) = rest.rsplit_once(") = ")?;
+    Some((
+        tag.to_ascii_lowercase(),
+        hash.trim().to_string(),
+        filename.to_string(),
+    ))
+}
+
 fn parse_checksum_line_format(line: &str) -> Option<(String, String)> {
     if let Some((filename, hash)) = line.split_once(':') {
         let filename = filename.trim();
@@ -244,28 +520,32 @@ fn parse_checksum_line_format(line: &str) -> Option<(String, String)> {
     None
 }
 
-fn detect_algorithm_from_hash(hash: &str) -> String {
+/// The algorithm behind a bare hash. BLAKE3 and BLAKE2b digests are as long as SHA-256
+/// and SHA-512 ones, so those are only assumed when the checksum file is named for them.
+fn detect_algorithm_from_hash(hash: &str, checksum_file: &str) -> String {
     match hash.len() {
+        32 => "md5".to_string(),
+        40 => "sha1".to_string(),
+        64 if is_named_for(checksum_file, "b3", "blake3") => "blake3".to_string(),
+        128 if is_named_for(checksum_file, "b2", "blake2") => "blake2b".to_string(),
         64 => "sha256".to_string(),
         128 => "sha512".to_string(),
         _ => "sha256".to_string(), // Default fallback
     }
 }
 
-async fn download_asset_data(asset: &Asset) -> Result<Vec<u8>> {
-    let response = reqwest::get(asset.browser_download_url.clone()).await?;
-    if !response.status().is_success() {
-        anyhow::bail!("Failed to download asset: {}", response.status());
-    }
-    Ok(response.bytes().await?.to_vec())
+/// Whether a checksum file is named like `B3SUMS`, `app.b3` or `blake3sums.txt`
+fn is_named_for(checksum_file: &str, short: &str, long: &str) -> bool {
+    let name = checksum_file.to_ascii_lowercase();
+    let name = name.strip_suffix(".txt").unwrap_or(&name);
+    name.contains(long)
+        || name.ends_with(&format!(".{}", short))
+        || name.ends_with(&format!("{}sum", short))
+        || name.ends_with(&format!("{}sums", short))
 }
 
-async fn download_asset_text(asset: &Asset) -> Result<String> {
-    let response = reqwest::get(asset.browser_download_url.clone()).await?;
-    if !response.status().is_success() {
-        anyhow::bail!("Failed to download asset: {}", response.status());
-    }
-    Ok(response.text().await?)
+async fn download_asset_data(asset: &Asset) -> Result<Vec<u8>> {
+    crate::utils::download::fetch(asset.browser_download_url.as_str()).await
 }
 
 fn get_filename_variants(filename: &str) -> Vec<String> {
@@ -334,30 +614,78 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_hash_sha256() {
-        let data = b"hello world";
-        let result = compute_hash(data, "sha256").unwrap();
+    fn test_digest_for_sha256() {
+        let digests = Digests::of(b"hello world");
+        let result = digest_for(&digests, "sha256").unwrap();
         assert_eq!(TEST_HELLO_SHA256, result);
     }
 
     #[test]
-    fn test_compute_hash_unsupported() {
-        let data = b"hello world";
-        let result = compute_hash(data, "unsupported_algorithm");
+    fn test_digest_for_unsupported() {
+        let digests = Digests::of(b"hello world");
+        let result = digest_for(&digests, "unsupported_algorithm");
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_checksum_text_accepts_sha1_and_blake() {
+        let sha1 = "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed";
+        assert_eq!(
+            parse_checksum_text(&format!("SHA1:{}", sha1)).unwrap(),
+            ("sha1".to_string(), sha1.to_string())
+        );
+        assert!(parse_checksum_text(&format!("blake3:{}", TEST_HELLO_SHA256)).is_ok());
+        assert!(parse_checksum_text(&format!("blake2b:{}", TEST_HELLO_SHA256)).is_err());
+    }
+
     #[test]
     fn test_detect_algorithm_from_hash() {
         assert_eq!(
-            detect_algorithm_from_hash("a".repeat(64).as_str()),
+            detect_algorithm_from_hash("a".repeat(64).as_str(), "checksums.txt"),
             "sha256"
         );
         assert_eq!(
-            detect_algorithm_from_hash("a".repeat(128).as_str()),
+            detect_algorithm_from_hash("a".repeat(128).as_str(), "SHA512SUMS"),
             "sha512"
         );
-        assert_eq!(detect_algorithm_from_hash("short"), "sha256");
+        assert_eq!(
+            detect_algorithm_from_hash("a".repeat(40).as_str(), "SHA1SUMS"),
+            "sha1"
+        );
+        assert_eq!(
+            detect_algorithm_from_hash("a".repeat(32).as_str(), "MD5SUMS"),
+            "md5"
+        );
+        assert_eq!(detect_algorithm_from_hash("short", "SHA256SUMS"), "sha256");
+    }
+
+    #[test]
+    fn test_detect_algorithm_from_hash_uses_blake_file_names() {
+        let b3 = "a".repeat(64);
+        let b2 = "a".repeat(128);
+        assert_eq!(detect_algorithm_from_hash(&b3, "B3SUMS"), "blake3");
+        assert_eq!(detect_algorithm_from_hash(&b3, "app.tar.gz.b3"), "blake3");
+        assert_eq!(detect_algorithm_from_hash(&b3, "blake3sums.txt"), "blake3");
+        assert_eq!(detect_algorithm_from_hash(&b2, "b2sums.txt"), "blake2b");
+        assert_eq!(detect_algorithm_from_hash(&b2, "app.b2"), "blake2b");
+        assert_eq!(detect_algorithm_from_hash(&b2, "checksums.txt"), "sha512");
+    }
+
+    #[test]
+    fn test_parse_checksum_file_reads_tagged_lines() {
+        let blake2b = "021ced8799296ceca557832ab941a50b4a11f83478cf141f51f933f653ab9fbcc05a037cddbed06e309bf334942c4e58cdf1a46e237911ccd7fcf9787cbc7fd0";
+        let content = format!(
+            "BLAKE2b (app.tar.gz) = {}\nSHA256 (app.zip) = {}\n",
+            blake2b, TEST_HELLO_SHA256
+        );
+        let result = parse_checksum_file(&content, "checksums.txt").unwrap();
+        let (algorithm, hash) = &result["app.tar.gz"];
+        assert_eq!(algorithm, "blake2b");
+        assert_eq!(
+            digest_for(&Digests::of(b"hello world"), algorithm).unwrap(),
+            hash
+        );
+        assert_eq!(result["app.zip"].0, "sha256");
     }
 
     #[test]
@@ -387,7 +715,7 @@ mod tests {
             "{}  file1.tar.gz\n{} *file2.zip\n# comment\nfile3.tar.xz: {}",
             TEST_HELLO_SHA256, TEST_HELLO_SHA256, TEST_HELLO_SHA256
         );
-        let result = parse_checksum_file(&content).unwrap();
+        let result = parse_checksum_file(&content, "checksums.txt").unwrap();
 
         assert_eq!(3, result.len());
         assert_eq!(
@@ -404,6 +732,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_checksum_file_matches_raw_binaries() {
+        let result = parse_checksum_file(
+            &format!("{}  ./dist/tool-linux-amd64\n", TEST_HELLO_SHA256),
+            "SHA256SUMS",
+        )
+        .unwrap();
+        assert!(result.contains_key("tool-linux-amd64"));
+
+        let result =
+            parse_checksum_file(&format!("{}\n", TEST_HELLO_SHA256), "kubectl.sha256").unwrap();
+        assert_eq!(result["kubectl"].1, TEST_HELLO_SHA256);
+        assert!(parse_checksum_file(TEST_HELLO_SHA256, "checksums.txt").is_err());
+    }
+
+    #[test]
+    fn test_checksum_file_names() {
+        assert_eq!(checksummed_file("kubectl.sha256"), Some("kubectl"));
+        assert_eq!(checksummed_file("tool.SHA256SUM"), Some("tool"));
+        assert_eq!(checksummed_file("SHA256SUMS"), None);
+        assert!(is_prefixed_checksum_file("tool_1.2.3_checksums.txt"));
+        assert!(is_prefixed_checksum_file("tool-v1.2.3-SHA256SUMS"));
+        assert!(!is_prefixed_checksum_file("mychecksums.txt"));
+    }
+
     #[test]
     fn test_get_filename_variants() {
         let variants = get_filename_variants("app.tar.gz");
@@ -413,18 +766,43 @@ mod tests {
 
     #[test]
     fn test_build_checksum_patterns() {
-        let patterns = build_checksum_patterns("app.tar.gz");
+        let patterns = build_checksum_patterns("app.tar.gz", ChecksumAlgorithm::Sha256, false);
         assert!(patterns.contains(&"app.tar.gz.sha256".to_string()));
         assert!(patterns.contains(&"app.sha256".to_string()));
         assert!(patterns.contains(&"SHA256SUMS".to_string()));
+        assert!(patterns.contains(&"app.tar.gz.b3".to_string()));
+        assert!(patterns.contains(&"B2SUMS".to_string()));
+        assert!(patterns.contains(&"SHA1SUMS".to_string()));
     }
 
     #[test]
-    fn test_load_public_key_rejects_http() {
-        // We can't call the async function directly in a sync test,
-        // so test the URL validation logic. The function should bail on http://
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        let result = rt.block_on(load_public_key("http://example.com/key.asc"));
+    fn test_build_checksum_patterns_orders_by_algorithm() {
+        let position =
+            |patterns: &[String], name: &str| patterns.iter().position(|p| p == name).unwrap();
+        let patterns = build_checksum_patterns("app", ChecksumAlgorithm::Sha512, false);
+        assert_eq!(patterns[0], "app.sha512");
+        assert!(position(&patterns, "SHA512SUMS") < position(&patterns, "app.sha256"));
+        assert!(position(&patterns, "checksums.txt") < position(&patterns, "app.sha1"));
+
+        let patterns = build_checksum_patterns("app", ChecksumAlgorithm::Sha256, true);
+        assert_eq!(patterns[0], "app.sha256");
+        assert!(
+            !patterns
+                .iter()
+                .any(|p| p.contains("sha1") || p.contains("SHA1"))
+        );
+        assert!(!patterns.iter().any(|p| p.to_lowercase().contains("md5")));
+    }
+
+    #[test]
+    fn test_parse_signature_rejects_garbage() {
+        assert!(parse_signature(b"not a signature".to_vec()).is_err());
+        assert!(parse_signature(b"-----BEGIN PGP SIGNATURE-----\n\n".to_vec()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_public_key_rejects_http() {
+        let result = load_public_key("http://example.com/key.asc").await;
         assert!(result.is_err());
         let err_msg = result.unwrap_err().to_string();
         assert!(
@@ -433,12 +811,11 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_load_public_key_accepts_non_url_as_inline_key() {
+    #[tokio::test]
+    async fn test_load_public_key_accepts_non_url_as_inline_key() {
         // Non-URL, non-file content should be treated as inline key data.
         // It will fail to parse as a PGP key, but should NOT fail with an HTTP error.
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        let result = rt.block_on(load_public_key("not-a-url-or-file"));
+        let result = load_public_key("not-a-url-or-file").await;
         assert!(result.is_err());
         let err_msg = result.unwrap_err().to_string();
         assert!(
@@ -0,0 +1,139 @@
+#[cfg(feature = "pkgx")]
+use crate::installers::pkgx;
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+
+pub struct GoInstallConfig<'a> {
+    pub packages: &'a [String],
+    pub install_dir: &'a str,
+    /// Go version to provision with pkgx when go is not already installed
+    pub go_version: &'a str,
+}
+
+pub async fn install(config: &GoInstallConfig<'_>) -> Result<()> {
+    utils::offline::ensure_online("go-install")?;
+    for package in config.packages {
+        validate_package(package)?;
+    }
+
+    let install_dir = Path::new(config.install_dir);
+    std::fs::create_dir_all(install_dir)
+        .with_context(|| format!("Failed to create {}", install_dir.display()))?;
+    let install_dir = install_dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", install_dir.display()))?;
+
+    // Module and build caches go to a temporary directory that is removed afterwards
+    let temp_dir = utils::tmp::tempdir()?;
+    let env_vars = go_env(&install_dir, temp_dir.path());
+
+    if which::which("go").is_ok() {
+        debug!("Using go from PATH");
+        for package in config.packages {
+            let mut cmd = Command::new("go");
+            cmd.args(["install", package]);
+            cmd.envs(env_vars.iter().filter_map(|v| v.split_once('=')));
+            utils::exec::run_command(&mut cmd, &format!("go install {}", package))?;
+        }
+    } else {
+        install_with_pkgx(config, temp_dir.path(), &env_vars).await?;
+    }
+
+    info!(
+        "Successfully installed Go packages to {}: {:?}",
+        install_dir.display(),
+        config.packages
+    );
+    Ok(())
+}
+
+/// go install needs a module path with an explicit version (e.g. @latest) outside a module
+fn validate_package(package: &str) -> Result<()> {
+    let valid = package
+        .split_once('@')
+        .is_some_and(|(path, version)| !path.is_empty() && !version.is_empty())
+        && !package.starts_with('-')
+        && !package.contains(char::is_whitespace);
+    anyhow::ensure!(
+        valid,
+        "Invalid Go package '{}'. Expected module/path@version (e.g. golang.org/x/tools/gopls@latest)",
+        package
+    );
+    Ok(())
+}
+
+/// Environment that sends binaries to `install_dir` and every cache to `temp_dir`
+fn go_env(install_dir: &Path, temp_dir: &Path) -> Vec<String> {
+    vec![
+        format!("GOBIN={}", install_dir.display()),
+        format!("GOPATH={}", temp_dir.join("gopath").display()),
+        format!("GOMODCACHE={}", temp_dir.join("gomodcache").display()),
+        format!("GOCACHE={}", temp_dir.join("gocache").display()),
+        "GOFLAGS=-modcacherw".to_string(),
+    ]
+}
+
+/// Run `go install` with a temporary toolchain provisioned by pkgx
+#[cfg(feature = "pkgx")]
+async fn install_with_pkgx(
+    config: &GoInstallConfig<'_>,
+    temp_dir: &Path,
+    env_vars: &[String],
+) -> Result<()> {
+    info!(
+        "go not found, provisioning a temporary Go {} toolchain with pkgx",
+        config.go_version
+    );
+    let working_dir = temp_dir
+        .to_str()
+        .context("Failed to convert temporary directory path to string")?;
+    for package in config.packages {
+        pkgx::execute(&pkgx::PkgxConfig {
+            tool: "go",
+            version: config.go_version,
+            args: vec!["install".to_string(), package.clone()],
+            working_dir,
+            env_vars: env_vars.to_vec(),
+            pantry_max_age: pkgx::DEFAULT_PANTRY_MAX_AGE,
+            capture_output: None,
+            env_filter: utils::env::EnvFilter::default(),
+        })
+        .await
+        .with_context(|| format!("go install {} failed", package))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "pkgx"))]
+async fn install_with_pkgx(
+    _config: &GoInstallConfig<'_>,
+    _temp_dir: &Path,
+    _env_vars: &[String],
+) -> Result<()> {
+    Err(super::not_built("pkgx").context("go not found in PATH"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_package_requires_version() {
+        assert!(validate_package("golang.org/x/tools/gopls@latest").is_ok());
+        assert!(validate_package("github.com/go-task/task/v3/cmd/task@v3.35.1").is_ok());
+        assert!(validate_package("golang.org/x/tools/gopls").is_err());
+        assert!(validate_package("@latest").is_err());
+        assert!(validate_package("-x@latest").is_err());
+    }
+
+    #[test]
+    fn go_env_keeps_caches_in_temp_dir() {
+        let env = go_env(Path::new("/usr/local/bin"), Path::new("/tmp/picolayer_x"));
+        assert!(env.contains(&"GOBIN=/usr/local/bin".to_string()));
+        assert!(env.contains(&"GOMODCACHE=/tmp/picolayer_x/gomodcache".to_string()));
+        assert!(env.contains(&"GOCACHE=/tmp/picolayer_x/gocache".to_string()));
+    }
+}
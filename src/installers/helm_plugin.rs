@@ -0,0 +1,172 @@
+use crate::cli::RetryConfig;
+use crate::installers::gh_release;
+use crate::utils;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub struct HelmPluginConfig<'a> {
+    /// Plugins as GitHub `owner/repo`, optionally pinned with `@version`
+    pub plugins: &'a [String],
+    /// Plugin directory; defaults to `helm env HELM_PLUGINS`
+    pub plugins_dir: Option<&'a str>,
+}
+
+/// A plugin reference such as `databus23/helm-diff@v3.9.11`
+#[derive(Debug, PartialEq)]
+struct PluginSpec<'a> {
+    owner: &'a str,
+    repo: &'a str,
+    version: &'a str,
+}
+
+pub async fn install(config: &HelmPluginConfig<'_>, retry_config: &RetryConfig) -> Result<()> {
+    utils::offline::ensure_online("helm-plugin")?;
+    let specs = config
+        .plugins
+        .iter()
+        .map(|plugin| parse_plugin_spec(plugin))
+        .collect::<Result<Vec<_>>>()?;
+    let plugins_dir = match config.plugins_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => helm_plugins_dir()?,
+    };
+
+    for spec in &specs {
+        install_plugin(spec, &plugins_dir, retry_config).await?;
+    }
+
+    info!(
+        "Successfully installed helm plugins to {}: {:?}",
+        plugins_dir.display(),
+        config.plugins
+    );
+    Ok(())
+}
+
+async fn install_plugin(
+    spec: &PluginSpec<'_>,
+    plugins_dir: &Path,
+    retry_config: &RetryConfig,
+) -> Result<()> {
+    let asset = gh_release::download(
+        spec.owner,
+        spec.repo,
+        spec.version,
+        None,
+        false,
+        retry_config,
+    )
+    .await?;
+    let lower = asset.name.to_lowercase();
+    anyhow::ensure!(
+        lower.ends_with(".tar.gz") || lower.ends_with(".tgz"),
+        "Unsupported helm plugin archive '{}' for {}/{}, expected a .tar.gz or .tgz",
+        asset.name,
+        spec.owner,
+        spec.repo
+    );
+
+    // Release archives ship the plugin with its binaries already built, so the
+    // install hooks `helm plugin install` would run (git clone, curl) are not needed
+    let dest = plugins_dir.join(spec.repo);
+    if dest.exists() {
+        std::fs::remove_dir_all(&dest)
+            .with_context(|| format!("Failed to remove {}", dest.display()))?;
+    }
+    let mut archive =
+        tar::Archive::new(GzDecoder::new(std::io::BufReader::new(asset.file.open()?)));
+    utils::archive::unpack_stripped(&mut archive, &dest)?;
+    anyhow::ensure!(
+        dest.join("plugin.yaml").is_file(),
+        "{} does not contain a plugin.yaml in its top-level directory",
+        asset.name
+    );
+
+    info!(
+        "Installed helm plugin {}/{} {} to {}",
+        spec.owner,
+        spec.repo,
+        asset.tag_name,
+        dest.display()
+    );
+    utils::state::record([utils::state::Installed::new(
+        "helm-plugin",
+        &format!("{}/{}", spec.owner, spec.repo),
+    )
+    .version(&asset.tag_name)
+    .files([&dest])]);
+    Ok(())
+}
+
+fn helm_plugins_dir() -> Result<PathBuf> {
+    let output = Command::new("helm")
+        .args(["env", "HELM_PLUGINS"])
+        .output()
+        .context("helm command not found in PATH. Install helm or pass --plugins-dir.")?;
+    let output = utils::exec::check_output(output, "Locate helm plugins directory")?;
+    let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    anyhow::ensure!(!dir.is_empty(), "helm did not report a plugins directory");
+    debug!("Using helm plugins directory {}", dir);
+    Ok(PathBuf::from(dir))
+}
+
+/// Parse `owner/repo[@version]`, also accepting a github.com URL
+fn parse_plugin_spec(plugin: &str) -> Result<PluginSpec<'_>> {
+    let trimmed = plugin
+        .trim_start_matches("https://")
+        .trim_start_matches("github.com/");
+    let (path, version) = match trimmed.split_once('@') {
+        Some((path, version)) => (path, version),
+        None => (trimmed, "latest"),
+    };
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+
+    match path.split_once('/') {
+        Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+            Ok(PluginSpec {
+                owner,
+                repo,
+                version,
+            })
+        }
+        _ => anyhow::bail!(
+            "Invalid helm plugin '{}'. Expected owner/repo[@version] (e.g. databus23/helm-diff)",
+            plugin
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plugin_spec_accepts_repos_and_urls() {
+        assert_eq!(
+            parse_plugin_spec("databus23/helm-diff@v3.9.11").unwrap(),
+            PluginSpec {
+                owner: "databus23",
+                repo: "helm-diff",
+                version: "v3.9.11"
+            }
+        );
+        assert_eq!(
+            parse_plugin_spec("https://github.com/jkroepke/helm-secrets.git").unwrap(),
+            PluginSpec {
+                owner: "jkroepke",
+                repo: "helm-secrets",
+                version: "latest"
+            }
+        );
+    }
+
+    #[test]
+    fn parse_plugin_spec_rejects_malformed_input() {
+        for plugin in ["helm-diff", "/helm-diff", "a/b/c", "databus23/"] {
+            assert!(parse_plugin_spec(plugin).is_err(), "{}", plugin);
+        }
+    }
+}
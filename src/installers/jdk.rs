@@ -0,0 +1,171 @@
+use crate::cli::JdkDistribution;
+use crate::utils;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use log::info;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const ADOPTIUM_API: &str = "https://api.adoptium.net/v3/assets/latest";
+
+pub struct JdkConfig<'a> {
+    /// Feature release, e.g. 21
+    pub version: u32,
+    pub distribution: JdkDistribution,
+    pub install_dir: &'a str,
+}
+
+/// A JDK archive resolved from the distribution's API
+#[derive(Debug, PartialEq)]
+struct JdkPackage {
+    release_name: String,
+    link: String,
+    checksum: String,
+    size: Option<u64>,
+}
+
+pub async fn install(config: &JdkConfig<'_>) -> Result<()> {
+    utils::offline::ensure_online("jdk")?;
+    let os = adoptium_os(std::env::consts::OS, utils::os::is_musl())?;
+    let arch = adoptium_arch(std::env::consts::ARCH)?;
+
+    let url = match config.distribution {
+        JdkDistribution::Temurin => format!(
+            "{}/{}/hotspot?architecture={}&image_type=jdk&os={}&vendor=eclipse",
+            ADOPTIUM_API, config.version, arch, os
+        ),
+    };
+    let assets = utils::download::fetch_json(&url).await?;
+    let package = parse_adoptium_assets(&assets).with_context(|| {
+        format!(
+            "No Temurin JDK {} build for {}/{}",
+            config.version, os, arch
+        )
+    })?;
+
+    utils::preflight::check(
+        Path::new(config.install_dir),
+        package.size,
+        &package.release_name,
+    )?;
+    info!("Installing {}", package.release_name);
+    let download = utils::download::fetch_to_file(&package.link, None).await?;
+    utils::download::verify_download(&package.link, &download.digests, &package.checksum)?;
+    utils::lock::record(
+        &format!("temurin-{}", config.version),
+        &package.release_name,
+        &package.link,
+        &download.digests,
+    )?;
+
+    let install_dir = Path::new(config.install_dir);
+    if install_dir.exists() {
+        std::fs::remove_dir_all(install_dir)
+            .with_context(|| format!("Failed to remove {}", install_dir.display()))?;
+    }
+    let mut archive = tar::Archive::new(GzDecoder::new(BufReader::new(download.open()?)));
+    utils::archive::unpack_stripped(&mut archive, install_dir)?;
+
+    let java_home = java_home(install_dir, os);
+    utils::env_registry::set_var("JAVA_HOME", &java_home.display().to_string());
+    utils::env_registry::add_path(&java_home.join("bin"));
+
+    info!(
+        "Installed {} to {} (set ENV JAVA_HOME={} for non-login shells)",
+        package.release_name,
+        install_dir.display(),
+        java_home.display()
+    );
+    utils::state::record([utils::state::Installed::new(
+        "jdk",
+        &format!("temurin-{}", config.version),
+    )
+    .version(&package.release_name)
+    .files([install_dir])]);
+    Ok(())
+}
+
+fn adoptium_os(os: &str, musl: bool) -> Result<&'static str> {
+    match os {
+        "linux" if musl => Ok("alpine-linux"),
+        "linux" => Ok("linux"),
+        "macos" => Ok("mac"),
+        _ => anyhow::bail!("JDK installation is not supported on {}", os),
+    }
+}
+
+fn adoptium_arch(arch: &str) -> Result<&'static str> {
+    match arch {
+        "x86_64" => Ok("x64"),
+        "aarch64" => Ok("aarch64"),
+        "powerpc64" => Ok("ppc64le"),
+        "s390x" => Ok("s390x"),
+        _ => anyhow::bail!("JDK installation is not supported on architecture {}", arch),
+    }
+}
+
+fn parse_adoptium_assets(assets: &serde_json::Value) -> Option<JdkPackage> {
+    assets.as_array()?.iter().find_map(|asset| {
+        let package = &asset["binary"]["package"];
+        let link = package["link"].as_str()?;
+        link.ends_with(".tar.gz").then(|| JdkPackage {
+            release_name: asset["release_name"].as_str().unwrap_or("JDK").to_string(),
+            link: link.to_string(),
+            checksum: package["checksum"].as_str().unwrap_or_default().to_string(),
+            size: package["size"].as_u64(),
+        })
+    })
+}
+
+/// macOS JDKs are bundles with the actual home under Contents/Home
+fn java_home(install_dir: &Path, os: &str) -> PathBuf {
+    if os == "mac" {
+        install_dir.join("Contents").join("Home")
+    } else {
+        install_dir.to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_adoptium_assets_picks_tarball() {
+        let assets = serde_json::json!([{
+            "binary": {
+                "package": {
+                    "checksum": "abc123",
+                    "size": 195000000,
+                    "link": "https://github.com/adoptium/temurin21-binaries/releases/download/jdk-21.0.5%2B11/OpenJDK21U-jdk_x64_linux_hotspot_21.0.5_11.tar.gz"
+                }
+            },
+            "release_name": "jdk-21.0.5+11"
+        }]);
+        let package = parse_adoptium_assets(&assets).unwrap();
+        assert_eq!(package.release_name, "jdk-21.0.5+11");
+        assert_eq!(package.checksum, "abc123");
+        assert_eq!(package.size, Some(195000000));
+        assert!(parse_adoptium_assets(&serde_json::json!([])).is_none());
+    }
+
+    #[test]
+    fn platform_names_match_adoptium_api() {
+        assert_eq!(adoptium_os("linux", true).unwrap(), "alpine-linux");
+        assert_eq!(adoptium_os("macos", false).unwrap(), "mac");
+        assert_eq!(adoptium_arch("x86_64").unwrap(), "x64");
+        assert!(adoptium_arch("riscv64").is_err());
+    }
+
+    #[test]
+    fn java_home_is_inside_macos_bundles() {
+        assert_eq!(
+            java_home(Path::new("/opt/java"), "linux"),
+            Path::new("/opt/java")
+        );
+        assert_eq!(
+            java_home(Path::new("/opt/java"), "mac"),
+            PathBuf::from("/opt/java/Contents/Home")
+        );
+    }
+}
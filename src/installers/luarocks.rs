@@ -0,0 +1,119 @@
+use crate::installers::package_manager;
+use crate::utils;
+use anyhow::Result;
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+
+pub struct LuarocksConfig<'a> {
+    pub packages: &'a [String],
+    /// Lua version rocks are installed for; Neovim embeds LuaJIT, which is 5.1
+    pub lua_version: &'a str,
+}
+
+pub fn install(config: &LuarocksConfig) -> Result<()> {
+    utils::offline::ensure_online("luarocks")?;
+    let luarocks = ensure_luarocks_available(config.lua_version)?;
+    install_packages(&luarocks, config)?;
+    cleanup(&luarocks)?;
+    Ok(())
+}
+
+/// Find luarocks, installing it with the system package manager if missing
+fn ensure_luarocks_available(lua_version: &str) -> Result<String> {
+    // Alpine ships one luarocks per Lua version, e.g. luarocks-5.1
+    let versioned = format!("luarocks-{}", lua_version);
+    for program in ["luarocks", versioned.as_str()] {
+        if which::which(program).is_ok() {
+            debug!("{} is already available", program);
+            return Ok(program.to_string());
+        }
+    }
+
+    utils::sudo::ensure_privileges("Installing luarocks")?;
+    info!("luarocks not found, installing it");
+    if utils::os::is_debian_like() {
+        let mut cmd = package_manager::apt_command("apt-get");
+        cmd.args(["update"]);
+        utils::exec::run_command(&mut cmd, "Update package lists")?;
+
+        let mut cmd = package_manager::apt_install_command("apt-get");
+        cmd.args(debian_packages(lua_version));
+        utils::exec::run_command(&mut cmd, "Install luarocks")?;
+        Ok("luarocks".to_string())
+    } else if utils::os::is_alpine() {
+        let mut cmd = utils::sudo::command("apk");
+        cmd.arg("add");
+        cmd.args([
+            format!("luarocks{}", lua_version),
+            format!("lua{}", lua_version),
+            format!("lua{}-dev", lua_version),
+        ]);
+        utils::exec::run_command(&mut cmd, "Install luarocks")?;
+        Ok(versioned)
+    } else {
+        anyhow::bail!("Unsupported OS for automatic luarocks installation")
+    }
+}
+
+/// luarocks plus the interpreter and headers needed to build rocks for `lua_version`
+fn debian_packages(lua_version: &str) -> [String; 3] {
+    // Lua 5.1 headers kept their SONAME suffix in the Debian package name
+    let dev = match lua_version {
+        "5.1" => "liblua5.1-0-dev".to_string(),
+        v => format!("liblua{}-dev", v),
+    };
+    ["luarocks".to_string(), format!("lua{}", lua_version), dev]
+}
+
+fn install_packages(luarocks: &str, config: &LuarocksConfig) -> Result<()> {
+    debug!("Installing rocks: {:?}", config.packages);
+
+    for package in config.packages {
+        // `luarocks install` takes the version as a separate argument
+        let (name, version) = match package.split_once('@') {
+            Some((name, version)) if !version.is_empty() => (name, Some(version)),
+            _ => (package.as_str(), None),
+        };
+
+        let mut cmd = utils::sudo::command(luarocks);
+        cmd.args(["--lua-version", config.lua_version, "install", name]);
+        if let Some(version) = version {
+            cmd.arg(version);
+        }
+        utils::exec::run_streaming(&mut cmd, &format!("Install rock {}", package))?;
+    }
+
+    info!("Successfully installed rocks: {:?}", config.packages);
+    Ok(())
+}
+
+/// Remove the luarocks download cache
+fn cleanup(luarocks: &str) -> Result<()> {
+    let output = Command::new(luarocks)
+        .args(["config", "local_cache"])
+        .output()?;
+    let cache = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || cache.is_empty() || !Path::new(&cache).is_dir() {
+        debug!("No luarocks cache to clean");
+        return Ok(());
+    }
+
+    info!("Cleaning luarocks cache: {}", cache);
+    std::fs::remove_dir_all(&cache)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debian_packages_match_lua_version() {
+        assert_eq!(
+            debian_packages("5.1"),
+            ["luarocks", "lua5.1", "liblua5.1-0-dev"]
+        );
+        assert_eq!(debian_packages("5.4")[2], "liblua5.4-dev");
+    }
+}
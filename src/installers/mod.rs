@@ -1,6 +1,176 @@
+pub mod cache;
+pub mod cargo;
+pub mod clean;
+pub mod composer;
+#[cfg(feature = "devcontainer")]
 pub mod devcontainer_feature;
+pub mod dotnet_tool;
+pub mod flatpak;
+pub mod gem;
+#[cfg(feature = "gh-release")]
 pub mod gh_release;
+pub mod go;
+#[cfg(feature = "gh-release")]
+pub mod helm_plugin;
+pub mod jdk;
+pub mod luarocks;
+pub mod nix;
 pub mod npm;
 pub mod package_manager;
 pub mod pipx;
+#[cfg(feature = "pkgx")]
 pub mod pkgx;
+pub mod runtime;
+#[cfg(feature = "gh-release")]
+pub mod self_update;
+pub mod snap;
+pub mod uninstall;
+pub mod venv;
+
+use crate::cli::RetryConfig;
+use anyhow::Result;
+
+/// Error for an installer left out of this build by its Cargo feature
+#[cfg(not(all(feature = "gh-release", feature = "pkgx", feature = "devcontainer")))]
+pub fn not_built(feature: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "This picolayer was built without the '{}' feature; rebuild with --features {}",
+        feature,
+        feature
+    )
+}
+
+/// A configured installer, for embedding picolayer's installers in other tools.
+///
+/// The futures are not `Send`; drive them on the caller's runtime, e.g. with `block_on`
+/// or a `LocalSet`.
+#[allow(async_fn_in_trait)]
+pub trait Installer {
+    /// Name of the picolayer command backed by this installer, e.g. `gh-release`
+    fn command(&self) -> &'static str;
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()>;
+}
+
+impl Installer for cargo::CargoConfig<'_> {
+    fn command(&self) -> &'static str {
+        "cargo"
+    }
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()> {
+        cargo::install(self, retry_config).await
+    }
+}
+
+impl Installer for composer::ComposerConfig<'_> {
+    fn command(&self) -> &'static str {
+        "composer"
+    }
+
+    async fn install(&self, _retry_config: &RetryConfig) -> Result<()> {
+        composer::install(self).await
+    }
+}
+
+#[cfg(feature = "devcontainer")]
+impl Installer for devcontainer_feature::DevcontainerFeatureConfig<'_> {
+    fn command(&self) -> &'static str {
+        "devcontainer-feature"
+    }
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()> {
+        devcontainer_feature::install_async(self, retry_config).await
+    }
+}
+
+#[cfg(feature = "gh-release")]
+impl Installer for gh_release::GhReleaseConfig<'_> {
+    fn command(&self) -> &'static str {
+        "gh-release"
+    }
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()> {
+        gh_release::install(self, retry_config).await
+    }
+}
+
+impl Installer for go::GoInstallConfig<'_> {
+    fn command(&self) -> &'static str {
+        "go-install"
+    }
+
+    async fn install(&self, _retry_config: &RetryConfig) -> Result<()> {
+        go::install(self).await
+    }
+}
+
+#[cfg(feature = "gh-release")]
+impl Installer for helm_plugin::HelmPluginConfig<'_> {
+    fn command(&self) -> &'static str {
+        "helm-plugin"
+    }
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()> {
+        helm_plugin::install(self, retry_config).await
+    }
+}
+
+impl Installer for jdk::JdkConfig<'_> {
+    fn command(&self) -> &'static str {
+        "jdk"
+    }
+
+    async fn install(&self, _retry_config: &RetryConfig) -> Result<()> {
+        jdk::install(self).await
+    }
+}
+
+impl Installer for luarocks::LuarocksConfig<'_> {
+    fn command(&self) -> &'static str {
+        "luarocks"
+    }
+
+    async fn install(&self, _retry_config: &RetryConfig) -> Result<()> {
+        luarocks::install(self)
+    }
+}
+
+impl Installer for npm::NpmConfig<'_> {
+    fn command(&self) -> &'static str {
+        "npm"
+    }
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()> {
+        npm::install(self, retry_config).await
+    }
+}
+
+impl Installer for pipx::PipxConfig<'_> {
+    fn command(&self) -> &'static str {
+        "pipx"
+    }
+
+    async fn install(&self, _retry_config: &RetryConfig) -> Result<()> {
+        pipx::install(self)
+    }
+}
+
+impl Installer for runtime::RuntimeConfig<'_> {
+    fn command(&self) -> &'static str {
+        "runtime"
+    }
+
+    async fn install(&self, retry_config: &RetryConfig) -> Result<()> {
+        runtime::install(self, retry_config).await
+    }
+}
+
+impl Installer for venv::VenvConfig<'_> {
+    fn command(&self) -> &'static str {
+        "venv"
+    }
+
+    async fn install(&self, _retry_config: &RetryConfig) -> Result<()> {
+        venv::install(self)
+    }
+}
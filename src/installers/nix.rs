@@ -0,0 +1,219 @@
+use crate::cli::RetryConfig;
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const NIX_INSTALL_PATH: &str = "/usr/local/bin/nix";
+const NIX_STORE_DIR: &str = "/nix";
+/// Nix release installed when nix is not already available
+const NIX_VERSION: &str = "2.24.10";
+const NIX_RELEASES_URL: &str = "https://releases.nixos.org/nix";
+const NIX_FEATURES: &str = "nix-command flakes";
+
+pub async fn install(packages: &[String], flake: &str, retry_config: &RetryConfig) -> Result<()> {
+    let nix = ensure_nix_available(retry_config).await?;
+    ensure_store_dir()?;
+    install_packages(&nix, packages, flake)?;
+    Ok(())
+}
+
+/// Use an existing nix if one is on PATH, otherwise install the pinned release, verified
+/// against the SHA-256 published next to it
+async fn ensure_nix_available(retry_config: &RetryConfig) -> Result<String> {
+    if let Ok(path) = which::which("nix") {
+        debug!("nix is already available at {}", path.display());
+        return Ok(path.to_string_lossy().to_string());
+    }
+
+    anyhow::ensure!(
+        cfg!(target_os = "linux"),
+        "nix not found. Automatic installation is only supported on Linux."
+    );
+
+    info!(
+        "nix not found, installing nix {} to {}",
+        NIX_VERSION, NIX_INSTALL_PATH
+    );
+    utils::offline::ensure_online("Installing nix")?;
+    utils::policy::enforce(
+        &utils::policy::Source {
+            command: "nix",
+            owner: "NixOS",
+            repo: "nix",
+        },
+        utils::policy::Verification::Checksum,
+    )?;
+    utils::sudo::ensure_privileges("Installing nix")?;
+    let url = nix_release_url(std::env::consts::ARCH)?;
+    let checksum_url = format!("{}.sha256", url);
+    let batch = utils::scheduler::Batch::new(retry_config);
+    let (download, checksum) = tokio::try_join!(
+        batch.fetch("nix", || utils::download::fetch_to_file(&url, None)),
+        batch.fetch("nix checksum", || utils::download::fetch(&checksum_url)),
+    )?;
+    drop(batch);
+    let checksum =
+        String::from_utf8(checksum).context("Nix release checksum is not valid UTF-8")?;
+    utils::download::verify_download(&url, &download.digests, checksum.trim())?;
+    utils::audit::fetched("nix", NIX_VERSION, &url, &download.digests);
+
+    // The release is a nix store closure: copy its paths into /nix/store, register them
+    // in the store database, and link the nix binary from there
+    utils::tmp::ensure_space(download.digests.size * 4, "the nix release")?;
+    let temp_dir = utils::tmp::tempdir()?;
+    let mut archive = tar::Archive::new(xz::read::XzDecoder::new(BufReader::new(download.open()?)));
+    utils::archive::unpack_stripped(&mut archive, temp_dir.path())?;
+
+    ensure_store_dir()?;
+    let nix = copy_store_paths(&temp_dir.path().join("store"))?;
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "exec \"$0\" --load-db < \"$1\""])
+        .arg(nix.join("bin/nix-store"))
+        .arg(temp_dir.path().join(".reginfo"));
+    utils::exec::run_command(&mut cmd, "Register nix store paths")?;
+
+    let mut cmd = utils::sudo::command("ln");
+    cmd.arg("-sf")
+        .arg(nix.join("bin/nix"))
+        .arg(NIX_INSTALL_PATH);
+    utils::exec::run_command(&mut cmd, "Link nix binary")?;
+
+    Ok(NIX_INSTALL_PATH.to_string())
+}
+
+fn nix_release_url(arch: &str) -> Result<String> {
+    anyhow::ensure!(
+        matches!(arch, "x86_64" | "aarch64"),
+        "No nix release available for architecture {}",
+        arch
+    );
+    Ok(format!(
+        "{}/nix-{}/nix-{}-{}-linux.tar.xz",
+        NIX_RELEASES_URL, NIX_VERSION, NIX_VERSION, arch
+    ))
+}
+
+/// Copy the store paths of an unpacked release into the nix store, keeping any already
+/// there, and return the store path of nix itself
+fn copy_store_paths(store: &Path) -> Result<PathBuf> {
+    let store_dir = Path::new(NIX_STORE_DIR).join("store");
+    std::fs::create_dir_all(&store_dir)
+        .with_context(|| format!("Failed to create {}", store_dir.display()))?;
+
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(store)
+        .with_context(|| format!("Nix release has no {}", store.display()))?
+    {
+        let name = entry?.file_name();
+        let target = store_dir.join(&name);
+        if !target.exists() {
+            let mut cmd = Command::new("cp");
+            cmd.arg("-RPp").arg(store.join(&name)).arg(&target);
+            utils::exec::run_command(&mut cmd, "Copy nix store path")?;
+        }
+        names.push(name.to_string_lossy().to_string());
+    }
+
+    let nix = nix_store_path(&names).context("Nix release does not contain nix itself")?;
+    Ok(store_dir.join(nix))
+}
+
+/// The store path named `<hash>-nix-<version>`, as opposed to its `-man` or `-doc` outputs
+fn nix_store_path(names: &[String]) -> Option<&str> {
+    let suffix = format!("-nix-{}", NIX_VERSION);
+    names
+        .iter()
+        .map(String::as_str)
+        .find(|name| name.ends_with(&suffix))
+}
+
+/// The nix store must exist and be writable by the user running nix
+fn ensure_store_dir() -> Result<()> {
+    if Path::new(NIX_STORE_DIR).exists() {
+        return Ok(());
+    }
+
+    utils::sudo::ensure_privileges("Creating the nix store")?;
+    let output = Command::new("id").arg("-un").output()?;
+    let user = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    anyhow::ensure!(!user.is_empty(), "Failed to determine current user");
+
+    info!("Creating nix store directory {}", NIX_STORE_DIR);
+    let mut cmd = utils::sudo::command("install");
+    cmd.args(["-d", "-m", "0755", "-o", &user, NIX_STORE_DIR]);
+    utils::exec::run_command(&mut cmd, "Create nix store directory")?;
+    Ok(())
+}
+
+fn install_packages(nix: &str, packages: &[String], flake: &str) -> Result<()> {
+    debug!("Installing nix packages: {:?}", packages);
+
+    let mut cmd = Command::new(nix);
+    cmd.args(["--extra-experimental-features", NIX_FEATURES]);
+    cmd.args(["profile", "install"]);
+    if utils::offline::is_enabled() {
+        // Resolve from the local store and flake cache only
+        cmd.arg("--offline");
+    }
+    cmd.args(flake_refs(packages, flake));
+    utils::exec::run_command(&mut cmd, "Install nix packages")?;
+
+    info!("Successfully installed nix packages: {:?}", packages);
+    Ok(())
+}
+
+/// Qualify bare package names with the flake; explicit references are kept as-is
+fn flake_refs(packages: &[String], flake: &str) -> Vec<String> {
+    packages
+        .iter()
+        .map(|p| {
+            if p.contains('#') {
+                p.clone()
+            } else {
+                format!("{}#{}", flake, p)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flake_refs_qualifies_bare_names() {
+        let packages = vec!["jq".to_string(), "github:owner/repo#tool".to_string()];
+        assert_eq!(
+            flake_refs(&packages, "nixpkgs"),
+            vec!["nixpkgs#jq", "github:owner/repo#tool"]
+        );
+    }
+
+    #[test]
+    fn nix_release_url_pins_the_version() {
+        assert_eq!(
+            nix_release_url("x86_64").unwrap(),
+            format!(
+                "https://releases.nixos.org/nix/nix-{v}/nix-{v}-x86_64-linux.tar.xz",
+                v = NIX_VERSION
+            )
+        );
+        assert!(nix_release_url("riscv64").is_err());
+    }
+
+    #[test]
+    fn nix_store_path_skips_other_outputs() {
+        let names = [
+            format!("abc-nix-{}-man", NIX_VERSION),
+            "def-openssl-3.0.14".to_string(),
+            format!("ghi-nix-{}", NIX_VERSION),
+        ];
+        assert_eq!(
+            nix_store_path(&names),
+            Some(format!("ghi-nix-{}", NIX_VERSION).as_str())
+        );
+    }
+}
@@ -1,11 +1,72 @@
+use crate::cli::{NodePackageManager, RetryConfig, RuntimePreset};
+use crate::installers::{package_manager, runtime};
 use crate::utils;
-use anyhow::Result;
-use log::{debug, info};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-pub fn install(packages: &[String]) -> Result<()> {
-    ensure_npm_available()?;
-    install_packages(packages)?;
+const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
+
+/// Default PNPM_HOME for global pnpm installs when the environment does not set one
+const DEFAULT_PNPM_HOME: &str = "/usr/local/share/pnpm";
+
+pub struct NpmConfig<'a> {
+    pub packages: &'a [String],
+    pub manager: NodePackageManager,
+    /// Registry to install from instead of the one configured in .npmrc
+    pub registry: Option<&'a str>,
+    /// Auth token for the registry, written to a temporary .npmrc
+    pub auth_token: Option<&'a str>,
+    /// Dedicated install prefix; executables land in `<prefix>/bin`
+    pub prefix: Option<&'a str>,
+    /// Node.js version to install from nodejs.org instead of the distro package
+    pub node_version: Option<&'a str>,
+}
+
+/// Where a Node.js bootstrapped from nodejs.org links node, npm and npx
+const NODE_BIN_DIR: &str = "/usr/local/bin";
+
+/// Prefixes of npm specs that point at a repository, tarball, or directory instead of the
+/// registry
+const URL_PREFIXES: &[&str] = &[
+    "git+https://",
+    "git+ssh://",
+    "git+http://",
+    "git+file://",
+    "git://",
+    "github:",
+    "gitlab:",
+    "bitbucket:",
+    "gist:",
+    "https://",
+    "http://",
+    "file:",
+];
+
+pub async fn install(config: &NpmConfig<'_>, retry_config: &RetryConfig) -> Result<()> {
+    for package in config.packages {
+        validate_package_spec(package)?;
+    }
+    // bun has no way to install only from its cache
+    if config.manager == NodePackageManager::Bun {
+        utils::offline::ensure_online("bun")?;
+    }
+    match config.node_version {
+        Some(version) => ensure_node_version(version, retry_config).await?,
+        None => ensure_npm_available()?,
+    }
+    ensure_manager_available(config.manager)?;
+    let prefix = install_packages(config)?;
+    if let Some(prefix) = config.prefix {
+        utils::env_registry::add_path(&Path::new(prefix).join("bin"));
+    } else if let Some(prefix) = &prefix
+        && utils::user_mode::is_enabled()
+        && !utils::profile::is_on_path(&prefix.join("bin"))
+    {
+        utils::env_registry::add_path(&prefix.join("bin"));
+    }
+    verify_installed(config, prefix.as_deref())?;
     Ok(())
 }
 
@@ -15,11 +76,66 @@ fn ensure_npm_available() -> Result<()> {
         return Ok(());
     }
 
+    utils::offline::ensure_online("Installing Node.js")?;
+    utils::sudo::ensure_privileges("Installing Node.js")?;
     info!("npm not found, installing Node.js");
     install_nodejs()?;
     Ok(())
 }
 
+/// Install the requested Node.js release unless a matching one is already on PATH
+async fn ensure_node_version(version: &str, retry_config: &RetryConfig) -> Result<()> {
+    let installed = Command::new("node")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    if let Some(installed) = installed
+        && node_version_matches(&installed, version)
+    {
+        debug!("Node.js {} already satisfies {}", installed, version);
+        return Ok(());
+    }
+
+    utils::offline::ensure_online("Installing Node.js")?;
+    utils::sudo::ensure_privileges("Installing Node.js")?;
+    info!("Installing Node.js {} from nodejs.org", version);
+    runtime::install(
+        &runtime::RuntimeConfig {
+            runtime: RuntimePreset::Node,
+            version,
+            install_dir: NODE_BIN_DIR,
+        },
+        retry_config,
+    )
+    .await
+}
+
+/// Whether an installed `node --version` satisfies a requested version or prefix
+fn node_version_matches(installed: &str, requested: &str) -> bool {
+    let installed = installed.trim_start_matches('v');
+    match requested.trim_start_matches('v') {
+        // Any installed release satisfies an alias; reinstalling would only churn the layer
+        "latest" | "lts" => true,
+        prefix => installed == prefix || installed.starts_with(&format!("{}.", prefix)),
+    }
+}
+
+/// Install pnpm, yarn or bun through npm when the selected manager is missing
+fn ensure_manager_available(manager: NodePackageManager) -> Result<()> {
+    if manager == NodePackageManager::Npm || which::which(manager.program()).is_ok() {
+        return Ok(());
+    }
+
+    utils::offline::ensure_online(&format!("Installing {}", manager.program()))?;
+    info!("{} not found, installing it with npm", manager.program());
+    let mut cmd = Command::new("npm");
+    cmd.args(["install", "-g", manager.program()]);
+    utils::exec::run_streaming(&mut cmd, &format!("Install {}", manager.program()))?;
+    Ok(())
+}
+
 fn install_nodejs() -> Result<()> {
     if utils::os::is_debian_like() {
         install_nodejs_debian()
@@ -32,13 +148,13 @@ fn install_nodejs() -> Result<()> {
 
 fn install_nodejs_debian() -> Result<()> {
     debug!("Installing Node.js on Debian-like system");
-    let mut cmd = utils::sudo::command("apt-get");
+    let mut cmd = package_manager::apt_command("apt-get");
     cmd.args(["update"]);
-    utils::subprocess::run_command(&mut cmd, "Update package lists")?;
+    utils::exec::run_command(&mut cmd, "Update package lists")?;
 
-    let mut cmd = utils::sudo::command("apt-get");
-    cmd.args(["install", "-y", "nodejs", "npm"]);
-    utils::subprocess::run_command(&mut cmd, "Install Node.js and npm")?;
+    let mut cmd = package_manager::apt_install_command("apt-get");
+    cmd.args(["nodejs", "npm"]);
+    utils::exec::run_command(&mut cmd, "Install Node.js and npm")?;
 
     Ok(())
 }
@@ -47,19 +163,379 @@ fn install_nodejs_alpine() -> Result<()> {
     debug!("Installing Node.js on Alpine Linux");
     let mut cmd = utils::sudo::command("apk");
     cmd.args(["add", "nodejs", "npm"]);
-    utils::subprocess::run_command(&mut cmd, "Install Node.js and npm")?;
+    utils::exec::run_command(&mut cmd, "Install Node.js and npm")?;
 
     Ok(())
 }
 
-fn install_packages(packages: &[String]) -> Result<()> {
-    debug!("Installing npm packages: {:?}", packages);
+/// Install the packages, returning the prefix they were installed under, if any
+fn install_packages(config: &NpmConfig) -> Result<Option<PathBuf>> {
+    let packages = config.packages;
+    debug!(
+        "Installing packages with {}: {:?}",
+        config.manager.program(),
+        packages
+    );
+
+    let mut cmd = global_install_command(config.manager);
+    let prefix = match config.prefix {
+        Some(prefix) => Some(PathBuf::from(prefix)),
+        None if config.manager == NodePackageManager::Npm && utils::user_mode::is_enabled() => {
+            Some(utils::user_mode::prefix()?)
+        }
+        None if config.manager == NodePackageManager::Npm && !utils::sudo::can_escalate() => {
+            let prefix = utils::user_mode::prefix()?;
+            warn!(
+                "Not running as root and sudo is unavailable, installing npm packages under {}",
+                prefix.display()
+            );
+            Some(prefix)
+        }
+        None => None,
+    };
+    match &prefix {
+        Some(prefix) => apply_prefix(&mut cmd, config.manager, prefix),
+        None if config.manager == NodePackageManager::Pnpm
+            && std::env::var_os("PNPM_HOME").is_none() =>
+        {
+            info!(
+                "PNPM_HOME is not set, installing pnpm globals under {}",
+                DEFAULT_PNPM_HOME
+            );
+            set_pnpm_home(&mut cmd, Path::new(DEFAULT_PNPM_HOME));
+        }
+        None => {}
+    }
+
+    // Keep the token off the command line by layering it over the user's .npmrc
+    let _npmrc = match config.auth_token {
+        Some(token) => {
+            let npmrc = write_auth_npmrc(config.registry, token)?;
+            cmd.env("NPM_CONFIG_USERCONFIG", npmrc.path());
+            Some(npmrc)
+        }
+        None => None,
+    };
+    if let Some(registry) = config.registry {
+        validate_registry_url(registry)?;
+        cmd.args(["--registry", registry]);
+    }
+    if utils::offline::is_enabled() {
+        cmd.arg("--offline");
+    }
 
-    let mut cmd = Command::new("npm");
-    cmd.args(["install", "-g"]);
     cmd.args(packages);
-    utils::subprocess::run_command(&mut cmd, "Install npm packages")?;
+    utils::exec::run_streaming(
+        &mut cmd,
+        &format!(
+            "{} install of {}",
+            config.manager.program(),
+            packages.join(", ")
+        ),
+    )?;
 
     info!("Successfully installed npm packages: {:?}", packages);
+    Ok(prefix)
+}
+
+/// Run `--version` on the executables each package declares in its package.json
+fn verify_installed(config: &NpmConfig, prefix: Option<&Path>) -> Result<()> {
+    if config.manager != NodePackageManager::Npm {
+        debug!(
+            "Skipping post-install verification for {}",
+            config.manager.program()
+        );
+        return Ok(());
+    }
+
+    let root = match prefix {
+        Some(prefix) => prefix.join("lib").join("node_modules"),
+        None => {
+            let output = utils::exec::run_command(
+                Command::new("npm").args(["root", "-g"]),
+                "Locate global node_modules",
+            )?;
+            PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+        }
+    };
+
+    let mut executables = Vec::new();
+    for package in config.packages {
+        let name = package_name(package);
+        let manifest = root.join(name).join("package.json");
+        match std::fs::read_to_string(&manifest) {
+            Ok(contents) => {
+                let manifest: serde_json::Value = serde_json::from_str(&contents)
+                    .with_context(|| format!("Failed to parse {}", manifest.display()))?;
+                executables.extend(bin_names(name, &manifest));
+            }
+            Err(_) => warn!(
+                "Could not read {}, skipping verification of {}",
+                manifest.display(),
+                name
+            ),
+        }
+    }
+
+    let bin_dir = prefix.map(|p| p.join("bin"));
+    utils::verify::verify_executables(&executables, bin_dir.as_deref())
+}
+
+/// Strip the version from a package spec: `@scope/name@^1` -> `@scope/name`
+pub(crate) fn package_name(spec: &str) -> &str {
+    match spec[1..].find('@') {
+        Some(i) => &spec[..i + 1],
+        None => spec,
+    }
+}
+
+/// Executables declared by a package.json `bin` field, either a path or a name -> path map
+fn bin_names(name: &str, manifest: &serde_json::Value) -> Vec<String> {
+    match &manifest["bin"] {
+        serde_json::Value::String(_) => {
+            vec![name.rsplit('/').next().unwrap_or(name).to_string()]
+        }
+        serde_json::Value::Object(bins) => bins.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Point the manager's global install location at `prefix`, with executables in `prefix/bin`
+fn apply_prefix(cmd: &mut Command, manager: NodePackageManager, prefix: &Path) {
+    match manager {
+        NodePackageManager::Npm | NodePackageManager::Yarn => {
+            cmd.arg("--prefix").arg(prefix);
+        }
+        NodePackageManager::Pnpm => set_pnpm_home(cmd, &prefix.join("bin")),
+        NodePackageManager::Bun => {
+            cmd.env("BUN_INSTALL", prefix);
+        }
+    }
+}
+
+/// pnpm refuses global installs unless PNPM_HOME is set and on PATH
+fn set_pnpm_home(cmd: &mut Command, pnpm_home: &Path) {
+    let path = std::env::var("PATH").unwrap_or_default();
+    cmd.env("PNPM_HOME", pnpm_home);
+    cmd.env("PATH", format!("{}:{}", pnpm_home.display(), path));
+}
+
+fn global_install_command(manager: NodePackageManager) -> Command {
+    let mut cmd = Command::new(manager.program());
+    match manager {
+        NodePackageManager::Npm => cmd.args(["install", "-g"]),
+        NodePackageManager::Pnpm | NodePackageManager::Bun => cmd.args(["add", "-g"]),
+        NodePackageManager::Yarn => cmd.args(["global", "add"]),
+    };
+    cmd
+}
+
+/// Write a temporary .npmrc that extends the user's own with an auth token for the registry
+fn write_auth_npmrc(registry: Option<&str>, token: &str) -> Result<tempfile::NamedTempFile> {
+    let existing = std::env::var("HOME")
+        .ok()
+        .and_then(|home| std::fs::read_to_string(PathBuf::from(home).join(".npmrc")).ok())
+        .unwrap_or_default();
+    let contents = auth_npmrc(&existing, registry.unwrap_or(DEFAULT_REGISTRY), token)?;
+
+    let mut file = tempfile::Builder::new()
+        .prefix("picolayer_npmrc_")
+        .tempfile_in(utils::tmp::dir())
+        .context("Failed to create temporary .npmrc")?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())
+        .context("Failed to write temporary .npmrc")?;
+    Ok(file)
+}
+
+fn auth_npmrc(existing: &str, registry: &str, token: &str) -> Result<String> {
+    validate_registry_url(registry)?;
+    anyhow::ensure!(
+        !token.trim().is_empty() && !token.contains(['\n', '\r']),
+        "Invalid npm auth token"
+    );
+
+    // npm keys credentials by the registry URL without its scheme, e.g. //npm.pkg.github.com/
+    let (_, host_path) = registry.split_once("://").unwrap_or(("", registry));
+    let key = format!("//{}", host_path.trim_end_matches('/'));
+
+    let mut contents = existing.to_string();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!("{}/:_authToken={}\n", key, token.trim()));
+    Ok(contents)
+}
+
+fn validate_registry_url(registry: &str) -> Result<()> {
+    anyhow::ensure!(
+        (registry.starts_with("http://") || registry.starts_with("https://"))
+            && !registry.contains(char::is_whitespace),
+        "Invalid npm registry URL '{}'. Expected http:// or https://",
+        registry
+    );
+    Ok(())
+}
+
+/// Validate a `name`, `@scope/name`, `name@version` or `name@range` package spec. URL,
+/// git, and path specs, including `user/repo` GitHub shorthand, are left to npm.
+fn validate_package_spec(spec: &str) -> Result<()> {
+    if URL_PREFIXES.iter().any(|p| spec.starts_with(p))
+        || spec.starts_with(['/', '.', '~'])
+        || (!spec.starts_with('@') && spec.contains('/'))
+    {
+        return Ok(());
+    }
+
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid npm package '{}'. Expected name, @scope/name, or name@version (e.g. typescript@^5)",
+            spec
+        )
+    };
+
+    let (scope, rest) = match spec.strip_prefix('@') {
+        Some(scoped) => {
+            let (scope, rest) = scoped.split_once('/').ok_or_else(invalid)?;
+            (Some(scope), rest)
+        }
+        None => (None, spec),
+    };
+    let (name, version) = match rest.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (rest, None),
+    };
+
+    // New names are lowercase, but npm still installs legacy ones such as JSONStream
+    let valid_name = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with(['.', '_', '-'])
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+    };
+    if !valid_name(name) || scope.is_some_and(|s| !valid_name(s)) {
+        return Err(invalid());
+    }
+    if let Some(version) = version
+        && (version.trim().is_empty() || version.chars().any(char::is_whitespace))
+    {
+        return Err(invalid());
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_install_command_matches_manager() {
+        let args = |m| {
+            global_install_command(m)
+                .get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(args(NodePackageManager::Npm), ["install", "-g"]);
+        assert_eq!(args(NodePackageManager::Pnpm), ["add", "-g"]);
+        assert_eq!(args(NodePackageManager::Yarn), ["global", "add"]);
+        assert_eq!(args(NodePackageManager::Bun), ["add", "-g"]);
+    }
+
+    #[test]
+    fn apply_prefix_uses_manager_specific_location() {
+        let prefix = Path::new("/opt/npm-tools");
+
+        let mut cmd = global_install_command(NodePackageManager::Npm);
+        apply_prefix(&mut cmd, NodePackageManager::Npm, prefix);
+        assert!(cmd.get_args().any(|a| a == "--prefix"));
+
+        let mut cmd = global_install_command(NodePackageManager::Pnpm);
+        apply_prefix(&mut cmd, NodePackageManager::Pnpm, prefix);
+        assert!(cmd.get_envs().any(
+            |(k, v)| k == "PNPM_HOME" && v == Some(std::ffi::OsStr::new("/opt/npm-tools/bin"))
+        ));
+    }
+
+    #[test]
+    fn node_version_matches_prefixes() {
+        assert!(node_version_matches("v20.18.0", "20"));
+        assert!(node_version_matches("v20.18.0", "v20.18"));
+        assert!(node_version_matches("v20.18.0", "20.18.0"));
+        assert!(node_version_matches("v18.20.4", "lts"));
+        assert!(!node_version_matches("v2.0.0", "20"));
+        assert!(!node_version_matches("v18.20.4", "20"));
+    }
+
+    #[test]
+    fn bin_names_reads_package_json() {
+        assert_eq!(package_name("@angular/cli@~17.1"), "@angular/cli");
+        assert_eq!(package_name("cowsay@1.6.0"), "cowsay");
+        assert_eq!(package_name("cowsay"), "cowsay");
+
+        let manifest = serde_json::json!({"bin": {"ng": "./bin/ng.js"}});
+        assert_eq!(bin_names("@angular/cli", &manifest), ["ng"]);
+        let manifest = serde_json::json!({"bin": "./cli.js"});
+        assert_eq!(bin_names("@biomejs/biome", &manifest), ["biome"]);
+        assert!(bin_names("left-pad", &serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn auth_npmrc_appends_token_for_registry() {
+        let contents =
+            auth_npmrc("fund=false", "https://npm.pkg.github.com/", "ghp_secret").unwrap();
+        assert_eq!(
+            contents,
+            "fund=false\n//npm.pkg.github.com/:_authToken=ghp_secret\n"
+        );
+
+        let contents = auth_npmrc("", "https://artifactory.example.com/api/npm/npm", "t").unwrap();
+        assert_eq!(
+            contents,
+            "//artifactory.example.com/api/npm/npm/:_authToken=t\n"
+        );
+    }
+
+    #[test]
+    fn auth_npmrc_rejects_invalid_input() {
+        assert!(auth_npmrc("", "registry.example.com", "t").is_err());
+        assert!(auth_npmrc("", DEFAULT_REGISTRY, "a\nregistry=http://evil").is_err());
+    }
+
+    #[test]
+    fn validate_package_spec_accepts_versions_and_ranges() {
+        for spec in [
+            "cowsay",
+            "cowsay@1.6.0",
+            "typescript@^5",
+            "@angular/cli@~17.1",
+            "@biomejs/biome",
+            "npm@latest",
+            "JSONStream",
+        ] {
+            assert!(validate_package_spec(spec).is_ok(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn validate_package_spec_passes_through_urls_and_paths() {
+        for spec in [
+            "git+https://github.com/piuccio/cowsay.git",
+            "git+ssh://git@github.com/piuccio/cowsay.git#v1.6.0",
+            "github:piuccio/cowsay",
+            "piuccio/cowsay",
+            "https://registry.npmjs.org/cowsay/-/cowsay-1.6.0.tgz",
+            "file:../cowsay",
+            "./cowsay-1.6.0.tgz",
+            "/opt/packages/cowsay",
+        ] {
+            assert!(validate_package_spec(spec).is_ok(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn validate_package_spec_rejects_malformed_specs() {
+        for spec in ["", "--global", "cowsay@", "@scope", "@/cli", "a b"] {
+            assert!(validate_package_spec(spec).is_err(), "{}", spec);
+        }
+    }
+}
@@ -1,9 +1,26 @@
 use crate::utils;
-use anyhow::Result;
-use log::info;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::path::Path;
+use std::process::Command;
 
-pub(super) fn install(packages: &[String]) -> Result<()> {
-    if std::process::Command::new("which")
+const APK_REPOSITORIES_FILE: &str = "/etc/apk/repositories";
+
+/// A repository passed with --repository
+#[derive(Debug, PartialEq)]
+enum ApkRepository<'a> {
+    /// Used only for this invocation (`apk --repository <url>`)
+    AdHoc(&'a str),
+    /// Persisted as `@tag url` so `package@tag` pins resolve against it
+    Tagged { tag: &'a str, url: &'a str },
+}
+
+use super::proxy;
+use super::report::{self, InstallReport};
+use super::{PackageManagerConfig, print_simulation, run_command};
+
+pub(super) fn install(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    if Command::new("which")
         .arg("apk")
         .output()
         .map(|o| !o.status.success())
@@ -12,33 +29,304 @@ pub(super) fn install(packages: &[String]) -> Result<()> {
         anyhow::bail!("apk command not found in PATH");
     }
 
-    update_repositories()?;
-    install_packages(packages)?;
-    cleanup()?;
+    let before = if config.report && !config.dry_run {
+        Some(report::apk_snapshot()?)
+    } else {
+        None
+    };
+
+    add_tagged_repositories(config)?;
+    check_pinned_packages(config)?;
+    update_repositories(config)?;
+    install_packages(config)?;
+
+    let report = match before {
+        Some(before) => Some(report::build(
+            "apk",
+            config.packages,
+            &before,
+            &report::apk_snapshot()?,
+        )),
+        None => None,
+    };
+
+    if !config.dry_run {
+        cleanup(config)?;
+    }
+
+    Ok(report)
+}
+
+pub(super) fn upgrade(
+    config: &PackageManagerConfig,
+    available: bool,
+) -> Result<Option<InstallReport>> {
+    let before = if config.report && !config.dry_run {
+        Some(report::apk_snapshot()?)
+    } else {
+        None
+    };
+
+    update_repositories(config)?;
+
+    let mut cmd = apk_command(config)?;
+    cmd.args(cache_args(
+        "upgrade",
+        utils::offline::is_enabled(),
+        config.keep_cache,
+    ));
+    add_repository_args(&mut cmd, config)?;
+    if available {
+        cmd.arg("--available");
+    }
+
+    if config.dry_run {
+        info!("Simulating apk upgrade");
+        cmd.arg("--simulate");
+        let output = run_command(&mut cmd, "Simulate apk upgrade", config)?;
+        print_simulation(&output, config)?;
+        return Ok(None);
+    }
+
+    info!("Upgrading apk packages");
+    run_command(&mut cmd, "Upgrade apk packages", config)?;
+
+    let report = match before {
+        Some(before) => Some(report::build("apk", &[], &before, &report::apk_snapshot()?)),
+        None => None,
+    };
+    cleanup(config)?;
+
+    Ok(report)
+}
+
+/// Create an apk command, routing downloads through the configured proxy if any
+fn apk_command(config: &PackageManagerConfig) -> Result<Command> {
+    match config.proxy {
+        Some(proxy) => Ok(utils::sudo::command_with_env(
+            "apk",
+            &proxy::proxy_env(proxy)?,
+        )),
+        None => Ok(utils::sudo::command("apk")),
+    }
+}
+
+fn parse_repository(value: &str) -> Result<ApkRepository<'_>> {
+    match value.strip_prefix('@') {
+        Some(tagged) => {
+            let (tag, url) = tagged.split_once('=').with_context(|| {
+                format!(
+                    "Invalid tagged repository '{}'. Expected @tag=https://...",
+                    value
+                )
+            })?;
+            anyhow::ensure!(
+                !tag.is_empty() && !url.is_empty(),
+                "Invalid tagged repository '{}'. Expected @tag=https://...",
+                value
+            );
+            Ok(ApkRepository::Tagged { tag, url })
+        }
+        None => Ok(ApkRepository::AdHoc(value)),
+    }
+}
+
+/// Append tagged repositories to /etc/apk/repositories if they are not already configured
+fn add_tagged_repositories(config: &PackageManagerConfig) -> Result<()> {
+    let tagged: Vec<(&str, &str)> = config
+        .repositories
+        .iter()
+        .map(|r| parse_repository(r))
+        .filter_map(|r| match r {
+            Ok(ApkRepository::Tagged { tag, url }) => Some(Ok((tag, url))),
+            Ok(ApkRepository::AdHoc(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<_>>()?;
+
+    if tagged.is_empty() {
+        return Ok(());
+    }
+
+    let path = Path::new(APK_REPOSITORIES_FILE);
+    let mut content = std::fs::read_to_string(path).unwrap_or_default();
+    let mut changed = false;
+
+    for (tag, url) in tagged {
+        let line = format!("@{} {}", tag, url);
+        if content.lines().any(|l| l.trim() == line) {
+            continue;
+        }
+        if config.dry_run {
+            warn!("Dry run: not adding tagged repository {}", line);
+            continue;
+        }
+        info!("Adding tagged apk repository: {}", line);
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&line);
+        content.push('\n');
+        changed = true;
+    }
 
+    if changed {
+        utils::sudo::write_file(path, &content)?;
+    }
+    Ok(())
+}
+
+/// Ensure every `package@tag` refers to a tag that is configured
+fn check_pinned_packages(config: &PackageManagerConfig) -> Result<()> {
+    let configured = std::fs::read_to_string(APK_REPOSITORIES_FILE).unwrap_or_default();
+
+    for package in config.packages {
+        let Some((_, tag)) = package.split_once('@') else {
+            continue;
+        };
+
+        let from_args = config.repositories.iter().any(
+            |r| matches!(parse_repository(r), Ok(ApkRepository::Tagged { tag: t, .. }) if t == tag),
+        );
+        let from_file = configured
+            .lines()
+            .any(|l| l.trim_start().starts_with(&format!("@{} ", tag)));
+
+        anyhow::ensure!(
+            from_args || from_file,
+            "Package '{}' is pinned to @{} but no repository is tagged @{}. \
+             Add one with --repository @{}=<url>",
+            package,
+            tag,
+            tag,
+            tag
+        );
+    }
     Ok(())
 }
 
-fn update_repositories() -> Result<()> {
+/// Add ad-hoc --repository arguments for this invocation
+fn add_repository_args(cmd: &mut Command, config: &PackageManagerConfig) -> Result<()> {
+    for repository in config.repositories {
+        if let ApkRepository::AdHoc(url) = parse_repository(repository)? {
+            cmd.args(["--repository", url]);
+        }
+    }
+    Ok(())
+}
+
+fn update_repositories(config: &PackageManagerConfig) -> Result<()> {
+    if utils::offline::is_enabled() {
+        info!("Offline: using the existing apk indexes");
+        return Ok(());
+    }
     info!("Updating apk repositories");
-    let mut cmd = utils::sudo::command("apk");
+    let mut cmd = apk_command(config)?;
     cmd.args(["update"]);
-    utils::subprocess::run_command(&mut cmd, "Update apk repositories")?;
+    add_repository_args(&mut cmd, config)?;
+    run_command(&mut cmd, "Update apk repositories", config)?;
     Ok(())
 }
 
-fn install_packages(packages: &[String]) -> Result<()> {
-    info!("Installing apk packages: {:?}", packages);
-    let mut cmd = utils::sudo::command("apk");
-    cmd.args(["add", "--no-cache"]).args(packages);
-    utils::subprocess::run_command(&mut cmd, "Install apk packages")?;
+fn install_packages(config: &PackageManagerConfig) -> Result<()> {
+    let mut cmd = apk_command(config)?;
+    cmd.args(cache_args(
+        "add",
+        utils::offline::is_enabled(),
+        config.keep_cache,
+    ));
+    add_repository_args(&mut cmd, config)?;
+
+    if config.dry_run {
+        info!("Simulating apk install: {:?}", config.packages);
+        cmd.arg("--simulate").args(config.packages);
+        let output = run_command(&mut cmd, "Simulate apk install", config)?;
+        print_simulation(&output, config)?;
+        return Ok(());
+    }
+
+    info!("Installing apk packages: {:?}", config.packages);
+    cmd.args(config.packages);
+    run_command(&mut cmd, "Install apk packages", config)?;
     Ok(())
 }
 
-fn cleanup() -> Result<()> {
+/// `subcommand` and how it uses the package cache in /etc/apk/cache: offline it installs
+/// from the cache instead of bypassing it, with --keep-cache it fills the cache, and
+/// otherwise it leaves the cache alone with `--no-cache`
+fn cache_args(subcommand: &'static str, offline: bool, keep_cache: bool) -> Vec<&'static str> {
+    if offline {
+        vec![subcommand, "--no-network"]
+    } else if keep_cache {
+        vec![subcommand]
+    } else {
+        vec![subcommand, "--no-cache"]
+    }
+}
+
+fn cleanup(config: &PackageManagerConfig) -> Result<()> {
+    if config.keep_cache {
+        info!("Keeping package cache (--keep-cache)");
+        return Ok(());
+    }
     info!("Cleaning up apk cache");
-    let mut cmd = utils::sudo::command("apk");
+    let mut cmd = apk_command(config)?;
     cmd.args(["cache", "clean"]);
-    utils::subprocess::run_command(&mut cmd, "Clean apk cache")?;
+    run_command(&mut cmd, "Clean apk cache", config)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_repository_distinguishes_tagged_and_adhoc() {
+        assert_eq!(
+            parse_repository("https://dl-cdn.alpinelinux.org/alpine/edge/community").unwrap(),
+            ApkRepository::AdHoc("https://dl-cdn.alpinelinux.org/alpine/edge/community")
+        );
+        assert_eq!(
+            parse_repository("@edge=https://dl-cdn.alpinelinux.org/alpine/edge/main").unwrap(),
+            ApkRepository::Tagged {
+                tag: "edge",
+                url: "https://dl-cdn.alpinelinux.org/alpine/edge/main"
+            }
+        );
+    }
+
+    #[test]
+    fn parse_repository_rejects_malformed_tags() {
+        assert!(parse_repository("@edge").is_err());
+        assert!(parse_repository("@=https://example.com").is_err());
+    }
+
+    #[test]
+    fn keep_cache_lets_downloads_reach_the_cache() {
+        assert_eq!(cache_args("add", false, false), ["add", "--no-cache"]);
+        assert_eq!(cache_args("add", false, true), ["add"]);
+        assert_eq!(
+            cache_args("upgrade", true, true),
+            ["upgrade", "--no-network"]
+        );
+    }
+
+    #[test]
+    fn check_pinned_packages_requires_tagged_repository() {
+        let packages = vec!["neovim@testing".to_string()];
+        let config = PackageManagerConfig {
+            packages: &packages,
+            ..Default::default()
+        };
+        let err = check_pinned_packages(&config).unwrap_err();
+        assert!(err.to_string().contains("--repository @testing=<url>"));
+
+        let repositories = vec!["@testing=https://example.com/alpine/edge/testing".to_string()];
+        let config = PackageManagerConfig {
+            packages: &packages,
+            repositories: &repositories,
+            ..Default::default()
+        };
+        assert!(check_pinned_packages(&config).is_ok());
+    }
+}
@@ -1,13 +1,46 @@
 use crate::utils;
 use anyhow::Result;
 use log::{info, warn};
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
 
 use super::PackageManagerConfig;
+use super::preferences;
+use super::proxy::AptProxyConfig;
+use super::report::{self, InstallReport, Snapshot};
 
 const PPA_SUPPORT_PACKAGES: &[&str] = &["software-properties-common"];
 const PPA_SUPPORT_PACKAGES_DEBIAN: &[&str] = &["python3-launchpadlib"];
 
-pub(super) fn install(tool: &str, config: &PackageManagerConfig) -> Result<()> {
+/// Environment that keeps apt and dpkg from prompting during unattended installs
+const NONINTERACTIVE_ENV: &[(&str, &str)] = &[
+    ("DEBIAN_FRONTEND", "noninteractive"),
+    ("APT_LISTCHANGES_FRONTEND", "none"),
+];
+
+/// Keep existing config files and accept package defaults instead of prompting
+const DPKG_OPTIONS: &[&str] = &[
+    "-o",
+    "Dpkg::Options::=--force-confdef",
+    "-o",
+    "Dpkg::Options::=--force-confold",
+];
+
+/// Install only from /var/cache/apt/archives, like `--no-download`
+const OFFLINE_OPTIONS: &[&str] = &["-o", "APT::Get::Download=false"];
+
+const LOCK_RETRY_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const LOCK_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Messages apt and dpkg print when another process holds the package database lock
+const LOCK_ERROR_MARKERS: &[&str] = &[
+    "Could not get lock",
+    "Unable to acquire the dpkg frontend lock",
+    "Unable to lock the administration directory",
+    "dpkg status database is locked by another process",
+];
+
+pub(super) fn install(tool: &str, config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
     anyhow::ensure!(
         which::which(tool).is_ok(),
         "{} command not found in PATH",
@@ -22,100 +55,458 @@ pub(super) fn install(tool: &str, config: &PackageManagerConfig) -> Result<()> {
         ppas.clear();
     }
 
-    update_repositories()?;
+    let _proxy = config.proxy.map(AptProxyConfig::install).transpose()?;
+    preferences::write_pins(config.pins, config.dry_run)?;
+    let before = take_snapshot(config)?;
+    add_architectures(config)?;
+    update_repositories(config)?;
 
     if !ppas.is_empty() {
-        install_ppa_support()?;
-        add_ppas(&ppas)?;
-        update_repositories()?;
+        if config.dry_run {
+            warn!(
+                "Dry run: skipping PPA setup, packages only available from {:?} are not previewed",
+                ppas
+            );
+        } else {
+            utils::offline::ensure_online("Adding PPAs")?;
+            install_ppa_support(config)?;
+            add_ppas(&ppas, config)?;
+            update_repositories(config)?;
+        }
+    }
+
+    install_packages(tool, config)?;
+    let report = build_report(tool, config, before)?;
+    if !config.dry_run {
+        cleanup(config)?;
     }
 
-    install_packages(tool, config.packages)?;
-    cleanup()?;
+    Ok(report)
+}
 
-    Ok(())
+pub(super) fn install_aptitude(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    // aptitude has no equivalent of apt-get --no-download
+    utils::offline::ensure_online("aptitude")?;
+    let _proxy = config.proxy.map(AptProxyConfig::install).transpose()?;
+    preferences::write_pins(config.pins, config.dry_run)?;
+    let before = take_snapshot(config)?;
+    add_architectures(config)?;
+    update_repositories(config)?;
+
+    if config.dry_run && which::which("aptitude").is_err() {
+        info!("Dry run: aptitude is not installed, previewing with apt-get instead");
+        install_packages("apt-get", config)?;
+        return Ok(None);
+    }
+
+    install_aptitude_tool(config)?;
+    install_packages_aptitude(config)?;
+    let report = build_report("aptitude", config, before)?;
+    if !config.dry_run {
+        cleanup_aptitude(config)?;
+    }
+
+    Ok(report)
+}
+
+pub(super) fn upgrade(
+    config: &PackageManagerConfig,
+    dist_upgrade: bool,
+) -> Result<Option<InstallReport>> {
+    anyhow::ensure!(
+        which::which("apt-get").is_ok(),
+        "apt-get command not found in PATH"
+    );
+
+    let _proxy = config.proxy.map(AptProxyConfig::install).transpose()?;
+    preferences::write_pins(config.pins, config.dry_run)?;
+    let before = take_snapshot(config)?;
+    update_repositories(config)?;
+
+    let action = if dist_upgrade {
+        "dist-upgrade"
+    } else {
+        "upgrade"
+    };
+    let mut cmd = apt_command("apt-get");
+    cmd.args([action, "-y", "--no-install-recommends"])
+        .args(DPKG_OPTIONS);
+    if utils::offline::is_enabled() {
+        cmd.args(OFFLINE_OPTIONS);
+    }
+
+    if config.dry_run {
+        info!("Simulating apt-get {}", action);
+        cmd.arg("--simulate");
+        let output = run_apt_command(&mut cmd, "Simulate package upgrade", config)?;
+        super::print_simulation(&output, config)?;
+        return Ok(None);
+    }
+
+    info!("Upgrading packages with apt-get {}", action);
+    run_apt_command(&mut cmd, "Upgrade packages", config)?;
+    let report = build_report("apt-get", config, before)?;
+    cleanup(config)?;
+
+    Ok(report)
+}
+
+/// Snapshot installed packages when a report was requested for a real install
+fn take_snapshot(config: &PackageManagerConfig) -> Result<Option<Snapshot>> {
+    if config.report && !config.dry_run {
+        Ok(Some(report::dpkg_snapshot()?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn build_report(
+    tool: &str,
+    config: &PackageManagerConfig,
+    before: Option<Snapshot>,
+) -> Result<Option<InstallReport>> {
+    let Some(before) = before else {
+        return Ok(None);
+    };
+
+    let after = report::dpkg_snapshot()?;
+    let mut report = report::build(tool, config.packages, &before, &after);
+    report::add_apt_download_sizes(&mut report.installed)?;
+    Ok(Some(report))
+}
+
+/// Create an apt-family command that runs non-interactively, escalating with sudo if needed
+pub(crate) fn apt_command(tool: &str) -> Command {
+    utils::sudo::command_with_env(tool, NONINTERACTIVE_ENV)
 }
 
-pub(super) fn install_aptitude(packages: &[String]) -> Result<()> {
-    update_repositories()?;
-    install_aptitude_tool()?;
-    install_packages_aptitude(packages)?;
-    cleanup_aptitude()?;
+/// Create an apt-family install command with non-interactive dpkg options applied.
+/// Offline, only packages already in the apt cache can be installed.
+pub(crate) fn apt_install_command(tool: &str) -> Command {
+    let mut cmd = apt_command(tool);
+    cmd.args(["install", "-y"]).args(DPKG_OPTIONS);
+    if utils::offline::is_enabled() {
+        cmd.args(OFFLINE_OPTIONS);
+    }
+    cmd
+}
+
+/// Run an apt-family command, retrying mirror/network failures per the retry config.
+fn run_apt_command(
+    cmd: &mut Command,
+    description: &str,
+    config: &PackageManagerConfig,
+) -> Result<Output> {
+    utils::retry::retry_sync(
+        &config.retry_config,
+        description,
+        || run_apt_once(cmd, description, config.lock_timeout),
+        super::is_mirror_error,
+    )
+}
+
+/// Run an apt-family command once, waiting with backoff while another process holds the dpkg lock.
+fn run_apt_once(cmd: &mut Command, description: &str, lock_timeout: Duration) -> Result<Output> {
+    let start = Instant::now();
+    let mut delay = LOCK_RETRY_INITIAL_DELAY;
+
+    loop {
+        let output = utils::exec::Exec::new(cmd, description).output()?;
+
+        if output.status.success() {
+            return Ok(output);
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_lock_error(&stderr) {
+            let elapsed = start.elapsed();
+            if elapsed + delay > lock_timeout {
+                anyhow::bail!(
+                    "{} failed: the dpkg lock is still held by another process after waiting {}s. \
+                     Another apt/dpkg process (e.g. unattended-upgrades) may be running; \
+                     increase --lock-timeout to wait longer.",
+                    description,
+                    elapsed.as_secs()
+                );
+            }
+
+            warn!(
+                "{}: waiting for dpkg lock held by another process, retrying in {}s",
+                description,
+                delay.as_secs()
+            );
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(LOCK_RETRY_MAX_DELAY);
+            continue;
+        }
+
+        return super::check_output(output, description);
+    }
+}
+
+fn is_lock_error(stderr: &str) -> bool {
+    LOCK_ERROR_MARKERS
+        .iter()
+        .any(|marker| stderr.contains(marker))
+}
+
+/// Enable foreign architectures with `dpkg --add-architecture` so `pkg:arch` packages resolve
+fn add_architectures(config: &PackageManagerConfig) -> Result<()> {
+    if config.architectures.is_empty() {
+        return Ok(());
+    }
+
+    let output = utils::exec::run_command(
+        Command::new("dpkg").arg("--print-architecture"),
+        "Query native dpkg architecture",
+    )?;
+    let native = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let output = utils::exec::run_command(
+        Command::new("dpkg").arg("--print-foreign-architectures"),
+        "Query foreign dpkg architectures",
+    )?;
+    let foreign = String::from_utf8_lossy(&output.stdout).to_string();
+
+    for arch in config.architectures {
+        if *arch == native || foreign.lines().any(|a| a.trim() == arch) {
+            info!("Architecture {} is already enabled", arch);
+            continue;
+        }
+
+        if config.dry_run {
+            warn!(
+                "Dry run: skipping dpkg --add-architecture {}, {} packages may not be previewed",
+                arch, arch
+            );
+            continue;
+        }
+
+        info!("Adding dpkg architecture: {}", arch);
+        let mut cmd = utils::sudo::command("dpkg");
+        cmd.args(["--add-architecture", arch]);
+        utils::exec::run_command(&mut cmd, &format!("Add dpkg architecture: {}", arch))?;
+    }
 
     Ok(())
 }
 
-fn update_repositories() -> Result<()> {
+/// Qualify packages with each requested architecture (`pkg:arch`), leaving
+/// already-qualified packages untouched. The qualifier goes on the name, ahead of any
+/// `=version` or `/release` suffix, as in `curl:i386=8.5.0-2`.
+fn qualify_packages(packages: &[String], architectures: &[String]) -> Vec<String> {
+    if architectures.is_empty() {
+        return packages.to_vec();
+    }
+
+    packages
+        .iter()
+        .flat_map(|pkg| {
+            let (name, suffix) = pkg.split_at(pkg.find(['=', '/']).unwrap_or(pkg.len()));
+            if name.contains(':') {
+                vec![pkg.clone()]
+            } else {
+                architectures
+                    .iter()
+                    .map(|arch| format!("{}:{}{}", name, arch, suffix))
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+fn update_repositories(config: &PackageManagerConfig) -> Result<()> {
+    if utils::offline::is_enabled() {
+        info!("Offline: using the existing package lists");
+        return Ok(());
+    }
     info!("Updating repositories");
-    let mut cmd = utils::sudo::command("apt-get");
+    let mut cmd = apt_command("apt-get");
     cmd.args(["update", "-y"]);
-    utils::subprocess::run_command(&mut cmd, "Update repositories")?;
+    run_apt_command(&mut cmd, "Update repositories", config)?;
     Ok(())
 }
 
-fn install_ppa_support() -> Result<()> {
+fn install_ppa_support(config: &PackageManagerConfig) -> Result<()> {
     info!("Installing PPA support packages");
-    let mut cmd = utils::sudo::command("apt-get");
-    cmd.args(["install", "-y", "--no-install-recommends"])
+    let mut cmd = apt_install_command("apt-get");
+    cmd.arg("--no-install-recommends")
         .args(PPA_SUPPORT_PACKAGES);
-    utils::subprocess::run_command(&mut cmd, "Install PPA support packages")?;
+    run_apt_command(&mut cmd, "Install PPA support packages", config)?;
 
     if utils::os::is_debian() {
-        let mut cmd = utils::sudo::command("apt-get");
-        cmd.args(["install", "-y", "--no-install-recommends"])
+        let mut cmd = apt_install_command("apt-get");
+        cmd.arg("--no-install-recommends")
             .args(PPA_SUPPORT_PACKAGES_DEBIAN);
-        utils::subprocess::run_command(&mut cmd, "Install Debian PPA support packages")?;
+        run_apt_command(&mut cmd, "Install Debian PPA support packages", config)?;
     }
     Ok(())
 }
 
-fn add_ppas(ppas: &[String]) -> Result<()> {
+fn add_ppas(ppas: &[String], config: &PackageManagerConfig) -> Result<()> {
     for ppa in ppas {
         info!("Adding PPA: {}", ppa);
-        let mut cmd = utils::sudo::command("add-apt-repository");
+        let mut cmd = apt_command("add-apt-repository");
         cmd.args(["-y", ppa]);
-        utils::subprocess::run_command(&mut cmd, &format!("Add PPA: {}", ppa))?;
+        run_apt_command(&mut cmd, &format!("Add PPA: {}", ppa), config)?;
     }
     Ok(())
 }
 
-fn install_packages(tool: &str, packages: &[String]) -> Result<()> {
+fn install_packages(tool: &str, config: &PackageManagerConfig) -> Result<()> {
+    let packages = qualify_packages(config.packages, config.architectures);
+    let mut cmd = apt_install_command(tool);
+    cmd.arg("--no-install-recommends");
+
+    if config.dry_run {
+        info!("Simulating install with {}: {:?}", tool, packages);
+        cmd.arg("--simulate").args(&packages);
+        let output = run_apt_command(&mut cmd, "Simulate package install", config)?;
+        super::print_simulation(&output, config)?;
+        return Ok(());
+    }
+
     info!("Installing packages with {}: {:?}", tool, packages);
-    let mut cmd = utils::sudo::command(tool);
-    cmd.args(["install", "-y", "--no-install-recommends"])
-        .args(packages);
-    utils::subprocess::run_command(&mut cmd, "Install packages")?;
+    cmd.args(&packages);
+    run_apt_command(&mut cmd, "Install packages", config)?;
     Ok(())
 }
 
-fn install_aptitude_tool() -> Result<()> {
+fn install_aptitude_tool(config: &PackageManagerConfig) -> Result<()> {
     info!("Installing aptitude");
-    let mut cmd = utils::sudo::command("apt-get");
-    cmd.args(["install", "-y", "--no-install-recommends", "aptitude"]);
-    utils::subprocess::run_command(&mut cmd, "Install aptitude")?;
+    let mut cmd = apt_install_command("apt-get");
+    cmd.args(["--no-install-recommends", "aptitude"]);
+    run_apt_command(&mut cmd, "Install aptitude", config)?;
     Ok(())
 }
 
-fn install_packages_aptitude(packages: &[String]) -> Result<()> {
+fn install_packages_aptitude(config: &PackageManagerConfig) -> Result<()> {
+    let packages = qualify_packages(config.packages, config.architectures);
+    let mut cmd = apt_install_command("aptitude");
+
+    if config.dry_run {
+        info!("Simulating install with aptitude: {:?}", packages);
+        cmd.arg("--simulate").args(&packages);
+        let output = run_apt_command(&mut cmd, "Simulate package install with aptitude", config)?;
+        super::print_simulation(&output, config)?;
+        return Ok(());
+    }
+
     info!("Installing packages with aptitude: {:?}", packages);
-    let mut cmd = utils::sudo::command("aptitude");
-    cmd.args(["install", "-y"]).args(packages);
-    utils::subprocess::run_command(&mut cmd, "Install packages with aptitude")?;
+    cmd.args(&packages);
+    run_apt_command(&mut cmd, "Install packages with aptitude", config)?;
     Ok(())
 }
 
-fn cleanup() -> Result<()> {
+fn cleanup(config: &PackageManagerConfig) -> Result<()> {
+    if config.keep_cache {
+        info!("Keeping package cache (--keep-cache)");
+        return Ok(());
+    }
     info!("Cleaning package cache");
-    let mut cmd = utils::sudo::command("apt-get");
+    let mut cmd = apt_command("apt-get");
     cmd.args(["clean"]);
-    utils::subprocess::run_command(&mut cmd, "Clean package cache")?;
+    run_apt_command(&mut cmd, "Clean package cache", config)?;
     Ok(())
 }
 
-fn cleanup_aptitude() -> Result<()> {
+fn cleanup_aptitude(config: &PackageManagerConfig) -> Result<()> {
+    if config.keep_cache {
+        info!("Keeping package cache (--keep-cache)");
+        return Ok(());
+    }
     info!("Cleaning aptitude cache");
-    let mut cmd = utils::sudo::command("aptitude");
+    let mut cmd = apt_command("aptitude");
     cmd.args(["clean"]);
-    utils::subprocess::run_command(&mut cmd, "Clean aptitude cache")?;
+    run_apt_command(&mut cmd, "Clean aptitude cache", config)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_line(cmd: &Command) -> Vec<String> {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|s| s.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn apt_command_sets_noninteractive_env() {
+        let cmd = apt_command("apt-get");
+        let line = command_line(&cmd);
+        let has_env = cmd
+            .get_envs()
+            .any(|(k, v)| k == "DEBIAN_FRONTEND" && v.is_some_and(|v| v == "noninteractive"));
+        let has_arg = line.iter().any(|a| a == "DEBIAN_FRONTEND=noninteractive");
+        assert!(has_env || has_arg, "missing DEBIAN_FRONTEND in {:?}", line);
+    }
+
+    #[test]
+    fn apt_install_command_includes_dpkg_options() {
+        let line = command_line(&apt_install_command("apt-get"));
+        assert!(line.contains(&"Dpkg::Options::=--force-confdef".to_string()));
+        assert!(line.contains(&"Dpkg::Options::=--force-confold".to_string()));
+    }
+
+    #[test]
+    fn qualify_packages_adds_each_architecture() {
+        let packages = vec!["libc6".to_string(), "wine32:i386".to_string()];
+        let archs = vec!["i386".to_string(), "armhf".to_string()];
+        assert_eq!(
+            qualify_packages(&packages, &archs),
+            vec!["libc6:i386", "libc6:armhf", "wine32:i386"]
+        );
+    }
+
+    #[test]
+    fn qualify_packages_qualifies_the_name_of_a_pinned_version() {
+        let archs = vec!["i386".to_string()];
+        assert_eq!(
+            qualify_packages(&["curl=8.5.0-2".to_string()], &archs),
+            vec!["curl:i386=8.5.0-2"]
+        );
+        assert_eq!(
+            qualify_packages(&["libssl3=1:3.0.11-1".to_string()], &archs),
+            vec!["libssl3:i386=1:3.0.11-1"]
+        );
+        assert_eq!(
+            qualify_packages(&["wine32:i386=8.0".to_string()], &archs),
+            vec!["wine32:i386=8.0"]
+        );
+        assert_eq!(
+            qualify_packages(&["curl/bookworm-backports".to_string()], &archs),
+            vec!["curl:i386/bookworm-backports"]
+        );
+    }
+
+    #[test]
+    fn qualify_packages_without_architectures_is_identity() {
+        let packages = vec!["curl".to_string()];
+        assert_eq!(qualify_packages(&packages, &[]), vec!["curl"]);
+    }
+
+    #[test]
+    fn is_lock_error_detects_dpkg_lock_messages() {
+        assert!(is_lock_error(
+            "E: Could not get lock /var/lib/dpkg/lock-frontend. It is held by process 1234 (unattended-upgr)"
+        ));
+        assert!(is_lock_error(
+            "E: Unable to acquire the dpkg frontend lock (/var/lib/dpkg/lock-frontend), is another process using it?"
+        ));
+        assert!(!is_lock_error("E: Unable to locate package nonexistent"));
+    }
+
+    #[test]
+    fn run_apt_command_gives_up_after_lock_timeout() {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "echo 'E: Could not get lock /var/lib/dpkg/lock' >&2; exit 100",
+        ]);
+        let err = run_apt_once(&mut cmd, "Locked command", Duration::ZERO).unwrap_err();
+        assert!(err.to_string().contains("dpkg lock is still held"));
+    }
+}
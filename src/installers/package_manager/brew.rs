@@ -1,40 +1,137 @@
 use crate::utils;
 use anyhow::Result;
 use log::info;
+use std::process::Command;
 
-pub(super) fn install(packages: &[String]) -> Result<()> {
+use super::proxy;
+use super::report::{self, InstallReport};
+use super::{PackageManagerConfig, print_simulation, run_command};
+
+pub(super) fn install(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
     anyhow::ensure!(
         which::which("brew").is_ok(),
         "Homebrew not installed or not in PATH"
     );
 
-    update()?;
-    install_packages(packages)?;
-    cleanup()?;
+    let before = if config.report && !config.dry_run {
+        Some(report::brew_snapshot()?)
+    } else {
+        None
+    };
 
-    Ok(())
+    update(config)?;
+    install_packages(config)?;
+
+    let report = match before {
+        Some(before) => Some(report::build(
+            "brew",
+            config.packages,
+            &before,
+            &report::brew_snapshot()?,
+        )),
+        None => None,
+    };
+
+    if !config.dry_run {
+        cleanup(config)?;
+    }
+
+    Ok(report)
+}
+
+pub(super) fn upgrade(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    anyhow::ensure!(
+        which::which("brew").is_ok(),
+        "Homebrew not installed or not in PATH"
+    );
+
+    let before = if config.report && !config.dry_run {
+        Some(report::brew_snapshot()?)
+    } else {
+        None
+    };
+
+    update(config)?;
+
+    let mut cmd = brew_command(config)?;
+    cmd.arg("upgrade");
+
+    if config.dry_run {
+        info!("Simulating Homebrew upgrade");
+        cmd.arg("--dry-run");
+        let output = run_command(&mut cmd, "Simulate Homebrew upgrade", config)?;
+        print_simulation(&output, config)?;
+        return Ok(None);
+    }
+
+    info!("Upgrading Homebrew packages");
+    run_command(&mut cmd, "Upgrade Homebrew packages", config)?;
+
+    let report = match before {
+        Some(before) => Some(report::build(
+            "brew",
+            &[],
+            &before,
+            &report::brew_snapshot()?,
+        )),
+        None => None,
+    };
+    cleanup(config)?;
+
+    Ok(report)
+}
+
+/// Create a brew command, routing downloads through the configured proxy if any
+fn brew_command(config: &PackageManagerConfig) -> Result<Command> {
+    let mut cmd = Command::new("brew");
+    if let Some(proxy) = config.proxy {
+        cmd.envs(proxy::proxy_env(proxy)?);
+    }
+    if utils::offline::is_enabled() {
+        // Bottles must already be in the Homebrew download cache
+        cmd.env("HOMEBREW_NO_AUTO_UPDATE", "1");
+    }
+    Ok(cmd)
 }
 
-fn update() -> Result<()> {
+fn update(config: &PackageManagerConfig) -> Result<()> {
+    if utils::offline::is_enabled() {
+        info!("Offline: skipping brew update");
+        return Ok(());
+    }
     info!("Updating Homebrew");
-    let mut cmd = std::process::Command::new("brew");
+    let mut cmd = brew_command(config)?;
     cmd.arg("update");
-    utils::subprocess::run_command(&mut cmd, "Update Homebrew")?;
+    run_command(&mut cmd, "Update Homebrew", config)?;
     Ok(())
 }
 
-fn install_packages(packages: &[String]) -> Result<()> {
-    info!("Installing Homebrew packages: {:?}", packages);
-    let mut cmd = std::process::Command::new("brew");
-    cmd.args(["install"]).args(packages);
-    utils::subprocess::run_command(&mut cmd, "Install Homebrew packages")?;
+fn install_packages(config: &PackageManagerConfig) -> Result<()> {
+    let mut cmd = brew_command(config)?;
+    cmd.args(["install"]);
+
+    if config.dry_run {
+        info!("Simulating Homebrew install: {:?}", config.packages);
+        cmd.arg("--dry-run").args(config.packages);
+        let output = run_command(&mut cmd, "Simulate Homebrew install", config)?;
+        print_simulation(&output, config)?;
+        return Ok(());
+    }
+
+    info!("Installing Homebrew packages: {:?}", config.packages);
+    cmd.args(config.packages);
+    run_command(&mut cmd, "Install Homebrew packages", config)?;
     Ok(())
 }
 
-fn cleanup() -> Result<()> {
+fn cleanup(config: &PackageManagerConfig) -> Result<()> {
+    if config.keep_cache {
+        info!("Keeping package cache (--keep-cache)");
+        return Ok(());
+    }
     info!("Cleaning up Homebrew cache");
-    let mut cmd = std::process::Command::new("brew");
+    let mut cmd = brew_command(config)?;
     cmd.arg("cleanup");
-    utils::subprocess::run_command(&mut cmd, "Clean up Homebrew cache")?;
+    run_command(&mut cmd, "Clean up Homebrew cache", config)?;
     Ok(())
 }
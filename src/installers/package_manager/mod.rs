@@ -1,31 +1,281 @@
 mod apk;
 mod apt_based;
 mod brew;
+mod preferences;
+mod proxy;
+mod report;
 
+use crate::cli::RetryConfig;
+use crate::utils;
 use anyhow::Result;
+use std::fmt;
+use std::process::{Command, Output};
+use std::time::Duration;
 
+pub(crate) use apt_based::{apt_command, apt_install_command};
+pub use report::{InstallReport, InstalledPackage};
+
+/// Output fragments that indicate a package mirror or network failure worth retrying
+const MIRROR_ERROR_MARKERS: &[&str] = &[
+    // apt
+    "Temporary failure resolving",
+    "Failed to fetch",
+    "Could not resolve",
+    "Could not connect to",
+    "Connection timed out",
+    "Connection failed",
+    "Unable to connect to",
+    "Hash Sum mismatch",
+    // apk
+    "temporary error (try again later)",
+    "network error",
+    "DNS lookup error",
+    // brew
+    "Failed to download resource",
+    "Could not resolve host",
+];
+
+#[derive(Default)]
 pub struct PackageManagerConfig<'a> {
     pub packages: &'a [String],
     pub ppas: Option<&'a [String]>,
     pub force_ppas_on_non_ubuntu: bool,
+    pub lock_timeout: Duration,
+    /// Foreign dpkg architectures to enable and install packages for (e.g. i386)
+    pub architectures: &'a [String],
+    /// apt preferences pins in `PACKAGE:PIN:PRIORITY` form
+    pub pins: &'a [String],
+    pub retry_config: RetryConfig,
+    pub dry_run: bool,
+    /// Collect an [`InstallReport`] of the resolved package versions, or on a dry run the
+    /// simulated output, for `--output json`
+    pub report: bool,
+    /// HTTP(S) proxy used to reach package mirrors for the duration of the command
+    pub proxy: Option<&'a str>,
+    /// Additional apk repositories, either a URL or `@tag=URL` for pinned packages
+    pub repositories: &'a [String],
+    /// Skip cache cleanup so a mounted package cache survives across builds
+    pub keep_cache: bool,
 }
 
-pub fn install_apt_get(config: &PackageManagerConfig) -> Result<()> {
+/// A package manager command failed because a mirror could not be reached
+#[derive(Debug)]
+struct MirrorError {
+    description: String,
+    detail: String,
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed: Network error reaching package mirrors: {}",
+            self.description, self.detail
+        )
+    }
+}
+
+impl std::error::Error for MirrorError {}
+
+pub fn install_apt_get(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    utils::sudo::ensure_privileges("Installing packages with apt-get")?;
     apt_based::install("apt-get", config)
 }
 
-pub fn install_apt(config: &PackageManagerConfig) -> Result<()> {
+pub fn install_apt(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    utils::sudo::ensure_privileges("Installing packages with apt")?;
     apt_based::install("apt", config)
 }
 
-pub fn install_aptitude(packages: &[String]) -> Result<()> {
-    apt_based::install_aptitude(packages)
+pub fn install_aptitude(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    utils::sudo::ensure_privileges("Installing packages with aptitude")?;
+    apt_based::install_aptitude(config)
+}
+
+pub fn install_apk(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    utils::sudo::ensure_privileges("Installing packages with apk")?;
+    apk::install(config)
+}
+
+pub fn install_brew(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    brew::install(config)
+}
+
+/// Upgrade all installed packages with apt-get, optionally allowing dependency changes (dist-upgrade)
+pub fn upgrade_apt(
+    config: &PackageManagerConfig,
+    dist_upgrade: bool,
+) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    utils::sudo::ensure_privileges("Upgrading packages with apt-get")?;
+    apt_based::upgrade(config, dist_upgrade)
+}
+
+/// Upgrade all installed packages with apk, optionally replacing them with available versions
+pub fn upgrade_apk(
+    config: &PackageManagerConfig,
+    available: bool,
+) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    utils::sudo::ensure_privileges("Upgrading packages with apk")?;
+    apk::upgrade(config, available)
+}
+
+/// Upgrade all installed Homebrew packages
+pub fn upgrade_brew(config: &PackageManagerConfig) -> Result<Option<InstallReport>> {
+    let _lock = utils::run_lock::acquire();
+    brew::upgrade(config)
 }
 
-pub fn install_apk(packages: &[String]) -> Result<()> {
-    apk::install(packages)
+/// Remove packages installed with apt-get, apt, aptitude, apk, or brew
+pub fn remove(tool: &str, packages: &[String]) -> Result<()> {
+    let _lock = utils::run_lock::acquire();
+    let names: Vec<&str> = packages.iter().map(|p| package_name(p)).collect();
+    let mut cmd = match tool {
+        "apt-get" | "apt" | "aptitude" => {
+            let mut cmd = apt_command("apt-get");
+            cmd.args(["purge", "-y", "--auto-remove"]);
+            cmd
+        }
+        "apk" => {
+            let mut cmd = utils::sudo::command("apk");
+            cmd.args(["del", "--no-cache"]);
+            cmd
+        }
+        "brew" => {
+            let mut cmd = Command::new("brew");
+            cmd.arg("uninstall");
+            cmd
+        }
+        _ => anyhow::bail!("Cannot remove packages installed with {}", tool),
+    };
+    cmd.args(&names);
+    utils::exec::run_command(&mut cmd, &format!("{} remove {}", tool, names.join(" ")))?;
+    Ok(())
 }
 
-pub fn install_brew(packages: &[String]) -> Result<()> {
-    brew::install(packages)
+/// Strip a version or repository pin: `curl=8.5.0-2` or `nginx@edge` -> the package name
+fn package_name(spec: &str) -> &str {
+    spec.split(['=', '@']).next().unwrap_or(spec)
+}
+
+/// Run a package manager command, retrying mirror/network failures per the retry config.
+fn run_command(
+    cmd: &mut Command,
+    description: &str,
+    config: &PackageManagerConfig,
+) -> Result<Output> {
+    utils::retry::retry_sync(
+        &config.retry_config,
+        description,
+        || {
+            let output = utils::exec::Exec::new(cmd, description).output()?;
+            check_output(output, description)
+        },
+        is_mirror_error,
+    )
+}
+
+/// Check a finished package manager command, classifying mirror/network failures as retryable.
+fn check_output(output: Output, description: &str) -> Result<Output> {
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(line) = stderr
+            .lines()
+            .chain(stdout.lines())
+            .find(|line| MIRROR_ERROR_MARKERS.iter().any(|m| line.contains(m)))
+        {
+            return Err(MirrorError {
+                description: description.to_string(),
+                detail: line.trim().to_string(),
+            }
+            .into());
+        }
+    }
+
+    utils::exec::check_output(output, description)
+}
+
+/// Show the output of a simulated install so the user can preview its impact. With a
+/// report requested it becomes the command's JSON details instead of being printed.
+fn print_simulation(output: &Output, config: &PackageManagerConfig) -> Result<()> {
+    let simulation = String::from_utf8_lossy(&output.stdout);
+    if config.report {
+        return crate::cli::set_details(&serde_json::json!({ "simulation": simulation }));
+    }
+    print!("{}", simulation);
+    Ok(())
+}
+
+fn is_mirror_error(err: &anyhow::Error) -> bool {
+    err.is::<MirrorError>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_name_strips_version_and_repository_pins() {
+        assert_eq!(package_name("curl=8.5.0-2"), "curl");
+        assert_eq!(package_name("nginx@edge"), "nginx");
+        assert_eq!(package_name("libc6:arm64"), "libc6:arm64");
+    }
+
+    fn config_with_retries(max_retries: u32) -> PackageManagerConfig<'static> {
+        PackageManagerConfig {
+            retry_config: RetryConfig {
+                max_retries,
+                initial_delay_ms: 1,
+                backoff_multiplier: 1.0,
+                ..RetryConfig::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn check_output_classifies_mirror_failures() {
+        let output = Command::new("sh")
+            .args([
+                "-c",
+                "echo 'E: Failed to fetch http://deb.debian.org/debian' >&2; exit 100",
+            ])
+            .output()
+            .unwrap();
+        let err = check_output(output, "Install packages").unwrap_err();
+        assert!(is_mirror_error(&err));
+    }
+
+    #[test]
+    fn check_output_does_not_retry_missing_packages() {
+        let output = Command::new("sh")
+            .args([
+                "-c",
+                "echo 'E: Unable to locate package nope' >&2; exit 100",
+            ])
+            .output()
+            .unwrap();
+        let err = check_output(output, "Install packages").unwrap_err();
+        assert!(!is_mirror_error(&err));
+    }
+
+    #[test]
+    fn run_command_retries_mirror_failures() {
+        let temp = tempfile::tempdir().unwrap();
+        let marker = temp.path().join("attempted");
+        let script = format!(
+            "if [ -e {0} ]; then exit 0; fi; touch {0}; echo 'ERROR: temporary error (try again later)' >&2; exit 1",
+            marker.display()
+        );
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script.as_str()]);
+        run_command(&mut cmd, "Update apk repositories", &config_with_retries(2)).unwrap();
+    }
 }
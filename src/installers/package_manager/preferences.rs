@@ -0,0 +1,119 @@
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::path::Path;
+
+const APT_PREFERENCES_FILE: &str = "/etc/apt/preferences.d/99picolayer";
+
+/// A single apt preferences entry parsed from `PACKAGE:PIN:PRIORITY`
+#[derive(Debug, PartialEq)]
+struct AptPin<'a> {
+    package: &'a str,
+    pin: &'a str,
+    priority: i32,
+}
+
+/// Write the requested pins to /etc/apt/preferences.d so they apply to this and later installs
+pub(super) fn write_pins(pins: &[String], dry_run: bool) -> Result<()> {
+    if pins.is_empty() {
+        return Ok(());
+    }
+
+    let parsed = pins
+        .iter()
+        .map(|p| parse_pin(p))
+        .collect::<Result<Vec<_>>>()?;
+    let contents = preferences_file(&parsed);
+
+    if dry_run {
+        warn!(
+            "Dry run: not writing {}:\n{}",
+            APT_PREFERENCES_FILE, contents
+        );
+        return Ok(());
+    }
+
+    info!("Writing apt pin preferences to {}", APT_PREFERENCES_FILE);
+    utils::sudo::write_file(Path::new(APT_PREFERENCES_FILE), &contents)
+}
+
+fn parse_pin(value: &str) -> Result<AptPin<'_>> {
+    let invalid = || {
+        format!(
+            "Invalid pin '{}'. Expected PACKAGE:PIN:PRIORITY, e.g. 'nginx:release a=bookworm-backports:900'",
+            value
+        )
+    };
+
+    let (package, rest) = value.split_once(':').with_context(invalid)?;
+    let (pin, priority) = rest.rsplit_once(':').with_context(invalid)?;
+    let priority = priority.trim().parse().with_context(invalid)?;
+    let (package, pin) = (package.trim(), pin.trim());
+
+    anyhow::ensure!(
+        !package.is_empty() && !pin.is_empty() && !value.contains('\n'),
+        invalid()
+    );
+
+    Ok(AptPin {
+        package,
+        pin,
+        priority,
+    })
+}
+
+fn preferences_file(pins: &[AptPin]) -> String {
+    pins.iter()
+        .map(|p| {
+            format!(
+                "Package: {}\nPin: {}\nPin-Priority: {}\n",
+                p.package, p.pin, p.priority
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pin_splits_package_pin_and_priority() {
+        assert_eq!(
+            parse_pin("nginx:release a=bookworm-backports:900").unwrap(),
+            AptPin {
+                package: "nginx",
+                pin: "release a=bookworm-backports",
+                priority: 900
+            }
+        );
+        assert_eq!(
+            parse_pin("*:origin packages.example.com:-1")
+                .unwrap()
+                .priority,
+            -1
+        );
+    }
+
+    #[test]
+    fn parse_pin_rejects_malformed_values() {
+        assert!(parse_pin("nginx").is_err());
+        assert!(parse_pin("nginx:release a=stable").is_err());
+        assert!(parse_pin(":release a=stable:500").is_err());
+        assert!(parse_pin("nginx:release a=stable:high").is_err());
+    }
+
+    #[test]
+    fn preferences_file_separates_entries_with_blank_lines() {
+        let pins = [
+            parse_pin("nginx:release a=bookworm-backports:900").unwrap(),
+            parse_pin("*:origin packages.example.com:100").unwrap(),
+        ];
+        assert_eq!(
+            preferences_file(&pins),
+            "Package: nginx\nPin: release a=bookworm-backports\nPin-Priority: 900\n\n\
+             Package: *\nPin: origin packages.example.com\nPin-Priority: 100\n"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::utils;
+use anyhow::Result;
+use log::{info, warn};
+use std::path::PathBuf;
+
+const APT_PROXY_CONF: &str = "/etc/apt/apt.conf.d/99picolayer-proxy";
+
+/// Temporary apt proxy configuration, removed again when dropped
+pub(super) struct AptProxyConfig {
+    path: PathBuf,
+}
+
+impl AptProxyConfig {
+    pub(super) fn install(proxy: &str) -> Result<Self> {
+        validate_proxy_url(proxy)?;
+
+        let path = PathBuf::from(APT_PROXY_CONF);
+        info!("Configuring apt proxy: {}", proxy);
+        utils::sudo::write_file(&path, &apt_proxy_conf(proxy))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for AptProxyConfig {
+    fn drop(&mut self) {
+        info!("Removing temporary apt proxy configuration");
+        if let Err(e) = utils::sudo::remove_file(&self.path) {
+            warn!(
+                "Failed to remove apt proxy configuration {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Environment variables that route apk and Homebrew downloads through a proxy
+pub(super) fn proxy_env(proxy: &str) -> Result<[(&'static str, &str); 2]> {
+    validate_proxy_url(proxy)?;
+    Ok([("http_proxy", proxy), ("https_proxy", proxy)])
+}
+
+fn apt_proxy_conf(proxy: &str) -> String {
+    format!(
+        "Acquire::http::Proxy \"{}\";\nAcquire::https::Proxy \"{}\";\n",
+        proxy, proxy
+    )
+}
+
+fn validate_proxy_url(proxy: &str) -> Result<()> {
+    anyhow::ensure!(
+        (proxy.starts_with("http://") || proxy.starts_with("https://"))
+            && !proxy.contains(['"', '\n', ';']),
+        "Invalid package proxy URL '{}'. Expected http://host:port or https://host:port",
+        proxy
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apt_proxy_conf_sets_http_and_https() {
+        let conf = apt_proxy_conf("http://proxy:3128");
+        assert!(conf.contains("Acquire::http::Proxy \"http://proxy:3128\";"));
+        assert!(conf.contains("Acquire::https::Proxy \"http://proxy:3128\";"));
+    }
+
+    #[test]
+    fn validate_proxy_url_rejects_invalid_values() {
+        assert!(validate_proxy_url("http://proxy:3128").is_ok());
+        assert!(validate_proxy_url("proxy:3128").is_err());
+        assert!(validate_proxy_url("http://proxy\";\nAPT::Foo \"x").is_err());
+    }
+
+    #[test]
+    fn proxy_env_sets_lowercase_variables() {
+        let env = proxy_env("http://proxy:3128").unwrap();
+        assert_eq!(env[0], ("http_proxy", "http://proxy:3128"));
+        assert_eq!(env[1], ("https_proxy", "http://proxy:3128"));
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+/// A package that was newly installed or changed version during an install
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    pub architecture: Option<String>,
+    pub download_size: Option<u64>,
+    pub installed_size: Option<u64>,
+}
+
+/// Summary of what a package manager install put into the layer
+#[derive(Debug, Serialize)]
+pub struct InstallReport {
+    pub package_manager: String,
+    pub requested: Vec<String>,
+    pub installed: Vec<InstalledPackage>,
+}
+
+/// Installed packages keyed by name (and architecture where relevant)
+pub(super) type Snapshot = BTreeMap<String, InstalledPackage>;
+
+/// Build a report from package snapshots taken before and after an install
+pub(super) fn build(
+    package_manager: &str,
+    requested: &[String],
+    before: &Snapshot,
+    after: &Snapshot,
+) -> InstallReport {
+    InstallReport {
+        package_manager: package_manager.to_string(),
+        requested: requested.to_vec(),
+        installed: diff_snapshots(before, after),
+    }
+}
+
+/// Return packages present in `after` that are missing from, or differ in version from, `before`
+pub(super) fn diff_snapshots(before: &Snapshot, after: &Snapshot) -> Vec<InstalledPackage> {
+    after
+        .iter()
+        .filter(|(key, pkg)| {
+            before
+                .get(*key)
+                .is_none_or(|prev| prev.version != pkg.version)
+        })
+        .map(|(_, pkg)| pkg.clone())
+        .collect()
+}
+
+/// Snapshot installed dpkg packages
+pub(super) fn dpkg_snapshot() -> Result<Snapshot> {
+    let output = Command::new("dpkg-query")
+        .args([
+            "-W",
+            "-f",
+            "${Package}\\t${Architecture}\\t${Version}\\t${Installed-Size}\\t${db:Status-Status}\\n",
+        ])
+        .output()
+        .context("Failed to execute dpkg-query")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "dpkg-query failed with exit code: {:?}",
+        output.status.code()
+    );
+    Ok(parse_dpkg_query(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_dpkg_query(content: &str) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 || fields[4] != "installed" {
+            continue;
+        }
+        let pkg = InstalledPackage {
+            name: fields[0].to_string(),
+            architecture: Some(fields[1].to_string()).filter(|a| !a.is_empty()),
+            version: fields[2].to_string(),
+            download_size: None,
+            // dpkg reports Installed-Size in KiB
+            installed_size: fields[3].parse::<u64>().ok().map(|kib| kib * 1024),
+        };
+        snapshot.insert(format!("{}:{}", fields[0], fields[1]), pkg);
+    }
+    snapshot
+}
+
+/// Fill in download sizes for the given packages from the apt cache
+pub(super) fn add_apt_download_sizes(packages: &mut [InstalledPackage]) -> Result<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+
+    let specs: Vec<String> = packages
+        .iter()
+        .map(|p| match &p.architecture {
+            Some(arch) if arch != "all" => format!("{}:{}={}", p.name, arch, p.version),
+            _ => format!("{}={}", p.name, p.version),
+        })
+        .collect();
+
+    let output = Command::new("apt-cache")
+        .args(["show", "--no-all-versions"])
+        .args(&specs)
+        .output()
+        .context("Failed to execute apt-cache")?;
+
+    let sizes = parse_apt_cache_sizes(&String::from_utf8_lossy(&output.stdout));
+    for pkg in packages.iter_mut() {
+        pkg.download_size = sizes.get(&(pkg.name.clone(), pkg.version.clone())).copied();
+    }
+    Ok(())
+}
+
+fn parse_apt_cache_sizes(content: &str) -> BTreeMap<(String, String), u64> {
+    let mut sizes = BTreeMap::new();
+    for record in content.split("\n\n") {
+        let mut name = None;
+        let mut version = None;
+        let mut size = None;
+        for line in record.lines() {
+            if let Some(v) = line.strip_prefix("Package: ") {
+                name = Some(v.trim().to_string());
+            } else if let Some(v) = line.strip_prefix("Version: ") {
+                version = Some(v.trim().to_string());
+            } else if let Some(v) = line.strip_prefix("Size: ") {
+                size = v.trim().parse::<u64>().ok();
+            }
+        }
+        if let (Some(name), Some(version), Some(size)) = (name, version, size) {
+            sizes.insert((name, version), size);
+        }
+    }
+    sizes
+}
+
+/// Snapshot installed apk packages
+pub(super) fn apk_snapshot() -> Result<Snapshot> {
+    let output = Command::new("apk")
+        .args(["info", "-v"])
+        .output()
+        .context("Failed to execute apk info")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "apk info failed with exit code: {:?}",
+        output.status.code()
+    );
+    Ok(parse_apk_info(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_apk_info(content: &str) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        // Entries look like `name-1.2.3-r0`; the name itself may contain dashes
+        let mut parts = line.rsplitn(3, '-');
+        let (Some(release), Some(version), Some(name)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        snapshot.insert(
+            name.to_string(),
+            InstalledPackage {
+                name: name.to_string(),
+                version: format!("{}-{}", version, release),
+                architecture: None,
+                download_size: None,
+                installed_size: None,
+            },
+        );
+    }
+    snapshot
+}
+
+/// Snapshot installed Homebrew formulae
+pub(super) fn brew_snapshot() -> Result<Snapshot> {
+    let output = Command::new("brew")
+        .args(["list", "--versions"])
+        .output()
+        .context("Failed to execute brew list")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "brew list failed with exit code: {:?}",
+        output.status.code()
+    );
+    Ok(parse_brew_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_brew_list(content: &str) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(version)) = (fields.next(), fields.last()) else {
+            continue;
+        };
+        snapshot.insert(
+            name.to_string(),
+            InstalledPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                architecture: None,
+                download_size: None,
+                installed_size: None,
+            },
+        );
+    }
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dpkg_query_keeps_installed_packages() {
+        let content = "curl\tamd64\t7.88.1-10\t500\tinstalled\n\
+                       old\tamd64\t1.0\t10\tconfig-files\n";
+        let snapshot = parse_dpkg_query(content);
+        assert_eq!(snapshot.len(), 1);
+        let curl = snapshot.get("curl:amd64").unwrap();
+        assert_eq!(curl.version, "7.88.1-10");
+        assert_eq!(curl.installed_size, Some(500 * 1024));
+    }
+
+    #[test]
+    fn parse_apt_cache_sizes_reads_records() {
+        let content = "Package: curl\nVersion: 7.88.1-10\nSize: 315000\n\nPackage: jq\nVersion: 1.6-2\nSize: 60000\n";
+        let sizes = parse_apt_cache_sizes(content);
+        assert_eq!(
+            sizes.get(&("curl".to_string(), "7.88.1-10".to_string())),
+            Some(&315000)
+        );
+        assert_eq!(sizes.len(), 2);
+    }
+
+    #[test]
+    fn parse_apk_info_splits_name_and_version() {
+        let snapshot = parse_apk_info("musl-1.2.4-r2\nca-certificates-bundle-20230506-r0\n");
+        assert_eq!(snapshot.get("musl").unwrap().version, "1.2.4-r2");
+        assert_eq!(
+            snapshot.get("ca-certificates-bundle").unwrap().version,
+            "20230506-r0"
+        );
+    }
+
+    #[test]
+    fn parse_brew_list_uses_latest_version() {
+        let snapshot = parse_brew_list("jq 1.6 1.7.1\ntree 2.1.1\n");
+        assert_eq!(snapshot.get("jq").unwrap().version, "1.7.1");
+        assert_eq!(snapshot.get("tree").unwrap().version, "2.1.1");
+    }
+
+    #[test]
+    fn diff_snapshots_reports_new_and_upgraded_packages() {
+        let before = parse_brew_list("jq 1.6\ntree 2.1.1\n");
+        let after = parse_brew_list("jq 1.7.1\ntree 2.1.1\nwget 1.24\n");
+        let names: Vec<String> = diff_snapshots(&before, &after)
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["jq", "wget"]);
+    }
+}
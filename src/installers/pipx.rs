@@ -1,22 +1,69 @@
+use crate::installers::package_manager;
 use crate::utils;
-use anyhow::Result;
-use log::{debug, info};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-pub fn install(packages: &[String], python_version: Option<&str>) -> Result<()> {
+pub struct PipxConfig<'a> {
+    pub packages: &'a [String],
+    pub python: Option<&'a str>,
+    /// Extra arguments forwarded to pip through `pipx install --pip-args`
+    pub pip_args: Option<&'a str>,
+    /// Install source (git URL, path, or requirement) for the single package given
+    pub spec: Option<&'a str>,
+    /// Directory app executables are linked into (PIPX_BIN_DIR)
+    pub bin_dir: Option<&'a str>,
+}
+
+/// Prefixes of pip requirements that point at a VCS repository or archive instead of PyPI
+const URL_PREFIXES: &[&str] = &[
+    "git+https://",
+    "git+ssh://",
+    "git+http://",
+    "git+file://",
+    "https://",
+    "http://",
+    "file://",
+];
+
+pub fn install(config: &PipxConfig) -> Result<()> {
+    if let Some(spec) = config.spec {
+        anyhow::ensure!(
+            config.packages.len() == 1,
+            "--spec installs a single package, but {} were given",
+            config.packages.len()
+        );
+        validate_package(spec)?;
+    } else {
+        for package in config.packages {
+            validate_package(package)?;
+        }
+    }
     ensure_pipx_available()?;
-    install_packages(packages, python_version)?;
+    install_packages(config)?;
+    let bin_dir = ensure_bin_dir_on_path(config.bin_dir)?;
+    verify_installed(config.packages, bin_dir.as_deref())?;
     Ok(())
 }
 
 fn ensure_pipx_available() -> Result<()> {
-    if Command::new("pipx").arg("--version").output().is_ok() {
+    if pipx_command()
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success())
+    {
         debug!("pipx is already available");
         return Ok(());
     }
 
+    utils::offline::ensure_online("Installing pipx")?;
     info!("pipx not found, installing pipx");
-    install_pipx()?;
+    if utils::sudo::can_escalate() {
+        install_pipx()?;
+    } else {
+        install_pipx_user()?;
+    }
     Ok(())
 }
 
@@ -32,13 +79,13 @@ fn install_pipx() -> Result<()> {
 
 fn install_pipx_debian() -> Result<()> {
     debug!("Installing pipx on Debian-like system");
-    let mut cmd = utils::sudo::command("apt-get");
+    let mut cmd = package_manager::apt_command("apt-get");
     cmd.args(["update"]);
-    utils::subprocess::run_command(&mut cmd, "Update package lists")?;
+    utils::exec::run_command(&mut cmd, "Update package lists")?;
 
-    let mut cmd = utils::sudo::command("apt-get");
-    cmd.args(["install", "-y", "pipx"]);
-    utils::subprocess::run_command(&mut cmd, "Install pipx")?;
+    let mut cmd = package_manager::apt_install_command("apt-get");
+    cmd.arg("pipx");
+    utils::exec::run_command(&mut cmd, "Install pipx")?;
 
     Ok(())
 }
@@ -47,29 +94,267 @@ fn install_pipx_alpine() -> Result<()> {
     debug!("Installing pipx on Alpine Linux");
     let mut cmd = utils::sudo::command("apk");
     cmd.args(["add", "py3-pip", "python3"]);
-    utils::subprocess::run_command(&mut cmd, "Install Python and pip")?;
+    utils::exec::run_command(&mut cmd, "Install Python and pip")?;
 
     let mut cmd = Command::new("pip3");
     cmd.args(["install", "--user", "pipx"]);
-    utils::subprocess::run_command(&mut cmd, "Install pipx via pip")?;
+    utils::exec::run_command(&mut cmd, "Install pipx via pip")?;
 
     Ok(())
 }
 
-fn install_packages(packages: &[String], python_version: Option<&str>) -> Result<()> {
+/// Install pipx into ~/.local with pip when root privileges are unavailable
+fn install_pipx_user() -> Result<()> {
+    if !utils::user_mode::is_enabled() {
+        warn!("Not running as root and sudo is unavailable, installing pipx under ~/.local");
+    }
+    anyhow::ensure!(
+        which::which("python3").is_ok(),
+        "pipx and python3 are not installed, and installing them requires root privileges. \
+         Re-run as root or install python3 first."
+    );
+
+    let mut cmd = Command::new("python3");
+    cmd.args(["-m", "pip", "install", "--user", "pipx"]);
+    utils::exec::run_command(&mut cmd, "Install pipx via pip")?;
+    Ok(())
+}
+
+/// Run pipx from PATH, or as a module when it was installed with `pip --user`
+pub(crate) fn pipx_command() -> Command {
+    if which::which("pipx").is_ok() {
+        Command::new("pipx")
+    } else {
+        let mut cmd = Command::new("python3");
+        cmd.args(["-m", "pipx"]);
+        cmd
+    }
+}
+
+fn install_packages(config: &PipxConfig) -> Result<()> {
+    let packages = config.packages;
     debug!("Installing pipx packages: {:?}", packages);
 
     for package in packages {
-        let mut cmd = Command::new("pipx");
-        cmd.args(["install", package]);
+        let mut cmd = pipx_command();
+        cmd.arg("install");
+        if let Some(spec) = config.spec {
+            cmd.args(["--spec", spec]);
+        }
+        cmd.arg(package);
 
-        if let Some(version) = python_version {
+        if let Some(version) = config.python {
             cmd.args(["--python", version]);
         }
+        if let Some(pip_args) = config.pip_args {
+            cmd.arg(format!("--pip-args={}", pip_args));
+        }
+        if let Some(bin_dir) = config.bin_dir {
+            cmd.env("PIPX_BIN_DIR", bin_dir);
+        }
+        if utils::offline::is_enabled() {
+            // pip then installs only from --find-links directories, e.g. PIP_FIND_LINKS
+            cmd.env("PIP_NO_INDEX", "1");
+        }
 
-        utils::subprocess::run_command(&mut cmd, &format!("Install pipx package: {}", package))?;
+        utils::exec::run_streaming(&mut cmd, &format!("Install pipx package: {}", package))?;
     }
 
     info!("Successfully installed pipx packages: {:?}", packages);
     Ok(())
 }
+
+/// Put the pipx bin directory on PATH for later shells when it is not already there,
+/// returning the directory if that makes it resolvable
+fn ensure_bin_dir_on_path(bin_dir: Option<&str>) -> Result<Option<PathBuf>> {
+    let bin_dir = match bin_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let mut cmd = pipx_command();
+            cmd.args(["environment", "--value", "PIPX_BIN_DIR"]);
+            match cmd.output() {
+                Ok(output) if output.status.success() => {
+                    PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+                }
+                _ => {
+                    debug!("Could not determine the pipx bin directory");
+                    return Ok(None);
+                }
+            }
+        }
+    };
+
+    if utils::profile::is_on_path(&bin_dir) {
+        return Ok(None);
+    }
+    if !utils::sudo::can_escalate() && !utils::user_mode::is_enabled() {
+        warn!(
+            "{} is not in PATH and {} cannot be written without root privileges",
+            bin_dir.display(),
+            utils::env_registry::PROFILE
+        );
+        return Ok(None);
+    }
+    utils::env_registry::add_path(&bin_dir);
+    Ok(Some(bin_dir))
+}
+
+/// Run `--version` on the apps pipx exposed for each installed package
+fn verify_installed(packages: &[String], bin_dir: Option<&Path>) -> Result<()> {
+    let mut cmd = pipx_command();
+    cmd.args(["list", "--json"]);
+    let output = utils::exec::run_command(&mut cmd, "List pipx packages")?;
+    let list: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse pipx list output")?;
+
+    let mut apps = Vec::new();
+    for package in packages {
+        if URL_PREFIXES.iter().any(|p| package.starts_with(p)) || is_local_path(package) {
+            debug!(
+                "Cannot determine the venv for {}, skipping verification",
+                package
+            );
+            continue;
+        }
+        match pipx_apps(&list, requirement_name(package)) {
+            Some(found) => apps.extend(found),
+            None => warn!("pipx does not list {}, skipping verification", package),
+        }
+    }
+    utils::verify::verify_executables(&apps, bin_dir)
+}
+
+/// Apps of the venv whose name matches `package`, compared as normalized PyPI names
+fn pipx_apps(list: &serde_json::Value, package: &str) -> Option<Vec<String>> {
+    let normalize = |s: &str| s.to_lowercase().replace(['_', '.'], "-");
+    let (_, venv) = list["venvs"]
+        .as_object()?
+        .iter()
+        .find(|(name, _)| normalize(name) == normalize(package))?;
+    let apps = venv["metadata"]["main_package"]["apps"].as_array()?;
+    Some(
+        apps.iter()
+            .filter_map(|app| app.as_str().map(str::to_string))
+            .collect(),
+    )
+}
+
+/// Distribution name of a requirement: `black[d]>=24` -> `black`
+pub(crate) fn requirement_name(requirement: &str) -> &str {
+    let end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+        .unwrap_or(requirement.len());
+    &requirement[..end]
+}
+
+/// Validate a package given as a requirement, a VCS/archive URL, or a local path
+fn validate_package(package: &str) -> Result<()> {
+    if URL_PREFIXES.iter().any(|p| package.starts_with(p)) {
+        return Ok(());
+    }
+    if is_local_path(package) {
+        anyhow::ensure!(
+            Path::new(package).exists(),
+            "Local package path '{}' does not exist",
+            package
+        );
+        return Ok(());
+    }
+    validate_requirement(package)
+}
+
+fn is_local_path(package: &str) -> bool {
+    package.starts_with(['/', '.'])
+}
+
+/// Validate a requirement such as `httpie`, `black[d,jupyter]` or `ruff>=0.4,<0.5`
+fn validate_requirement(requirement: &str) -> Result<()> {
+    let name = requirement_name(requirement);
+    let mut rest = &requirement[name.len()..];
+
+    let valid = !name.is_empty()
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && {
+            if let Some(extras) = rest.strip_prefix('[') {
+                match extras.split_once(']') {
+                    Some((extras, after)) => {
+                        rest = after;
+                        extras.split(',').all(|e| {
+                            let e = e.trim();
+                            !e.is_empty()
+                                && e.chars()
+                                    .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+                        })
+                    }
+                    None => false,
+                }
+            } else {
+                true
+            }
+        }
+        && (rest.is_empty()
+            || rest
+                .trim_start()
+                .starts_with(['<', '>', '=', '!', '~', ';']));
+
+    anyhow::ensure!(
+        valid,
+        "Invalid Python package '{}'. Expected name, name[extras], or name==version",
+        requirement
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_package_accepts_urls_and_paths() {
+        assert!(validate_package("git+https://github.com/psf/black.git@24.1.0").is_ok());
+        assert!(validate_package("https://example.com/tool-1.0.tar.gz").is_ok());
+        assert!(validate_package(".").is_ok());
+        assert!(validate_package("./does-not-exist").is_err());
+    }
+
+    #[test]
+    fn pipx_apps_matches_normalized_venv_names() {
+        let list = serde_json::json!({
+            "venvs": {
+                "zope-interface": {"metadata": {"main_package": {"apps": []}}},
+                "black": {"metadata": {"main_package": {"apps": ["black", "blackd"]}}}
+            }
+        });
+        assert_eq!(
+            pipx_apps(&list, requirement_name("black[d]>=24")).unwrap(),
+            ["black", "blackd"]
+        );
+        assert!(pipx_apps(&list, "Zope_Interface").unwrap().is_empty());
+        assert!(pipx_apps(&list, "ruff").is_none());
+    }
+
+    #[test]
+    fn validate_requirement_accepts_pins_and_extras() {
+        for requirement in [
+            "httpie",
+            "black==24.1.0",
+            "black[d,jupyter]==24.1.0",
+            "ruff>=0.4,<0.5",
+            "poetry~=1.8",
+            "zope.interface",
+        ] {
+            assert!(validate_requirement(requirement).is_ok(), "{}", requirement);
+        }
+    }
+
+    #[test]
+    fn validate_requirement_rejects_malformed_specs() {
+        for requirement in ["", "--index-url", "black[d", "black[]", "black 24"] {
+            assert!(
+                validate_requirement(requirement).is_err(),
+                "{}",
+                requirement
+            );
+        }
+    }
+}
@@ -1,35 +1,57 @@
+mod pantry;
 mod resolver;
 
+use crate::utils;
 use anyhow::{Context, Result};
 use log::{debug, info, warn};
 use std::path::Path;
+use std::time::Duration;
 use std::{collections::HashMap, env};
 use tempfile::TempDir;
 
+/// How long a cached pantry is used before it is synced again, unless --pantry-max-age says
+/// otherwise
+pub const DEFAULT_PANTRY_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct PkgxConfig<'a> {
     pub tool: &'a str,
     pub version: &'a str,
     pub args: Vec<String>,
     pub working_dir: &'a str,
     pub env_vars: Vec<String>,
+    /// Sync the cached pantry again once it is older than this
+    pub pantry_max_age: Duration,
+    /// File to write the tool's stdout to, while still showing it
+    pub capture_output: Option<&'a str>,
+    /// Which of picolayer's environment variables the tool inherits
+    pub env_filter: utils::env::EnvFilter<'a>,
 }
 
 struct PkgxEnv {
     pkgx_dir: String,
     pantry_dir: String,
+    /// Whether the pantry is the one kept under the cache dir rather than a temporary one
+    cached_pantry: bool,
     _temp_dir: TempDir,
 }
 
 impl PkgxEnv {
     fn new() -> Result<Self> {
-        let temp_dir =
-            TempDir::with_prefix("picolayer_").context("Failed to create temporary directory")?;
+        let temp_dir = utils::tmp::tempdir()?;
 
         let pkgx_dir = temp_dir.path().join("pkgx").join("tools");
-        let pantry_dir = temp_dir.path().join("pkgx").join("pantry");
-
         std::fs::create_dir_all(&pkgx_dir).context("Failed to create pkgx directory")?;
-        std::fs::create_dir_all(&pantry_dir).context("Failed to create pantry directory")?;
+
+        // Caching the pantry is best effort: an unwritable cache dir only costs a sync
+        let (pantry_dir, cached_pantry) = match pantry::cached_dir() {
+            Ok(dir) => (dir, true),
+            Err(e) => {
+                debug!("Not caching the pkgx pantry: {:#}", e);
+                let dir = temp_dir.path().join("pkgx").join("pantry");
+                std::fs::create_dir_all(&dir).context("Failed to create pantry directory")?;
+                (dir, false)
+            }
+        };
 
         Ok(Self {
             pkgx_dir: pkgx_dir
@@ -40,12 +62,14 @@ impl PkgxEnv {
                 .to_str()
                 .context("Failed to convert pantry directory path to string")?
                 .to_string(),
+            cached_pantry,
             _temp_dir: temp_dir,
         })
     }
 }
 
 pub async fn execute(input: &PkgxConfig<'_>) -> Result<()> {
+    utils::offline::ensure_online("pkgx")?;
     validate_working_directory(input.working_dir)?;
     debug!("Working directory: {}", input.working_dir);
     debug!("Tool: {} ({})", input.tool, input.version);
@@ -69,15 +93,13 @@ pub async fn execute(input: &PkgxConfig<'_>) -> Result<()> {
         env::set_var("PKGX_PANTRY_DIR", &exec_env.pantry_dir);
     }
 
-    let result = execute_with_pkgx_library(
-        input.tool,
-        input.version,
-        &input.args,
-        working_path,
-        &env_map,
-        &exec_env,
-    )
-    .await;
+    if exec_env.cached_pantry
+        && let Err(e) = pantry::expire(input.pantry_max_age)
+    {
+        warn!("Failed to refresh the cached pkgx pantry: {:#}", e);
+    }
+
+    let result = execute_with_pkgx_library(input, working_path, &env_map, &exec_env).await;
 
     // Restore original environment variables
     unsafe {
@@ -121,8 +143,11 @@ fn create_command_env(
     env_map: &[(String, String)],
     pkgx_dir: &str,
     pantry_dir: &str,
+    env_filter: &utils::env::EnvFilter,
 ) -> HashMap<String, String> {
-    let mut cmd_env: HashMap<String, String> = env::vars().collect();
+    let mut cmd_env: HashMap<String, String> = env::vars()
+        .filter(|(name, _)| env_filter.keeps(name))
+        .collect();
 
     // https://docs.pkgx.sh/pkgx/pkgx#virtual-environments
     cmd_env.insert("PKGX_DIR".to_string(), pkgx_dir.to_string());
@@ -139,13 +164,12 @@ fn create_command_env(
 }
 
 async fn execute_with_pkgx_library(
-    tool_name: &str,
-    version_spec: &str,
-    args: &[String],
+    input: &PkgxConfig<'_>,
     working_path: &Path,
     env_map: &[(String, String)],
     exec_env: &PkgxEnv,
 ) -> Result<()> {
+    let (tool_name, version_spec) = (input.tool, input.version);
     info!("Using pkgx library integration with virtual environment");
 
     let project_name = resolver::resolve_tool_to_project(tool_name)
@@ -157,27 +181,25 @@ async fn execute_with_pkgx_library(
 
     match resolver::resolve_package_with_libpkgx(&[tool_spec]).await {
         Ok((pkgx_env, installations)) => {
-            let mut cmd_env = create_command_env(env_map, &exec_env.pkgx_dir, &exec_env.pantry_dir);
+            let mut cmd_env = create_command_env(
+                env_map,
+                &exec_env.pkgx_dir,
+                &exec_env.pantry_dir,
+                &input.env_filter,
+            );
             cmd_env.extend(pkgx_env);
 
             log_installations(&installations, &project_name, tool_name);
 
             debug!("Resolved package with libpkgx");
-            let status = std::process::Command::new(tool_name)
-                .args(args)
+            let mut cmd = std::process::Command::new(tool_name);
+            input.env_filter.apply_to(&mut cmd);
+            cmd.args(&input.args)
                 .current_dir(working_path.to_str().context("Invalid working directory")?)
-                .envs(&cmd_env)
-                .stdout(std::process::Stdio::inherit())
-                .stderr(std::process::Stdio::inherit())
-                .status()
-                .context("Failed to execute command with libpkgx")?;
-
-            if status.success() {
-                debug!("Command executed successfully with pkgx library!");
-                Ok(())
-            } else {
-                anyhow::bail!("Command failed with exit code: {:?}", status.code());
-            }
+                .envs(&cmd_env);
+            run_tool(&mut cmd, tool_name, input.capture_output)?;
+            debug!("Command executed successfully with pkgx library!");
+            Ok(())
         }
         Err(e) => {
             if resolver::check_pkgx_binary() {
@@ -185,15 +207,7 @@ async fn execute_with_pkgx_library(
                     "Failed to resolve package with libpkgx, falling back to pkgx binary: {}",
                     e
                 );
-                execute_with_pkgx_binary(
-                    tool_name,
-                    version_spec,
-                    args,
-                    working_path,
-                    env_map,
-                    exec_env,
-                )
-                .await
+                execute_with_pkgx_binary(input, working_path, env_map, exec_env).await
             } else {
                 anyhow::bail!(
                     "Failed to resolve package with libpkgx and no pkgx binary available: {}",
@@ -204,6 +218,30 @@ async fn execute_with_pkgx_library(
     }
 }
 
+/// Run the tool attached to the console, or with its stdout also written to `capture_output`
+/// for a caller that parses it. The file is written even when the tool fails.
+fn run_tool(
+    cmd: &mut std::process::Command,
+    tool_name: &str,
+    capture_output: Option<&str>,
+) -> Result<()> {
+    let exec = utils::exec::Exec::new(cmd, tool_name);
+    let Some(path) = capture_output else {
+        exec.inherit().run()?;
+        return Ok(());
+    };
+    let output = exec.streamed().output()?;
+    std::fs::write(path, &output.stdout)
+        .with_context(|| format!("Failed to write {} output to {}", tool_name, path))?;
+    debug!(
+        "Wrote {} bytes of {} output to {}",
+        output.stdout.len(),
+        tool_name,
+        path
+    );
+    utils::exec::ensure_success(output.status, tool_name)
+}
+
 fn log_installations(
     installations: &[libpkgx::types::Installation],
     project_name: &str,
@@ -225,13 +263,12 @@ fn log_installations(
 }
 
 async fn execute_with_pkgx_binary(
-    tool_name: &str,
-    version_spec: &str,
-    args: &[String],
+    input: &PkgxConfig<'_>,
     working_path: &Path,
     env_map: &[(String, String)],
     exec_env: &PkgxEnv,
 ) -> Result<()> {
+    let (tool_name, version_spec) = (input.tool, input.version);
     if !resolver::check_pkgx_binary() {
         anyhow::bail!("pkgx is not available. Install pkgx from https://pkgx.sh.");
     }
@@ -245,22 +282,16 @@ async fn execute_with_pkgx_binary(
     info!("Using pkgx binary with virtual environment");
 
     let mut cmd = std::process::Command::new("pkgx");
+    input.env_filter.apply_to(&mut cmd);
     cmd.arg(&project_arg)
         .arg(tool_name)
-        .args(args)
+        .args(&input.args)
         .current_dir(working_path.to_str().context("Invalid working directory")?)
         .env("PKGX_DIR", &exec_env.pkgx_dir)
         .env("PKGX_PANTRY_DIR", &exec_env.pantry_dir)
         .envs(env_map.iter().map(|(k, v)| (k.as_str(), v.as_str())));
 
-    let status = cmd
-        .status()
-        .context("Failed to execute command with pkgx")?;
-
-    if status.success() {
-        info!("Command executed successfully with pkgx binary!");
-        Ok(())
-    } else {
-        anyhow::bail!("Command failed with exit code: {:?}", status.code());
-    }
+    run_tool(&mut cmd, tool_name, input.capture_output)?;
+    info!("Command executed successfully with pkgx binary!");
+    Ok(())
 }
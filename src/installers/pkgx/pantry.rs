@@ -0,0 +1,101 @@
+//! The pkgx pantry, the database of packages pkgx can provide, kept under the cache dir so
+//! it is synced once and reused by later runs instead of being downloaded on every one.
+//! Tools themselves are still installed into a directory of each run's own.
+
+use crate::utils;
+use anyhow::{Context, Result};
+use libpkgx::config::Config;
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Directory under the cache dir holding the pantry and its sync stamp
+const CACHE_SUBDIR: &str = "pkgx-pantry";
+
+/// File next to the pantry whose modification time is when it was last synced
+const STAMP_FILE: &str = "synced";
+
+/// The pantry directory under the cache dir, created if needed
+pub(super) fn cached_dir() -> Result<PathBuf> {
+    let dir = utils::settings::cache_dir()
+        .join(CACHE_SUBDIR)
+        .join("pantry");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Remove a pantry synced more than `max_age` ago, or left incomplete by `cache prune`, so
+/// the next lookup syncs it afresh
+pub(super) fn expire(max_age: Duration) -> Result<()> {
+    let config = Config::new().context("Failed to initialize libpkgx config")?;
+    let (pantry_dir, db_file) = (&config.pantry_dir, &config.pantry_db_file);
+    if is_fresh(pantry_dir, db_file, max_age, SystemTime::now()) {
+        debug!("Reusing the pkgx pantry in {}", pantry_dir.display());
+        return Ok(());
+    }
+
+    if pantry_dir.join("projects").exists() {
+        info!(
+            "Cached pkgx pantry is older than {:?}, syncing it again",
+            max_age
+        );
+    }
+    if pantry_dir.exists() {
+        std::fs::remove_dir_all(pantry_dir)
+            .with_context(|| format!("Failed to remove {}", pantry_dir.display()))?;
+    }
+    std::fs::create_dir_all(pantry_dir)
+        .with_context(|| format!("Failed to create {}", pantry_dir.display()))?;
+    if db_file.exists() {
+        std::fs::remove_file(db_file)
+            .with_context(|| format!("Failed to remove {}", db_file.display()))?;
+    }
+    Ok(())
+}
+
+/// Record that the pantry was just synced
+pub(super) fn mark_synced(config: &Config) {
+    let stamp = stamp(&config.pantry_dir);
+    if let Err(e) = std::fs::write(&stamp, b"") {
+        debug!("Failed to write {}: {}", stamp.display(), e);
+    }
+}
+
+fn stamp(pantry_dir: &Path) -> PathBuf {
+    pantry_dir.with_file_name(STAMP_FILE)
+}
+
+/// Whether the pantry and its database are both present and were synced within `max_age`
+fn is_fresh(pantry_dir: &Path, db_file: &Path, max_age: Duration, now: SystemTime) -> bool {
+    let synced = std::fs::metadata(stamp(pantry_dir)).and_then(|m| m.modified());
+    pantry_dir.join("projects").is_dir()
+        && std::fs::metadata(db_file).is_ok_and(|m| m.len() > 0)
+        && synced.is_ok_and(|synced| now.duration_since(synced).unwrap_or_default() <= max_age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn pantry_is_fresh_only_when_complete_and_recent() {
+        let dir = tempfile::tempdir().unwrap();
+        let pantry_dir = dir.path().join("pantry");
+        let db_file = dir.path().join("pantry.db");
+        let now = SystemTime::now();
+        assert!(!is_fresh(&pantry_dir, &db_file, DAY, now));
+
+        std::fs::create_dir_all(pantry_dir.join("projects")).unwrap();
+        std::fs::write(&db_file, b"SQLite").unwrap();
+        assert!(!is_fresh(&pantry_dir, &db_file, DAY, now), "never synced");
+
+        std::fs::write(stamp(&pantry_dir), b"").unwrap();
+        assert!(is_fresh(&pantry_dir, &db_file, DAY, now));
+        assert!(!is_fresh(&pantry_dir, &db_file, DAY, now + DAY * 2));
+
+        std::fs::write(&db_file, b"").unwrap();
+        assert!(!is_fresh(&pantry_dir, &db_file, DAY, now), "empty database");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::PicolayerError;
 use anyhow::{Context, Result};
 use libpkgx::config::Config;
 use libpkgx::{
@@ -49,16 +50,7 @@ async fn resolve_dependencies_async(
     assert!(std::env::var("PKGX_PANTRY_DIR").is_ok());
 
     let config = Config::new().context("Failed to initialize libpkgx config")?;
-
-    std::fs::create_dir_all(config.pantry_db_file.parent().unwrap())?;
-    let mut conn = rusqlite::Connection::open(&config.pantry_db_file)?;
-
-    if sync::should(&config).map_err(|e| anyhow::anyhow!("{}", e))? {
-        info!("Syncing pkgx pantry database");
-        sync::ensure(&config, &mut conn)
-            .await
-            .map_err(|e| anyhow::anyhow!("{}", e))?;
-    }
+    let conn = open_pantry(&config).await?;
 
     let mut package_reqs = Vec::new();
     for dep in dependencies {
@@ -129,13 +121,25 @@ fn map_tool_to_project(tool_name: &str, conn: &rusqlite::Connection) -> Result<S
                 Ok(projects[0].clone())
             }
         }
-        Ok(_) => {
-            debug!(
-                "No project found for tool '{}' in pantry database, using tool name as project",
-                tool_name
-            );
-            Ok(tool_name.to_string())
-        }
+        // Project names such as nodejs.org are domains, and need not provide a program
+        Ok(_) if tool_name.contains('.') => Ok(tool_name.to_string()),
+        Ok(_) => match pantry_names(conn) {
+            Ok(names) => Err(PicolayerError::ToolNotFound {
+                tool: tool_name.to_string(),
+                similar: crate::error::closest(tool_name, names.iter().map(String::as_str), 5)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            }
+            .into()),
+            Err(e) => {
+                debug!(
+                    "No project found for tool '{}' in pantry database ({}), using tool name as project",
+                    tool_name, e
+                );
+                Ok(tool_name.to_string())
+            }
+        },
         Err(e) => {
             debug!(
                 "Failed to query pantry database for tool '{}': {}, using tool name as project",
@@ -146,23 +150,36 @@ fn map_tool_to_project(tool_name: &str, conn: &rusqlite::Connection) -> Result<S
     }
 }
 
+/// Every program and project in the pantry database, to suggest instead of an unknown tool
+fn pantry_names(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<String>> {
+    let mut statement =
+        conn.prepare("SELECT program FROM provides UNION SELECT project FROM provides")?;
+    let names = statement.query_map([], |row| row.get(0))?;
+    names.collect()
+}
+
 /// Resolve a tool name to a project name
 pub(super) async fn resolve_tool_to_project(tool_name: &str) -> Result<String> {
     assert!(std::env::var("PKGX_DIR").is_ok());
     assert!(std::env::var("PKGX_PANTRY_DIR").is_ok());
     let config = Config::new().context("Failed to initialize libpkgx config")?;
+    let conn = open_pantry(&config).await?;
+    map_tool_to_project(tool_name, &conn)
+}
+
+/// Open the pantry database, syncing the pantry first when it has not been yet
+async fn open_pantry(config: &Config) -> Result<rusqlite::Connection> {
     std::fs::create_dir_all(config.pantry_db_file.parent().unwrap())?;
     let mut conn = rusqlite::Connection::open(&config.pantry_db_file)?;
 
-    // Sync if needed
-    if sync::should(&config).map_err(|e| anyhow::anyhow!("{}", e))? {
+    if sync::should(config).map_err(|e| anyhow::anyhow!("{}", e))? {
         info!("Syncing pkgx pantry database");
-        sync::ensure(&config, &mut conn)
+        sync::ensure(config, &mut conn)
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
+        super::pantry::mark_synced(config);
     }
-
-    map_tool_to_project(tool_name, &conn)
+    Ok(conn)
 }
 
 /// Format tool spec for libpkgx (without + prefix)
@@ -195,3 +212,21 @@ pub(super) fn check_pkgx_binary() -> bool {
         .map(|s| s.success())
         .unwrap_or(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pantry_names_lists_programs_and_projects() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE provides (project TEXT, program TEXT);
+             INSERT INTO provides VALUES ('nodejs.org', 'node'), ('nodejs.org', 'npm');",
+        )
+        .unwrap();
+        let mut names = pantry_names(&conn).unwrap();
+        names.sort();
+        assert_eq!(names, ["node", "nodejs.org", "npm"]);
+    }
+}
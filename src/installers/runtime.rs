@@ -0,0 +1,400 @@
+use crate::cli::{RetryConfig, RuntimePreset};
+#[cfg(feature = "gh-release")]
+use crate::installers::gh_release;
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const NODE_INDEX_URL: &str = "https://nodejs.org/dist/index.json";
+const ZIG_INDEX_URL: &str = "https://ziglang.org/download/index.json";
+
+/// Directory that holds runtimes shipped as a full tree (node, zig)
+const RUNTIME_LIB_DIR: &str = "/usr/local/lib";
+
+pub struct RuntimeConfig<'a> {
+    pub runtime: RuntimePreset,
+    pub version: &'a str,
+    pub install_dir: &'a str,
+}
+
+pub async fn install(config: &RuntimeConfig<'_>, retry_config: &RetryConfig) -> Result<()> {
+    utils::offline::ensure_online("runtime")?;
+    let arch = std::env::consts::ARCH;
+    let os = std::env::consts::OS;
+
+    match config.runtime {
+        #[cfg(feature = "gh-release")]
+        RuntimePreset::Deno => {
+            let filter = deno_asset_filter(arch, os)?;
+            install_from_github(
+                "denoland",
+                "deno",
+                "deno",
+                &deno_tag(config.version),
+                &filter,
+                config,
+                retry_config,
+            )
+            .await
+        }
+        #[cfg(feature = "gh-release")]
+        RuntimePreset::Bun => {
+            let filter = bun_asset_filter(arch, os, utils::os::is_musl())?;
+            install_from_github(
+                "oven-sh",
+                "bun",
+                "bun",
+                &bun_tag(config.version),
+                &filter,
+                config,
+                retry_config,
+            )
+            .await
+        }
+        #[cfg(not(feature = "gh-release"))]
+        RuntimePreset::Deno | RuntimePreset::Bun => Err(super::not_built("gh-release")),
+        RuntimePreset::Node => install_node(config, arch, os, retry_config).await,
+        RuntimePreset::Zig => install_zig(config, arch, os).await,
+    }
+}
+
+#[cfg(feature = "gh-release")]
+async fn install_from_github(
+    owner: &str,
+    repo: &str,
+    binary: &str,
+    version: &str,
+    filter: &str,
+    config: &RuntimeConfig<'_>,
+    retry_config: &RetryConfig,
+) -> Result<()> {
+    gh_release::install(
+        &gh_release::GhReleaseConfig {
+            owner,
+            repo,
+            binary_names: &[binary.to_string()],
+            version,
+            tag_prefix: None,
+            install_dir: config.install_dir,
+            filter: Some(filter),
+            chown: None,
+            mode: None,
+            verify_checksum: false,
+            checksum_text: None,
+            checksum_algorithm: Default::default(),
+            gpg_key: None,
+            include_prerelease: false,
+            from_tag: false,
+            wait_for_release: None,
+        },
+        retry_config,
+    )
+    .await
+}
+
+#[cfg_attr(not(feature = "gh-release"), allow(dead_code))]
+fn deno_tag(version: &str) -> String {
+    match version {
+        "latest" => version.to_string(),
+        v => format!("v{}", v.trim_start_matches('v')),
+    }
+}
+
+#[cfg_attr(not(feature = "gh-release"), allow(dead_code))]
+fn bun_tag(version: &str) -> String {
+    match version {
+        "latest" => version.to_string(),
+        v => format!(
+            "bun-v{}",
+            v.trim_start_matches("bun-").trim_start_matches('v')
+        ),
+    }
+}
+
+#[cfg_attr(not(feature = "gh-release"), allow(dead_code))]
+fn deno_asset_filter(arch: &str, os: &str) -> Result<String> {
+    let target = match os {
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        _ => anyhow::bail!("deno preset does not support {}", os),
+    };
+    anyhow::ensure!(
+        matches!(arch, "x86_64" | "aarch64"),
+        "deno preset does not support architecture {}",
+        arch
+    );
+    Ok(format!(r"^deno-{}-{}\.zip$", arch, target))
+}
+
+#[cfg_attr(not(feature = "gh-release"), allow(dead_code))]
+fn bun_asset_filter(arch: &str, os: &str, musl: bool) -> Result<String> {
+    let os = match os {
+        "linux" => "linux",
+        "macos" => "darwin",
+        _ => anyhow::bail!("bun preset does not support {}", os),
+    };
+    let arch = match arch {
+        "x86_64" => "x64",
+        "aarch64" => "aarch64",
+        _ => anyhow::bail!("bun preset does not support architecture {}", arch),
+    };
+    let libc = if musl && os == "linux" { "-musl" } else { "" };
+    Ok(format!(r"^bun-{}-{}{}\.zip$", os, arch, libc))
+}
+
+async fn install_node(
+    config: &RuntimeConfig<'_>,
+    arch: &str,
+    os: &str,
+    retry_config: &RetryConfig,
+) -> Result<()> {
+    let node_os = match os {
+        "linux" => "linux",
+        "macos" => "darwin",
+        _ => anyhow::bail!("node preset does not support {}", os),
+    };
+    let node_arch = match arch {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        _ => anyhow::bail!("node preset does not support architecture {}", arch),
+    };
+
+    let index: serde_json::Value = utils::download::fetch_json(NODE_INDEX_URL).await?;
+    let version = resolve_node_version(&index, config.version)?;
+    let name = format!("node-{}-{}-{}", version, node_os, node_arch);
+    let url = format!("https://nodejs.org/dist/{}/{}.tar.xz", version, name);
+
+    preflight(config, None, &name)?;
+    info!("Installing Node.js {}", version);
+    let shasums_url = format!("https://nodejs.org/dist/{}/SHASUMS256.txt", version);
+    let batch = utils::scheduler::Batch::new(retry_config);
+    let (data, shasums) = tokio::try_join!(
+        batch.fetch(&name, || utils::download::fetch(&url)),
+        batch.fetch("SHASUMS256.txt", || utils::http_cache::fetch(&shasums_url)),
+    )?;
+    drop(batch);
+    let shasums =
+        String::from_utf8(shasums).context("Node.js SHASUMS256.txt is not valid UTF-8")?;
+    let expected = find_shasum(&shasums, &format!("{}.tar.xz", name))
+        .with_context(|| format!("No checksum published for {}.tar.xz", name))?;
+    let digests = utils::download::Digests::of(&data);
+    utils::download::verify_download(&url, &digests, expected)?;
+    utils::lock::record("nodejs", &version, &url, &digests)?;
+    let files = install_tree(
+        &data,
+        "nodejs",
+        &["bin/node", "bin/npm", "bin/npx", "bin/corepack"],
+        config.install_dir,
+    )?;
+    utils::state::record([utils::state::Installed::new("runtime", "node")
+        .version(&version)
+        .files(files)]);
+
+    // npm defaults its global prefix to the node tree, whose bin directory is not
+    // on PATH; point it at the parent of install_dir so globals land next to node
+    if let Some(prefix) = Path::new(config.install_dir).parent() {
+        let npmrc = Path::new(RUNTIME_LIB_DIR).join("nodejs/etc/npmrc");
+        fs::create_dir_all(npmrc.parent().context("Invalid npmrc path")?)?;
+        fs::write(&npmrc, format!("prefix={}\n", prefix.display()))
+            .with_context(|| format!("Failed to write {}", npmrc.display()))?;
+    }
+    Ok(())
+}
+
+/// Resolve "latest", "lts", a major/minor prefix ("20", "20.11") or an exact version against index.json
+fn resolve_node_version(index: &serde_json::Value, requested: &str) -> Result<String> {
+    let releases = index
+        .as_array()
+        .context("Unexpected Node.js release index format")?;
+    let requested = requested.trim_start_matches('v');
+
+    releases
+        .iter()
+        .find(|release| match requested {
+            "latest" => true,
+            "lts" => release["lts"].is_string(),
+            prefix => release["version"].as_str().is_some_and(|v| {
+                let v = v.trim_start_matches('v');
+                v == prefix || v.starts_with(&format!("{}.", prefix))
+            }),
+        })
+        .and_then(|release| release["version"].as_str())
+        .map(str::to_string)
+        .with_context(|| format!("Node.js version '{}' not found", requested))
+}
+
+async fn install_zig(config: &RuntimeConfig<'_>, arch: &str, os: &str) -> Result<()> {
+    let target = format!("{}-{}", arch, os);
+    let index: serde_json::Value = utils::download::fetch_json(ZIG_INDEX_URL).await?;
+    let version = resolve_zig_version(&index, config.version)?;
+
+    let build = &index[&version][&target];
+    let url = build["tarball"]
+        .as_str()
+        .with_context(|| format!("No zig {} build for {}", version, target))?;
+    let shasum = build["shasum"].as_str();
+    let size = build["size"].as_str().and_then(|size| size.parse().ok());
+
+    preflight(config, size, &format!("zig {}", version))?;
+    info!("Installing zig {}", version);
+    let data = utils::download::fetch(url).await?;
+    let digests = utils::download::Digests::of(&data);
+    if let Some(expected) = shasum {
+        utils::download::verify_download(url, &digests, expected)?;
+    } else {
+        warn!("No checksum published for zig {} ({})", version, target);
+    }
+    utils::lock::record("zig", &version, url, &digests)?;
+    let files = install_tree(&data, "zig", &["zig"], config.install_dir)?;
+    utils::state::record([utils::state::Installed::new("runtime", "zig")
+        .version(&version)
+        .files(files)]);
+    Ok(())
+}
+
+/// Resolve "latest" (newest stable release) or an exact version against the zig download index
+fn resolve_zig_version(index: &serde_json::Value, requested: &str) -> Result<String> {
+    let releases = index
+        .as_object()
+        .context("Unexpected zig release index format")?;
+
+    if requested != "latest" {
+        anyhow::ensure!(
+            releases.contains_key(requested),
+            "zig version '{}' not found",
+            requested
+        );
+        return Ok(requested.to_string());
+    }
+
+    releases
+        .keys()
+        .filter_map(|v| {
+            let parts: Vec<u64> = v
+                .split('.')
+                .map(|p| p.parse().ok())
+                .collect::<Option<_>>()?;
+            Some((parts, v))
+        })
+        .max()
+        .map(|(_, v)| v.clone())
+        .context("No stable zig release found")
+}
+
+/// Find the checksum for `file_name` in a `sha256sum`-style listing
+fn find_shasum<'a>(listing: &'a str, file_name: &str) -> Option<&'a str> {
+    listing.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim() == file_name).then_some(hash)
+    })
+}
+
+/// Fail before downloading a runtime of `size` bytes that cannot be unpacked into
+/// RUNTIME_LIB_DIR or linked into `install_dir`
+fn preflight(config: &RuntimeConfig<'_>, size: Option<u64>, what: &str) -> Result<()> {
+    utils::preflight::check(Path::new(RUNTIME_LIB_DIR), size, what)?;
+    utils::preflight::ensure_writable(Path::new(config.install_dir))
+}
+
+/// Unpack a tar.xz runtime into RUNTIME_LIB_DIR/<name>, dropping the top-level
+/// directory, and link its executables into `install_dir`. Returns the tree and the links.
+fn install_tree(
+    data: &[u8],
+    name: &str,
+    binaries: &[&str],
+    install_dir: &str,
+) -> Result<Vec<PathBuf>> {
+    let root = Path::new(RUNTIME_LIB_DIR).join(name);
+    if root.exists() {
+        fs::remove_dir_all(&root)
+            .with_context(|| format!("Failed to remove {}", root.display()))?;
+    }
+
+    let mut archive = tar::Archive::new(xz::read::XzDecoder::new(std::io::Cursor::new(data)));
+    utils::archive::unpack_stripped(&mut archive, &root)?;
+
+    fs::create_dir_all(install_dir)?;
+    let mut files = vec![root.clone()];
+    for binary in binaries {
+        let target = root.join(binary);
+        if !target.exists() {
+            continue;
+        }
+        let file_name = Path::new(binary)
+            .file_name()
+            .context("Invalid binary path")?;
+        let link = Path::new(install_dir).join(file_name);
+        if link.symlink_metadata().is_ok() {
+            fs::remove_file(&link)?;
+        }
+        std::os::unix::fs::symlink(&target, &link)
+            .with_context(|| format!("Failed to link {}", link.display()))?;
+        info!("Installed: {} -> {}", link.display(), target.display());
+        files.push(link);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_filters_match_release_layouts() {
+        let deno = regex::Regex::new(&deno_asset_filter("x86_64", "linux").unwrap()).unwrap();
+        assert!(deno.is_match("deno-x86_64-unknown-linux-gnu.zip"));
+        assert!(!deno.is_match("denort-x86_64-unknown-linux-gnu.zip"));
+
+        let bun = regex::Regex::new(&bun_asset_filter("x86_64", "linux", true).unwrap()).unwrap();
+        assert!(bun.is_match("bun-linux-x64-musl.zip"));
+        assert!(!bun.is_match("bun-linux-x64-musl-baseline.zip"));
+    }
+
+    #[test]
+    fn version_tags_follow_project_conventions() {
+        assert_eq!(deno_tag("2.1.4"), "v2.1.4");
+        assert_eq!(deno_tag("latest"), "latest");
+        assert_eq!(bun_tag("1.1.38"), "bun-v1.1.38");
+        assert_eq!(bun_tag("v1.1.38"), "bun-v1.1.38");
+    }
+
+    #[test]
+    fn resolve_node_version_supports_aliases_and_prefixes() {
+        let index = serde_json::json!([
+            {"version": "v23.1.0", "lts": false},
+            {"version": "v22.11.0", "lts": "Jod"},
+            {"version": "v20.18.0", "lts": "Iron"},
+            {"version": "v2.0.0", "lts": false}
+        ]);
+        assert_eq!(resolve_node_version(&index, "latest").unwrap(), "v23.1.0");
+        assert_eq!(resolve_node_version(&index, "lts").unwrap(), "v22.11.0");
+        assert_eq!(resolve_node_version(&index, "20").unwrap(), "v20.18.0");
+        assert_eq!(resolve_node_version(&index, "2").unwrap(), "v2.0.0");
+        assert!(resolve_node_version(&index, "19").is_err());
+    }
+
+    #[test]
+    fn resolve_zig_version_picks_newest_stable() {
+        let index = serde_json::json!({
+            "master": {}, "0.9.1": {}, "0.13.0": {}, "0.12.1": {}
+        });
+        assert_eq!(resolve_zig_version(&index, "latest").unwrap(), "0.13.0");
+        assert_eq!(resolve_zig_version(&index, "0.12.1").unwrap(), "0.12.1");
+        assert!(resolve_zig_version(&index, "0.1.0").is_err());
+    }
+
+    #[test]
+    fn find_shasum_matches_exact_file_name() {
+        let listing = "aaa  node-v22.11.0-linux-x64.tar.gz\nbbb  node-v22.11.0-linux-x64.tar.xz\n";
+        assert_eq!(
+            find_shasum(listing, "node-v22.11.0-linux-x64.tar.xz"),
+            Some("bbb")
+        );
+        assert_eq!(
+            find_shasum(listing, "node-v22.11.0-linux-arm64.tar.xz"),
+            None
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::cli::RetryConfig;
+use crate::installers::gh_release;
+use crate::utils::{self, exec};
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use log::info;
+use std::fs;
+use std::io::{BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const OWNER: &str = "skevetter";
+const REPO: &str = "picolayer";
+const BINARY: &str = "picolayer";
+
+pub struct SelfUpdateConfig<'a> {
+    /// Release tag to install, or `latest`
+    pub version: &'a str,
+}
+
+pub async fn update(config: &SelfUpdateConfig<'_>, retry_config: &RetryConfig) -> Result<()> {
+    utils::offline::ensure_online("self-update")?;
+    let current = env!("CARGO_PKG_VERSION");
+    if config.version.trim_start_matches('v') == current {
+        info!("picolayer {} is already installed", current);
+        return Ok(());
+    }
+
+    let target = release_target(std::env::consts::ARCH, std::env::consts::OS)?;
+    let filter = format!("^{}$", regex::escape(&asset_name(&target)));
+    let asset = gh_release::download(
+        OWNER,
+        REPO,
+        config.version,
+        Some(&filter),
+        true,
+        retry_config,
+    )
+    .await?;
+    if asset.tag_name.trim_start_matches('v') == current {
+        info!("picolayer {} is already the latest version", current);
+        return Ok(());
+    }
+
+    let binary = extract_binary(BufReader::new(asset.file.open()?))
+        .with_context(|| format!("Failed to extract {} from {}", BINARY, asset.name))?;
+    let exe = std::env::current_exe()
+        .and_then(fs::canonicalize)
+        .context("Failed to locate the running picolayer binary")?;
+    replace_binary(&exe, &binary)?;
+
+    info!(
+        "Updated picolayer from {} to {} at {}",
+        current,
+        asset.tag_name,
+        exe.display()
+    );
+    Ok(())
+}
+
+/// Rust target triple used in release archive names, e.g. `x86_64-unknown-linux-gnu`
+fn release_target(arch: &str, os: &str) -> Result<String> {
+    let arch = match arch {
+        "x86_64" | "aarch64" => arch,
+        _ => anyhow::bail!(
+            "No picolayer release is published for architecture {}",
+            arch
+        ),
+    };
+    let os = match os {
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        _ => anyhow::bail!("No picolayer release is published for {}", os),
+    };
+    Ok(format!("{}-{}", arch, os))
+}
+
+fn asset_name(target: &str) -> String {
+    format!("{}-{}.tar.gz", BINARY, target)
+}
+
+/// Read the picolayer binary out of a release archive
+fn extract_binary(archive: impl Read) -> Result<Vec<u8>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name().is_some_and(|name| name == BINARY) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            return Ok(data);
+        }
+    }
+    anyhow::bail!("Release archive does not contain {}", BINARY)
+}
+
+/// Swap in the new binary with a rename, so the running binary is never left half-written
+fn replace_binary(exe: &Path, binary: &[u8]) -> Result<()> {
+    let dir = exe.parent().context("Binary has no parent directory")?;
+    // Closed before it runs, since executing a file that is open for writing fails
+    let staging = tempfile::Builder::new()
+        .prefix(".picolayer-update")
+        .tempfile()?
+        .into_temp_path();
+    fs::write(&staging, binary)?;
+    fs::set_permissions(&staging, fs::Permissions::from_mode(0o755))?;
+
+    // Refuse to install a binary that cannot run on this host
+    exec::run_command(
+        Command::new(&*staging).arg("--version"),
+        "new picolayer --version",
+    )?;
+
+    let staged = staged_path(exe);
+    if is_writable(dir) {
+        fs::copy(&*staging, &staged)
+            .with_context(|| format!("Failed to write {}", staged.display()))?;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+        return fs::rename(&staged, exe)
+            .with_context(|| format!("Failed to replace {}", exe.display()));
+    }
+
+    utils::sudo::ensure_privileges(&format!("Replacing {}", exe.display()))?;
+    exec::run_command(
+        utils::sudo::command("install")
+            .args(["-m", "755"])
+            .arg(&*staging)
+            .arg(&staged),
+        "install new picolayer binary",
+    )?;
+    exec::run_command(
+        utils::sudo::command("mv").arg("-f").arg(&staged).arg(exe),
+        "replace picolayer binary",
+    )?;
+    Ok(())
+}
+
+/// Staging path next to the binary, on the same filesystem so the final rename is atomic
+fn staged_path(exe: &Path) -> PathBuf {
+    let name = exe.file_name().unwrap_or_default().to_string_lossy();
+    exe.with_file_name(format!(".{}.new", name))
+}
+
+fn is_writable(dir: &Path) -> bool {
+    tempfile::tempfile_in(dir).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    #[test]
+    fn release_target_matches_release_assets() {
+        assert_eq!(
+            asset_name(&release_target("x86_64", "linux").unwrap()),
+            "picolayer-x86_64-unknown-linux-gnu.tar.gz"
+        );
+        assert_eq!(
+            release_target("aarch64", "macos").unwrap(),
+            "aarch64-apple-darwin"
+        );
+        assert!(release_target("riscv64", "linux").is_err());
+        assert!(release_target("x86_64", "windows").is_err());
+    }
+
+    #[test]
+    fn extract_binary_reads_picolayer_entry() {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let contents = b"#!/bin/sh\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "picolayer", &contents[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(extract_binary(&archive[..]).unwrap(), contents);
+        assert!(extract_binary(&[][..]).is_err());
+    }
+
+    #[test]
+    fn staged_path_is_hidden_sibling() {
+        assert_eq!(
+            staged_path(Path::new("/usr/local/bin/picolayer")),
+            Path::new("/usr/local/bin/.picolayer.new")
+        );
+    }
+}
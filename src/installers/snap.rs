@@ -0,0 +1,84 @@
+use crate::utils;
+use anyhow::Result;
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+
+const SNAPD_SOCKET: &str = "/run/snapd.socket";
+const CONTAINER_MARKERS: &[&str] = &["/.dockerenv", "/run/.containerenv"];
+
+pub fn install(packages: &[String], channel: Option<&str>, classic: bool) -> Result<()> {
+    utils::offline::ensure_online("snap")?;
+    ensure_snapd_available()?;
+    install_packages(packages, channel, classic)?;
+    Ok(())
+}
+
+/// snapd needs systemd and a running daemon, which containers typically lack
+fn ensure_snapd_available() -> Result<()> {
+    utils::sudo::ensure_privileges("Installing snaps")?;
+
+    if let Some(marker) = CONTAINER_MARKERS.iter().find(|m| Path::new(m).exists()) {
+        anyhow::bail!(
+            "snap is not supported inside containers (found {}). Use a VM or host with snapd running.",
+            marker
+        );
+    }
+
+    anyhow::ensure!(
+        which::which("snap").is_ok(),
+        "snap command not found. Install snapd before installing snaps."
+    );
+
+    anyhow::ensure!(
+        Path::new(SNAPD_SOCKET).exists(),
+        "snapd is not running ({} not found). Start it with 'systemctl start snapd.socket'.",
+        SNAPD_SOCKET
+    );
+
+    debug!("snapd is available");
+    Ok(())
+}
+
+fn install_packages(packages: &[String], channel: Option<&str>, classic: bool) -> Result<()> {
+    for package in packages {
+        info!("Installing snap: {}", package);
+        let mut cmd = snap_install_command(package, channel, classic);
+        utils::exec::run_command(&mut cmd, &format!("Install snap {}", package))?;
+    }
+
+    info!("Successfully installed snaps: {:?}", packages);
+    Ok(())
+}
+
+fn snap_install_command(package: &str, channel: Option<&str>, classic: bool) -> Command {
+    let mut cmd = utils::sudo::command("snap");
+    cmd.args(["install", package]);
+    if let Some(channel) = channel {
+        cmd.arg(format!("--channel={}", channel));
+    }
+    if classic {
+        cmd.arg("--classic");
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snap_install_command_includes_channel_and_classic() {
+        let cmd = snap_install_command("code", Some("latest/stable"), true);
+        let args: Vec<_> = cmd
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
+        assert!(args.ends_with(&[
+            "install".to_string(),
+            "code".to_string(),
+            "--channel=latest/stable".to_string(),
+            "--classic".to_string(),
+        ]));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::installers::{npm, package_manager, pipx};
+use crate::utils::{
+    self,
+    state::{Installed, State},
+};
+use anyhow::{Context, Result};
+use log::{error, info};
+use std::path::Path;
+use std::process::Command;
+
+/// Remove what picolayer installed under each name, as recorded in the state database
+pub fn uninstall(names: &[String]) -> Result<()> {
+    let _lock = utils::run_lock::acquire();
+    let mut state = State::load()?;
+    let mut entries = Vec::new();
+    for name in names {
+        let removed = state.remove(name);
+        anyhow::ensure!(
+            !removed.is_empty(),
+            "'{}' was not installed by picolayer (see `picolayer list`)",
+            name
+        );
+        entries.extend(removed);
+    }
+
+    let mut failed = Vec::new();
+    for entry in entries {
+        info!("Uninstalling {} ({})", entry.name, entry.kind);
+        if let Err(e) = remove(&entry) {
+            error!("Failed to uninstall {}: {:#}", entry.name, e);
+            failed.push(entry);
+        }
+    }
+
+    // Entries that could not be removed stay recorded so a later uninstall can retry
+    let failures = failed.len();
+    state.installed.extend(failed);
+    state.save()?;
+    anyhow::ensure!(failures == 0, "{} uninstall(s) failed", failures);
+    Ok(())
+}
+
+fn remove(entry: &Installed) -> Result<()> {
+    let name = entry.name.as_str();
+    match entry.kind.as_str() {
+        "gh-release" | "runtime" | "jdk" | "helm-plugin" | "venv" => remove_files(entry),
+        "apt-get" | "apt" | "aptitude" | "apk" | "brew" => {
+            package_manager::remove(&entry.kind, std::slice::from_ref(&entry.name))
+        }
+        "npm" => run(
+            Command::new("npm").args(["uninstall", "-g", npm::package_name(name)]),
+            entry,
+        ),
+        "pipx" => run(
+            pipx::pipx_command().args(["uninstall", pipx::requirement_name(name)]),
+            entry,
+        ),
+        "gem" => run(
+            utils::sudo::command("gem").args(["uninstall", "-a", "-x", spec_name(name)]),
+            entry,
+        ),
+        "snap" => run(utils::sudo::command("snap").args(["remove", name]), entry),
+        "flatpak" => run(
+            utils::sudo::command("flatpak").args(["uninstall", "-y", "--noninteractive", name]),
+            entry,
+        ),
+        "luarocks" => run(
+            utils::sudo::command("luarocks").args(["remove", spec_name(name)]),
+            entry,
+        ),
+        kind => anyhow::bail!(
+            "{} installs cannot be removed automatically; rebuild the image without {}",
+            kind,
+            name
+        ),
+    }
+}
+
+fn run(cmd: &mut Command, entry: &Installed) -> Result<()> {
+    utils::exec::run_streaming(cmd, &format!("Uninstall {} {}", entry.kind, entry.name))
+}
+
+/// Strip a version pin: `fpm:1.15.1` or `luacheck@1.2.0` -> the package name
+fn spec_name(spec: &str) -> &str {
+    spec.split([':', '@']).next().unwrap_or(spec)
+}
+
+/// Delete the files and directories recorded for an install
+fn remove_files(entry: &Installed) -> Result<()> {
+    anyhow::ensure!(
+        !entry.files.is_empty(),
+        "No files were recorded for {}",
+        entry.name
+    );
+    for file in &entry.files {
+        let path = Path::new(&file.path);
+        let Ok(metadata) = path.symlink_metadata() else {
+            continue;
+        };
+        let removed = if metadata.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        if removed.is_err() {
+            // Installs under /usr/local and /opt are usually root-owned
+            utils::exec::run_command(
+                utils::sudo::command("rm").arg("-rf").arg(path),
+                &format!("rm -rf {}", path.display()),
+            )
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        info!("Removed {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_name_strips_versions() {
+        assert_eq!(spec_name("fpm:1.15.1"), "fpm");
+        assert_eq!(spec_name("luacheck@1.2.0"), "luacheck");
+        assert_eq!(spec_name("rake"), "rake");
+    }
+
+    #[test]
+    fn remove_files_deletes_recorded_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        std::fs::create_dir_all(tree.join("bin")).unwrap();
+        let link = dir.path().join("tool");
+        std::os::unix::fs::symlink(tree.join("bin/tool"), &link).unwrap();
+
+        let entry = Installed::new("runtime", "tool").files([&tree, &link]);
+        remove_files(&entry).unwrap();
+        assert!(!tree.exists());
+        assert!(link.symlink_metadata().is_err());
+    }
+}
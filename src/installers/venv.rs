@@ -0,0 +1,132 @@
+use crate::utils;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+
+pub struct VenvConfig<'a> {
+    /// Directory the virtualenv is created in
+    pub path: &'a str,
+    /// requirements.txt to install into the virtualenv
+    pub requirements: Option<&'a str>,
+    /// Python version (3.12) or interpreter (python3.12, /usr/bin/python3)
+    pub python: Option<&'a str>,
+    /// Fail unless every requirement is pinned with --hash
+    pub require_hashes: bool,
+}
+
+pub fn install(config: &VenvConfig) -> Result<()> {
+    let python = interpreter(config.python);
+    anyhow::ensure!(
+        which::which(&python).is_ok(),
+        "{} not found in PATH. Install Python first (e.g. picolayer apt-get python3-venv).",
+        python
+    );
+    if let Some(requirements) = config.requirements {
+        anyhow::ensure!(
+            Path::new(requirements).is_file(),
+            "Requirements file '{}' does not exist",
+            requirements
+        );
+    }
+
+    let venv = Path::new(config.path);
+    info!("Creating virtualenv at {} with {}", venv.display(), python);
+    let mut cmd = Command::new(&python);
+    cmd.args(["-m", "venv"]);
+    cmd.arg(venv);
+    utils::exec::run_command(&mut cmd, "Create virtualenv").context(
+        "Creating the virtualenv failed. On Debian/Ubuntu the python3-venv package is required.",
+    )?;
+
+    if let Some(requirements) = config.requirements {
+        debug!("Installing requirements from {}", requirements);
+        let mut cmd = Command::new(venv.join("bin").join("pip"));
+        cmd.env("PIP_DISABLE_PIP_VERSION_CHECK", "1");
+        cmd.args([
+            "install",
+            "--no-cache-dir",
+            "--no-compile",
+            "-r",
+            requirements,
+        ]);
+        if config.require_hashes {
+            cmd.arg("--require-hashes");
+        }
+        if utils::offline::is_enabled() {
+            // pip then installs only from --find-links directories, e.g. PIP_FIND_LINKS
+            cmd.env("PIP_NO_INDEX", "1");
+        }
+        utils::exec::run_streaming(
+            &mut cmd,
+            &format!("Install requirements from {}", requirements),
+        )?;
+    }
+
+    let removed = strip_bytecode(venv)?;
+    debug!("Removed {} bytecode cache directories", removed);
+
+    info!("Virtualenv ready at {}", venv.display());
+    utils::state::record([utils::state::Installed::new("venv", config.path).files([venv])]);
+    Ok(())
+}
+
+/// Map `3.12` to `python3.12`; interpreter names and paths are used as given
+fn interpreter(python: Option<&str>) -> String {
+    match python {
+        None => "python3".to_string(),
+        Some(version) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+            format!("python{}", version)
+        }
+        Some(interpreter) => interpreter.to_string(),
+    }
+}
+
+/// Remove `__pycache__` directories; Python regenerates them on demand
+fn strip_bytecode(dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if !file_type.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_name() == "__pycache__" {
+            std::fs::remove_dir_all(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            removed += 1;
+        } else {
+            removed += strip_bytecode(&path)?;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpreter_accepts_versions_and_names() {
+        assert_eq!(interpreter(None), "python3");
+        assert_eq!(interpreter(Some("3.12")), "python3.12");
+        assert_eq!(interpreter(Some("pypy3")), "pypy3");
+        assert_eq!(interpreter(Some("/usr/bin/python3")), "/usr/bin/python3");
+    }
+
+    #[test]
+    fn strip_bytecode_removes_nested_pycache() {
+        let dir = tempfile::tempdir().unwrap();
+        let pkg = dir.path().join("lib/python3.12/site-packages/pkg");
+        std::fs::create_dir_all(pkg.join("__pycache__")).unwrap();
+        std::fs::write(pkg.join("__pycache__/mod.cpython-312.pyc"), b"").unwrap();
+        std::fs::write(pkg.join("mod.py"), b"").unwrap();
+
+        assert_eq!(strip_bytecode(dir.path()).unwrap(), 1);
+        assert!(!pkg.join("__pycache__").exists());
+        assert!(pkg.join("mod.py").exists());
+    }
+}
@@ -1,6 +1,49 @@
+//! Picolayer's installers as a library, for tools that build images or provision
+//! machines without shelling out to the `picolayer` binary.
+//!
+//! Each installer takes a config struct, and most configs implement [`Installer`]:
+//!
+//! ```no_run
+//! use picolayer::installers::gh_release::GhReleaseConfig;
+//! use picolayer::utils::policy::ChecksumAlgorithm;
+//! use picolayer::{Installer, RetryConfig};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let binaries = ["gh".to_string()];
+//! let config = GhReleaseConfig {
+//!     owner: "cli",
+//!     repo: "cli",
+//!     binary_names: &binaries,
+//!     version: "latest",
+//!     tag_prefix: None,
+//!     install_dir: "/usr/local/bin",
+//!     filter: None,
+//!     chown: None,
+//!     mode: None,
+//!     verify_checksum: true,
+//!     checksum_text: None,
+//!     checksum_algorithm: ChecksumAlgorithm::Sha256,
+//!     gpg_key: None,
+//!     include_prerelease: false,
+//!     from_tag: false,
+//!     wait_for_release: None,
+//! };
+//! config.install(&RetryConfig::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The gh-release, pkgx and devcontainer installers are behind Cargo features of the
+//! same names, all enabled by the default `full` feature.
+//!
+//! Downloads use rustls; install a process-wide `rustls::crypto::CryptoProvider`
+//! before the first install, as the binary does.
+
 pub mod cli;
 pub mod error;
 pub mod installers;
 pub mod utils;
 
-pub use error::PicolayerError;
+pub use cli::RetryConfig;
+pub use error::{ErrorKind, PicolayerError};
+pub use installers::Installer;
@@ -1,12 +1,8 @@
-mod cli;
-mod error;
-mod installers;
-mod utils;
-
 use anyhow::{Context, Result};
 use clap::Parser;
-use error::PicolayerError;
 use log::info;
+use picolayer::error::Report;
+use picolayer::{ErrorKind, cli, utils};
 use std::process;
 
 #[tokio::main]
@@ -16,14 +12,13 @@ async fn main() {
         .expect("Failed to install default CryptoProvider");
 
     if let Err(e) = run().await {
-        let picolayer_error: PicolayerError = e.into();
-        eprintln!("{}", picolayer_error);
-        process::exit(1);
+        eprintln!("{}", Report(&e));
+        process::exit(ErrorKind::of(&e).exit_code());
     }
 }
 
 async fn run() -> Result<()> {
-    let cli = cli::Cli::parse();
+    let cli = cli::Cli::parse_from(utils::env::expand_args(std::env::args_os())?);
     utils::logging::init_logging(cli.verbose, cli.quiet).context("Failed to initialize logging")?;
     info!("Starting picolayer");
     cli::run(cli).await?;
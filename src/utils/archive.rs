@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Unpack a tar archive into `dest`, dropping its top-level directory
+/// (e.g. `jdk-21.0.5+11/bin/java` becomes `dest/bin/java`).
+///
+/// Entries that would escape `dest` are skipped.
+pub fn unpack_stripped<R: Read>(archive: &mut tar::Archive<R>, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    archive.set_preserve_permissions(true);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        let Some(relative) = strip_top_level(&path) else {
+            continue;
+        };
+        let target = dest.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry
+            .unpack(&target)
+            .with_context(|| format!("Failed to extract {}", target.display()))?;
+    }
+    Ok(())
+}
+
+/// Drop the archive's top-level directory, rejecting entries that would escape the destination
+fn strip_top_level(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    components.next()?;
+    let relative: PathBuf = components.collect();
+    let safe = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    (safe && !relative.as_os_str().is_empty()).then_some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_top_level_rejects_escaping_paths() {
+        assert_eq!(
+            strip_top_level(Path::new("node-v22/bin/node")),
+            Some(PathBuf::from("bin/node"))
+        );
+        assert_eq!(strip_top_level(Path::new("node-v22")), None);
+        assert_eq!(strip_top_level(Path::new("node-v22/../etc/passwd")), None);
+    }
+}
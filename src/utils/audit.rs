@@ -0,0 +1,217 @@
+use super::download::Digests;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How a downloaded artifact was checked, e.g. `sha256`, `checksum-file`, `gpg`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verification {
+    pub method: String,
+    /// The hash algorithm that checked the artifact, when a checksum did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    pub result: VerificationResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationResult {
+    Passed,
+    Failed,
+    /// Nothing to verify against was published or requested
+    Unverified,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub command: String,
+    /// Manifest step, when run by `apply`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<usize>,
+    pub url: String,
+    /// What the artifact is, e.g. owner/repo; unknown when verification failed first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub sha256: String,
+    pub size: u64,
+    pub verification: Verification,
+}
+
+struct AuditLog {
+    file: File,
+    command: String,
+    /// Verification methods that passed, and the algorithm they used, by URL, until the
+    /// artifact is recorded
+    verified: HashMap<String, (&'static str, Option<String>)>,
+}
+
+static LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+/// Append an entry for every artifact fetched from now on to `path`
+pub fn enable(path: &Path, command: &str) -> Result<()> {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+    *LOG.lock().unwrap() = Some(AuditLog {
+        file,
+        command: command.to_string(),
+        verified: HashMap::new(),
+    });
+    Ok(())
+}
+
+/// Read the entries of an audit log
+pub fn read(path: &Path) -> Result<Vec<AuditEntry>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read audit log {}", path.display()))?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid entry on line {} of {}", i + 1, path.display()))
+        })
+        .collect()
+}
+
+/// Note that the content at `url` passed verification; its entry is written when it is recorded
+pub fn verified(url: &str, method: &'static str) {
+    if let Some(log) = LOG.lock().unwrap().as_mut() {
+        log.verified.insert(url.to_string(), (method, None));
+    }
+}
+
+/// Like [`verified`], noting the hash algorithm that checked the content
+pub fn verified_with(url: &str, method: &'static str, algorithm: &str) {
+    if let Some(log) = LOG.lock().unwrap().as_mut() {
+        log.verified
+            .insert(url.to_string(), (method, Some(algorithm.to_string())));
+    }
+}
+
+/// Log an artifact that failed verification
+pub fn verification_failed(url: &str, digests: &Digests, method: &'static str) {
+    append(
+        url,
+        None,
+        digests,
+        Verification {
+            method: method.to_string(),
+            algorithm: None,
+            result: VerificationResult::Failed,
+        },
+    );
+}
+
+/// Log a fetched artifact, with the verification noted for its URL if any
+pub fn fetched(name: &str, version: &str, url: &str, digests: &Digests) {
+    let method = LOG
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|log| log.verified.remove(url));
+    let verification = match method {
+        Some((method, algorithm)) => Verification {
+            method: method.to_string(),
+            algorithm,
+            result: VerificationResult::Passed,
+        },
+        None => Verification {
+            method: "none".to_string(),
+            algorithm: None,
+            result: VerificationResult::Unverified,
+        },
+    };
+    append(url, Some((name, version)), digests, verification);
+}
+
+/// Best effort: a failure to write the log is reported and never fails the install
+fn append(
+    url: &str,
+    artifact: Option<(&str, &str)>,
+    digests: &Digests,
+    verification: Verification,
+) {
+    let mut guard = LOG.lock().unwrap();
+    let Some(log) = guard.as_mut() else {
+        return;
+    };
+
+    let step = super::lock::current_step();
+    let entry = AuditEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        command: step
+            .as_ref()
+            .map(|(_, command)| command.clone())
+            .unwrap_or_else(|| log.command.clone()),
+        step: step.map(|(step, _)| step),
+        url: url.to_string(),
+        name: artifact.map(|(name, _)| name.to_string()),
+        version: artifact.map(|(_, version)| version.to_string()),
+        sha256: digests.sha256.clone(),
+        size: digests.size,
+        verification,
+    };
+    debug!("Auditing {} ({})", entry.url, entry.sha256);
+
+    let written = serde_json::to_string(&entry)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| Ok(writeln!(log.file, "{}", line)?));
+    if let Err(e) = written {
+        warn!("Failed to write audit log entry for {}: {:#}", url, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_through_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        enable(&path, "gh-release").unwrap();
+
+        verified_with("https://example.com/tool.tar.gz", "checksum-file", "sha512");
+        fetched(
+            "owner/tool",
+            "v1.0.0",
+            "https://example.com/tool.tar.gz",
+            &Digests::of(b"hello"),
+        );
+        verification_failed("https://example.com/other", &Digests::of(b"x"), "gpg");
+        *LOG.lock().unwrap() = None;
+
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command, "gh-release");
+        assert_eq!(entries[0].name.as_deref(), Some("owner/tool"));
+        assert_eq!(entries[0].size, 5);
+        assert_eq!(
+            entries[0].sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(entries[0].verification.method, "checksum-file");
+        assert_eq!(entries[0].verification.algorithm.as_deref(), Some("sha512"));
+        assert_eq!(entries[0].verification.result, VerificationResult::Passed);
+        assert_eq!(entries[1].name, None);
+        assert_eq!(entries[1].verification.result, VerificationResult::Failed);
+    }
+}
@@ -0,0 +1,31 @@
+//! Collapsible log groups for CI systems that render them.
+//!
+//! GitHub Actions folds everything between `::group::<title>` and `::endgroup::` into one
+//! line of the job log, so a long install can be expanded only when it is of interest.
+//! Elsewhere groups print nothing.
+
+/// Whether the job log understands group markers
+pub fn enabled() -> bool {
+    std::env::var("GITHUB_ACTIONS").is_ok_and(|val| val == "true")
+}
+
+/// An open group, closed when dropped
+pub struct Group(bool);
+
+/// Open a group titled `title`. Inside a manifest step the step is already the group,
+/// and groups do not nest, so this opens nothing there.
+pub fn group(title: &str) -> Group {
+    let open = enabled() && super::lock::current_step().is_none();
+    if open {
+        eprintln!("::group::{}", title);
+    }
+    Group(open)
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        if self.0 {
+            eprintln!("::endgroup::");
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use crate::error::{Permanent, PicolayerError};
+use anyhow::{Context as _, Result};
+use blake2::Blake2b512;
+use futures_util::StreamExt;
+use sha1::{Digest as _, Sha1};
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tempfile::NamedTempFile;
+
+/// Download a URL into memory, failing on non-success HTTP status codes
+pub async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    stream(url, None, |chunk| {
+        data.extend_from_slice(chunk);
+        Ok(())
+    })
+    .await?;
+    Ok(data)
+}
+
+/// Download a URL into memory, failing if the body is larger than `limit` bytes
+pub async fn fetch_at_most(url: &str, limit: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    stream(url, Some(limit), |chunk| {
+        data.extend_from_slice(chunk);
+        Ok(())
+    })
+    .await?;
+    Ok(data)
+}
+
+/// Download a URL into a temporary file, hashing it as it arrives so it never has
+/// to be held in memory or read back to be verified
+pub async fn fetch_to_file(url: &str, limit: Option<u64>) -> Result<DownloadedFile> {
+    DownloadedFile::save(async |writer| {
+        stream(url, limit, |chunk| {
+            writer
+                .write_all(chunk)
+                .with_context(|| format!("Failed to save {}", url))
+        })
+        .await
+    })
+    .await
+}
+
+/// Pass the body of `url` to `sink` chunk by chunk, failing past `limit` bytes
+async fn stream(
+    url: &str,
+    limit: Option<u64>,
+    mut sink: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
+    let _slot = super::scheduler::slot().await;
+    let response = super::http::get(url).await?;
+    let content_length = response.content_length();
+    // Too large stays too large, so neither failure is worth a retry
+    if let Some((len, limit)) = content_length.zip(limit)
+        && len > limit
+    {
+        return Err(Permanent::new(format!(
+            "{} is too large: {} bytes (max {} bytes)",
+            url, len, limit
+        ))
+        .into());
+    }
+
+    let name = url.rsplit('/').next().unwrap_or(url);
+    let bar = super::progress::download_bar(name, content_length);
+    let mut received = 0u64;
+    let mut chunks = response.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.with_context(|| format!("Failed to download {}", url))?;
+        received += chunk.len() as u64;
+        if let Some(limit) = limit
+            && received > limit
+        {
+            bar.finish_and_clear();
+            return Err(Permanent::new(format!(
+                "{} exceeds the size limit of {} bytes",
+                url, limit
+            ))
+            .into());
+        }
+        if let Err(e) = sink(&chunk) {
+            bar.finish_and_clear();
+            return Err(e);
+        }
+        bar.inc(chunk.len() as u64);
+        super::progress::record_download(chunk.len() as u64);
+    }
+    bar.finish_and_clear();
+    Ok(())
+}
+
+/// The size and digests of a downloaded artifact. SHA-1 and BLAKE2b are only kept to
+/// check releases that publish nothing stronger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digests {
+    pub size: u64,
+    pub sha1: String,
+    pub sha256: String,
+    pub sha512: String,
+    pub blake2b: String,
+    pub blake3: String,
+}
+
+impl Digests {
+    /// Digests of data already in memory
+    pub fn of(data: &[u8]) -> Self {
+        let mut hasher = Hasher::default();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// The hex digest for `algorithm`: `sha1`, `sha256`, `sha512`, `blake2b` (BLAKE2b-512,
+    /// as printed by b2sum) or `blake3`
+    pub fn get(&self, algorithm: &str) -> Option<&str> {
+        match algorithm {
+            "sha1" => Some(&self.sha1),
+            "sha256" => Some(&self.sha256),
+            "sha512" => Some(&self.sha512),
+            "blake2b" => Some(&self.blake2b),
+            "blake3" => Some(&self.blake3),
+            _ => None,
+        }
+    }
+}
+
+/// Every digest of [`Digests`], fed in one pass over the data
+#[derive(Default)]
+struct Hasher {
+    size: u64,
+    sha1: Sha1,
+    sha256: Sha256,
+    sha512: Sha512,
+    blake2b: Blake2b512,
+    blake3: blake3::Hasher,
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+        self.sha1.update(data);
+        self.sha256.update(data);
+        self.sha512.update(data);
+        self.blake2b.update(data);
+        self.blake3.update(data);
+    }
+
+    fn finish(self) -> Digests {
+        Digests {
+            size: self.size,
+            sha1: hex::encode(self.sha1.finalize()),
+            sha256: hex::encode(self.sha256.finalize()),
+            sha512: hex::encode(self.sha512.finalize()),
+            blake2b: hex::encode(self.blake2b.finalize()),
+            blake3: self.blake3.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Writes to a file while computing the [`Digests`] of everything written
+pub struct HashingWriter {
+    file: File,
+    hasher: Hasher,
+}
+
+impl HashingWriter {
+    pub fn new(file: File) -> Self {
+        HashingWriter {
+            file,
+            hasher: Hasher::default(),
+        }
+    }
+
+    /// Flush the file and return the digests of what was written
+    pub fn finish(mut self) -> Result<Digests> {
+        self.file.flush()?;
+        Ok(self.hasher.finish())
+    }
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes complete immediately, so a [`HashingWriter`] can also receive async copies
+/// such as OCI blob pulls
+impl tokio::io::AsyncWrite for HashingWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_mut().flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_mut().flush())
+    }
+}
+
+/// A download saved by [`fetch_to_file`], deleted when dropped
+pub struct DownloadedFile {
+    file: NamedTempFile,
+    pub digests: Digests,
+}
+
+impl DownloadedFile {
+    /// Save what `write` writes to a new temporary file under [`super::tmp::dir`]
+    pub async fn save(write: impl AsyncFnOnce(&mut HashingWriter) -> Result<()>) -> Result<Self> {
+        let dir = super::tmp::dir();
+        let file = tempfile::Builder::new()
+            .prefix("picolayer_")
+            .tempfile_in(dir)
+            .with_context(|| format!("Failed to create a temporary file in {}", dir.display()))?;
+        let mut writer = HashingWriter::new(file.reopen()?);
+        write(&mut writer).await?;
+        let digests = writer.finish()?;
+        Ok(DownloadedFile { file, digests })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Open the download for reading from the start
+    pub fn open(&self) -> Result<File> {
+        File::open(self.path()).with_context(|| format!("Failed to open {}", self.path().display()))
+    }
+}
+
+/// Download and parse a JSON document
+pub async fn fetch_json(url: &str) -> Result<serde_json::Value> {
+    let data = fetch(url).await?;
+    serde_json::from_slice(&data).with_context(|| format!("Failed to parse {}", url))
+}
+
+/// Check the digests of what was downloaded from `url` against a hex-encoded SHA-256
+/// digest, noting the result in the audit log
+pub fn verify_download(url: &str, digests: &Digests, expected: &str) -> Result<()> {
+    match ensure_digest(&digests.sha256, expected) {
+        Ok(()) => {
+            super::audit::verified(url, "sha256");
+            Ok(())
+        }
+        Err(e) => {
+            super::audit::verification_failed(url, digests, "sha256");
+            Err(e.context(format!("Verifying {}", url)))
+        }
+    }
+}
+
+/// Check `data` against a hex-encoded SHA-256 digest
+pub fn verify_sha256(data: &[u8], expected: &str) -> Result<()> {
+    ensure_digest(&hex::encode(Sha256::digest(data)), expected)
+}
+
+/// Fail with [`PicolayerError::ChecksumMismatch`] unless the hex digests match
+pub fn ensure_digest(actual: &str, expected: &str) -> Result<()> {
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(PicolayerError::ChecksumMismatch {
+            expected: expected.trim().to_string(),
+            actual: actual.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_sha256_compares_case_insensitively() {
+        let empty = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+        assert!(verify_sha256(b"", empty).is_ok());
+        assert!(verify_sha256(b"x", empty).is_err());
+    }
+
+    #[test]
+    fn hashing_writer_matches_in_memory_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("asset");
+        let mut writer = HashingWriter::new(File::create(&path).unwrap());
+        writer.write_all(b"hello ").unwrap();
+        writer.write_all(b"world").unwrap();
+        let digests = writer.finish().unwrap();
+
+        assert_eq!(digests, Digests::of(b"hello world"));
+        assert_eq!(digests.size, 11);
+        assert_eq!(
+            digests.get("sha256"),
+            Some("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+        );
+        assert_eq!(
+            digests.get("sha1"),
+            Some("2aae6c35c94fcfb415dbe95f408b9ce91ee846ed")
+        );
+        assert_eq!(
+            digests.get("blake3"),
+            Some("d74981efa70a0c880b8d8c1985d075dbcbf679b99a5f9914e5aaf96b831a9e24")
+        );
+        assert_eq!(digests.get("md5"), None);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+    }
+}
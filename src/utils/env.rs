@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::process::Command;
+
+/// Variables a cleaned environment keeps, which tools need to find programs, the user's
+/// home, the locale, and the terminal
+const BASELINE: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "LANG", "LC_ALL", "TERM", "TZ", "TMPDIR",
+];
+
+/// Which of picolayer's own environment variables a child process inherits. Build
+/// environments often carry proxy credentials and CI tokens that a third-party script has
+/// no business seeing; with `clean` it gets only the baseline and the `pass` names.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvFilter<'a> {
+    pub clean: bool,
+    pub pass: &'a [String],
+}
+
+impl EnvFilter<'_> {
+    /// Whether a child keeps the variable `name`
+    pub fn keeps(&self, name: &str) -> bool {
+        !self.clean || BASELINE.contains(&name) || self.pass.iter().any(|pass| pass == name)
+    }
+
+    /// Restrict what `cmd` inherits. This clears variables already set on `cmd` too, so
+    /// call it before adding the child's own.
+    pub fn apply_to(&self, cmd: &mut Command) {
+        if self.clean {
+            cmd.env_clear().envs(
+                std::env::vars_os()
+                    .filter(|(name, _)| name.to_str().is_some_and(|name| self.keeps(name))),
+            );
+        }
+    }
+}
+
+/// Expand `${VAR}` and `${VAR:-default}` in `value`, as a shell would, so a manifest or an
+/// exec-form `RUN` can be parameterized by build args. `$${` stands for a literal `${`, and
+/// `$` not followed by a brace is left alone for regexes and package specs.
+pub fn expand(value: &str) -> Result<String> {
+    expand_with(value, |name| std::env::var(name).ok())
+}
+
+/// Expand every command-line argument that is valid UTF-8, up to the first `--`. What
+/// follows it belongs to the tool, plugin, or script being run, which expands it itself.
+pub fn expand_args(args: impl IntoIterator<Item = OsString>) -> Result<Vec<OsString>> {
+    let mut passthrough = false;
+    args.into_iter()
+        .map(|arg| {
+            if passthrough {
+                return Ok(arg);
+            }
+            passthrough = arg == "--";
+            match arg.to_str() {
+                Some(value) if value.contains('$') => expand(value).map(OsString::from),
+                _ => Ok(arg),
+            }
+        })
+        .collect()
+}
+
+fn expand_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(escaped) = after.strip_prefix("${") {
+            expanded.push_str("${");
+            rest = escaped;
+        } else if let Some(body) = after.strip_prefix('{') {
+            let end = body
+                .find('}')
+                .with_context(|| format!("Unclosed '${{' in '{}'", value))?;
+            let (name, default) = match body[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&body[..end], None),
+            };
+            anyhow::ensure!(
+                is_variable_name(name),
+                "Invalid variable name '{}' in '{}'",
+                name,
+                value
+            );
+            // Like the shell, a default also replaces a variable that is set but empty
+            let resolved = match (lookup(name), default) {
+                (Some(set), Some(default)) if set.is_empty() => default.to_string(),
+                (Some(set), _) => set,
+                (None, Some(default)) => default.to_string(),
+                (None, None) => anyhow::bail!(
+                    "Environment variable {} is not set (use ${{{}:-default}} for a fallback)",
+                    name,
+                    name
+                ),
+            };
+            expanded.push_str(&resolved);
+            rest = &body[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = after;
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_test(value: &str) -> Result<String> {
+        expand_with(value, |name| match name {
+            "VERSION" => Some("1.2.3".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn expands_variables_and_defaults() {
+        assert_eq!(expand_test("v${VERSION}").unwrap(), "v1.2.3");
+        assert_eq!(expand_test("${MISSING:-latest}").unwrap(), "latest");
+        assert_eq!(expand_test("${EMPTY:-/opt/bin}").unwrap(), "/opt/bin");
+        assert_eq!(expand_test("${EMPTY}").unwrap(), "");
+    }
+
+    #[test]
+    fn leaves_other_dollars_alone() {
+        assert_eq!(expand_test("^gh_.*linux$").unwrap(), "^gh_.*linux$");
+        assert_eq!(
+            expand_test("$${VERSION} $HOME").unwrap(),
+            "${VERSION} $HOME"
+        );
+    }
+
+    #[test]
+    fn clean_filter_keeps_the_baseline_and_passed_names() {
+        let pass = ["NPM_TOKEN".to_string()];
+        let filter = EnvFilter {
+            clean: true,
+            pass: &pass,
+        };
+        assert!(filter.keeps("PATH"));
+        assert!(filter.keeps("NPM_TOKEN"));
+        assert!(!filter.keeps("GITHUB_TOKEN"));
+        assert!(!filter.keeps("HTTPS_PROXY"));
+        assert!(EnvFilter::default().keeps("GITHUB_TOKEN"));
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_passed_through() {
+        let args = [
+            "picolayer",
+            "pkgx",
+            "--tool",
+            "bash",
+            "--",
+            "-c",
+            "echo ${#arr[@]} ${UNSET_X}",
+        ]
+        .map(OsString::from);
+        assert_eq!(expand_args(args.clone()).unwrap(), args);
+
+        let err = expand_args(["picolayer", "--version=${1BAD}"].map(OsString::from));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn rejects_unset_and_malformed_variables() {
+        assert!(expand_test("${MISSING}").is_err());
+        assert!(expand_test("${VERSION").is_err());
+        assert!(expand_test("${1BAD}").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
+
+/// Profile script collecting every installer's environment for login shells
+pub const PROFILE: &str = "/etc/profile.d/picolayer-env.sh";
+
+const PROFILE_HEADER: &str = "# Environment of tools installed by picolayer";
+
+/// Environment installers contribute for later shells and manifest steps, such as
+/// PATH entries and JAVA_HOME
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Contributions {
+    /// Directories to put on PATH, in the order they were added
+    pub path: Vec<PathBuf>,
+    pub vars: BTreeMap<String, String>,
+}
+
+static REGISTRY: Mutex<Contributions> = Mutex::new(Contributions {
+    path: Vec::new(),
+    vars: BTreeMap::new(),
+});
+
+fn registry() -> MutexGuard<'static, Contributions> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Put `dir` on PATH for later shells and manifest steps
+pub fn add_path(dir: &Path) {
+    let mut registry = registry();
+    if !registry.path.iter().any(|p| p == dir) {
+        registry.path.push(dir.to_path_buf());
+    }
+}
+
+/// Set `name` to `value` for later shells and manifest steps
+pub fn set_var(name: &str, value: &str) {
+    registry().vars.insert(name.to_string(), value.to_string());
+}
+
+/// Everything contributed so far in this process
+pub fn snapshot() -> Contributions {
+    registry().clone()
+}
+
+impl Contributions {
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty() && self.vars.is_empty()
+    }
+
+    /// Give `cmd` the contributed environment on top of the inherited one
+    pub fn apply_to(&self, cmd: &mut Command) {
+        cmd.envs(&self.vars);
+        let current = std::env::var_os("PATH").unwrap_or_default();
+        let inherited: Vec<PathBuf> = std::env::split_paths(&current).collect();
+        // Each profile entry prepends its directory, so the last one added comes first
+        let added = self
+            .path
+            .iter()
+            .rev()
+            .filter(|dir| !inherited.contains(dir))
+            .cloned();
+        if let Ok(path) = std::env::join_paths(added.chain(inherited.iter().cloned())) {
+            cmd.env("PATH", path);
+        }
+    }
+
+    /// Dockerfile `ENV` instructions with the same effect as the profile script, for
+    /// non-login shells such as later `RUN` steps
+    pub fn dockerfile(&self) -> String {
+        let mut lines: Vec<String> = self
+            .vars
+            .iter()
+            .map(|(name, value)| format!("ENV {}={}", name, double_quote(value)))
+            .collect();
+        if !self.path.is_empty() {
+            let dirs: Vec<String> = self
+                .path
+                .iter()
+                .rev()
+                .map(|dir| dir.display().to_string())
+                .collect();
+            lines.push(format!("ENV PATH=\"{}:$PATH\"", dirs.join(":")));
+        }
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// One profile script line per contribution
+    fn lines(&self) -> Vec<String> {
+        let vars = self
+            .vars
+            .iter()
+            .map(|(name, value)| format!("export {}={}", name, double_quote(value)));
+        let path = self.path.iter().map(|dir| {
+            format!(
+                "case \":$PATH:\" in *\":{0}:\"*) ;; *) export PATH=\"{0}:$PATH\" ;; esac",
+                dir.display()
+            )
+        });
+        vars.chain(path).collect()
+    }
+}
+
+/// Write what this run contributed to /etc/profile.d/picolayer-env.sh, keeping what
+/// earlier runs wrote. In user mode it goes to the user's shell rc instead.
+pub fn write_profile() -> Result<()> {
+    let contributions = snapshot();
+    if contributions.is_empty() {
+        return Ok(());
+    }
+    if super::user_mode::is_enabled() {
+        for (name, value) in &contributions.vars {
+            super::user_mode::add_snippet(
+                &format!("export {}={}\n", name, double_quote(value)),
+                &format!("Setting {}", name),
+            )?;
+        }
+        for dir in &contributions.path {
+            if !super::profile::is_on_path(dir) {
+                super::user_mode::add_to_path(dir)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let existing = std::fs::read_to_string(PROFILE).unwrap_or_default();
+    let Some(contents) = merge(&existing, &contributions) else {
+        return Ok(());
+    };
+    if !super::sudo::can_escalate() {
+        warn!(
+            "{} cannot be written without root privileges; set this environment yourself:\n{}",
+            PROFILE,
+            contributions.dockerfile().trim_end()
+        );
+        return Ok(());
+    }
+    info!(
+        "Writing {} (non-login shells need the same ENV, see apply --print-env)",
+        PROFILE
+    );
+    super::sudo::write_file(Path::new(PROFILE), &contents)
+}
+
+/// `existing` with `contributions` added, replacing earlier values of the same variables,
+/// or None when it already has them all
+fn merge(existing: &str, contributions: &Contributions) -> Option<String> {
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| {
+            !contributions
+                .vars
+                .keys()
+                .any(|name| line.starts_with(&format!("export {}=", name)))
+        })
+        .map(str::to_string)
+        .collect();
+    if lines.is_empty() {
+        lines.push(PROFILE_HEADER.to_string());
+    }
+    for line in contributions.lines() {
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    }
+    let merged = lines.join("\n") + "\n";
+    (merged != existing).then_some(merged)
+}
+
+/// Quote a value for both sh and Dockerfile `ENV`, which treat `"`, `\`, and `$` alike
+fn double_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contributions(path: &[&str], vars: &[(&str, &str)]) -> Contributions {
+        Contributions {
+            path: path.iter().map(PathBuf::from).collect(),
+            vars: vars
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn merge_keeps_earlier_runs_and_replaces_variables() {
+        let first = merge(
+            "",
+            &contributions(&["/opt/jdk/bin"], &[("JAVA_HOME", "/opt/jdk")]),
+        )
+        .unwrap();
+        assert!(first.starts_with(PROFILE_HEADER));
+        assert!(first.contains("export JAVA_HOME=\"/opt/jdk\"\n"));
+        assert!(first.contains("export PATH=\"/opt/jdk/bin:$PATH\""));
+        assert_eq!(merge(&first, &contributions(&["/opt/jdk/bin"], &[])), None);
+
+        let second = merge(
+            &first,
+            &contributions(&["/opt/go/bin"], &[("JAVA_HOME", "/opt/jdk21")]),
+        )
+        .unwrap();
+        assert!(!second.contains("/opt/jdk\"\n"));
+        assert!(second.contains("export JAVA_HOME=\"/opt/jdk21\"\n"));
+        assert!(second.contains("export PATH=\"/opt/jdk/bin:$PATH\""));
+        assert!(second.contains("export PATH=\"/opt/go/bin:$PATH\""));
+    }
+
+    #[test]
+    fn dockerfile_prepends_path_like_the_profile() {
+        let env = contributions(&["/opt/a/bin", "/opt/b/bin"], &[("GOPATH", "/opt/$go")]);
+        assert_eq!(
+            env.dockerfile(),
+            "ENV GOPATH=\"/opt/\\$go\"\nENV PATH=\"/opt/b/bin:/opt/a/bin:$PATH\"\n"
+        );
+    }
+
+    #[test]
+    fn apply_to_puts_contributed_dirs_first() {
+        let env = contributions(&["/opt/picolayer-test/bin"], &[("JAVA_HOME", "/opt/jdk")]);
+        let mut cmd = Command::new("true");
+        env.apply_to(&mut cmd);
+        let envs: BTreeMap<_, _> = cmd
+            .get_envs()
+            .filter_map(|(name, value)| Some((name.to_str()?, value?.to_str()?)))
+            .collect();
+        assert_eq!(envs["JAVA_HOME"], "/opt/jdk");
+        assert!(envs["PATH"].starts_with("/opt/picolayer-test/bin"));
+    }
+}
@@ -0,0 +1,544 @@
+//! Running the commands installers shell out to.
+//!
+//! Every command goes through [`Exec`], which logs its full command line at debug level,
+//! forwards SIGINT and SIGTERM to it, optionally stops it after a timeout, and keeps its
+//! output for the error report when it fails. Quick probes whose failure is itself the
+//! answer, such as `tool --version`, may still use [`Command`] directly.
+
+use crate::error::PicolayerError;
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, debug, log};
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::fd::AsFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Children tracked for signal forwarding at once; any beyond this are not forwarded to
+const MAX_CHILDREN: usize = 32;
+
+/// Running children to signal: a process ID, or a negated process group ID for a child
+/// leading a group of its own. 0 marks a free slot.
+static CHILDREN: [AtomicI32; MAX_CHILDREN] = [const { AtomicI32::new(0) }; MAX_CHILDREN];
+
+/// Whether picolayer's stdout is kept for its own result, as with `--output json`
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// How often a command with a timeout is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where a command's stdout and stderr go
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Captured, and only shown if the command fails
+    Capture,
+    /// Copied to the console as it arrives, and captured too
+    Stream,
+    /// Handed to the console directly, for tools that may be interactive
+    Inherit,
+}
+
+/// A command about to run
+pub struct Exec<'a> {
+    cmd: &'a mut Command,
+    description: &'a str,
+    mode: Mode,
+    /// Level failed output is logged at when it was not already shown
+    failure_level: Level,
+    timeout: Option<Duration>,
+    /// Label put in front of each streamed line
+    prefix: Option<String>,
+}
+
+impl<'a> Exec<'a> {
+    /// Capture the command's output, logging it only if the command fails
+    pub fn new(cmd: &'a mut Command, description: &'a str) -> Self {
+        Exec {
+            cmd,
+            description,
+            mode: Mode::Capture,
+            failure_level: Level::Warn,
+            timeout: None,
+            prefix: None,
+        }
+    }
+
+    /// Show the output as it arrives while still capturing it. With `--quiet` it is only
+    /// captured, and logged as an error if the command fails.
+    pub fn streamed(mut self) -> Self {
+        if log::max_level() <= LevelFilter::Error {
+            self.failure_level = Level::Error;
+        } else {
+            self.mode = Mode::Stream;
+        }
+        self
+    }
+
+    /// Give the command the console's stdin, stdout and stderr; nothing is captured
+    pub fn inherit(mut self) -> Self {
+        self.mode = Mode::Inherit;
+        self
+    }
+
+    /// Start each streamed line with `[label] `, so output from commands running side by
+    /// side can be told apart. Inside a manifest step the step's number and command are
+    /// the label unless one is given.
+    pub fn prefixed(mut self, label: impl Into<String>) -> Self {
+        self.prefix = Some(label.into());
+        self
+    }
+
+    /// Stop the command, SIGTERM first and SIGKILL after a grace period, once `limit` passes
+    pub fn timeout(mut self, limit: Duration) -> Self {
+        self.timeout = Some(limit);
+        self
+    }
+
+    /// Run the command to completion and return its output, whatever its exit status
+    pub fn output(self) -> Result<Output> {
+        debug!("Running {}: {}", self.description, command_line(self.cmd));
+        match self.mode {
+            Mode::Capture => {
+                self.cmd
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+            }
+            Mode::Stream => {
+                self.cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+            }
+            Mode::Inherit => {
+                let stdout = if STDOUT_RESERVED.load(Ordering::Relaxed) {
+                    Stdio::from(io::stderr().as_fd().try_clone_to_owned()?)
+                } else {
+                    Stdio::inherit()
+                };
+                self.cmd
+                    .stdin(Stdio::inherit())
+                    .stdout(stdout)
+                    .stderr(Stdio::inherit());
+            }
+        }
+
+        // Without a terminal, lead a process group so a timeout also stops the child's own
+        // children. On a terminal it stays in the foreground group, where it can prompt for
+        // a password and receives Ctrl-C.
+        let grouped = !io::stdin().is_terminal();
+        if grouped {
+            self.cmd.process_group(0);
+        }
+
+        forward_signals();
+        let mut child = self
+            .cmd
+            .spawn()
+            .with_context(|| format!("Failed to execute: {}", self.description))?;
+        let _tracked = Tracked::new(child.id(), grouped);
+
+        let echo = self.mode == Mode::Stream;
+        let prefix = self.prefix.clone().or_else(|| {
+            super::lock::current_step().map(|(step, command)| format!("{} {}", step, command))
+        });
+        let reserved = STDOUT_RESERVED.load(Ordering::Relaxed);
+        let stdout = child.stdout.take().map(|pipe| {
+            drain(
+                pipe,
+                echo.then(|| {
+                    if reserved {
+                        Prefixed::boxed(io::stderr(), prefix.as_deref())
+                    } else {
+                        Prefixed::boxed(io::stdout(), prefix.as_deref())
+                    }
+                }),
+            )
+        });
+        let stderr = child.stderr.take().map(|pipe| {
+            drain(
+                pipe,
+                echo.then(|| Prefixed::boxed(io::stderr(), prefix.as_deref())),
+            )
+        });
+
+        let waited = wait(&mut child, self.timeout);
+        let collect = |reader: Option<JoinHandle<Vec<u8>>>| {
+            reader
+                .and_then(|reader| reader.join().ok())
+                .unwrap_or_default()
+        };
+        let (status, timed_out) = waited?;
+        let output = Output {
+            status,
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        };
+        if timed_out {
+            if self.mode == Mode::Capture {
+                log_output(&output, self.description, self.failure_level);
+            }
+            anyhow::bail!(
+                "{} timed out after {}s",
+                self.description,
+                self.timeout.unwrap_or_default().as_secs_f32()
+            );
+        }
+        Ok(output)
+    }
+
+    /// Run the command and fail with [`PicolayerError::ScriptFailed`] unless it succeeds.
+    /// Output the console has not already shown is logged first.
+    pub fn run(self) -> Result<Output> {
+        let description = self.description;
+        let shown = self.mode != Mode::Capture;
+        let level = self.failure_level;
+        let output = self.output()?;
+        if !output.status.success() && !shown {
+            log_output(&output, description, level);
+        }
+        ensure_success(output.status, description)?;
+        Ok(output)
+    }
+}
+
+/// Send the output of the commands picolayer runs to stderr, leaving stdout to the
+/// single JSON object `--output json` prints
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+/// Run a command, check its exit status, and log stderr/stdout on failure.
+pub fn run_command(cmd: &mut Command, description: &str) -> Result<Output> {
+    Exec::new(cmd, description).run()
+}
+
+/// Run a command with its output shown as it arrives and check its exit status.
+///
+/// Used for long-running installs where the tool's own progress and error
+/// output is more useful to the user than a captured dump after the fact.
+/// With `--quiet` the output is captured instead and only shown if the command fails.
+pub fn run_streaming(cmd: &mut Command, description: &str) -> Result<()> {
+    Exec::new(cmd, description).streamed().run()?;
+    Ok(())
+}
+
+/// Check the exit status of a finished command and log stderr/stdout on failure.
+pub fn check_output(output: Output, description: &str) -> Result<Output> {
+    if !output.status.success() {
+        log_output(&output, description, Level::Warn);
+        ensure_success(output.status, description)?;
+    }
+
+    Ok(output)
+}
+
+/// A [`PicolayerError::ScriptFailed`] naming `description` unless `status` is success
+pub fn ensure_success(status: ExitStatus, description: &str) -> Result<()> {
+    if !status.success() {
+        return Err(PicolayerError::ScriptFailed {
+            script: description.to_string(),
+            code: status.code(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn log_output(output: &Output, description: &str, level: Level) {
+    for (name, stream) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        let text = String::from_utf8_lossy(stream);
+        if !text.trim().is_empty() {
+            log!(level, "{} {}:\n{}", description, name, text.trim());
+        }
+    }
+}
+
+/// The command line as it could be pasted into a shell
+fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@+,%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// A writer that starts every line with a label
+struct Prefixed<W> {
+    inner: W,
+    prefix: String,
+    line_start: bool,
+}
+
+impl<W: Write + Send + 'static> Prefixed<W> {
+    /// `inner` itself without a label, or wrapped to start each line with `[label] `
+    fn boxed(inner: W, label: Option<&str>) -> Box<dyn Write + Send> {
+        match label {
+            Some(label) => Box::new(Prefixed {
+                inner,
+                prefix: format!("[{}] ", label),
+                line_start: true,
+            }),
+            None => Box::new(inner),
+        }
+    }
+}
+
+impl<W: Write> Write for Prefixed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Assemble the chunk first so it reaches the console in one write and lines from
+        // another command are less likely to land in the middle of it
+        let mut out = Vec::with_capacity(buf.len() + self.prefix.len());
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            if self.line_start {
+                out.extend_from_slice(self.prefix.as_bytes());
+            }
+            out.extend_from_slice(line);
+            self.line_start = line.ends_with(b"\n");
+        }
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Read a pipe to its end on a thread of its own, copying each chunk to `echo` if given
+fn drain(
+    mut pipe: impl Read + Send + 'static,
+    mut echo: Option<Box<dyn Write + Send>>,
+) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut captured = Vec::new();
+        let mut buf = [0; 8192];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if let Some(echo) = echo.as_mut() {
+                        let _ = echo.write_all(&buf[..n]);
+                        let _ = echo.flush();
+                    }
+                    captured.extend_from_slice(&buf[..n]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        captured
+    })
+}
+
+/// Wait for the child, stopping it once `timeout` passes. True alongside the status when
+/// the child had to be stopped.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> Result<(ExitStatus, bool)> {
+    let Some(limit) = timeout else {
+        return Ok((child.wait()?, false));
+    };
+    let deadline = Instant::now() + limit;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    // SAFETY: the child has not been waited for, so its pid still refers to it
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    let grace = Instant::now() + super::timeout::GRACE_PERIOD;
+    while Instant::now() < grace {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, true));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    child.kill()?;
+    Ok((child.wait()?, true))
+}
+
+/// A running child registered for signal forwarding, unregistered when dropped
+struct Tracked(Option<usize>);
+
+impl Tracked {
+    fn new(pid: u32, grouped: bool) -> Self {
+        let target = if grouped { -(pid as i32) } else { pid as i32 };
+        let slot = CHILDREN.iter().position(|slot| {
+            slot.compare_exchange(0, target, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        Tracked(slot)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0 {
+            CHILDREN[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Send `signal` to every running child, or to its whole process group when it leads one.
+/// Returns what was signalled, in the form [`CHILDREN`] keeps.
+pub(crate) fn signal_children(signal: libc::c_int) -> Vec<libc::pid_t> {
+    let targets: Vec<libc::pid_t> = CHILDREN
+        .iter()
+        .map(|slot| slot.load(Ordering::SeqCst))
+        .filter(|&target| target != 0)
+        .collect();
+    for &target in &targets {
+        // SAFETY: kill has no memory-safety preconditions
+        unsafe {
+            libc::kill(target, signal);
+        }
+    }
+    targets
+}
+
+/// Pass SIGINT and SIGTERM on to the running children, so `docker stop` or a cancelled CI
+/// job stops an install cleanly instead of leaving it orphaned. With no child running,
+/// the signal takes its default action on picolayer itself.
+fn forward_signals() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: the handler only loads atomics and calls async-signal-safe functions
+            unsafe {
+                libc::signal(
+                    signal,
+                    forward as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                );
+            }
+        }
+    });
+}
+
+extern "C" fn forward(signal: libc::c_int) {
+    let mut forwarded = false;
+    for slot in &CHILDREN {
+        let target = slot.load(Ordering::SeqCst);
+        if target != 0 {
+            // SAFETY: kill is async-signal-safe
+            unsafe {
+                libc::kill(target, signal);
+            }
+            forwarded = true;
+        }
+    }
+    if !forwarded {
+        // SAFETY: signal and raise are async-signal-safe
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_command_succeeds_on_true() {
+        let output = run_command(&mut Command::new("true"), "true command").unwrap();
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn run_command_fails_on_false() {
+        let result = run_command(&mut Command::new("false"), "false command");
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("false command failed with exit code"));
+    }
+
+    #[test]
+    fn run_command_captures_stderr_in_error() {
+        let result = run_command(
+            Command::new("sh").args(["-c", "echo err >&2; exit 1"]),
+            "stderr test",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn run_streaming_reports_exit_code() {
+        assert!(run_streaming(&mut Command::new("true"), "true command").is_ok());
+        let err = run_streaming(Command::new("sh").args(["-c", "exit 3"]), "exit test")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("exit test failed with exit code: Some(3)"));
+    }
+
+    #[test]
+    fn run_command_returns_output_on_success() {
+        let output =
+            run_command(Command::new("sh").args(["-c", "echo hello"]), "echo test").unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("hello"));
+    }
+
+    #[test]
+    fn streamed_output_is_captured_too() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2"]);
+        let mut exec = Exec::new(&mut cmd, "stream test");
+        exec.mode = Mode::Stream;
+        let output = exec.output().unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn prefixed_writer_labels_every_line() {
+        let mut writer = Prefixed {
+            inner: Vec::new(),
+            prefix: "[node] ".to_string(),
+            line_start: true,
+        };
+        writer.write_all(b"one\ntw").unwrap();
+        writer.write_all(b"o\n\nthree").unwrap();
+        assert_eq!(
+            writer.inner,
+            b"[node] one\n[node] two\n[node] \n[node] three"
+        );
+    }
+
+    #[test]
+    fn timeout_stops_the_command() {
+        let started = Instant::now();
+        let err = Exec::new(Command::new("sleep").arg("10"), "sleep test")
+            .timeout(Duration::from_millis(100))
+            .run()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("sleep test timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn command_line_quotes_what_the_shell_would_split() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo 'hi' there", "--flag=value"]);
+        assert_eq!(
+            command_line(&cmd),
+            r#"sh -c 'echo '\''hi'\'' there' --flag=value"#
+        );
+    }
+}
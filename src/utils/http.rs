@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// User-Agent sent with every request picolayer makes
+pub const USER_AGENT: &str = concat!("picolayer/", env!("CARGO_PKG_VERSION"));
+
+/// Environment variable naming a PEM bundle of extra CA certificates, like `--ca-bundle`
+const CA_BUNDLE_ENV: &str = "PICOLAYER_CA_BUNDLE";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait for the next chunk of a response. Whole downloads are not bounded
+/// since JDKs and toolchains can take minutes on slow links; use --timeout for that.
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Network settings shared by every installer
+#[derive(Debug, Default, Clone)]
+pub struct HttpConfig {
+    /// Proxy for all requests. Without one, HTTPS_PROXY, HTTP_PROXY and NO_PROXY apply.
+    pub proxy: Option<String>,
+    /// PEM bundle of CA certificates trusted in addition to the built-in roots
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl HttpConfig {
+    /// The CA bundle from the config, or else from PICOLAYER_CA_BUNDLE
+    pub fn ca_bundle(&self) -> Option<PathBuf> {
+        self.ca_bundle.clone().or_else(|| {
+            std::env::var_os(CA_BUNDLE_ENV)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    /// Contents of the CA bundle, if one is configured
+    pub fn ca_bundle_pem(&self) -> Result<Option<Vec<u8>>> {
+        self.ca_bundle()
+            .map(|path| {
+                std::fs::read(&path)
+                    .with_context(|| format!("Failed to read CA bundle {}", path.display()))
+            })
+            .transpose()
+    }
+}
+
+static CONFIG: OnceLock<HttpConfig> = OnceLock::new();
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Set the network settings; only the first call before any request takes effect
+pub fn configure(config: HttpConfig) {
+    let _ = CONFIG.set(config);
+}
+
+/// The network settings in effect
+pub fn config() -> &'static HttpConfig {
+    CONFIG.get_or_init(HttpConfig::default)
+}
+
+/// The shared client, built on first use so connections are pooled across installers
+pub fn client() -> Result<&'static reqwest::Client> {
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = build_client(config())?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+fn build_client(config: &HttpConfig) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT);
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).context("Invalid --proxy URL")?);
+    }
+    if let Some(pem) = config.ca_bundle_pem()? {
+        let certificates =
+            reqwest::Certificate::from_pem_bundle(&pem).context("Failed to parse CA bundle")?;
+        // A file with no PEM blocks parses as an empty bundle, which would trust nothing
+        // extra without saying so
+        anyhow::ensure!(
+            !certificates.is_empty(),
+            "CA bundle contains no certificates"
+        );
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    builder.build().context("Failed to create HTTP client")
+}
+
+/// GET a URL with the shared client, failing on non-success status codes
+pub async fn get(url: &str) -> Result<reqwest::Response> {
+    super::offline::ensure_online(&format!("Downloading {}", url))?;
+    let response = client()?
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?;
+    if !response.status().is_success() {
+        return Err(StatusError {
+            url: url.to_string(),
+            status: response.status(),
+        }
+        .into());
+    }
+    Ok(response)
+}
+
+/// A request answered with a non-success HTTP status
+#[derive(Debug)]
+pub struct StatusError {
+    pub url: String,
+    pub status: reqwest::StatusCode,
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to download {}: {}", self.url, self.status)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+impl crate::error::Classify for StatusError {
+    fn class(&self) -> Option<crate::error::ErrorClass> {
+        Some(crate::error::ErrorClass::Status(self.status.as_u16()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_client_rejects_bad_settings() {
+        assert!(build_client(&HttpConfig::default()).is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("ca.pem");
+        std::fs::write(&bundle, "not a certificate").unwrap();
+        let config = HttpConfig {
+            proxy: None,
+            ca_bundle: Some(bundle.clone()),
+        };
+        let err = build_client(&config).unwrap_err().to_string();
+        assert!(err.contains("no certificates"), "{}", err);
+
+        std::fs::write(&bundle, "").unwrap();
+        assert!(build_client(&config).is_err());
+
+        let config = HttpConfig {
+            proxy: Some("not a url".to_string()),
+            ca_bundle: None,
+        };
+        assert!(build_client(&config).is_err());
+    }
+}
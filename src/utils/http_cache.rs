@@ -0,0 +1,182 @@
+use super::http::{self, StatusError};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory under the cache dir holding one entry per URL
+const CACHE_SUBDIR: &str = "http";
+
+const BODY_FILE: &str = "body";
+const ETAG_FILE: &str = "etag";
+
+/// A response saved by an earlier run
+#[derive(Debug, PartialEq)]
+struct Cached {
+    body: Vec<u8>,
+    etag: Option<String>,
+}
+
+/// Download a small file such as a checksum file or GPG key, revalidating a cached copy
+/// with `If-None-Match` instead of downloading it again.
+///
+/// When the server cannot be reached or answers with a transient error, a cached copy is
+/// used in its place. Caching is best effort: an unwritable cache dir only costs the
+/// download.
+pub async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let dir = entry_dir(&super::settings::cache_dir(), url);
+    let cached = read(&dir);
+    let etag = cached.as_ref().and_then(|c| c.etag.as_deref());
+
+    match request(url, etag).await {
+        Ok(Some((body, etag))) => {
+            if let Err(e) = write(&dir, &body, etag.as_deref()) {
+                debug!("Not caching {}: {:#}", url, e);
+            }
+            Ok(body)
+        }
+        Ok(None) => {
+            let cached = cached.with_context(|| {
+                format!("{} answered 304 Not Modified without a cached copy", url)
+            })?;
+            debug!("{} is unchanged, using the cached copy", url);
+            super::progress::record_cache_hit();
+            Ok(cached.body)
+        }
+        Err(e) => match cached {
+            Some(cached) if is_transient(&e) => {
+                warn!("{:#}; using the copy cached by an earlier run", e);
+                super::progress::record_cache_hit();
+                Ok(cached.body)
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+/// GET `url`, conditionally on `etag`. None means the cached copy is still current.
+async fn request(url: &str, etag: Option<&str>) -> Result<Option<(Vec<u8>, Option<String>)>> {
+    super::offline::ensure_online(&format!("Downloading {}", url))?;
+    let _slot = super::scheduler::slot().await;
+    let mut request = http::client()?.get(url);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?;
+
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(StatusError {
+            url: url.to_string(),
+            status,
+        }
+        .into());
+    }
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = response
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?;
+    super::progress::record_download(body.len() as u64);
+    Ok(Some((body.to_vec(), etag)))
+}
+
+/// Failures a cached copy can stand in for: no connection, or a server that is briefly
+/// overloaded or down. A 404 means the URL is wrong, which the cache must not hide.
+fn is_transient(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<StatusError>() {
+        Some(e) => {
+            e.status.is_server_error()
+                || e.status == StatusCode::REQUEST_TIMEOUT
+                || e.status == StatusCode::TOO_MANY_REQUESTS
+        }
+        None => err.chain().any(|cause| cause.is::<reqwest::Error>()),
+    }
+}
+
+/// One directory per URL, so `cache prune` removes a body and its ETag together
+fn entry_dir(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir
+        .join(CACHE_SUBDIR)
+        .join(hex::encode(Sha256::digest(url.as_bytes())))
+}
+
+fn read(dir: &Path) -> Option<Cached> {
+    let body = std::fs::read(dir.join(BODY_FILE)).ok()?;
+    let etag = std::fs::read_to_string(dir.join(ETAG_FILE)).ok();
+    Some(Cached { body, etag })
+}
+
+fn write(dir: &Path, body: &[u8], etag: Option<&str>) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    // A stale ETag next to a new body would keep the old body "current" forever
+    let _ = std::fs::remove_file(dir.join(ETAG_FILE));
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(body)?;
+    file.persist(dir.join(BODY_FILE))?;
+    if let Some(etag) = etag {
+        std::fs::write(dir.join(ETAG_FILE), etag)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_and_replace_the_etag() {
+        let cache = tempfile::tempdir().unwrap();
+        let dir = entry_dir(cache.path(), "https://example.com/SHA256SUMS");
+        assert_eq!(read(&dir), None);
+
+        write(&dir, b"abc  tool.tar.gz\n", Some("\"v1\"")).unwrap();
+        assert_eq!(
+            read(&dir),
+            Some(Cached {
+                body: b"abc  tool.tar.gz\n".to_vec(),
+                etag: Some("\"v1\"".to_string()),
+            })
+        );
+
+        write(&dir, b"def  tool.tar.gz\n", None).unwrap();
+        assert_eq!(read(&dir).unwrap().etag, None);
+    }
+
+    #[test]
+    fn entry_dir_is_one_directory_per_url() {
+        let cache = Path::new("/var/cache/picolayer");
+        let a = entry_dir(cache, "https://example.com/a.asc");
+        assert!(a.starts_with("/var/cache/picolayer/http"));
+        assert_eq!(a, entry_dir(cache, "https://example.com/a.asc"));
+        assert_ne!(a, entry_dir(cache, "https://example.com/b.asc"));
+    }
+
+    #[test]
+    fn only_transient_failures_fall_back_to_the_cache() {
+        let status = |status| {
+            anyhow::Error::from(StatusError {
+                url: "https://example.com/key.asc".to_string(),
+                status,
+            })
+        };
+        assert!(is_transient(&status(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(is_transient(&status(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!is_transient(&status(StatusCode::NOT_FOUND)));
+        assert!(!is_transient(&anyhow::anyhow!("not valid UTF-8")));
+    }
+}
@@ -0,0 +1,211 @@
+use super::download::Digests;
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Name of the lockfile written next to a manifest
+pub const LOCKFILE_NAME: &str = "picolayer.lock";
+
+const LOCKFILE_VERSION: u32 = 1;
+
+/// A downloaded artifact as resolved by an installer step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedArtifact {
+    pub step: usize,
+    pub command: String,
+    /// Stable identifier for the artifact within its step, e.g. owner/repo
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    pub artifacts: Vec<LockedArtifact>,
+}
+
+impl Lockfile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lockfile {}", path.display()))?;
+        let lockfile: Lockfile = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse lockfile {}", path.display()))?;
+        anyhow::ensure!(
+            lockfile.version == LOCKFILE_VERSION,
+            "Unsupported lockfile version {} in {}",
+            lockfile.version,
+            path.display()
+        );
+        Ok(lockfile)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write lockfile {}", path.display()))
+    }
+}
+
+/// Artifacts recorded while running a manifest, optionally checked against a frozen lockfile
+struct Session {
+    recorded: Vec<LockedArtifact>,
+    frozen: Option<Vec<LockedArtifact>>,
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+tokio::task_local! {
+    /// The manifest step a future runs for, so concurrent steps record their own artifacts
+    static STEP: (usize, String);
+}
+
+/// Start recording artifacts. With a frozen lockfile, any artifact that differs from it is refused.
+pub fn begin(frozen: Option<Lockfile>) {
+    *SESSION.lock().unwrap() = Some(Session {
+        recorded: Vec::new(),
+        frozen: frozen.map(|lockfile| lockfile.artifacts),
+    });
+}
+
+/// Attribute artifacts recorded while `future` runs to a manifest step
+pub async fn in_step<F: Future>(step: usize, command: &str, future: F) -> F::Output {
+    STEP.scope((step, command.to_string()), future).await
+}
+
+/// The manifest step and command the current task runs for, if any
+pub fn current_step() -> Option<(usize, String)> {
+    STEP.try_with(|step| step.clone()).ok()
+}
+
+/// Stop recording and return the resolved lockfile, failing if a frozen lockfile
+/// lists artifacts that were never resolved
+pub fn finish() -> Result<Lockfile> {
+    let mut session = SESSION
+        .lock()
+        .unwrap()
+        .take()
+        .context("No lock session in progress")?;
+    // Steps may finish out of order; keep the lockfile in manifest order
+    session.recorded.sort_by_key(|a| a.step);
+    if let Some(frozen) = &session.frozen
+        && let Some(missing) = frozen.iter().find(|a| !session.recorded.contains(a))
+    {
+        anyhow::bail!(
+            "Lockfile drift: step {} ({}) no longer resolves {} {}",
+            missing.step,
+            missing.command,
+            missing.name,
+            missing.version
+        );
+    }
+    Ok(Lockfile {
+        version: LOCKFILE_VERSION,
+        artifacts: session.recorded,
+    })
+}
+
+/// Record a downloaded artifact in the audit log and, during a session, the lockfile.
+///
+/// Call this after downloading and before installing, so frozen mode refuses
+/// drift before anything is written.
+pub fn record(name: &str, version: &str, url: &str, digests: &Digests) -> Result<()> {
+    super::audit::fetched(name, version, url, digests);
+    let mut guard = SESSION.lock().unwrap();
+    let Some(session) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    let (step, command) = STEP.try_with(|step| step.clone()).unwrap_or_default();
+    let artifact = LockedArtifact {
+        step,
+        command,
+        name: name.to_string(),
+        version: version.to_string(),
+        url: url.to_string(),
+        sha256: digests.sha256.clone(),
+    };
+    if let Some(frozen) = &session.frozen {
+        check_frozen(frozen, &artifact)?;
+    }
+    debug!(
+        "Recorded {} {} ({})",
+        artifact.name, artifact.version, artifact.sha256
+    );
+    session.recorded.push(artifact);
+    Ok(())
+}
+
+fn check_frozen(frozen: &[LockedArtifact], artifact: &LockedArtifact) -> Result<()> {
+    let locked = frozen
+        .iter()
+        .find(|a| a.step == artifact.step && a.name == artifact.name)
+        .with_context(|| {
+            format!(
+                "Lockfile drift: step {} ({}) resolved {}, which is not in the lockfile",
+                artifact.step, artifact.command, artifact.name
+            )
+        })?;
+
+    for (field, expected, actual) in [
+        ("version", &locked.version, &artifact.version),
+        ("url", &locked.url, &artifact.url),
+        ("sha256", &locked.sha256, &artifact.sha256),
+    ] {
+        anyhow::ensure!(
+            expected == actual,
+            "Lockfile drift: step {} ({}) resolved {} with {} {}, but the lockfile has {}",
+            artifact.step,
+            artifact.command,
+            artifact.name,
+            field,
+            actual,
+            expected
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(version: &str) -> LockedArtifact {
+        LockedArtifact {
+            step: 1,
+            command: "gh-release".to_string(),
+            name: "cli/cli".to_string(),
+            version: version.to_string(),
+            url: format!("https://example.com/gh_{}.tar.gz", version),
+            sha256: "abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn check_frozen_detects_drift() {
+        let frozen = [artifact("v2.62.0")];
+        assert!(check_frozen(&frozen, &artifact("v2.62.0")).is_ok());
+
+        let err = check_frozen(&frozen, &artifact("v2.63.0")).unwrap_err();
+        assert!(err.to_string().contains("version v2.63.0"));
+
+        let mut other = artifact("v2.62.0");
+        other.name = "junegunn/fzf".to_string();
+        assert!(check_frozen(&frozen, &other).is_err());
+    }
+
+    #[test]
+    fn lockfile_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCKFILE_NAME);
+        let lockfile = Lockfile {
+            version: LOCKFILE_VERSION,
+            artifacts: vec![artifact("v2.62.0")],
+        };
+        lockfile.write(&path).unwrap();
+        assert_eq!(Lockfile::read(&path).unwrap().artifacts, lockfile.artifacts);
+    }
+}
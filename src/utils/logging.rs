@@ -1,22 +1,163 @@
 use anyhow::{Context, Result};
-use log::LevelFilter;
-use std::fs;
-use std::path::PathBuf;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Size a log file grows to before it is rotated, unless PICOLAYER_LOG_MAX_SIZE says otherwise
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Rotated log files kept next to the current one, as `<file>.1` (newest) to `<file>.3`
+const ROTATED_FILES: usize = 3;
 
 pub fn init_logging(verbose: u8, quiet: bool) -> Result<()> {
-    let mut builder = env_logger::Builder::new();
-    builder.filter_level(get_log_level(verbose, quiet));
+    let level = get_log_level(verbose, quiet);
 
-    if let Ok(log_file_path) = std::env::var("PICOLAYER_LOG_FILE")
-        && !log_file_path.is_empty()
-    {
-        setup_file_logging(&mut builder, &log_file_path)?;
-    }
+    let file = match std::env::var("PICOLAYER_LOG_FILE") {
+        Ok(path) if !path.is_empty() => Some(file_logger(Path::new(&path), level, max_size())?),
+        _ => None,
+    };
+    // A log file replaces stderr unless PICOLAYER_LOG_TEE asks for both
+    let console = (file.is_none() || tee_enabled())
+        .then(|| env_logger::Builder::new().filter_level(level).build());
 
-    builder.init();
+    log::set_boxed_logger(Box::new(Tee { console, file }))
+        .context("A logger is already installed")?;
+    log::set_max_level(level);
     Ok(())
 }
 
+/// Sends each record to stderr, the log file, or both
+struct Tee {
+    console: Option<env_logger::Logger>,
+    file: Option<env_logger::Logger>,
+}
+
+impl Tee {
+    fn loggers(&self) -> impl Iterator<Item = &env_logger::Logger> {
+        self.console.iter().chain(self.file.iter())
+    }
+}
+
+impl Log for Tee {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.loggers().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        for logger in self.loggers() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        for logger in self.loggers() {
+            logger.flush();
+        }
+    }
+}
+
+fn tee_enabled() -> bool {
+    std::env::var("PICOLAYER_LOG_TEE")
+        .is_ok_and(|val| val == "1" || val.eq_ignore_ascii_case("true"))
+}
+
+/// PICOLAYER_LOG_MAX_SIZE in bytes, where 0 turns rotation off
+fn max_size() -> u64 {
+    std::env::var("PICOLAYER_LOG_MAX_SIZE")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+/// A logger appending timestamped records, tagged with the module that logged
+/// them, to `path`
+fn file_logger(path: &Path, level: LevelFilter, max_size: u64) -> Result<env_logger::Logger> {
+    let file = RotatingFile::open(path, max_size)?;
+    Ok(env_logger::Builder::new()
+        .filter_level(level)
+        .write_style(env_logger::WriteStyle::Never)
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "{} {:<5} [{}] {}",
+                buf.timestamp_millis(),
+                record.level(),
+                record.module_path().unwrap_or(record.target()),
+                record.args()
+            )
+        })
+        .target(env_logger::Target::Pipe(Box::new(file)))
+        .build())
+}
+
+/// A log file that is moved aside to `<file>.1` once it reaches `max_size` bytes
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_size: u64) -> Result<Self> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create log directory: {}", parent.display()))?;
+        }
+        let file = append(path)
+            .with_context(|| format!("Failed to create log file: {}", path.display()))?;
+        let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..ROTATED_FILES).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        self.file = append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
 fn get_log_level(verbose: u8, quiet: bool) -> LevelFilter {
     // CLI flags take precedence
     if quiet {
@@ -46,21 +187,6 @@ fn get_log_level(verbose: u8, quiet: bool) -> LevelFilter {
     LevelFilter::Warn
 }
 
-fn setup_file_logging(builder: &mut env_logger::Builder, log_file_path: &str) -> Result<()> {
-    let path = PathBuf::from(log_file_path);
-
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create log directory: {}", parent.display()))?;
-    }
-
-    let file = fs::File::create(&path)
-        .with_context(|| format!("Failed to create log file: {}", path.display()))?;
-
-    builder.target(env_logger::Target::Pipe(Box::new(file)));
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,4 +304,75 @@ mod tests {
         }
         assert_eq!(level, LevelFilter::Debug);
     }
+
+    #[test]
+    fn rotating_file_moves_full_logs_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/picolayer.log");
+        let mut file = RotatingFile::open(&path, 16).unwrap();
+        for line in [
+            "first line\n",
+            "second line\n",
+            "third line\n",
+            "fourth line\n",
+            "fifth line\n",
+        ] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fifth line\n");
+        assert_eq!(
+            fs::read_to_string(rotated(&path, 1)).unwrap(),
+            "fourth line\n"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated(&path, 3)).unwrap(),
+            "second line\n"
+        );
+        assert!(!rotated(&path, 4).exists());
+    }
+
+    #[test]
+    fn rotating_file_appends_to_an_existing_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("picolayer.log");
+        fs::write(&path, "earlier run\n").unwrap();
+        let mut file = RotatingFile::open(&path, 0).unwrap();
+        file.write_all(b"this run\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "earlier run\nthis run\n"
+        );
+    }
+
+    #[test]
+    fn file_logger_writes_timestamps_and_module_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("picolayer.log");
+        let logger = file_logger(&path, LevelFilter::Info, 0).unwrap();
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Info)
+                .module_path(Some("picolayer::installers::gh_release"))
+                .args(format_args!("Installed gh"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Debug)
+                .args(format_args!("hidden"))
+                .build(),
+        );
+        logger.flush();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let line = contents.lines().next().unwrap();
+        assert!(
+            line.ends_with("INFO  [picolayer::installers::gh_release] Installed gh"),
+            "{}",
+            line
+        );
+        assert!(line.contains('T') && line.contains('Z'), "{}", line);
+        assert!(!contents.contains("hidden"));
+    }
 }
@@ -1,5 +1,28 @@
+pub mod archive;
+pub mod audit;
+pub mod ci;
+pub mod download;
+pub mod env;
+pub mod env_registry;
+pub mod exec;
+pub mod http;
+pub mod http_cache;
+pub mod lock;
 pub mod logging;
+pub mod offline;
 pub mod os;
+pub mod ownership;
+pub mod policy;
+pub mod preflight;
+pub mod profile;
+pub mod progress;
 pub mod retry;
-pub mod subprocess;
+pub mod run_lock;
+pub mod scheduler;
+pub mod settings;
+pub mod state;
 pub mod sudo;
+pub mod timeout;
+pub mod tmp;
+pub mod user_mode;
+pub mod verify;
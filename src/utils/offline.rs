@@ -0,0 +1,27 @@
+use crate::error::Permanent;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Disable network access for the rest of the process
+pub fn enable() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--offline` is in effect
+pub fn is_enabled() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Fail fast, before anything is changed, when `what` (e.g. an installer) needs the network
+pub fn ensure_online(what: &str) -> Result<()> {
+    if is_enabled() {
+        return Err(Permanent::new(format!(
+            "{} needs network access, which --offline disables",
+            what
+        ))
+        .into());
+    }
+    Ok(())
+}
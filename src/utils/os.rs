@@ -1,58 +1,200 @@
 use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LinuxDistro {
     Ubuntu,
     Debian,
     Alpine,
+    Fedora,
+    /// RHEL and its rebuilds: CentOS, Rocky, AlmaLinux, Oracle Linux
+    Rhel,
+    Arch,
+    /// openSUSE and SLES
+    Suse,
+    AmazonLinux,
+    Wolfi,
+    NixOS,
     Other,
 }
 
-/// Detect the Linux distribution
-pub fn detect_distro() -> Result<LinuxDistro> {
-    if let Ok(contents) = fs::read_to_string("/etc/os-release") {
-        let mut kv = std::collections::HashMap::new();
+impl LinuxDistro {
+    /// The distribution an os-release `ID` (or `ID_LIKE` entry) names
+    fn from_id(id: &str) -> Option<Self> {
+        let distro = match id.to_ascii_lowercase().as_str() {
+            "ubuntu" => Self::Ubuntu,
+            "debian" => Self::Debian,
+            "alpine" => Self::Alpine,
+            "fedora" => Self::Fedora,
+            "rhel" | "centos" | "rocky" | "almalinux" | "ol" => Self::Rhel,
+            "arch" | "archarm" => Self::Arch,
+            "suse" | "opensuse" | "opensuse-leap" | "opensuse-tumbleweed" | "sles" | "sled" => {
+                Self::Suse
+            }
+            "amzn" => Self::AmazonLinux,
+            "wolfi" | "chainguard" => Self::Wolfi,
+            "nixos" => Self::NixOS,
+            _ => return None,
+        };
+        Some(distro)
+    }
+}
+
+/// The C library binaries on this system link against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Glibc,
+    Musl,
+}
+
+impl fmt::Display for Libc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Libc::Glibc => "glibc",
+            Libc::Musl => "musl",
+        })
+    }
+}
 
+/// The fields of /etc/os-release picolayer uses
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OsRelease {
+    pub id: String,
+    pub id_like: Vec<String>,
+    pub version_id: Option<String>,
+    pub codename: Option<String>,
+}
+
+impl OsRelease {
+    pub fn parse(contents: &str) -> Self {
+        let mut kv = HashMap::new();
         for line in contents.lines() {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
-            if let Some(pos) = line.find('=') {
-                let key = line[..pos].trim().to_ascii_uppercase();
-                let mut val = line[pos + 1..]
-                    .trim()
-                    .trim_matches(|c| c == '"' || c == '\'')
-                    .to_string();
-                if key == "ID_LIKE" {
-                    val = val.replace(&[',', ';'][..], " ");
-                }
-                kv.insert(key, val);
+            if let Some((key, val)) = line.split_once('=') {
+                let val = val.trim().trim_matches(|c| c == '"' || c == '\'');
+                kv.insert(key.trim().to_ascii_uppercase(), val.to_string());
             }
         }
+        let mut take = |key: &str| kv.remove(key).filter(|val| !val.is_empty());
 
-        let id = kv.get("ID").map(|s| s.as_str()).unwrap_or_default();
-        let id_like = kv.get("ID_LIKE").map(|s| s.as_str()).unwrap_or_default();
+        OsRelease {
+            id: take("ID").unwrap_or_default(),
+            id_like: take("ID_LIKE")
+                .map(|val| {
+                    val.replace(&[',', ';'][..], " ")
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            version_id: take("VERSION_ID"),
+            codename: take("VERSION_CODENAME").or_else(|| take("UBUNTU_CODENAME")),
+        }
+    }
 
-        let matches_any = |target: &str| {
-            if id.eq_ignore_ascii_case(target) {
-                return true;
-            }
-            id_like
-                .split_whitespace()
-                .any(|token| token.eq_ignore_ascii_case(target))
-        };
+    /// The distribution `ID` names, or else the first known one in `ID_LIKE`
+    pub fn distro(&self) -> Option<LinuxDistro> {
+        LinuxDistro::from_id(&self.id).or_else(|| {
+            self.id_like
+                .iter()
+                .find_map(|like| LinuxDistro::from_id(like))
+        })
+    }
+}
+
+/// The operating system picolayer runs on, for selecting release assets and
+/// package managers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    /// `std::env::consts::OS`, e.g. `linux` or `macos`
+    pub os: &'static str,
+    /// [`LinuxDistro::Other`] outside Linux
+    pub distro: LinuxDistro,
+    /// The distribution's `VERSION_ID`, e.g. `22.04` or `3.19.1`
+    pub version: Option<String>,
+    /// The distribution's `VERSION_CODENAME`, e.g. `jammy`
+    pub codename: Option<String>,
+    /// `std::env::consts::ARCH`, e.g. `x86_64` or `aarch64`
+    pub arch: &'static str,
+    /// `None` outside Linux
+    pub libc: Option<Libc>,
+}
+
+impl Platform {
+    pub fn detect() -> Self {
+        let os = std::env::consts::OS;
+        let arch = std::env::consts::ARCH;
+        if os != "linux" {
+            return Platform {
+                os,
+                distro: LinuxDistro::Other,
+                version: None,
+                codename: None,
+                arch,
+                libc: None,
+            };
+        }
 
-        if matches_any("ubuntu") {
-            return Ok(LinuxDistro::Ubuntu);
+        let release = read_os_release().unwrap_or_default();
+        let distro = detect_distro().unwrap_or(LinuxDistro::Other);
+        Platform {
+            os,
+            distro,
+            version: release.version_id,
+            codename: release.codename,
+            arch,
+            libc: Some(detect_libc(distro)),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.os, self.arch)?;
+        if self.os != "linux" {
+            return Ok(());
+        }
+        match self.distro {
+            LinuxDistro::Other => write!(f, ", unrecognized distribution")?,
+            distro => write!(f, ", {:?}", distro)?,
         }
-        if matches_any("alpine") {
-            return Ok(LinuxDistro::Alpine);
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
         }
-        if matches_any("debian") {
-            return Ok(LinuxDistro::Debian);
+        if let Some(codename) = &self.codename {
+            write!(f, " ({})", codename)?;
         }
+        if let Some(libc) = self.libc {
+            write!(f, ", {}", libc)?;
+        }
+        Ok(())
+    }
+}
+
+/// The platform picolayer runs on, detected once
+pub fn platform() -> &'static Platform {
+    static PLATFORM: OnceLock<Platform> = OnceLock::new();
+    PLATFORM.get_or_init(Platform::detect)
+}
+
+fn read_os_release() -> Option<OsRelease> {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .into_iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|contents| OsRelease::parse(&contents))
+}
+
+/// Detect the Linux distribution
+pub fn detect_distro() -> Result<LinuxDistro> {
+    if let Some(distro) = read_os_release().and_then(|release| release.distro()) {
+        return Ok(distro);
     }
 
     if fs::metadata("/etc/alpine-release").is_ok() {
@@ -61,6 +203,15 @@ pub fn detect_distro() -> Result<LinuxDistro> {
     if fs::metadata("/etc/debian_version").is_ok() {
         return Ok(LinuxDistro::Debian);
     }
+    if fs::metadata("/etc/fedora-release").is_ok() {
+        return Ok(LinuxDistro::Fedora);
+    }
+    if fs::metadata("/etc/redhat-release").is_ok() {
+        return Ok(LinuxDistro::Rhel);
+    }
+    if fs::metadata("/etc/arch-release").is_ok() {
+        return Ok(LinuxDistro::Arch);
+    }
     if let Ok(contents) = fs::read_to_string("/etc/lsb-release") {
         for line in contents.lines() {
             let line = line.trim();
@@ -79,6 +230,34 @@ pub fn detect_distro() -> Result<LinuxDistro> {
     Ok(LinuxDistro::Other)
 }
 
+/// musl on Alpine, or when only musl's dynamic loader is installed (e.g.
+/// `/lib/ld-musl-x86_64.so.1`), otherwise glibc
+fn detect_libc(distro: LinuxDistro) -> Libc {
+    let loaders: Vec<String> = ["/lib", "/lib64"]
+        .into_iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("ld-"))
+        .collect();
+    libc_for(distro, &loaders)
+}
+
+fn libc_for(distro: LinuxDistro, loaders: &[String]) -> Libc {
+    let musl = loaders.iter().any(|name| name.starts_with("ld-musl-"));
+    let glibc = loaders.iter().any(|name| name.starts_with("ld-linux"));
+    if distro == LinuxDistro::Alpine || (musl && !glibc) {
+        Libc::Musl
+    } else {
+        Libc::Glibc
+    }
+}
+
+/// Check if binaries on this system link against musl rather than glibc
+pub fn is_musl() -> bool {
+    platform().libc == Some(Libc::Musl)
+}
+
 /// Check if the system is Ubuntu
 pub fn is_ubuntu() -> bool {
     matches!(detect_distro(), Ok(LinuxDistro::Ubuntu))
@@ -112,6 +291,51 @@ pub fn is_linux() -> bool {
     std::env::consts::OS == "linux"
 }
 
+/// Space on the filesystem containing a path, in bytes
+#[derive(Debug, Clone, Copy)]
+pub struct FilesystemUsage {
+    /// Available to unprivileged users
+    pub available: u64,
+    pub used: u64,
+}
+
+pub fn filesystem_usage(path: &Path) -> std::io::Result<FilesystemUsage> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL-terminated string and stat is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The field widths differ between Linux and macOS
+    #[allow(clippy::unnecessary_cast)]
+    let (blocks, free, available, block_size) = (
+        stat.f_blocks as u64,
+        stat.f_bfree as u64,
+        stat.f_bavail as u64,
+        stat.f_frsize as u64,
+    );
+    Ok(FilesystemUsage {
+        available: available * block_size,
+        used: blocks.saturating_sub(free) * block_size,
+    })
+}
+
+/// Human-readable size, e.g. `512 MiB` or `1.5 GiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const GIB: u64 = 1024 * 1024 * 1024;
+    const MIB: u64 = 1024 * 1024;
+    const KIB: u64 = 1024;
+    if bytes >= GIB {
+        format!("{:.1} GiB", bytes as f64 / GIB as f64)
+    } else if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else {
+        format!("{} KiB", bytes / KIB)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,4 +393,114 @@ mod tests {
             assert!(!is_debian_like());
         }
     }
+
+    #[test]
+    fn os_release_parses_version_and_codename() {
+        let release = OsRelease::parse(
+            "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nID=ubuntu\nID_LIKE=debian\n\
+             VERSION_CODENAME=jammy\nUBUNTU_CODENAME=jammy\n",
+        );
+        assert_eq!(release.id, "ubuntu");
+        assert_eq!(release.id_like, vec!["debian"]);
+        assert_eq!(release.version_id.as_deref(), Some("22.04"));
+        assert_eq!(release.codename.as_deref(), Some("jammy"));
+        assert_eq!(release.distro(), Some(LinuxDistro::Ubuntu));
+    }
+
+    #[test]
+    fn os_release_maps_ids_and_derivatives() {
+        let cases = [
+            ("ID=fedora\nVERSION_ID=40", LinuxDistro::Fedora),
+            ("ID=\"rhel\"\nID_LIKE=\"fedora\"", LinuxDistro::Rhel),
+            (
+                "ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"",
+                LinuxDistro::Rhel,
+            ),
+            ("ID=arch", LinuxDistro::Arch),
+            ("ID=manjaro\nID_LIKE=arch", LinuxDistro::Arch),
+            (
+                "ID=\"opensuse-leap\"\nID_LIKE=\"suse opensuse\"",
+                LinuxDistro::Suse,
+            ),
+            ("ID=\"sles\"", LinuxDistro::Suse),
+            (
+                "ID=\"amzn\"\nID_LIKE=\"centos rhel fedora\"",
+                LinuxDistro::AmazonLinux,
+            ),
+            ("ID=wolfi", LinuxDistro::Wolfi),
+            ("ID=nixos", LinuxDistro::NixOS),
+            ("ID=alpine\nVERSION_ID=3.19.1", LinuxDistro::Alpine),
+            (
+                "ID=linuxmint\nID_LIKE=\"ubuntu debian\"",
+                LinuxDistro::Ubuntu,
+            ),
+            ("ID=raspbian\nID_LIKE=debian", LinuxDistro::Debian),
+        ];
+        for (contents, expected) in cases {
+            assert_eq!(
+                OsRelease::parse(contents).distro(),
+                Some(expected),
+                "{}",
+                contents
+            );
+        }
+        assert_eq!(OsRelease::parse("ID=gentoo").distro(), None);
+        assert_eq!(OsRelease::parse("ID=arch").version_id, None);
+    }
+
+    #[test]
+    fn libc_prefers_glibc_unless_only_musl_is_installed() {
+        let loaders = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(libc_for(LinuxDistro::Alpine, &[]), Libc::Musl);
+        assert_eq!(
+            libc_for(LinuxDistro::Other, &loaders(&["ld-musl-x86_64.so.1"])),
+            Libc::Musl
+        );
+        assert_eq!(
+            libc_for(
+                LinuxDistro::Debian,
+                &loaders(&["ld-linux-x86-64.so.2", "ld-musl-x86_64.so.1"])
+            ),
+            Libc::Glibc
+        );
+        assert_eq!(
+            libc_for(LinuxDistro::Wolfi, &loaders(&["ld-linux-aarch64.so.1"])),
+            Libc::Glibc
+        );
+    }
+
+    #[test]
+    fn platform_display_summarizes_the_system() {
+        let ubuntu = Platform {
+            os: "linux",
+            distro: LinuxDistro::Ubuntu,
+            version: Some("22.04".to_string()),
+            codename: Some("jammy".to_string()),
+            arch: "x86_64",
+            libc: Some(Libc::Glibc),
+        };
+        assert_eq!(
+            ubuntu.to_string(),
+            "linux x86_64, Ubuntu 22.04 (jammy), glibc"
+        );
+        assert_eq!(platform().os, std::env::consts::OS);
+    }
+
+    #[test]
+    fn format_bytes_uses_readable_units() {
+        assert_eq!(format_bytes(512 * 1024 * 1024), "512.0 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024 / 2), "1.5 GiB");
+        assert_eq!(format_bytes(4096), "4 KiB");
+    }
+
+    #[test]
+    fn filesystem_usage_reports_root_filesystem() {
+        assert!(filesystem_usage(Path::new("/")).unwrap().used > 0);
+        assert!(filesystem_usage(Path::new("/does/not/exist")).is_err());
+    }
 }
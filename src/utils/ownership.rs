@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::path::Path;
+
+/// Owner and group to give installed files, from `--chown user:group`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ownership {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl Ownership {
+    /// Parse `user`, `user:group` or `:group`. Numeric ids are taken as they are, so files
+    /// staged for another rootfs can name users this host does not have; names are looked
+    /// up in /etc/passwd and /etc/group.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (user, group) = match spec.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (spec, None),
+        };
+        let ownership = Ownership {
+            uid: resolve(user, "/etc/passwd", "user")?,
+            gid: group
+                .map(|group| resolve(group, "/etc/group", "group"))
+                .transpose()?
+                .flatten(),
+        };
+        anyhow::ensure!(
+            ownership.uid.is_some() || ownership.gid.is_some(),
+            "--chown needs a user, a group, or both, e.g. 1000:1000"
+        );
+        Ok(ownership)
+    }
+}
+
+fn resolve(name: &str, database: &str, what: &str) -> Result<Option<u32>> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(id) = name.parse() {
+        return Ok(Some(id));
+    }
+    let entries = std::fs::read_to_string(database)
+        .with_context(|| format!("Failed to read {} to look up {}", database, name))?;
+    lookup(&entries, name).map(Some).with_context(|| {
+        format!(
+            "No {} named {} in {}; use a numeric id",
+            what, name, database
+        )
+    })
+}
+
+/// The id of `name` in passwd or group file contents, whose third field is the id
+fn lookup(entries: &str, name: &str) -> Option<u32> {
+    entries.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        (fields.next() == Some(name))
+            .then(|| fields.nth(1)?.parse().ok())
+            .flatten()
+    })
+}
+
+/// Give an installed file the requested owner and mode; either may be left as it is
+pub fn apply(path: &Path, ownership: Option<Ownership>, mode: Option<u32>) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set mode {:o} on {}", mode, path.display()))?;
+        }
+        if let Some(Ownership { uid, gid }) = ownership {
+            std::os::unix::fs::chown(path, uid, gid).with_context(|| {
+                format!(
+                    "Failed to change the owner of {}; changing it to another user requires root",
+                    path.display()
+                )
+            })?;
+            info!("Changed the owner of {}", path.display());
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (path, ownership, mode);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\napp:x:1000:1000::/home/app:/bin/sh\n";
+
+    #[test]
+    fn numeric_ids_need_no_lookup() {
+        assert_eq!(
+            Ownership::parse("1000:2000").unwrap(),
+            Ownership {
+                uid: Some(1000),
+                gid: Some(2000)
+            }
+        );
+        assert_eq!(
+            Ownership::parse(":50").unwrap(),
+            Ownership {
+                uid: None,
+                gid: Some(50)
+            }
+        );
+        assert_eq!(Ownership::parse("0").unwrap().gid, None);
+        assert!(Ownership::parse(":").is_err());
+    }
+
+    #[test]
+    fn names_are_looked_up_by_their_first_field() {
+        assert_eq!(lookup(PASSWD, "app"), Some(1000));
+        assert_eq!(lookup(PASSWD, "root"), Some(0));
+        assert_eq!(lookup(PASSWD, "ap"), None);
+    }
+
+    #[test]
+    fn apply_sets_the_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tool");
+        std::fs::write(&path, b"#!/bin/sh\n").unwrap();
+
+        apply(&path, None, Some(0o750)).unwrap();
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::error::Permanent;
+use anyhow::Result;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How strongly a downloaded artifact is verified, weakest first
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Verification {
+    #[default]
+    None,
+    /// A published or pinned checksum
+    Checksum,
+    /// A GPG signature checked against a trusted key
+    Gpg,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Verification::None => "no verification",
+            Verification::Checksum => "checksum verification",
+            Verification::Gpg => "GPG signature verification",
+        })
+    }
+}
+
+/// The checksum files to prefer when a release publishes several, e.g. `SHA256SUMS` and
+/// `SHA512SUMS`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+/// Algorithms with practical collision attacks, which `--reject-weak-checksums` refuses
+pub const WEAK_ALGORITHMS: [&str; 2] = ["md5", "sha1"];
+
+/// A stricter requirement for artifacts from matching sources; unset fields match anything
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Command name, e.g. `gh-release` or `runtime`
+    pub command: Option<String>,
+    /// Repository owner or publisher, e.g. `hashicorp`
+    pub owner: Option<String>,
+    pub repo: Option<String>,
+    pub require: Verification,
+}
+
+impl Rule {
+    fn matches(&self, source: &Source) -> bool {
+        let field = |rule: &Option<String>, value: &str| {
+            rule.as_ref()
+                .is_none_or(|rule| rule.eq_ignore_ascii_case(value))
+        };
+        field(&self.command, source.command)
+            && field(&self.owner, source.owner)
+            && field(&self.repo, source.repo)
+    }
+}
+
+/// Where an artifact comes from, in the terms policy rules match on
+#[derive(Debug)]
+pub struct Source<'a> {
+    pub command: &'a str,
+    pub owner: &'a str,
+    pub repo: &'a str,
+}
+
+impl fmt::Display for Source<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/{}", self.command, self.owner, self.repo)
+    }
+}
+
+static REQUIRE: OnceLock<Verification> = OnceLock::new();
+static REJECT_WEAK: AtomicBool = AtomicBool::new(false);
+
+/// Apply `--require-verification` and `--reject-weak-checksums` on top of the configured policy
+pub fn configure(require: Option<Verification>, reject_weak_checksums: bool) {
+    if let Some(require) = require {
+        let _ = REQUIRE.set(require);
+    }
+    if reject_weak_checksums {
+        REJECT_WEAK.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether MD5 and SHA-1 checksums are refused rather than trusted to verify an artifact
+pub fn rejects_weak_checksums() -> bool {
+    REJECT_WEAK.load(Ordering::Relaxed) || super::settings::get().policy.reject_weak_checksums
+}
+
+/// Fail when `algorithm` is weak and the policy rejects weak checksums
+pub fn ensure_strong(algorithm: &str, what: &str) -> Result<()> {
+    if !rejects_weak_checksums() || !WEAK_ALGORITHMS.contains(&algorithm) {
+        return Ok(());
+    }
+    Err(Permanent::new(format!(
+        "Policy rejects weak checksums, but {} is only verifiable with {}",
+        what, algorithm
+    ))
+    .into())
+}
+
+/// The verification the policy requires of artifacts from `source`. The flag, the
+/// default and every matching rule all apply, so the strictest of them wins.
+pub fn required(source: &Source) -> Verification {
+    let policy = &super::settings::get().policy;
+    required_by(policy, REQUIRE.get().copied(), source)
+}
+
+fn required_by(
+    policy: &super::settings::PolicySettings,
+    flag: Option<Verification>,
+    source: &Source,
+) -> Verification {
+    policy
+        .rules
+        .iter()
+        .filter(|rule| rule.matches(source))
+        .map(|rule| rule.require)
+        .chain(policy.require)
+        .chain(flag)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Fail before anything is downloaded when `provided` is weaker than the policy requires
+pub fn enforce(source: &Source, provided: Verification) -> Result<()> {
+    let required = required(source);
+    if provided >= required {
+        return Ok(());
+    }
+    Err(Permanent::new(format!(
+        "Policy requires {} for {}, but this install has {}",
+        required, source, provided
+    ))
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::settings::PolicySettings;
+
+    const TERRAFORM: Source = Source {
+        command: "gh-release",
+        owner: "hashicorp",
+        repo: "terraform",
+    };
+    const GH: Source = Source {
+        command: "gh-release",
+        owner: "cli",
+        repo: "cli",
+    };
+
+    #[test]
+    fn strictest_matching_requirement_wins() {
+        let policy: PolicySettings = toml::from_str(
+            r#"
+            require = "checksum"
+
+            [[rules]]
+            owner = "HashiCorp"
+            require = "gpg"
+
+            [[rules]]
+            command = "gh-release"
+            require = "none"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(required_by(&policy, None, &TERRAFORM), Verification::Gpg);
+        assert_eq!(required_by(&policy, None, &GH), Verification::Checksum);
+        assert_eq!(
+            required_by(&policy, Some(Verification::Gpg), &GH),
+            Verification::Gpg
+        );
+        assert_eq!(
+            required_by(&PolicySettings::default(), None, &GH),
+            Verification::None
+        );
+    }
+}
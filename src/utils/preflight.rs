@@ -0,0 +1,135 @@
+use super::os;
+use crate::error::PicolayerError;
+use anyhow::Result;
+use std::path::Path;
+
+/// Room unpacking needs per downloaded byte: a copy of the archive plus its
+/// contents, which compress about 3:1
+pub const EXTRACT_FACTOR: u64 = 4;
+
+/// Check, before a download of `size` bytes for `what`, that `dir` can be written
+/// (or created) and has room for the unpacked result
+pub fn check(dir: &Path, size: Option<u64>, what: &str) -> Result<()> {
+    ensure_writable(dir)?;
+    if let Some(size) = size {
+        ensure_space(dir, size.saturating_mul(EXTRACT_FACTOR), what)?;
+    }
+    Ok(())
+}
+
+/// Fail unless `dir` is a writable directory, or does not exist yet and its nearest
+/// existing parent is writable
+pub fn ensure_writable(dir: &Path) -> Result<()> {
+    let existing = existing_ancestor(dir);
+    anyhow::ensure!(
+        existing.is_dir(),
+        "Cannot install into {}: {} is not a directory",
+        dir.display(),
+        existing.display()
+    );
+    if !is_writable(existing) {
+        let problem = if existing == dir {
+            format!("{} is not writable", dir.display())
+        } else {
+            format!(
+                "cannot create {}, {} is not writable",
+                dir.display(),
+                existing.display()
+            )
+        };
+        return Err(PicolayerError::PermissionDenied(format!(
+            "{}; run as root, pass --user-mode, or pick another --install-dir",
+            problem
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Whether `dir` is a writable directory, or does not exist yet and its nearest existing
+/// parent is writable
+pub fn can_write(dir: &Path) -> bool {
+    let existing = existing_ancestor(dir);
+    existing.is_dir() && is_writable(existing)
+}
+
+/// Fail unless the filesystem `dir` is on (or will be created on) has `needed` bytes free
+pub fn ensure_space(dir: &Path, needed: u64, what: &str) -> Result<()> {
+    let existing = existing_ancestor(dir);
+    // A filesystem that cannot be measured is left to fail on its own
+    let Ok(usage) = os::filesystem_usage(existing) else {
+        return Ok(());
+    };
+    check_space(
+        dir,
+        usage.available,
+        needed,
+        what,
+        "free up space or pick another directory",
+    )
+}
+
+/// An [`PicolayerError::InsufficientDiskSpace`] unless `needed` bytes fit in `available`
+pub(super) fn check_space(
+    dir: &Path,
+    available: u64,
+    needed: u64,
+    what: &str,
+    hint: &str,
+) -> Result<()> {
+    if available < needed {
+        return Err(PicolayerError::InsufficientDiskSpace(format!(
+            "No space left in {} for {}: needs about {}, {} free; {}",
+            dir.display(),
+            what,
+            os::format_bytes(needed),
+            os::format_bytes(available),
+            hint
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn existing_ancestor(dir: &Path) -> &Path {
+    dir.ancestors()
+        .find(|path| path.exists())
+        .unwrap_or(Path::new("/"))
+}
+
+fn is_writable(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: c_path is a valid NUL-terminated string
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_directories_are_checked_at_their_nearest_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ensure_writable(&dir.path().join("a/b/bin")).is_ok());
+
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(ensure_writable(&file).is_err());
+        assert!(ensure_writable(&file.join("bin")).is_err());
+    }
+
+    #[test]
+    fn check_space_is_classified_as_insufficient_disk_space() {
+        let dir = Path::new("/usr/local/bin");
+        assert!(check_space(dir, 2048, 1024, "gh", "hint").is_ok());
+        let err = check_space(dir, 1024, 4096, "gh", "free up space").unwrap_err();
+        assert_eq!(
+            crate::error::ErrorKind::of(&err),
+            crate::error::ErrorKind::InsufficientDiskSpace
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+/// Shell snippet that prepends `dir` to PATH unless it is already there
+pub fn path_snippet(dir: &Path) -> String {
+    format!(
+        "case \":$PATH:\" in\n  *\":{0}:\"*) ;;\n  *) export PATH=\"{0}:$PATH\" ;;\nesac\n",
+        dir.display()
+    )
+}
+
+/// Whether `dir` is one of the entries of the current PATH
+pub fn is_on_path(dir: &Path) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|p| p == dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_snippet_prepends_dir_once() {
+        let snippet = path_snippet(Path::new("/opt/npm-tools/bin"));
+        assert!(snippet.contains("*\":/opt/npm-tools/bin:\"*) ;;"));
+        assert!(snippet.contains("export PATH=\"/opt/npm-tools/bin:$PATH\""));
+    }
+
+    #[test]
+    fn is_on_path_checks_whole_entries() {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        if let Some(first) = std::env::split_paths(&path).next() {
+            assert!(is_on_path(&first));
+        }
+        assert!(!is_on_path(Path::new("/picolayer/not/on/path")));
+    }
+}
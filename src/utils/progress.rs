@@ -0,0 +1,104 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::LevelFilter;
+use std::cell::Cell;
+use std::future::Future;
+use std::io::IsTerminal;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Every bar shares one draw target, so concurrent downloads stack instead of overwriting each other
+static BARS: LazyLock<MultiProgress> = LazyLock::new(|| {
+    if std::io::stderr().is_terminal() && log::max_level() >= LevelFilter::Info {
+        MultiProgress::new()
+    } else {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    }
+});
+
+static DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// Bytes downloaded by the running manifest step
+    static STEP_DOWNLOADED: Cell<u64>;
+}
+
+/// A bar for a download of `len` bytes, or a byte-counting spinner when the length is unknown.
+/// Hidden unless stderr is a terminal and info logging is on.
+pub fn download_bar(name: &str, len: Option<u64>) -> ProgressBar {
+    let bar = match len {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} {msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+        ),
+        None => ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner:.green} {msg} {bytes} ({bytes_per_sec})")
+                .unwrap(),
+        ),
+    };
+    BARS.add(bar.with_message(name.to_string()))
+}
+
+/// A bar counting finished downloads out of those started, for an install fetching several at once
+pub fn batch_bar(message: &str) -> ProgressBar {
+    let bar = ProgressBar::new(0)
+        .with_style(ProgressStyle::with_template("{spinner:.green} {msg} {pos}/{len}").unwrap())
+        .with_message(message.to_string());
+    BARS.add(bar)
+}
+
+/// A spinner for work whose progress cannot be measured, such as a registry pull
+pub fn spinner(message: &str) -> ProgressBar {
+    let bar = ProgressBar::new_spinner()
+        .with_style(ProgressStyle::with_template("{spinner:.green} {msg} ({elapsed})").unwrap())
+        .with_message(message.to_string());
+    bar.enable_steady_tick(std::time::Duration::from_millis(120));
+    BARS.add(bar)
+}
+
+/// Count bytes fetched over the network towards the totals
+pub fn record_download(bytes: u64) {
+    DOWNLOADED.fetch_add(bytes, Ordering::Relaxed);
+    let _ = STEP_DOWNLOADED.try_with(|step| step.set(step.get() + bytes));
+}
+
+/// Bytes downloaded so far by this process
+pub fn downloaded() -> u64 {
+    DOWNLOADED.load(Ordering::Relaxed)
+}
+
+/// Count an artifact served from a cache instead of the network
+pub fn record_cache_hit() {
+    CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Artifacts served from caches so far by this process
+pub fn cache_hits() -> u64 {
+    CACHE_HITS.load(Ordering::Relaxed)
+}
+
+/// Run a manifest step, returning the bytes it downloaded alongside its output
+pub async fn measure<F: Future>(future: F) -> (F::Output, u64) {
+    STEP_DOWNLOADED
+        .scope(Cell::new(0), async {
+            let output = future.await;
+            (output, STEP_DOWNLOADED.with(Cell::get))
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn measure_counts_only_its_own_downloads() {
+        record_download(5);
+        let ((), step) = measure(async { record_download(7) }).await;
+        assert_eq!(step, 7);
+        assert!(downloaded() >= 12);
+    }
+}
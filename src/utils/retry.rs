@@ -1,12 +1,58 @@
 use anyhow::Result;
-use log::warn;
+use log::{info, warn};
 use std::future::Future;
-use std::time::Duration;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
 use crate::cli::RetryConfig;
+use crate::error::ErrorClass;
 
-/// Execute a function with retry logic and exponential backoff
+/// Compute the backoff delay before the retry following `attempt` (zero-based)
+fn backoff_delay(config: &RetryConfig, attempt: u32) -> Duration {
+    let delay_ms =
+        (config.initial_delay_ms as f64 * config.backoff_multiplier.powi(attempt as i32)) as u64;
+    Duration::from_millis(delay_ms)
+}
+
+/// Scale `delay` by a random factor within `jitter` of 1, e.g. 0.9 to 1.1 for a jitter of 0.1
+fn jittered(delay: Duration, jitter: f64) -> Duration {
+    if jitter <= 0.0 {
+        return delay;
+    }
+    // Each RandomState is freshly keyed, which is random enough to spread out retries
+    let unit = (std::collections::hash_map::RandomState::new().hash_one(()) >> 11) as f64
+        / (1u64 << 53) as f64;
+    delay.mul_f64((1.0 + jitter * (2.0 * unit - 1.0)).max(0.0))
+}
+
+/// The delay before retrying after `attempt`, or None when the retry would end after
+/// the max elapsed time
+fn next_delay(config: &RetryConfig, attempt: u32, started: Instant) -> Option<Duration> {
+    let delay = jittered(backoff_delay(config, attempt), config.jitter);
+    match config.max_elapsed_ms {
+        Some(max) if started.elapsed() + delay > Duration::from_millis(max) => None,
+        _ => Some(delay),
+    }
+}
+
+/// Whether an error is worth retrying, judged by the first cause that classifies itself.
+///
+/// HTTP errors are retried only for statuses in the config's allow-list, so a 404 fails
+/// at once, and [`Permanent`](crate::error::Permanent) failures such as missing assets or
+/// checksum mismatches are never retried. Errors nothing classifies are retried.
+pub fn is_retryable(config: &RetryConfig, err: &anyhow::Error) -> bool {
+    match crate::error::classify(err) {
+        Some(ErrorClass::Permanent) => false,
+        Some(ErrorClass::Status(status)) => config.retry_statuses.contains(&status),
+        Some(ErrorClass::Transient) | None => true,
+    }
+}
+
+/// Execute a function with retry logic and exponential backoff.
+///
+/// Only errors that [`is_retryable`] accepts are retried; any other error is returned
+/// immediately.
 pub async fn retry_async<F, Fut, T>(
     config: &RetryConfig,
     operation_name: &str,
@@ -16,45 +62,152 @@ where
     F: FnMut() -> Fut,
     Fut: Future<Output = Result<T>>,
 {
-    if config.max_retries == 0 {
-        return operation().await;
+    let started = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        let err = match operation().await {
+            Ok(result) => return Ok(result),
+            Err(err) => err,
+        };
+        let Some(delay) = retry_delay(
+            config,
+            operation_name,
+            attempt,
+            started,
+            is_retryable(config, &err),
+        ) else {
+            return Err(err);
+        };
+
+        warn!(
+            "{} failed (attempt {}/{}), retrying in {}ms: {}",
+            operation_name,
+            attempt + 1,
+            config.max_retries + 1,
+            delay.as_millis(),
+            err
+        );
+        sleep(delay).await;
+        attempt += 1;
     }
+}
 
-    let mut last_error = None;
+/// Longest wait between two polls of [`poll_async`], however far the backoff has grown
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
 
-    for attempt in 0..=config.max_retries {
-        match operation().await {
+/// Poll `operation` until it succeeds, for as long as its failures are `pending` ones that
+/// a later attempt may not see, such as a release whose assets are still uploading.
+///
+/// Polls back off like retries do, up to once a minute, and stop once `timeout` would pass
+/// before the next one. Any other failure is returned at once.
+pub async fn poll_async<F, Fut, T, P>(
+    config: &RetryConfig,
+    what: &str,
+    timeout: Duration,
+    mut operation: F,
+    pending: P,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+    P: Fn(&anyhow::Error) -> bool,
+{
+    let started = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        let err = match operation().await {
             Ok(result) => return Ok(result),
-            Err(err) => {
-                last_error = Some(err);
-
-                if attempt < config.max_retries {
-                    let delay_ms = (config.initial_delay_ms as f64
-                        * config.backoff_multiplier.powi(attempt as i32))
-                        as u64;
-
-                    warn!(
-                        "{} failed (attempt {}/{}), retrying in {}ms: {}",
-                        operation_name,
-                        attempt + 1,
-                        config.max_retries + 1,
-                        delay_ms,
-                        last_error.as_ref().unwrap()
-                    );
-
-                    sleep(Duration::from_millis(delay_ms)).await;
-                } else {
-                    warn!(
-                        "{} failed after {} attempts",
-                        operation_name,
-                        config.max_retries + 1
-                    );
-                }
-            }
+            Err(err) => err,
+        };
+        if !pending(&err) {
+            return Err(err);
+        }
+        let delay = jittered(backoff_delay(config, attempt), config.jitter).min(MAX_POLL_INTERVAL);
+        if started.elapsed() + delay > timeout {
+            return Err(err.context(format!(
+                "Gave up waiting for {} after {}s",
+                what,
+                started.elapsed().as_secs()
+            )));
         }
+
+        info!(
+            "Waiting for {} (checking again in {}s): {}",
+            what,
+            delay.as_secs(),
+            err
+        );
+        sleep(delay).await;
+        attempt += 1;
     }
+}
 
-    Err(last_error.unwrap())
+/// Execute a blocking function with retry logic and exponential backoff.
+///
+/// Only errors for which `is_retryable` returns true are retried; any other error
+/// is returned immediately.
+pub fn retry_sync<F, T, R>(
+    config: &RetryConfig,
+    operation_name: &str,
+    mut operation: F,
+    is_retryable: R,
+) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+    R: Fn(&anyhow::Error) -> bool,
+{
+    let started = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        let err = match operation() {
+            Ok(result) => return Ok(result),
+            Err(err) => err,
+        };
+        let Some(delay) = retry_delay(config, operation_name, attempt, started, is_retryable(&err))
+        else {
+            return Err(err);
+        };
+
+        warn!(
+            "{} failed (attempt {}/{}), retrying in {}ms: {}",
+            operation_name,
+            attempt + 1,
+            config.max_retries + 1,
+            delay.as_millis(),
+            err
+        );
+        std::thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// The delay before the next attempt, or None after logging why there is none
+fn retry_delay(
+    config: &RetryConfig,
+    operation_name: &str,
+    attempt: u32,
+    started: Instant,
+    retryable: bool,
+) -> Option<Duration> {
+    if !retryable || attempt >= config.max_retries {
+        if attempt > 0 {
+            warn!("{} failed after {} attempts", operation_name, attempt + 1);
+        }
+        return None;
+    }
+    let delay = next_delay(config, attempt, started);
+    if delay.is_none() {
+        warn!(
+            "{} failed after {} attempts, giving up since another retry would exceed {}ms",
+            operation_name,
+            attempt + 1,
+            config.max_elapsed_ms.unwrap_or_default()
+        );
+    }
+    delay
 }
 
 #[cfg(test)]
@@ -67,9 +220,59 @@ mod tests {
             max_retries,
             initial_delay_ms: 1, // 1ms for fast tests
             backoff_multiplier: 1.0,
+            ..RetryConfig::default()
         }
     }
 
+    #[tokio::test]
+    async fn poll_waits_only_for_pending_failures() {
+        let attempts = AtomicU32::new(0);
+        let result = poll_async(
+            &test_config(0),
+            "test",
+            Duration::from_secs(5),
+            || {
+                let count = attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if count < 3 {
+                        Err(anyhow::anyhow!("not yet"))
+                    } else {
+                        Ok(42)
+                    }
+                }
+            },
+            |err| err.to_string() == "not yet",
+        )
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        let result: Result<()> = poll_async(
+            &test_config(0),
+            "test",
+            Duration::from_secs(5),
+            || async { Err(anyhow::anyhow!("broken")) },
+            |err| err.to_string() == "not yet",
+        )
+        .await;
+        assert_eq!(result.unwrap_err().to_string(), "broken");
+
+        let result: Result<()> = poll_async(
+            &test_config(0),
+            "test",
+            Duration::ZERO,
+            || async { Err(anyhow::anyhow!("not yet")) },
+            |err| err.to_string() == "not yet",
+        )
+        .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .starts_with("Gave up waiting for test")
+        );
+    }
+
     #[tokio::test]
     async fn retry_succeeds_on_first_attempt() {
         let result = retry_async(&test_config(3), "test", || async {
@@ -120,4 +323,118 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(attempts.load(Ordering::SeqCst), 1);
     }
+
+    #[test]
+    fn retry_sync_retries_retryable_errors() {
+        let mut attempts = 0;
+        let result = retry_sync(
+            &test_config(3),
+            "test",
+            || {
+                attempts += 1;
+                if attempts < 3 {
+                    Err(anyhow::anyhow!("transient failure"))
+                } else {
+                    Ok(42)
+                }
+            },
+            |_| true,
+        );
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn retry_sync_stops_on_permanent_error() {
+        let mut attempts = 0;
+        let result: Result<i32> = retry_sync(
+            &test_config(3),
+            "test",
+            || {
+                attempts += 1;
+                Err(anyhow::anyhow!("permanent failure"))
+            },
+            |_| false,
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn retry_async_does_not_retry_not_found() {
+        let attempts = AtomicU32::new(0);
+        let result: Result<i32> = retry_async(&test_config(3), "test", || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async {
+                Err(crate::utils::http::StatusError {
+                    url: "https://example.com/missing".to_string(),
+                    status: reqwest::StatusCode::NOT_FOUND,
+                }
+                .into())
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn is_retryable_follows_the_status_allow_list() {
+        let config = test_config(3);
+        let status = |status| {
+            anyhow::Error::from(crate::utils::http::StatusError {
+                url: "https://example.com".to_string(),
+                status,
+            })
+            .context("Failed to fetch release")
+        };
+        assert!(is_retryable(
+            &config,
+            &status(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert!(is_retryable(
+            &config,
+            &status(reqwest::StatusCode::TOO_MANY_REQUESTS)
+        ));
+        assert!(!is_retryable(
+            &config,
+            &status(reqwest::StatusCode::NOT_FOUND)
+        ));
+        assert!(!is_retryable(
+            &config,
+            &crate::error::Permanent::new("Checksum mismatch: expected a, got b").into()
+        ));
+        assert!(is_retryable(&config, &anyhow::anyhow!("connection reset")));
+    }
+
+    #[test]
+    fn retry_sync_stops_when_max_elapsed_is_reached() {
+        let config = RetryConfig {
+            initial_delay_ms: 50,
+            max_elapsed_ms: Some(10),
+            ..test_config(5)
+        };
+        let mut attempts = 0;
+        let result: Result<i32> = retry_sync(
+            &config,
+            "test",
+            || {
+                attempts += 1;
+                Err(anyhow::anyhow!("transient failure"))
+            },
+            |_| true,
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn jittered_stays_within_bounds() {
+        let delay = Duration::from_millis(1000);
+        assert_eq!(jittered(delay, 0.0), delay);
+        for _ in 0..100 {
+            let d = jittered(delay, 0.25);
+            assert!(d >= Duration::from_millis(750) && d <= Duration::from_millis(1250));
+        }
+    }
 }
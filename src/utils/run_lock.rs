@@ -0,0 +1,133 @@
+use log::{debug, info};
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const LOCK_FILE_NAME: &str = "lock";
+
+/// The lock file while this process holds it, and how many guards share it
+struct Held {
+    _file: Option<File>,
+    guards: usize,
+}
+
+static HELD: Mutex<Option<Held>> = Mutex::new(None);
+
+/// Keeps other picolayer processes out of the state database and package managers until
+/// dropped
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct RunLock(());
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(h) = held.as_mut() {
+            h.guards -= 1;
+            if h.guards == 0 {
+                // Closing the file releases the flock
+                *held = None;
+            }
+        }
+    }
+}
+
+/// Where the advisory lock lives: next to the state database, so every picolayer process
+/// sharing that database also shares the lock
+pub fn path() -> PathBuf {
+    super::state::dir().join(LOCK_FILE_NAME)
+}
+
+/// Take the machine-wide advisory lock, waiting while another picolayer process holds it.
+///
+/// Re-entrant within a process, so an uninstall holding it can still call into package
+/// managers. Best effort: when the lock file cannot be opened, e.g. a non-root run before
+/// the state directory exists, this proceeds without it.
+pub fn acquire() -> RunLock {
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    match held.as_mut() {
+        Some(h) => h.guards += 1,
+        None => {
+            *held = Some(Held {
+                _file: lock_file(),
+                guards: 1,
+            })
+        }
+    }
+    RunLock(())
+}
+
+fn lock_file() -> Option<File> {
+    let path = path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    // flock works on read-only descriptors, so a lock file created by root still serves
+    // other users
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .or_else(|_| File::open(&path));
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            debug!("Running without {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    if !flock(&file, libc::LOCK_EX | libc::LOCK_NB) {
+        info!(
+            "Waiting for another picolayer process to release {}",
+            path.display()
+        );
+        if !flock(&file, libc::LOCK_EX) {
+            debug!(
+                "Running without {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            );
+            return None;
+        }
+    }
+    Some(file)
+}
+
+fn flock(file: &File, operation: libc::c_int) -> bool {
+    // SAFETY: the descriptor stays open for the duration of the call
+    unsafe { libc::flock(file.as_raw_fd(), operation) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn lock_is_reentrant_and_excludes_other_descriptors() {
+        let dir = tempfile::tempdir().unwrap();
+        // SAFETY: serialized via #[serial] so no concurrent env access
+        unsafe {
+            std::env::set_var("PICOLAYER_STATE_DIR", dir.path());
+        }
+
+        let outer = acquire();
+        let inner = acquire();
+        let other = File::open(path()).unwrap();
+        assert!(!flock(&other, libc::LOCK_EX | libc::LOCK_NB));
+
+        drop(inner);
+        assert!(!flock(&other, libc::LOCK_EX | libc::LOCK_NB));
+        drop(outer);
+        assert!(flock(&other, libc::LOCK_EX | libc::LOCK_NB));
+
+        // SAFETY: serialized via #[serial] so no concurrent env access
+        unsafe {
+            std::env::remove_var("PICOLAYER_STATE_DIR");
+        }
+    }
+}
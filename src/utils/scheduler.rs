@@ -0,0 +1,122 @@
+use super::retry::retry_async;
+use crate::cli::RetryConfig;
+use anyhow::Result;
+use indicatif::ProgressBar;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const MAX_DOWNLOADS_ENV: &str = "PICOLAYER_MAX_DOWNLOADS";
+
+/// Transfers in flight at once unless --max-downloads or PICOLAYER_MAX_DOWNLOADS says otherwise
+pub const DEFAULT_MAX_DOWNLOADS: usize = 4;
+
+static SLOTS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+
+/// Allow the --max-downloads flag, or else PICOLAYER_MAX_DOWNLOADS, transfers at once
+/// across the whole process, including manifest steps running in parallel
+pub fn configure(flag: Option<usize>) {
+    let max = flag
+        .or_else(|| {
+            std::env::var(MAX_DOWNLOADS_ENV)
+                .ok()
+                .and_then(|max| max.parse().ok())
+        })
+        .unwrap_or(DEFAULT_MAX_DOWNLOADS);
+    let _ = SLOTS.set(Arc::new(Semaphore::new(max.max(1))));
+}
+
+fn slots() -> &'static Arc<Semaphore> {
+    SLOTS.get_or_init(|| Arc::new(Semaphore::new(DEFAULT_MAX_DOWNLOADS)))
+}
+
+/// Wait for a download slot, held for the length of one transfer so a retry backing
+/// off leaves it to others
+pub async fn slot() -> OwnedSemaphorePermit {
+    slots()
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("download slots are never closed")
+}
+
+/// The downloads one install needs, run concurrently behind a single progress line
+pub struct Batch<'a> {
+    retry_config: &'a RetryConfig,
+    bar: ProgressBar,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(retry_config: &'a RetryConfig) -> Self {
+        Batch {
+            retry_config,
+            bar: super::progress::batch_bar("Downloading"),
+        }
+    }
+
+    /// Run one download of the batch, retried on its own so a flaky transfer does not
+    /// restart the others
+    pub async fn fetch<T, F, Fut>(&self, what: &str, operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.bar.inc_length(1);
+        let result = retry_async(self.retry_config, what, operation).await;
+        self.bar.inc(1);
+        result
+    }
+}
+
+impl Drop for Batch<'_> {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn batch_retries_each_download_on_its_own() {
+        let retry_config = RetryConfig {
+            max_retries: 2,
+            initial_delay_ms: 1,
+            ..RetryConfig::default()
+        };
+        let batch = Batch::new(&retry_config);
+        let flaky_attempts = AtomicUsize::new(0);
+        let steady_attempts = AtomicUsize::new(0);
+
+        let (flaky, steady) = tokio::join!(
+            batch.fetch("flaky", || async {
+                match flaky_attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => anyhow::bail!("connection reset"),
+                    _ => Ok("asset"),
+                }
+            }),
+            batch.fetch("steady", || async {
+                steady_attempts.fetch_add(1, Ordering::SeqCst);
+                Ok("checksums")
+            }),
+        );
+
+        assert_eq!(flaky.unwrap(), "asset");
+        assert_eq!(steady.unwrap(), "checksums");
+        assert_eq!(flaky_attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(steady_attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(batch.bar.position(), 2);
+        assert_eq!(batch.bar.length(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn slots_bound_concurrent_transfers() {
+        let held: Vec<_> =
+            futures_util::future::join_all((0..DEFAULT_MAX_DOWNLOADS).map(|_| slot())).await;
+        assert_eq!(slots().available_permits(), 0);
+        drop(held);
+        assert_eq!(slots().available_permits(), DEFAULT_MAX_DOWNLOADS);
+    }
+}
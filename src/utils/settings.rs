@@ -0,0 +1,302 @@
+use super::policy::{self, Verification};
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Once, OnceLock};
+
+/// System-wide config file, read first
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/picolayer/config.toml";
+
+/// Environment variable naming a config file to read instead of the user config file
+const CONFIG_ENV: &str = "PICOLAYER_CONFIG";
+
+/// Environment variable that overrides `cache_dir`
+const CACHE_DIR_ENV: &str = "PICOLAYER_CACHE_DIR";
+
+/// Environment variable that overrides `install_dir`
+const INSTALL_DIR_ENV: &str = "PICOLAYER_INSTALL_DIR";
+
+pub const DEFAULT_CACHE_DIR: &str = "/var/cache/picolayer";
+
+pub const DEFAULT_INSTALL_DIR: &str = "/usr/local/bin";
+
+/// Defaults read from config.toml. Command-line flags and environment variables win
+/// over the user file, which wins over the system file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Token for the GitHub API, used when GITHUB_TOKEN is unset
+    pub github_token: Option<String>,
+    /// Proxy for downloads, used when --proxy is not given
+    pub proxy: Option<String>,
+    /// PEM bundle of extra CA certificates, used when --ca-bundle is not given
+    pub ca_bundle: Option<PathBuf>,
+    /// Directory for cached downloads
+    pub cache_dir: Option<PathBuf>,
+    /// Where binaries go when --install-dir is not given
+    pub install_dir: Option<String>,
+    pub retry: RetrySettings,
+    /// Credentials keyed by registry host, e.g. `ghcr.io`
+    pub registries: BTreeMap<String, RegistryCredentials>,
+    pub policy: PolicySettings,
+}
+
+/// Retry defaults for `--max-retries`, `--retry-delay-ms`, `--retry-backoff-multiplier`,
+/// `--retry-jitter`, `--retry-max-elapsed-ms` and `--retry-statuses`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrySettings {
+    pub max_retries: Option<u32>,
+    pub delay_ms: Option<u64>,
+    pub backoff_multiplier: Option<f64>,
+    pub jitter: Option<f64>,
+    pub max_elapsed_ms: Option<u64>,
+    pub statuses: Option<Vec<u16>>,
+}
+
+/// Verification required of downloaded artifacts. Rules from every file apply, so the
+/// user file can tighten the system policy but not loosen it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicySettings {
+    /// Required of every artifact, default `none`
+    pub require: Option<Verification>,
+    /// Refuse MD5 and SHA-1 checksums; set in any file, it cannot be unset by a later one
+    pub reject_weak_checksums: bool,
+    pub rules: Vec<policy::Rule>,
+}
+
+/// Login for an OCI registry; a token takes precedence over username and password
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistryCredentials {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+impl Settings {
+    /// Config files in the order they are read; later files override earlier ones
+    pub fn paths() -> Vec<PathBuf> {
+        let user = std::env::var_os(CONFIG_ENV)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                let config_home = std::env::var_os("XDG_CONFIG_HOME")
+                    .filter(|dir| !dir.is_empty())
+                    .map(PathBuf::from)
+                    .or_else(|| {
+                        std::env::var_os("HOME")
+                            .filter(|home| !home.is_empty())
+                            .map(|home| Path::new(&home).join(".config"))
+                    })?;
+                Some(config_home.join("picolayer").join("config.toml"))
+            });
+        std::iter::once(PathBuf::from(SYSTEM_CONFIG_PATH))
+            .chain(user)
+            .collect()
+    }
+
+    /// Read and merge every config file that exists
+    pub fn load() -> Result<Self> {
+        let mut settings = Self::default();
+        for path in Self::paths() {
+            if !path.exists() {
+                continue;
+            }
+            debug!("Reading config from {}", path.display());
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let file: Settings = toml::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            settings = settings.merge(file);
+        }
+        Ok(settings)
+    }
+
+    /// Overlay `other` on these settings, field by field
+    fn merge(mut self, other: Settings) -> Self {
+        self.registries.extend(other.registries);
+        self.policy.rules.extend(other.policy.rules);
+        Self {
+            github_token: other.github_token.or(self.github_token),
+            proxy: other.proxy.or(self.proxy),
+            ca_bundle: other.ca_bundle.or(self.ca_bundle),
+            cache_dir: other.cache_dir.or(self.cache_dir),
+            install_dir: other.install_dir.or(self.install_dir),
+            retry: RetrySettings {
+                max_retries: other.retry.max_retries.or(self.retry.max_retries),
+                delay_ms: other.retry.delay_ms.or(self.retry.delay_ms),
+                backoff_multiplier: other
+                    .retry
+                    .backoff_multiplier
+                    .or(self.retry.backoff_multiplier),
+                jitter: other.retry.jitter.or(self.retry.jitter),
+                max_elapsed_ms: other.retry.max_elapsed_ms.or(self.retry.max_elapsed_ms),
+                statuses: other.retry.statuses.or(self.retry.statuses),
+            },
+            registries: self.registries,
+            policy: PolicySettings {
+                require: other.policy.require.max(self.policy.require),
+                reject_weak_checksums: other.policy.reject_weak_checksums
+                    || self.policy.reject_weak_checksums,
+                rules: self.policy.rules,
+            },
+        }
+    }
+}
+
+/// Use `settings` for the rest of the process; only the first call takes effect
+pub fn init(settings: Settings) {
+    let _ = SETTINGS.set(settings);
+}
+
+/// The settings in effect, or the defaults if none were loaded
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+/// GITHUB_TOKEN, or else `github_token` from the config file
+pub fn github_token() -> Option<String> {
+    std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .or_else(|| get().github_token.clone())
+}
+
+/// PICOLAYER_CACHE_DIR, or else `cache_dir` from the config file
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os(CACHE_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| get().cache_dir.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR))
+}
+
+/// The --install-dir flag, or else PICOLAYER_INSTALL_DIR, or else `install_dir` from the
+/// config file, or else ~/.local/bin in user mode, or else /usr/local/bin
+pub fn install_dir(flag: Option<String>) -> String {
+    flag.or_else(|| {
+        std::env::var(INSTALL_DIR_ENV)
+            .ok()
+            .filter(|dir| !dir.is_empty())
+    })
+    .or_else(|| get().install_dir.clone())
+    .or_else(|| {
+        super::user_mode::is_enabled()
+            .then(super::user_mode::bin_dir)
+            .and_then(Result::ok)
+            .map(|dir| dir.display().to_string())
+    })
+    .unwrap_or_else(default_install_dir)
+}
+
+/// /usr/local/bin, or ~/.local/bin for a non-root user who cannot write there, so an
+/// unconfigured install works without privileges instead of failing
+fn default_install_dir() -> String {
+    let default = Path::new(DEFAULT_INSTALL_DIR);
+    if super::sudo::is_root() || super::preflight::can_write(default) {
+        return DEFAULT_INSTALL_DIR.to_string();
+    }
+    let Ok(fallback) = super::user_mode::bin_dir() else {
+        return DEFAULT_INSTALL_DIR.to_string();
+    };
+
+    // Resolved again for confirmation and presence checks, so only say it once
+    static FALLBACK_NOTICE: Once = Once::new();
+    FALLBACK_NOTICE.call_once(|| {
+        info!(
+            "{} is not writable, installing into {} instead; set --install-dir or {} to choose",
+            DEFAULT_INSTALL_DIR,
+            fallback.display(),
+            INSTALL_DIR_ENV
+        )
+    });
+    fallback.display().to_string()
+}
+
+/// Credentials configured for a registry host
+pub fn registry_credentials(registry: &str) -> Option<&'static RegistryCredentials> {
+    get().registries.get(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn later_files_override_earlier_ones() {
+        let system: Settings = toml::from_str(
+            r#"
+            github_token = "system"
+            install_dir = "/opt/bin"
+
+            [retry]
+            max_retries = 3
+
+            [registries."ghcr.io"]
+            token = "system-token"
+
+            [policy]
+            require = "checksum"
+            reject_weak_checksums = true
+            "#,
+        )
+        .unwrap();
+        let user: Settings = toml::from_str(
+            r#"
+            github_token = "user"
+
+            [retry]
+            delay_ms = 500
+
+            [registries."registry.example.com"]
+            username = "me"
+            password = "secret"
+
+            [policy]
+            require = "none"
+
+            [[policy.rules]]
+            owner = "hashicorp"
+            require = "gpg"
+            "#,
+        )
+        .unwrap();
+
+        let settings = system.merge(user);
+        assert_eq!(settings.github_token.as_deref(), Some("user"));
+        assert_eq!(settings.install_dir.as_deref(), Some("/opt/bin"));
+        assert_eq!(settings.retry.max_retries, Some(3));
+        assert_eq!(settings.retry.delay_ms, Some(500));
+        assert_eq!(settings.registries.len(), 2);
+        assert_eq!(settings.policy.require, Some(Verification::Checksum));
+        assert_eq!(settings.policy.rules.len(), 1);
+        assert!(settings.policy.reject_weak_checksums);
+    }
+
+    #[test]
+    #[serial]
+    fn install_dir_env_is_overridden_by_the_flag() {
+        // SAFETY: serialized via #[serial] so no concurrent env access
+        unsafe {
+            std::env::set_var(INSTALL_DIR_ENV, "/opt/tools/bin");
+        }
+        assert_eq!(install_dir(None), "/opt/tools/bin");
+        assert_eq!(install_dir(Some("/opt/bin".to_string())), "/opt/bin");
+        // SAFETY: serialized via #[serial] so no concurrent env access
+        unsafe {
+            std::env::remove_var(INSTALL_DIR_ENV);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Settings>("github-token = \"x\"").is_err());
+    }
+}
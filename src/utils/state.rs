@@ -0,0 +1,241 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory holding the installation state database
+pub const DEFAULT_STATE_DIR: &str = "/var/lib/picolayer";
+
+/// Environment variable that overrides the state directory
+const STATE_DIR_ENV: &str = "PICOLAYER_STATE_DIR";
+
+const STATE_FILE_NAME: &str = "state.json";
+
+const STATE_VERSION: u32 = 1;
+
+/// Serializes read-modify-write updates from manifest steps running concurrently
+static UPDATE: Mutex<()> = Mutex::new(());
+
+/// A file or directory put into the image by an install
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledFile {
+    pub path: String,
+    /// SHA-256 of regular files; directories and symlinks have none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl InstalledFile {
+    pub fn new(path: &Path) -> Self {
+        let is_file = path.symlink_metadata().is_ok_and(|m| m.is_file());
+        let sha256 = is_file
+            .then(|| std::fs::read(path).ok())
+            .flatten()
+            .map(|data| hex::encode(Sha256::digest(&data)));
+        Self {
+            path: path.display().to_string(),
+            sha256,
+        }
+    }
+}
+
+/// Something picolayer installed, keyed by the command that installed it and its name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Installed {
+    /// Command that installed it, e.g. `apt-get` or `gh-release`
+    pub kind: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<InstalledFile>,
+    /// Seconds since the Unix epoch
+    pub installed_at: u64,
+}
+
+impl Installed {
+    pub fn new(kind: &str, name: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            version: None,
+            files: Vec::new(),
+            installed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    pub fn files<P: AsRef<Path>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.files = paths
+            .into_iter()
+            .map(|p| InstalledFile::new(p.as_ref()))
+            .collect();
+        self
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    pub version: u32,
+    pub installed: Vec<Installed>,
+}
+
+/// Directory of the state database, PICOLAYER_STATE_DIR or /var/lib/picolayer
+pub fn dir() -> PathBuf {
+    std::env::var(STATE_DIR_ENV)
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR))
+}
+
+impl State {
+    pub fn path() -> PathBuf {
+        dir().join(STATE_FILE_NAME)
+    }
+
+    /// Load the state database, or an empty one if nothing has been installed yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self {
+                version: STATE_VERSION,
+                installed: Vec::new(),
+            });
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let state: State = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        anyhow::ensure!(
+            state.version == STATE_VERSION,
+            "Unsupported state version {} in {}",
+            state.version,
+            path.display()
+        );
+        Ok(state)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        let dir = path.parent().context("Invalid state path")?;
+        let contents = serde_json::to_string_pretty(self)? + "\n";
+        if std::fs::create_dir_all(dir).is_ok() && std::fs::write(&path, &contents).is_ok() {
+            return Ok(());
+        }
+
+        // /var/lib is root-owned, so non-root installs write the database as root
+        super::exec::run_command(
+            super::sudo::command("mkdir").arg("-p").arg(dir),
+            &format!("mkdir -p {}", dir.display()),
+        )?;
+        super::sudo::write_file(&path, &contents)
+    }
+
+    /// Add an entry, replacing any earlier install of the same kind and name
+    pub fn insert(&mut self, entry: Installed) {
+        self.installed
+            .retain(|i| !(i.kind == entry.kind && i.name == entry.name));
+        self.installed.push(entry);
+    }
+
+    /// Remove and return the entries called `name`, with or without their version pin
+    pub fn remove(&mut self, name: &str) -> Vec<Installed> {
+        let (removed, kept) = std::mem::take(&mut self.installed)
+            .into_iter()
+            .partition(|i| matches_name(&i.name, name));
+        self.installed = kept;
+        removed
+    }
+}
+
+/// Whether a recorded spec such as `typescript@5` or `curl=8.5.0-2` names `name`
+fn matches_name(recorded: &str, name: &str) -> bool {
+    match recorded.strip_prefix(name) {
+        Some("") => true,
+        Some(rest) => rest.starts_with(['@', '=', '<', '>', '!', '~', '[', ':']),
+        None => false,
+    }
+}
+
+/// Record an install in the state database.
+///
+/// Best effort: a failure to update the database is logged and never fails the install.
+pub fn record(entries: impl IntoIterator<Item = Installed>) {
+    let update = || -> Result<()> {
+        let _guard = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+        let _lock = super::run_lock::acquire();
+        let mut state = State::load()?;
+        for entry in entries {
+            debug!("Recording {} {}", entry.kind, entry.name);
+            state.insert(entry);
+        }
+        state.save()
+    };
+    if let Err(e) = update() {
+        warn!(
+            "Failed to update installation state (set {} to a writable directory): {:#}",
+            STATE_DIR_ENV, e
+        );
+    }
+}
+
+/// Record packages installed by name through a package manager
+pub fn record_packages(kind: &str, packages: &[String]) {
+    record(packages.iter().map(|p| Installed::new(kind, p)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_replaces_same_kind_and_name() {
+        let mut state = State::default();
+        state.insert(Installed::new("npm", "typescript").version("5.4.0"));
+        state.insert(Installed::new("pipx", "typescript"));
+        state.insert(Installed::new("npm", "typescript").version("5.5.0"));
+
+        assert_eq!(state.installed.len(), 2);
+        assert_eq!(state.installed[1].version.as_deref(), Some("5.5.0"));
+
+        let removed = state.remove("typescript");
+        assert_eq!(removed.len(), 2);
+        assert!(state.installed.is_empty());
+    }
+
+    #[test]
+    fn remove_matches_names_without_versions() {
+        let mut state = State::default();
+        state.insert(Installed::new("npm", "@scope/tool@^1"));
+        state.insert(Installed::new("apt-get", "curl=8.5.0-2"));
+        state.insert(Installed::new("apt-get", "curlie"));
+
+        assert_eq!(state.remove("curl").len(), 1);
+        assert_eq!(state.remove("@scope/tool").len(), 1);
+        assert_eq!(state.installed[0].name, "curlie");
+    }
+
+    #[test]
+    fn installed_file_digests_regular_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("tool");
+        std::fs::write(&file, b"hello").unwrap();
+
+        assert_eq!(
+            InstalledFile::new(&file).sha256.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(InstalledFile::new(dir.path()).sha256, None);
+    }
+}
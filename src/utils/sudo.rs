@@ -1,25 +1,377 @@
-use std::process::Command;
+use anyhow::{Context, Result};
+use log::warn;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-/// Create a command with sudo if not running as root
+/// Environment variable that selects the privilege-escalation tool (sudo, doas or su)
+const ESCALATION_ENV: &str = "PICOLAYER_ESCALATION";
+
+static NO_SUDO: AtomicBool = AtomicBool::new(false);
+
+/// Tool used to run commands as root when picolayer itself is not root
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escalation {
+    Sudo,
+    Doas,
+    Su,
+}
+
+impl Escalation {
+    const ALL: [Escalation; 3] = [Escalation::Sudo, Escalation::Doas, Escalation::Su];
+
+    /// Tools looked for on PATH, in order, when none is selected explicitly. su is on
+    /// almost every image but usually asks for a password, so it is only used when
+    /// PICOLAYER_ESCALATION selects it.
+    const DETECTED: [Escalation; 2] = [Escalation::Sudo, Escalation::Doas];
+
+    fn program(self) -> &'static str {
+        match self {
+            Escalation::Sudo => "sudo",
+            Escalation::Doas => "doas",
+            Escalation::Su => "su",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|e| e.program().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Build a command that runs `program` as root; arguments appended later are passed through.
+    /// sudo and doas run with `-n`, failing instead of waiting for a password nobody can type.
+    fn command(self, program: &str) -> Command {
+        match self {
+            Escalation::Sudo | Escalation::Doas => {
+                let mut cmd = Command::new(self.program());
+                cmd.args(["-n", program]);
+                cmd
+            }
+            Escalation::Su => {
+                // su only takes a shell string, so forward the program and its arguments
+                // as positional parameters. POSIXLY_CORRECT keeps util-linux su from
+                // treating the forwarded arguments as its own options.
+                let mut cmd = Command::new("su");
+                cmd.env("POSIXLY_CORRECT", "1");
+                cmd.args(["-c", "exec \"$0\" \"$@\"", "root", program]);
+                cmd
+            }
+        }
+    }
+
+    /// Whether the tool is known to run commands without asking for a password. Checked
+    /// once per process with `-n true`. su has no non-interactive mode and reads its
+    /// password from the terminal, so it never counts.
+    fn is_passwordless(self) -> bool {
+        static PASSWORDLESS: OnceLock<bool> = OnceLock::new();
+        match self {
+            Escalation::Sudo | Escalation::Doas => *PASSWORDLESS.get_or_init(|| {
+                self.command("true")
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .is_ok_and(|status| status.success())
+            }),
+            Escalation::Su => false,
+        }
+    }
+}
+
+/// Never escalate privileges with sudo, doas or su; steps that need root fail with an error
+/// saying so instead
+pub fn disable() {
+    NO_SUDO.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--no-sudo` is in effect
+pub fn is_disabled() -> bool {
+    NO_SUDO.load(Ordering::Relaxed)
+}
+
+/// Create a command that runs as root, escalating with sudo, doas or su when needed.
+/// In user mode or with `--no-sudo` the command runs as the current user.
 pub fn command(program: &str) -> Command {
-    if is_root() {
+    if is_root() || super::user_mode::is_enabled() || is_disabled() {
         Command::new(program)
     } else {
-        let mut cmd = Command::new("sudo");
+        escalation_or_default().command(program)
+    }
+}
+
+/// Create a command that runs as root, preserving the given
+/// environment variables across privilege escalation.
+///
+/// sudo and doas reset the environment by default, so the variables are passed through
+/// `env` on the command line instead of being set on the escalating process itself.
+pub fn command_with_env(program: &str, envs: &[(&str, &str)]) -> Command {
+    if is_root() || super::user_mode::is_enabled() || is_disabled() {
+        let mut cmd = Command::new(program);
+        cmd.envs(envs.iter().copied());
+        cmd
+    } else {
+        let mut cmd = escalation_or_default().command("env");
+        cmd.args(envs.iter().map(|(k, v)| format!("{}={}", k, v)));
         cmd.arg(program);
         cmd
     }
 }
 
-/// Check if running as root
-fn is_root() -> bool {
-    if let Ok(output) = std::process::Command::new("id").arg("-u").output()
-        && let Ok(uid_str) = String::from_utf8(output.stdout)
-        && uid_str.trim() == "0"
-    {
-        return true;
+/// Write a file that may require root privileges, using `tee` as root when not running as root
+pub fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if is_root() {
+        return std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()));
+    }
+
+    let mut child = command("tee")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to execute: tee {} as root", path.display()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(contents.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    let status = child.wait()?;
+    anyhow::ensure!(
+        status.success(),
+        "Failed to write {} with exit code: {:?}",
+        path.display(),
+        status.code()
+    );
+    Ok(())
+}
+
+/// Remove a file that may require root privileges, using `rm` as root when not running as root
+pub fn remove_file(path: &Path) -> Result<()> {
+    if is_root() {
+        return std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove {}", path.display()));
+    }
+
+    super::exec::run_command(
+        command("rm").arg("-f").arg(path),
+        &format!("Removing {} as root", path.display()),
+    )?;
+    Ok(())
+}
+
+/// Create a directory and its parents, as root when the current user cannot
+pub fn create_dir_all(path: &Path) -> Result<()> {
+    if std::fs::create_dir_all(path).is_ok() {
+        return Ok(());
     }
+    super::exec::run_command(
+        command("mkdir").arg("-p").arg(path),
+        &format!("Creating {} as root", path.display()),
+    )?;
+    Ok(())
+}
 
-    std::env::var("USER").is_ok_and(|user| user == "root")
-        || std::env::var("EUID").is_ok_and(|euid| euid == "0")
+/// Copy an executable to `dest` with mode 755, as root when the current user cannot write there
+pub fn install_executable(source: &Path, dest: &Path) -> Result<()> {
+    let copied = std::fs::copy(source, dest).and_then(|_| {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dest, std::fs::Permissions::from_mode(0o755))
+    });
+    if copied.is_ok() {
+        return Ok(());
+    }
+    super::exec::run_command(
+        command("install").args(["-m", "755"]).arg(source).arg(dest),
+        &format!("Installing {} as root", dest.display()),
+    )?;
+    Ok(())
+}
+
+/// Whether privileged commands can run without anyone typing a password, either as root or
+/// through passwordless sudo or doas; su may always prompt, so it does not count. Always
+/// false in user mode, which installs into the user's home instead.
+pub fn can_escalate() -> bool {
+    !super::user_mode::is_enabled()
+        && (is_root() || usable_escalation().is_some_and(Escalation::is_passwordless))
+}
+
+/// How privileged commands run: `root`, the escalation tool's name, or None when they cannot
+pub fn privilege_mode() -> Option<&'static str> {
+    if super::user_mode::is_enabled() {
+        None
+    } else if is_root() {
+        Some("root")
+    } else {
+        usable_escalation().map(Escalation::program)
+    }
+}
+
+/// Fail with an actionable error when an operation needs root but cannot escalate
+pub fn ensure_privileges(action: &str) -> Result<()> {
+    anyhow::ensure!(
+        !super::user_mode::is_enabled(),
+        "{} requires root privileges, which --user-mode never uses. \
+         Run this step without --user-mode, as root.",
+        action
+    );
+    if is_root() {
+        return Ok(());
+    }
+    anyhow::ensure!(
+        !is_disabled(),
+        "{} requires root privileges, but picolayer is not running as root and --no-sudo forbids escalating. \
+         Re-run as root (e.g. `USER root` in a Dockerfile) or drop --no-sudo.",
+        action
+    );
+    let Some(escalation) = escalation() else {
+        anyhow::bail!(
+            "{} requires root privileges, but picolayer is not running as root and neither sudo nor doas is installed. \
+             Re-run as root (e.g. `USER root` in a Dockerfile), install sudo or doas, or set {}=su.",
+            action,
+            ESCALATION_ENV
+        );
+    };
+    // su can only be used where someone is at the terminal to answer its prompt
+    if escalation == Escalation::Su {
+        anyhow::ensure!(
+            std::io::stdin().is_terminal(),
+            "{} requires root privileges, but su may ask for a password and this run is not interactive. \
+             Re-run as root (e.g. `USER root` in a Dockerfile) or use passwordless sudo or doas.",
+            action
+        );
+        return Ok(());
+    }
+    anyhow::ensure!(
+        escalation.is_passwordless(),
+        "{} requires root privileges, but {} asks for a password and picolayer cannot prompt for one. \
+         Re-run as root (e.g. `USER root` in a Dockerfile) or allow passwordless {} for this user.",
+        action,
+        escalation.program(),
+        escalation.program()
+    );
+    Ok(())
+}
+
+/// The escalation tool, unless --no-sudo rules escalating out
+fn usable_escalation() -> Option<Escalation> {
+    if is_disabled() { None } else { escalation() }
+}
+
+/// The escalation tool from PICOLAYER_ESCALATION, or the first of sudo and doas found on PATH
+fn escalation() -> Option<Escalation> {
+    detect(std::env::var(ESCALATION_ENV).ok().as_deref(), |program| {
+        which::which(program).is_ok()
+    })
+}
+
+/// The tool `selected` names, or else the first of [`Escalation::DETECTED`] installed
+fn detect(selected: Option<&str>, installed: impl Fn(&str) -> bool) -> Option<Escalation> {
+    if let Some(name) = selected {
+        match Escalation::from_name(name) {
+            Some(escalation) => return Some(escalation),
+            None => warn!(
+                "Ignoring unknown {}='{}', expected sudo, doas or su",
+                ESCALATION_ENV, name
+            ),
+        }
+    }
+
+    Escalation::DETECTED
+        .into_iter()
+        .find(|e| installed(e.program()))
+}
+
+/// Fall back to sudo so a missing tool surfaces as a clear spawn error for `sudo`
+fn escalation_or_default() -> Escalation {
+    escalation().unwrap_or(Escalation::Sudo)
+}
+
+/// Whether picolayer runs with an effective user ID of root. `USER` and `EUID` are not
+/// consulted, since images often leave them stale after `USER` switches.
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_line(cmd: &Command) -> Vec<String> {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|s| s.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn is_root_matches_the_effective_uid() {
+        let output = Command::new("id").arg("-u").output().unwrap();
+        let uid = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(is_root(), uid.trim() == "0");
+    }
+
+    #[test]
+    fn from_name_accepts_known_tools() {
+        assert_eq!(Escalation::from_name("doas"), Some(Escalation::Doas));
+        assert_eq!(Escalation::from_name(" SU "), Some(Escalation::Su));
+        assert_eq!(Escalation::from_name("pkexec"), None);
+    }
+
+    #[test]
+    fn su_is_only_used_when_selected() {
+        let everything = |_: &str| true;
+        assert_eq!(detect(None, everything), Some(Escalation::Sudo));
+        assert_eq!(detect(None, |p| p != "sudo"), Some(Escalation::Doas));
+        assert_eq!(detect(None, |p| p == "su"), None);
+        assert_eq!(detect(Some("su"), everything), Some(Escalation::Su));
+    }
+
+    #[test]
+    fn su_alone_is_not_a_non_interactive_escalation() {
+        let only_su = |p: &str| p == "su";
+        assert_eq!(detect(None, only_su), None);
+        let selected = detect(Some("su"), only_su).unwrap();
+        assert!(!selected.is_passwordless());
+    }
+
+    #[test]
+    fn doas_prefixes_the_program() {
+        let mut cmd = Escalation::Doas.command("apk");
+        cmd.arg("add");
+        assert_eq!(command_line(&cmd), ["doas", "-n", "apk", "add"]);
+    }
+
+    #[test]
+    fn sudo_never_prompts_for_a_password() {
+        let mut cmd = Escalation::Sudo.command("apt-get");
+        cmd.args(["install", "-y"]);
+        assert_eq!(
+            command_line(&cmd),
+            ["sudo", "-n", "apt-get", "install", "-y"]
+        );
+    }
+
+    #[test]
+    fn su_forwards_arguments_as_positional_parameters() {
+        let mut cmd = Escalation::Su.command("apk");
+        cmd.args(["add", "--no-cache"]);
+        assert_eq!(
+            command_line(&cmd),
+            [
+                "su",
+                "-c",
+                "exec \"$0\" \"$@\"",
+                "root",
+                "apk",
+                "add",
+                "--no-cache"
+            ]
+        );
+    }
 }
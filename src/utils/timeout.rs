@@ -0,0 +1,106 @@
+use log::warn;
+use std::time::{Duration, Instant};
+
+/// Exit status after a timeout, matching coreutils `timeout`
+pub const EXIT_TIMED_OUT: i32 = 124;
+
+/// How long child processes get to exit after SIGTERM before they are killed
+pub const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Start a watchdog that stops picolayer, and every process it started, once `limit` passes.
+///
+/// `on_expiry` runs after the child processes are gone, just before exiting.
+pub fn start(limit: Duration, on_expiry: impl FnOnce() + Send + 'static) {
+    std::thread::spawn(move || {
+        std::thread::sleep(limit);
+        warn!(
+            "Timed out after {}s, stopping child processes",
+            limit.as_secs()
+        );
+        stop_children();
+        on_expiry();
+        std::process::exit(EXIT_TIMED_OUT);
+    });
+}
+
+/// SIGTERM the running children, with the process groups they lead, then SIGKILL whatever
+/// is still running after the grace period
+fn stop_children() {
+    let targets = super::exec::signal_children(libc::SIGTERM);
+
+    let deadline = Instant::now() + GRACE_PERIOD;
+    let mut remaining = survivors(&targets);
+    while !remaining.is_empty() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        remaining = survivors(&targets);
+    }
+    for pid in remaining {
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+}
+
+/// Live processes among `targets`, which name a process ID or a negated process group ID.
+/// Empty where /proc is unavailable.
+fn survivors(targets: &[libc::pid_t]) -> Vec<libc::pid_t> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .filter(|&pid: &libc::pid_t| {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                .ok()
+                .and_then(|stat| live_process_group(&stat))
+                .is_some_and(|group| targets.contains(&pid) || targets.contains(&-group))
+        })
+        .collect()
+}
+
+/// Process group from a /proc/<pid>/stat line, or None for zombies
+fn live_process_group(stat: &str) -> Option<libc::pid_t> {
+    // The command name in parentheses may contain spaces; the fields after it are
+    // state, parent pid, and process group
+    let mut fields = stat.rsplit_once(')')?.1.split_whitespace();
+    let state = fields.next()?;
+    if state == "Z" {
+        return None;
+    }
+    fields.nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn live_process_group_parses_stat() {
+        assert_eq!(
+            live_process_group("4242 (apt (worker)) S 4200 4100 4100 0 -1 4194560"),
+            Some(4100)
+        );
+        assert_eq!(live_process_group("4243 (sh) Z 4200 4100 4100 0"), None);
+        assert_eq!(live_process_group("garbage"), None);
+    }
+
+    #[test]
+    fn survivors_include_the_process_group() {
+        use std::os::unix::process::CommandExt;
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id() as libc::pid_t;
+        let found = survivors(&[-pid]);
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        if std::path::Path::new("/proc").is_dir() {
+            assert_eq!(found, [pid]);
+        }
+        assert!(survivors(&[-pid]).is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+use super::os;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tempfile::TempDir;
+
+const TMP_ENV: &str = "PICOLAYER_TMP";
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use the --tmp-dir flag, or else PICOLAYER_TMP, or else the system temp directory
+/// (TMPDIR or /tmp) for picolayer's downloads and extraction
+pub fn configure(flag: Option<String>) {
+    let dir = flag
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os(TMP_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(std::env::temp_dir);
+    let _ = DIR.set(dir);
+}
+
+/// The directory temporary files are created in
+pub fn dir() -> &'static Path {
+    DIR.get_or_init(std::env::temp_dir)
+}
+
+/// A temporary directory under [`dir`], removed when dropped
+pub fn tempdir() -> Result<TempDir> {
+    let dir = dir();
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create temp directory {}", dir.display()))?;
+    tempfile::Builder::new()
+        .prefix("picolayer_")
+        .tempdir_in(dir)
+        .with_context(|| format!("Failed to create temporary directory in {}", dir.display()))
+}
+
+/// Fail before writing `needed` bytes for `what` into the temp directory when they
+/// will not fit, rather than with ENOSPC halfway through
+pub fn ensure_space(needed: u64, what: &str) -> Result<()> {
+    let dir = dir();
+    // A temp directory that cannot be measured is left to fail on its own
+    let Ok(usage) = os::filesystem_usage(dir) else {
+        return Ok(());
+    };
+    check_space(dir, usage.available, needed, what)
+}
+
+fn check_space(dir: &Path, available: u64, needed: u64, what: &str) -> Result<()> {
+    let hint = format!("point --tmp-dir or {} at a larger filesystem", TMP_ENV);
+    super::preflight::check_space(dir, available, needed, what, &hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_space_names_the_directory_and_the_fix() {
+        let dir = Path::new("/tmp");
+        assert!(check_space(dir, 2048, 1024, "gh.tar.gz").is_ok());
+        let err = check_space(dir, 1024, 4 * 1024 * 1024, "gh.tar.gz")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("No space left in /tmp for gh.tar.gz"),
+            "{}",
+            err
+        );
+        assert!(err.contains("--tmp-dir"), "{}", err);
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static USER_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Install into the user's home directory and never escalate privileges
+pub fn enable() {
+    USER_MODE.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--user-mode` is in effect
+pub fn is_enabled() -> bool {
+    USER_MODE.load(Ordering::Relaxed)
+}
+
+fn home() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .context("HOME is not set, cannot install into the user's home directory")
+}
+
+/// User-local prefix (~/.local) that npm and pip install under
+pub fn prefix() -> Result<PathBuf> {
+    Ok(home()?.join(".local"))
+}
+
+/// Where binaries go in user mode (~/.local/bin)
+pub fn bin_dir() -> Result<PathBuf> {
+    Ok(prefix()?.join("bin"))
+}
+
+/// The rc file of the user's login shell: ~/.zshrc, ~/.bashrc, or else ~/.profile
+pub fn shell_rc() -> Result<PathBuf> {
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    let file = match shell.rsplit('/').next() {
+        Some("zsh") => ".zshrc",
+        Some("bash") => ".bashrc",
+        _ => ".profile",
+    };
+    Ok(home()?.join(file))
+}
+
+/// Append a snippet adding `dir` to PATH to the user's shell rc, unless it is already there
+pub fn add_to_path(dir: &Path) -> Result<()> {
+    append_path_snippet(&shell_rc()?, dir)
+}
+
+/// Append `snippet` to the user's shell rc, unless it is already there. `what` describes
+/// it for the log, e.g. "Setting JAVA_HOME".
+pub fn add_snippet(snippet: &str, what: &str) -> Result<()> {
+    append_snippet(&shell_rc()?, snippet, what)
+}
+
+fn append_path_snippet(rc: &Path, dir: &Path) -> Result<()> {
+    let what = format!("Adding {} to PATH", dir.display());
+    append_snippet(rc, &super::profile::path_snippet(dir), &what)
+}
+
+fn append_snippet(rc: &Path, snippet: &str, what: &str) -> Result<()> {
+    let existing = std::fs::read_to_string(rc).unwrap_or_default();
+    if existing.contains(snippet) {
+        return Ok(());
+    }
+
+    info!(
+        "{} in {} (open a new shell to pick it up)",
+        what,
+        rc.display()
+    );
+    let mut file = std::fs::File::options()
+        .create(true)
+        .append(true)
+        .open(rc)
+        .with_context(|| format!("Failed to open {}", rc.display()))?;
+    let separator = if existing.is_empty() || existing.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    write!(file, "{}# Added by picolayer\n{}", separator, snippet)
+        .with_context(|| format!("Failed to write {}", rc.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_snippet_is_appended_once() {
+        let home = tempfile::tempdir().unwrap();
+        let rc = home.path().join(".profile");
+        std::fs::write(&rc, "alias ll='ls -l'").unwrap();
+
+        let bin = home.path().join(".local/bin");
+        append_path_snippet(&rc, &bin).unwrap();
+        append_path_snippet(&rc, &bin).unwrap();
+
+        let contents = std::fs::read_to_string(&rc).unwrap();
+        assert!(contents.starts_with("alias ll='ls -l'\n# Added by picolayer\n"));
+        assert_eq!(contents.matches("export PATH=").count(), 1);
+    }
+}
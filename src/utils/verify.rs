@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::path::Path;
+use std::process::Command;
+
+/// Check that installed executables resolve and respond to `--version`.
+///
+/// An executable that is missing from PATH fails verification, unless it was
+/// installed into `bin_dir`, an explicitly chosen directory the caller knows is
+/// not always on PATH; that case only warns.
+pub fn verify_executables(names: &[String], bin_dir: Option<&Path>) -> Result<()> {
+    for name in names {
+        let path = match which::which(name) {
+            Ok(path) => path,
+            Err(_) => match bin_dir.map(|dir| dir.join(name)).filter(|p| p.is_file()) {
+                Some(path) => {
+                    warn!(
+                        "{} was installed to {}, which is not in PATH",
+                        name,
+                        path.display()
+                    );
+                    path
+                }
+                None => anyhow::bail!(
+                    "{} was installed but is not in PATH ({}). Add the tool's bin directory to PATH",
+                    name,
+                    std::env::var("PATH").unwrap_or_default()
+                ),
+            },
+        };
+
+        let output = Command::new(&path)
+            .arg("--version")
+            .output()
+            .with_context(|| format!("Failed to run {}", path.display()))?;
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            info!(
+                "Verified {}: {}",
+                name,
+                stdout.lines().next().unwrap_or_default().trim()
+            );
+        } else {
+            // Some tools have no --version flag, so a non-zero exit is not fatal
+            warn!(
+                "{} --version exited with code {:?}",
+                name,
+                output.status.code()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_executables_accepts_tools_on_path() {
+        assert!(verify_executables(&["sh".to_string()], None).is_ok());
+    }
+
+    #[test]
+    fn verify_executables_fails_when_not_on_path() {
+        let err = verify_executables(&["picolayer-missing-tool".to_string()], None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("is not in PATH"));
+    }
+
+    #[test]
+    fn verify_executables_allows_explicit_bin_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join("picolayer-test-tool");
+        std::fs::write(&tool, "#!/bin/sh\necho 1.0.0\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let names = ["picolayer-test-tool".to_string()];
+        assert!(verify_executables(&names, Some(dir.path())).is_ok());
+        assert!(verify_executables(&names, None).is_err());
+    }
+}
@@ -1,4 +1,7 @@
+#[cfg(feature = "devcontainer")]
 mod devcontainer_feature;
+#[cfg(feature = "gh-release")]
 mod gh_release;
 mod package_manager;
+#[cfg(feature = "pkgx")]
 mod pkgx;
@@ -22,6 +22,61 @@ fn test_main_version() {
     assert!(stdout.contains("picolayer"));
 }
 
+#[test]
+#[serial]
+fn test_apply_help() {
+    let output = run_picolayer(&["apply", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("manifest"));
+    assert!(stdout.contains("--report-size"));
+    assert!(stdout.contains("--print-env"));
+}
+
+#[test]
+#[serial]
+fn test_apply_rejects_forward_dependency() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("picolayer.yaml");
+    std::fs::write(
+        &manifest,
+        "steps:\n  - npm:\n      packages: [typescript]\n      after: 2\n  - apk:\n      packages: [curl]\n",
+    )
+    .unwrap();
+    let output = run_picolayer(&["apply", "--jobs", "2", manifest.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid step 1"));
+}
+
+#[test]
+#[serial]
+fn test_apply_rejects_invalid_step() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("picolayer.yaml");
+    std::fs::write(&manifest, "steps:\n  - not-a-command: {}\n").unwrap();
+    let output = run_picolayer(&["apply", manifest.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid step 1"));
+}
+
+#[test]
+#[serial]
+fn test_json_output_reports_failure() {
+    let output = run_picolayer(&["--output", "json", "apply", "does-not-exist.yaml"]);
+    assert!(!output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["action"], "apply");
+    assert_eq!(result["status"], "failed");
+    assert!(
+        result["error"]
+            .as_str()
+            .unwrap()
+            .contains("does-not-exist.yaml")
+    );
+}
+
 #[test]
 #[serial]
 fn test_apt_get_help() {
@@ -31,6 +86,300 @@ fn test_apt_get_help() {
     assert!(stdout.contains("apt-get"));
 }
 
+#[test]
+#[serial]
+fn test_apt_get_help_lists_dry_run() {
+    let output = run_picolayer(&["apt-get", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--dry-run"));
+}
+
+#[test]
+#[serial]
+fn test_upgrade_help() {
+    let output = run_picolayer(&["upgrade", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--dist-upgrade"));
+}
+
+#[test]
+#[serial]
+fn test_snap_help() {
+    let output = run_picolayer(&["snap", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--classic"));
+}
+
+#[test]
+#[serial]
+fn test_flatpak_help() {
+    let output = run_picolayer(&["flatpak", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("flathub"));
+}
+
+#[test]
+#[serial]
+fn test_nix_help() {
+    let output = run_picolayer(&["nix", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--flake"));
+}
+
+#[test]
+#[serial]
+fn test_cargo_help() {
+    let output = run_picolayer(&["cargo", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--no-binstall"));
+}
+
+#[test]
+#[serial]
+fn test_go_install_help() {
+    let output = run_picolayer(&["go-install", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--go-version"));
+}
+
+#[test]
+#[serial]
+fn test_gem_help() {
+    let output = run_picolayer(&["gem", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--with-docs"));
+}
+
+#[test]
+#[serial]
+fn test_helm_plugin_help() {
+    let output = run_picolayer(&["helm-plugin", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--plugins-dir"));
+}
+
+#[test]
+#[serial]
+fn test_luarocks_help() {
+    let output = run_picolayer(&["luarocks", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--lua-version"));
+}
+
+#[test]
+#[serial]
+fn test_dotnet_tool_help() {
+    let output = run_picolayer(&["dotnet-tool", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--tool-path"));
+}
+
+#[test]
+#[serial]
+fn test_composer_help() {
+    let output = run_picolayer(&["composer", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--composer-home"));
+}
+
+#[test]
+#[serial]
+fn test_runtime_help() {
+    let output = run_picolayer(&["runtime", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("deno"));
+    assert!(stdout.contains("zig"));
+}
+
+#[test]
+#[serial]
+fn test_clean_help() {
+    let output = run_picolayer(&["clean", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--aggressive"));
+    assert!(stdout.contains("apt-lists"));
+}
+
+#[test]
+#[serial]
+fn test_cache_prune_requires_a_limit() {
+    let output = run_picolayer(&["cache", "prune"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--max-size"));
+}
+
+#[test]
+#[serial]
+fn test_cache_prune_help() {
+    let output = run_picolayer(&["cache", "prune", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--older-than"));
+    assert!(stdout.contains("--all"));
+}
+
+#[test]
+#[serial]
+fn test_doctor_help() {
+    let output = run_picolayer(&["doctor", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("doctor"));
+}
+
+#[test]
+#[serial]
+fn test_audit_log_must_be_writable() {
+    let output = run_picolayer(&["--audit-log", "/nonexistent/audit.jsonl", "list"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to open audit log /nonexistent/audit.jsonl"));
+}
+
+#[test]
+#[serial]
+fn test_exit_codes_lists_failure_classes() {
+    let output = run_picolayer(&["--output", "json", "exit-codes"]);
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let codes = result["details"].as_array().unwrap();
+    assert!(codes.iter().any(|c| c["code"] == 12
+        && c["meaning"] == "checksum or signature verification failed"));
+
+    let output = run_picolayer(&["no-such-plugin"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+#[serial]
+fn test_skip_if_present_skips_recorded_installs() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("state.json"),
+        r#"{"version":1,"installed":[{"kind":"devcontainer-feature","name":"ghcr.io/devcontainers/features/go:1","installed_at":0}]}"#,
+    )
+    .unwrap();
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args([
+            "--output",
+            "json",
+            "--skip-if-present",
+            "devcontainer-feature",
+            "ghcr.io/devcontainers/features/go:1",
+        ])
+        .env("PICOLAYER_STATE_DIR", dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        result["details"]["skipped"],
+        "ghcr.io/devcontainers/features/go:1 already installed"
+    );
+}
+
+#[test]
+#[serial]
+fn test_verify_reports_modified_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let binary = dir.path().join("tool");
+    std::fs::write(&binary, b"tampered").unwrap();
+    std::fs::write(
+        dir.path().join("state.json"),
+        format!(
+            r#"{{"version":1,"installed":[{{"kind":"gh-release","name":"owner/tool","version":"v1.0.0","files":[{{"path":"{}","sha256":"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"}}],"installed_at":0}}]}}"#,
+            binary.display()
+        ),
+    )
+    .unwrap();
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args(["--output", "json", "verify"])
+        .env("PICOLAYER_STATE_DIR", dir.path())
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(12));
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["details"][0]["status"], "drift");
+    assert_eq!(result["error_code"], "drift");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error [drift]: "));
+}
+
+#[test]
+#[serial]
+fn test_list_reads_state_database() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("state.json"),
+        r#"{"version":1,"installed":[{"kind":"npm","name":"typescript","installed_at":0}]}"#,
+    )
+    .unwrap();
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args(["--output", "json", "list"])
+        .env("PICOLAYER_STATE_DIR", dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["details"][0]["name"], "typescript");
+}
+
+#[test]
+#[serial]
+fn test_uninstall_rejects_unknown_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args(["uninstall", "not-installed"])
+        .env("PICOLAYER_STATE_DIR", dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("was not installed by picolayer"));
+}
+
+#[test]
+#[serial]
+fn test_self_update_help() {
+    let output = run_picolayer(&["self-update", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--version"));
+}
+
+#[test]
+#[serial]
+fn test_jdk_help() {
+    let output = run_picolayer(&["jdk", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("temurin"));
+}
+
+#[test]
+#[serial]
+fn test_venv_help() {
+    let output = run_picolayer(&["venv", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--require-hashes"));
+}
+
 #[test]
 #[serial]
 fn test_apk_help() {
@@ -123,3 +472,327 @@ fn test_error_handling_shows_debug_hint() {
         stderr
     );
 }
+
+#[test]
+#[serial]
+fn test_unknown_command_runs_plugin() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plugin = dir.path().join("picolayer-hello");
+    std::fs::write(
+        &plugin,
+        "#!/bin/sh\necho \"{\\\"greeting\\\": \\\"$1\\\", \\\"output\\\": \\\"$PICOLAYER_OUTPUT\\\"}\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!(
+        "{}:{}",
+        dir.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args(["--output", "json", "hello", "world"])
+        .env("PATH", path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["details"]["greeting"], "world");
+    assert_eq!(result["details"]["output"], "json");
+}
+
+#[test]
+#[serial]
+fn test_config_file_sets_defaults_under_flags() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plugin = dir.path().join("picolayer-retries");
+    std::fs::write(&plugin, "#!/bin/sh\necho \"$PICOLAYER_MAX_RETRIES\"\n").unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[retry]\nmax_retries = 4\n").unwrap();
+
+    let path = format!(
+        "{}:{}",
+        dir.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let run = |args: &[&str]| {
+        std::process::Command::new(crate::common::PICOLAYER_BIN)
+            .args(args)
+            .env("PATH", &path)
+            .env("PICOLAYER_CONFIG", &config)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["retries"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "4");
+
+    let output = run(&["--max-retries", "2", "retries"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2");
+}
+
+#[test]
+#[serial]
+fn test_invalid_config_file_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "github-token = \"x\"\n").unwrap();
+
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .arg("list")
+        .env("PICOLAYER_CONFIG", &config)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("Failed to parse {}", config.display())));
+}
+
+#[test]
+#[serial]
+fn test_unknown_command_without_plugin_fails() {
+    let output = run_picolayer(&["no-such-plugin"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("picolayer-no-such-plugin"));
+}
+
+#[test]
+#[serial]
+fn test_timeout_stops_child_processes() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plugin = dir.path().join("picolayer-sleepy");
+    std::fs::write(&plugin, "#!/bin/sh\nsleep 60\n").unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!(
+        "{}:{}",
+        dir.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let started = std::time::Instant::now();
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args(["--timeout", "1", "sleepy"])
+        .env("PATH", path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124));
+    assert!(started.elapsed() < std::time::Duration::from_secs(30));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Timed out after 1s"));
+}
+
+#[test]
+#[serial]
+fn test_offline_rejects_network_installers() {
+    let output = run_picolayer(&["--offline", "runtime", "zig"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("runtime needs network access"));
+}
+
+#[test]
+#[serial]
+fn test_missing_ca_bundle_fails_downloads() {
+    let output = run_picolayer(&["--ca-bundle", "/nonexistent/ca.pem", "runtime", "zig"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to read CA bundle /nonexistent/ca.pem"));
+}
+
+#[test]
+#[serial]
+fn test_generate_dockerfile_from_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("picolayer.yaml");
+    std::fs::write(
+        &manifest,
+        "steps:\n  - apt-get:\n      packages: [curl]\n  - clean:\n      aggressive: true\n",
+    )
+    .unwrap();
+    let output = run_picolayer(&["generate", "dockerfile", manifest.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("RUN --mount=type=cache,target=/var/cache/apt,sharing=locked"));
+    assert!(stdout.contains("picolayer apt-get --keep-cache -- curl"));
+    assert!(
+        stdout
+            .trim_end()
+            .ends_with("--skip=apt-lists --skip=package-cache")
+    );
+}
+
+#[test]
+#[serial]
+fn test_user_mode_skips_system_package_managers() {
+    let output = run_picolayer(&["--output", "json", "--user-mode", "apt-get", "curl"]);
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        result["details"]["skipped"]
+            .as_str()
+            .unwrap()
+            .contains("--user-mode")
+    );
+}
+
+#[test]
+#[serial]
+fn test_add_to_path_flag_on_installers_with_install_dir() {
+    for command in ["gh-release", "runtime", "cargo", "go-install"] {
+        let output = run_picolayer(&[command, "--help"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("--add-to-path"), "{}", command);
+    }
+}
+
+#[test]
+#[serial]
+fn test_no_sudo_flag_is_global() {
+    let output = run_picolayer(&["apt-get", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--no-sudo"));
+}
+
+#[test]
+#[serial]
+fn test_require_verification_refuses_unverified_release() {
+    let output = run_picolayer(&[
+        "--require-verification",
+        "checksum",
+        "gh-release",
+        "--owner",
+        "cli",
+        "--repo",
+        "cli",
+        "--binary",
+        "gh",
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Policy requires checksum verification for gh-release cli/cli"));
+}
+
+#[test]
+#[serial]
+fn test_confirm_requires_a_terminal_or_yes() {
+    let output = run_picolayer(&["--confirm", "apt-get", "curl"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("install curl with apt-get"));
+    assert!(stderr.contains("--yes"));
+
+    let output = run_picolayer(&["--confirm", "list"]);
+    assert!(output.status.success());
+}
+
+#[test]
+#[serial]
+fn test_arguments_expand_environment_variables() {
+    let output = std::process::Command::new(crate::common::PICOLAYER_BIN)
+        .args([
+            "--confirm",
+            "gh-release",
+            "--owner",
+            "cli",
+            "--repo",
+            "cli",
+            "--version",
+            "${GH_VERSION}",
+            "--install-dir",
+            "${PICOLAYER_TEST_UNSET_DIR:-/opt/tools}",
+        ])
+        .env("GH_VERSION", "v2.60.0")
+        .env_remove("PICOLAYER_TEST_UNSET_DIR")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(
+        "install cli (v2.60.0) from https://github.com/cli/cli/releases into /opt/tools"
+    ));
+
+    let output = run_picolayer(&[
+        "gh-release",
+        "--owner",
+        "${PICOLAYER_TEST_UNSET_OWNER}",
+        "--repo",
+        "cli",
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("PICOLAYER_TEST_UNSET_OWNER is not set")
+    );
+}
+
+#[test]
+#[serial]
+fn test_summary_file_records_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("summary.json");
+    let output = run_picolayer(&[
+        "--summary-file",
+        path.to_str().unwrap(),
+        "clean",
+        "--dry-run",
+    ]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("clean: ok in"));
+
+    let summary: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(summary["action"], "clean");
+    assert_eq!(summary["status"], "ok");
+    assert_eq!(summary["cache_hits"], 0);
+}
+
+#[test]
+#[serial]
+fn test_tmp_dir_flag_is_global() {
+    let output = run_picolayer(&["gh-release", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--tmp-dir <PATH>"));
+    assert!(stdout.contains("PICOLAYER_TMP"));
+}
+
+#[test]
+#[serial]
+fn test_max_downloads_flag_is_global() {
+    let output = run_picolayer(&["apply", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--max-downloads <N>"));
+    assert!(stdout.contains("PICOLAYER_MAX_DOWNLOADS"));
+}
+
+#[test]
+#[serial]
+fn test_gh_release_checks_install_dir_before_downloading() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+    let install_dir = file.join("bin");
+    let output = run_picolayer(&[
+        "gh-release",
+        "--owner",
+        "cli",
+        "--repo",
+        "cli",
+        "--install-dir",
+        install_dir.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not a directory"), "{}", stderr);
+}